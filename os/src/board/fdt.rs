@@ -0,0 +1,176 @@
+//! A minimal flattened device tree (FDT) parser
+//!
+//! It only walks the structure block and reports each node with the few
+//! properties we care about. No allocation is needed, so it can be used in
+//! M-mode before the kernel heap is ready.
+
+use core::slice;
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+
+// structure block tokens
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+
+const MAX_DEPTH: usize = 16;
+
+/// A device tree node with its interesting properties
+#[derive(Clone, Copy)]
+pub struct Node<'a> {
+    pub name: &'a [u8],
+    compatible: &'a [u8],
+    device_type: &'a [u8],
+    reg: &'a [u8],
+    // cells used by `reg` of this node (given by its parent)
+    reg_address_cells: usize,
+    reg_size_cells: usize,
+    // cells used by `reg` of children
+    address_cells: usize,
+    size_cells: usize,
+}
+
+impl<'a> Node<'a> {
+    const EMPTY: Self = Self {
+        name: &[],
+        compatible: &[],
+        device_type: &[],
+        reg: &[],
+        reg_address_cells: 2,
+        reg_size_cells: 1,
+        address_cells: 2,
+        size_cells: 1,
+    };
+
+    /// Whether `compatible` of the node contains `name`
+    pub fn is_compatible(&self, name: &str) -> bool {
+        self.compatible
+            .split(|&c| c == 0)
+            .any(|s| s == name.as_bytes())
+    }
+
+    pub fn is_device_type(&self, name: &str) -> bool {
+        self.device_type
+            .strip_suffix(&[0])
+            .unwrap_or(self.device_type)
+            == name.as_bytes()
+    }
+
+    /// (address, size) pairs in `reg`
+    pub fn reg(&self) -> impl Iterator<Item = (usize, usize)> + 'a {
+        let (address_cells, size_cells) = (self.reg_address_cells, self.reg_size_cells);
+        self.reg
+            .chunks_exact((address_cells + size_cells) * 4)
+            .map(move |entry| {
+                let (address, size) = entry.split_at(address_cells * 4);
+                (read_cells(address), read_cells(size))
+            })
+    }
+}
+
+fn read_cells(bytes: &[u8]) -> usize {
+    bytes
+        .chunks_exact(4)
+        .fold(0, |acc, cell| (acc << 32) | read_u32(cell) as usize)
+}
+
+fn read_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+/// Get a nul-terminated string starting from `bytes[0]` (without the nul)
+fn read_str(bytes: &[u8]) -> &[u8] {
+    let len = bytes.iter().position(|&c| c == 0).unwrap_or(bytes.len());
+    &bytes[..len]
+}
+
+fn align4(offset: usize) -> usize {
+    (offset + 3) & !3
+}
+
+pub struct Fdt<'a> {
+    data: &'a [u8],
+}
+
+impl Fdt<'static> {
+    /// Get the device tree at physical address `addr`.
+    ///
+    /// Return `None` if there is no valid device tree.
+    ///
+    /// # Safety
+    ///
+    /// `addr` must be readable (at least the header) and stay unchanged while
+    /// the device tree is in use.
+    pub unsafe fn from_addr(addr: usize) -> Option<Self> {
+        if addr == 0 || addr % 4 != 0 {
+            return None;
+        }
+        let header = slice::from_raw_parts(addr as *const u8, 8);
+        if read_u32(&header[0..4]) != FDT_MAGIC {
+            return None;
+        }
+        let total_size = read_u32(&header[4..8]) as usize;
+        Some(Self {
+            data: slice::from_raw_parts(addr as *const u8, total_size),
+        })
+    }
+}
+
+impl<'a> Fdt<'a> {
+    fn u32_at(&self, offset: usize) -> u32 {
+        read_u32(&self.data[offset..offset + 4])
+    }
+
+    /// Call `f` on every node, after all its properties are read.
+    pub fn walk(&self, mut f: impl FnMut(&Node<'a>)) {
+        let mut offset = self.u32_at(8) as usize; // off_dt_struct
+        let strings = self.u32_at(12) as usize; // off_dt_strings
+        let mut nodes = [Node::EMPTY; MAX_DEPTH];
+        let mut depth = 0;
+        loop {
+            let token = self.u32_at(offset);
+            offset += 4;
+            match token {
+                FDT_BEGIN_NODE => {
+                    let name = read_str(&self.data[offset..]);
+                    offset = align4(offset + name.len() + 1);
+                    assert!(depth < MAX_DEPTH, "device tree is too deep!");
+                    let mut node = Node::EMPTY;
+                    node.name = name;
+                    if depth > 0 {
+                        node.reg_address_cells = nodes[depth - 1].address_cells;
+                        node.reg_size_cells = nodes[depth - 1].size_cells;
+                    }
+                    nodes[depth] = node;
+                    depth += 1;
+                }
+                FDT_END_NODE => {
+                    depth -= 1;
+                    f(&nodes[depth]);
+                    if depth == 0 {
+                        break;
+                    }
+                }
+                FDT_PROP => {
+                    let len = self.u32_at(offset) as usize;
+                    let name_offset = self.u32_at(offset + 4) as usize;
+                    let value = &self.data[offset + 8..offset + 8 + len];
+                    offset = align4(offset + 8 + len);
+                    let node = &mut nodes[depth - 1];
+                    match read_str(&self.data[strings + name_offset..]) {
+                        b"compatible" => node.compatible = value,
+                        b"device_type" => node.device_type = value,
+                        b"reg" => node.reg = value,
+                        b"#address-cells" => node.address_cells = read_u32(value) as usize,
+                        b"#size-cells" => node.size_cells = read_u32(value) as usize,
+                        _ => {}
+                    }
+                }
+                FDT_NOP => {}
+                // FDT_END or something unexpected
+                _ => break,
+            }
+        }
+    }
+}
@@ -0,0 +1,124 @@
+//! Board information discovered from the device tree
+//!
+//! QEMU passes the address of a flattened device tree in `a1` on boot. It is
+//! parsed once in M-mode (before anything else uses the devices), and the
+//! defaults of QEMU virt machine are kept if it is missing.
+
+mod fdt;
+
+use crate::{info, warn};
+use core::ptr::{addr_of, addr_of_mut};
+use fdt::{Fdt, Node};
+
+pub const MAX_VIRTIO: usize = 8;
+
+/// A range of physical memory or MMIO registers
+#[derive(Debug, Clone, Copy)]
+pub struct Region {
+    pub base: usize,
+    pub size: usize,
+}
+
+impl Region {
+    const fn new(base: usize, size: usize) -> Self {
+        Self { base, size }
+    }
+
+    pub fn end(&self) -> usize {
+        self.base + self.size
+    }
+}
+
+pub struct BoardInfo {
+    pub memory: Region,
+    pub uart: Region,
+    pub clint: Region,
+    pub plic: Option<Region>,
+    /// sifive test device, used for shutdown
+    pub test: Region,
+    pub virtio: [Region; MAX_VIRTIO],
+    pub virtio_num: usize,
+}
+
+impl BoardInfo {
+    /// QEMU virt machine with 64MB memory
+    const QEMU_VIRT: Self = Self {
+        memory: Region::new(0x8000_0000, 0x400_0000),
+        uart: Region::new(0x1000_0000, 0x100),
+        clint: Region::new(0x200_0000, 0x10000),
+        plic: None,
+        test: Region::new(0x10_0000, 0x1000),
+        virtio: [Region::new(0, 0); MAX_VIRTIO],
+        virtio_num: 0,
+    };
+
+    fn update(&mut self, node: &Node) {
+        let region = match node.reg().next() {
+            Some((base, size)) => Region::new(base, size),
+            None => return,
+        };
+        if node.is_device_type("memory") {
+            self.memory = region;
+        } else if node.is_compatible("ns16550a") {
+            self.uart = region;
+        } else if node.is_compatible("riscv,clint0") || node.is_compatible("sifive,clint0") {
+            self.clint = region;
+        } else if node.is_compatible("riscv,plic0") || node.is_compatible("sifive,plic-1.0.0") {
+            self.plic = Some(region);
+        } else if node.is_compatible("sifive,test0") {
+            self.test = region;
+        } else if node.is_compatible("virtio,mmio") && self.virtio_num < MAX_VIRTIO {
+            self.virtio[self.virtio_num] = region;
+            self.virtio_num += 1;
+        }
+    }
+
+    pub fn memory_end(&self) -> usize {
+        self.memory.end()
+    }
+
+    pub fn virtio(&self) -> &[Region] {
+        &self.virtio[..self.virtio_num]
+    }
+}
+
+static mut BOARD_INFO: BoardInfo = BoardInfo::QEMU_VIRT;
+static mut FROM_DEVICE_TREE: bool = false;
+
+/// Parse the device tree at `dtb` (called once in M-mode on boot).
+pub fn init(dtb: usize) {
+    unsafe {
+        if let Some(fdt) = Fdt::from_addr(dtb) {
+            let board_info = &mut *addr_of_mut!(BOARD_INFO);
+            fdt.walk(|node| board_info.update(node));
+            FROM_DEVICE_TREE = true;
+        }
+    }
+}
+
+pub fn board_info() -> &'static BoardInfo {
+    unsafe { &*addr_of!(BOARD_INFO) }
+}
+
+pub fn print_board_info() {
+    let board_info = board_info();
+    if unsafe { FROM_DEVICE_TREE } {
+        info!("[kernel] Board info from device tree:");
+    } else {
+        warn!("[kernel] No device tree found, use defaults of QEMU virt:");
+    }
+    info!(
+        "[kernel] memory [{:#x}, {:#x}) ({}MB)",
+        board_info.memory.base,
+        board_info.memory_end(),
+        board_info.memory.size >> 20
+    );
+    info!("[kernel] uart @ {:#x}", board_info.uart.base);
+    info!("[kernel] clint @ {:#x}", board_info.clint.base);
+    if let Some(plic) = board_info.plic {
+        info!("[kernel] plic @ {:#x}", plic.base);
+    }
+    for virtio in board_info.virtio() {
+        info!("[kernel] virtio-mmio @ {:#x}", virtio.base);
+    }
+}
@@ -1,17 +1,45 @@
-pub const USER_STACK_SIZE: usize = 4096 * 4; // 16KB
+pub const USER_STACK_SIZE: usize = 4096 * 4; // 16KB, grows on demand
+pub const USER_STACK_MAX_SIZE: usize = 0x800000; // 8MB, reserved for every process, the hard limit
+pub const USER_STACK_LIMIT: usize = 0x100000; // 1MB, default limit of a process, see `sys_setrlimit`
+pub const USER_STACK_GUARD_SIZE: usize = 0x10000; // 64KB, never mapped below the stack
 pub const KERNEL_STACK_SIZE: usize = 4096 * 4; // 16KB
 pub const KERNEL_HEAP_SIZE: usize = 0x300000; // 3MB
 
 pub const PAGE_SIZE: usize = 0x1000; // 4KB
 pub const PAGE_SIZE_BITS: usize = 12;
 
-pub const MEMORY_END: usize = 0x84000000; // 64MB
+// the end of memory is detected from device tree, see `board`
+pub const SWAP_SIZE: usize = 0x1000000; // 16MB, reserved at the top of memory
+
+pub const BLOCK_CACHE_SIZE: usize = 64; // blocks of disks kept in memory, 32KB
+pub const PAGE_CACHE_SIZE: usize = 256; // pages of files kept when not mapped, 1MB
+pub const DENTRY_CACHE_SIZE: usize = 256; // directory entries kept when not in use
+
+pub const MAX_FD: usize = 128; // file descriptors of a process are below
+
+/// Mappings of `sys_mmap` are placed from here, above the program and the stacks
+/// of threads
+pub const MMAP_BASE: usize = 0x1000_0000;
 
 pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
 pub const TRAP_CONTEXT: usize = TRAMPOLINE - PAGE_SIZE;
 
 pub const CLOCK_FREQ: usize = 12500000; // 12.5MHz
 
+/// Return (bottom, top) of the user stack of thread `tid` (not the main thread).
+///
+/// They are placed above the main stack (whose top is `base`), each with a guard page,
+/// up to `MMAP_BASE`.
+pub fn thread_stack_position(base: usize, tid: usize) -> (usize, usize) {
+    let bottom = base + (tid - 1) * (USER_STACK_SIZE + PAGE_SIZE) + PAGE_SIZE;
+    (bottom, bottom + USER_STACK_SIZE)
+}
+
+/// Return the trap context of thread `tid` in user space.
+pub fn trap_cx_position(tid: usize) -> usize {
+    TRAP_CONTEXT - tid * PAGE_SIZE
+}
+
 /// Return (bottom, top) of a kernel stack in kernel space.
 pub fn kernel_stack_position(id: usize) -> (usize, usize) {
     let top = TRAMPOLINE - id * (KERNEL_STACK_SIZE + PAGE_SIZE);
@@ -1,5 +1,9 @@
-use crate::sbi::console_putchar;
+use crate::sbi::{console_getchar, console_putchar};
+use crate::task::signal::{send_signal_to_group, SIGINT, SIGQUIT, SIGTSTP};
+use crate::UPSafeCell;
+use alloc::collections::VecDeque;
 use core::fmt::{self, Write};
+use lazy_static::lazy_static;
 
 struct Stdout;
 
@@ -69,3 +73,60 @@ macro_rules! error {
         ) $(, $($arg)+)?));
     }
 }
+
+// ------------------------- controlling terminal -------------------------
+
+const CTRL_C: u8 = 3;
+const CTRL_Z: u8 = 26;
+const CTRL_BACKSLASH: u8 = 28;
+
+/// The console as a terminal
+///
+/// It is the controlling terminal of the session of initproc. Only its
+/// foreground process group may read it, and the job control characters
+/// (Ctrl-C, Ctrl-Z, Ctrl-\) send signals to the foreground group instead of
+/// being read.
+pub struct Terminal {
+    /// session the console is the controlling terminal of
+    pub session: usize,
+    /// foreground process group
+    pub foreground: usize,
+    /// characters received but not read yet
+    input: VecDeque<u8>,
+}
+
+lazy_static! {
+    pub static ref TERMINAL: UPSafeCell<Terminal> = UPSafeCell::new(Terminal {
+        session: 1,
+        foreground: 1,
+        input: VecDeque::new(),
+    });
+}
+
+/// Move characters from the UART to the terminal, handling job control characters.
+///
+/// Called on every timer interrupt, so that Ctrl-C works even if nobody reads.
+pub fn poll_console_input() {
+    loop {
+        let c = console_getchar();
+        let signum = match c {
+            0 => return,
+            CTRL_C => SIGINT,
+            CTRL_Z => SIGTSTP,
+            CTRL_BACKSLASH => SIGQUIT,
+            _ => {
+                TERMINAL.exclusive_access().input.push_back(c);
+                continue;
+            }
+        };
+        print!("^{}", (c + b'@') as char);
+        let foreground = TERMINAL.exclusive_access().foreground;
+        send_signal_to_group(foreground, signum);
+    }
+}
+
+/// Read a character from the terminal, return None if there is no input.
+pub fn console_read() -> Option<u8> {
+    poll_console_input();
+    TERMINAL.exclusive_access().input.pop_front()
+}
@@ -0,0 +1,145 @@
+//! Cache of the blocks of disks
+//!
+//! A fixed number of blocks, [`BLOCK_CACHE_SIZE`], are kept in memory for all
+//! devices wrapped in a [`CachedDevice`]. When the cache is full, the least
+//! recently used block is replaced. Writes only change the cached block, which
+//! is written back to the device when it is replaced, or by `flush` and
+//! [`sync_all`], see `sys_fsync`, `sys_sync` and `sbi::shutdown`.
+
+use super::{BlockDevice, BLOCK_SZ};
+use crate::config::BLOCK_CACHE_SIZE;
+use crate::UPSafeCell;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+
+struct CachedBlock {
+    device: Arc<dyn BlockDevice>,
+    block_id: usize,
+    data: [u8; BLOCK_SZ],
+    /// changed since it was read or written back
+    dirty: bool,
+    /// `BlockCache::clock` when last used
+    last_used: usize,
+}
+
+impl CachedBlock {
+    fn write_back(&mut self) {
+        if self.dirty {
+            self.device.write_block(self.block_id, &self.data);
+            self.dirty = false;
+        }
+    }
+}
+
+struct BlockCache {
+    blocks: Vec<CachedBlock>,
+    /// counts the uses of blocks
+    clock: usize,
+}
+
+impl BlockCache {
+    /// Block `block_id` of `device`, read from the device unless `overwrite`
+    /// as the caller writes all of it.
+    fn get(
+        &mut self,
+        device: &Arc<dyn BlockDevice>,
+        block_id: usize,
+        overwrite: bool,
+    ) -> &mut CachedBlock {
+        self.clock += 1;
+        let found = self
+            .blocks
+            .iter()
+            .position(|block| block.block_id == block_id && Arc::ptr_eq(&block.device, device));
+        let index = match found {
+            Some(index) => index,
+            None => {
+                let mut data = [0u8; BLOCK_SZ];
+                if !overwrite {
+                    device.read_block(block_id, &mut data);
+                }
+                let block = CachedBlock {
+                    device: device.clone(),
+                    block_id,
+                    data,
+                    dirty: false,
+                    last_used: 0,
+                };
+                if self.blocks.len() < BLOCK_CACHE_SIZE {
+                    self.blocks.push(block);
+                    self.blocks.len() - 1
+                } else {
+                    let (index, _) = self
+                        .blocks
+                        .iter()
+                        .enumerate()
+                        .min_by_key(|(_, block)| block.last_used)
+                        .unwrap();
+                    self.blocks[index].write_back();
+                    self.blocks[index] = block;
+                    index
+                }
+            }
+        };
+        let block = &mut self.blocks[index];
+        block.last_used = self.clock;
+        block
+    }
+}
+
+lazy_static! {
+    static ref BLOCK_CACHE: UPSafeCell<BlockCache> = UPSafeCell::new(BlockCache {
+        blocks: Vec::with_capacity(BLOCK_CACHE_SIZE),
+        clock: 0,
+    });
+}
+
+/// A block device whose blocks are cached
+pub struct CachedDevice {
+    device: Arc<dyn BlockDevice>,
+}
+
+impl CachedDevice {
+    pub fn new(device: Arc<dyn BlockDevice>) -> Self {
+        Self { device }
+    }
+}
+
+impl BlockDevice for CachedDevice {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        let mut cache = BLOCK_CACHE.exclusive_access();
+        buf.copy_from_slice(&cache.get(&self.device, block_id, false).data);
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        let mut cache = BLOCK_CACHE.exclusive_access();
+        let block = cache.get(&self.device, block_id, true);
+        block.data.copy_from_slice(buf);
+        block.dirty = true;
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.device.num_blocks()
+    }
+
+    fn flush(&self) {
+        let mut cache = BLOCK_CACHE.exclusive_access();
+        for block in cache.blocks.iter_mut() {
+            if Arc::ptr_eq(&block.device, &self.device) {
+                block.write_back();
+            }
+        }
+    }
+}
+
+/// Write all changed blocks back to their devices.
+///
+/// Nothing is written if the cache is in use, when the kernel panics in it.
+pub fn sync_all() {
+    if let Ok(mut cache) = BLOCK_CACHE.try_borrow_mut() {
+        for block in cache.blocks.iter_mut() {
+            block.write_back();
+        }
+    }
+}
@@ -0,0 +1,40 @@
+//! Block device layer
+//!
+//! Every block device exposes fixed-size blocks of [`BLOCK_SZ`] bytes. Disks
+//! go through the block cache, see [`CachedDevice`].
+
+mod cache;
+mod ramdisk;
+mod virtio_blk;
+
+use crate::board::board_info;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+pub use cache::{sync_all, CachedDevice};
+use core::any::Any;
+pub use ramdisk::RamDisk;
+pub use virtio_blk::VirtIOBlock;
+
+pub const BLOCK_SZ: usize = 512;
+
+pub trait BlockDevice: Send + Sync + Any {
+    /// Read block `block_id` into `buf` (`buf.len() == BLOCK_SZ`).
+    fn read_block(&self, block_id: usize, buf: &mut [u8]);
+    /// Write `buf` (`buf.len() == BLOCK_SZ`) into block `block_id`.
+    fn write_block(&self, block_id: usize, buf: &[u8]);
+    /// Total number of blocks of the device.
+    fn num_blocks(&self) -> usize;
+    /// Write blocks changed in a cache back to the device.
+    fn flush(&self) {}
+}
+
+/// Initialize the virtio block devices found in the device tree, in order,
+/// behind the block cache.
+pub fn virtio_block_devices() -> Vec<Arc<dyn BlockDevice>> {
+    board_info()
+        .virtio()
+        .iter()
+        .filter_map(|&region| VirtIOBlock::new(region))
+        .map(|device| Arc::new(CachedDevice::new(Arc::new(device))) as Arc<dyn BlockDevice>)
+        .collect()
+}
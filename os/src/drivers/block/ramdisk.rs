@@ -0,0 +1,47 @@
+use super::{BlockDevice, BLOCK_SZ};
+
+/// A block device backed by a reserved range of physical memory.
+///
+/// The range must be identically mapped in kernel space.
+pub struct RamDisk {
+    start: usize,
+    size: usize,
+}
+
+impl RamDisk {
+    pub fn new(start: usize, size: usize) -> Self {
+        assert_eq!(
+            size % BLOCK_SZ,
+            0,
+            "ramdisk size must be aligned to block size!"
+        );
+        Self { start, size }
+    }
+
+    fn block_ptr(&self, block_id: usize) -> *mut u8 {
+        assert!(
+            block_id < self.num_blocks(),
+            "block {} out of range!",
+            block_id
+        );
+        (self.start + block_id * BLOCK_SZ) as *mut u8
+    }
+}
+
+impl BlockDevice for RamDisk {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        assert_eq!(buf.len(), BLOCK_SZ);
+        let src = self.block_ptr(block_id);
+        unsafe { core::ptr::copy_nonoverlapping(src, buf.as_mut_ptr(), BLOCK_SZ) };
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        assert_eq!(buf.len(), BLOCK_SZ);
+        let dst = self.block_ptr(block_id);
+        unsafe { core::ptr::copy_nonoverlapping(buf.as_ptr(), dst, BLOCK_SZ) };
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.size / BLOCK_SZ
+    }
+}
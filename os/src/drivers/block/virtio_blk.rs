@@ -0,0 +1,295 @@
+//! VirtIO block device over MMIO, such as a disk image given to QEMU with
+//! `-drive file=fs.img,if=none,id=x0 -device virtio-blk-device,drive=x0`
+//!
+//! Both the legacy (version 1) and the modern (version 2) register layouts are
+//! supported. There is a single request in flight, whose completion is polled,
+//! so the driver needs no interrupt.
+
+use super::{BlockDevice, BLOCK_SZ};
+use crate::board::Region;
+use crate::config::PAGE_SIZE;
+use crate::mm::address::PhysAddr;
+use crate::mm::frame_allocator::{frame_alloc_contiguous, FrameTracker};
+use crate::UPSafeCell;
+use alloc::vec::Vec;
+use core::sync::atomic::{fence, Ordering};
+
+// registers
+const MAGIC_VALUE: usize = 0x000;
+const VERSION: usize = 0x004;
+const DEVICE_ID: usize = 0x008;
+const DEVICE_FEATURES_SEL: usize = 0x014;
+const DRIVER_FEATURES: usize = 0x020;
+const DRIVER_FEATURES_SEL: usize = 0x024;
+/// legacy only
+const GUEST_PAGE_SIZE: usize = 0x028;
+const QUEUE_SEL: usize = 0x030;
+const QUEUE_NUM_MAX: usize = 0x034;
+const QUEUE_NUM: usize = 0x038;
+/// legacy only
+const QUEUE_ALIGN: usize = 0x03c;
+/// legacy only
+const QUEUE_PFN: usize = 0x040;
+const QUEUE_READY: usize = 0x044;
+const QUEUE_NOTIFY: usize = 0x050;
+const INTERRUPT_STATUS: usize = 0x060;
+const INTERRUPT_ACK: usize = 0x064;
+const STATUS: usize = 0x070;
+const QUEUE_DESC_LOW: usize = 0x080;
+const QUEUE_DESC_HIGH: usize = 0x084;
+const QUEUE_DRIVER_LOW: usize = 0x090;
+const QUEUE_DRIVER_HIGH: usize = 0x094;
+const QUEUE_DEVICE_LOW: usize = 0x0a0;
+const QUEUE_DEVICE_HIGH: usize = 0x0a4;
+/// `capacity` of `struct virtio_blk_config`, in sectors of 512 bytes
+const CONFIG_CAPACITY: usize = 0x100;
+
+const MAGIC: u32 = 0x7472_6976; // "virt"
+const DEVICE_ID_BLOCK: u32 = 2;
+
+// bits of `STATUS`
+const STATUS_ACKNOWLEDGE: u32 = 1;
+const STATUS_DRIVER: u32 = 2;
+const STATUS_DRIVER_OK: u32 = 4;
+const STATUS_FEATURES_OK: u32 = 8;
+
+/// `VIRTIO_F_VERSION_1`, bit 32 of the features (bit 0 of the second word),
+/// required by version 2
+const FEATURE_VERSION_1: u32 = 1;
+
+// flags of a descriptor
+const DESC_NEXT: u16 = 1;
+/// written by the device
+const DESC_WRITE: u16 = 2;
+
+// `type` of a request
+const REQUEST_IN: u32 = 0;
+const REQUEST_OUT: u32 = 1;
+
+/// number of descriptors, a request takes 3
+const QUEUE_SIZE: usize = 8;
+
+#[repr(C)]
+struct Descriptor {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+#[repr(C)]
+struct AvailRing {
+    flags: u16,
+    idx: u16,
+    ring: [u16; QUEUE_SIZE],
+}
+
+#[repr(C)]
+struct UsedRing {
+    flags: u16,
+    idx: u16,
+    /// (id, len)
+    ring: [(u32, u32); QUEUE_SIZE],
+}
+
+/// `struct virtio_blk_req` without the data
+#[repr(C)]
+struct RequestHeader {
+    request_type: u32,
+    reserved: u32,
+    sector: u64,
+}
+
+// Layout of the two pages of the queue: descriptors and the available ring in
+// the first, as the legacy interface requires the used ring to be page-aligned,
+// then the used ring, the request header, the status and the data in the second.
+const USED_OFFSET: usize = PAGE_SIZE;
+const HEADER_OFFSET: usize = PAGE_SIZE + 128;
+const STATUS_OFFSET: usize = PAGE_SIZE + 192;
+const DATA_OFFSET: usize = PAGE_SIZE + BLOCK_SZ;
+
+struct VirtQueue {
+    /// two contiguous frames, identically mapped in kernel space
+    frames: Vec<FrameTracker>,
+    /// `idx` of the used ring seen last
+    used_idx: u16,
+}
+
+impl VirtQueue {
+    fn base(&self) -> usize {
+        PhysAddr::from(self.frames[0].ppn).0
+    }
+
+    fn at<T>(&self, offset: usize) -> *mut T {
+        (self.base() + offset) as *mut T
+    }
+}
+
+pub struct VirtIOBlock {
+    regs: usize,
+    capacity: usize,
+    queue: UPSafeCell<VirtQueue>,
+}
+
+impl VirtIOBlock {
+    /// Initialize the block device at `region`, return None if there is none.
+    pub fn new(region: Region) -> Option<Self> {
+        let regs = region.base;
+        let read = |offset: usize| unsafe { ((regs + offset) as *const u32).read_volatile() };
+        let write = |offset: usize, value: u32| unsafe {
+            ((regs + offset) as *mut u32).write_volatile(value)
+        };
+        let version = read(VERSION);
+        // QEMU has several slots, empty ones have device id 0
+        if read(MAGIC_VALUE) != MAGIC || read(DEVICE_ID) != DEVICE_ID_BLOCK {
+            return None;
+        }
+        if version != 1 && version != 2 {
+            return None;
+        }
+        write(STATUS, 0);
+        write(STATUS, STATUS_ACKNOWLEDGE);
+        write(STATUS, STATUS_ACKNOWLEDGE | STATUS_DRIVER);
+        // none of the optional features
+        let mut status = STATUS_ACKNOWLEDGE | STATUS_DRIVER;
+        write(DEVICE_FEATURES_SEL, 0);
+        write(DRIVER_FEATURES_SEL, 0);
+        write(DRIVER_FEATURES, 0);
+        if version == 2 {
+            write(DRIVER_FEATURES_SEL, 1);
+            write(DRIVER_FEATURES, FEATURE_VERSION_1);
+            status |= STATUS_FEATURES_OK;
+            write(STATUS, status);
+            if read(STATUS) & STATUS_FEATURES_OK == 0 {
+                return None;
+            }
+        }
+        let frames = frame_alloc_contiguous(2)?;
+        for frame in frames.iter() {
+            frame.ppn.get_bytes_array().fill(0);
+        }
+        let queue = VirtQueue {
+            frames,
+            used_idx: 0,
+        };
+        write(QUEUE_SEL, 0);
+        if (read(QUEUE_NUM_MAX) as usize) < QUEUE_SIZE {
+            return None;
+        }
+        write(QUEUE_NUM, QUEUE_SIZE as u32);
+        let base = queue.base();
+        if version == 1 {
+            write(GUEST_PAGE_SIZE, PAGE_SIZE as u32);
+            write(QUEUE_ALIGN, PAGE_SIZE as u32);
+            write(QUEUE_PFN, (base / PAGE_SIZE) as u32);
+        } else {
+            let avail = base + QUEUE_SIZE * core::mem::size_of::<Descriptor>();
+            let used = base + USED_OFFSET;
+            write(QUEUE_DESC_LOW, base as u32);
+            write(QUEUE_DESC_HIGH, (base >> 32) as u32);
+            write(QUEUE_DRIVER_LOW, avail as u32);
+            write(QUEUE_DRIVER_HIGH, (avail >> 32) as u32);
+            write(QUEUE_DEVICE_LOW, used as u32);
+            write(QUEUE_DEVICE_HIGH, (used >> 32) as u32);
+            write(QUEUE_READY, 1);
+        }
+        write(STATUS, status | STATUS_DRIVER_OK);
+        let capacity = unsafe { ((regs + CONFIG_CAPACITY) as *const u64).read_volatile() };
+        Some(Self {
+            regs,
+            capacity: capacity as usize,
+            queue: UPSafeCell::new(queue),
+        })
+    }
+
+    fn write_reg(&self, offset: usize, value: u32) {
+        unsafe { ((self.regs + offset) as *mut u32).write_volatile(value) }
+    }
+
+    fn read_reg(&self, offset: usize) -> u32 {
+        unsafe { ((self.regs + offset) as *const u32).read_volatile() }
+    }
+
+    /// Transfer block `block_id` through the data buffer of the queue, wait
+    /// for the device to finish.
+    fn request(&self, queue: &mut VirtQueue, request_type: u32, block_id: usize) {
+        assert!(block_id < self.capacity, "block {} out of range!", block_id);
+        let base = queue.base();
+        unsafe {
+            queue
+                .at::<RequestHeader>(HEADER_OFFSET)
+                .write_volatile(RequestHeader {
+                    request_type,
+                    reserved: 0,
+                    sector: block_id as u64,
+                });
+            queue.at::<u8>(STATUS_OFFSET).write_volatile(0xff);
+            let desc = queue.at::<Descriptor>(0);
+            let data_flags = if request_type == REQUEST_IN {
+                DESC_WRITE
+            } else {
+                0
+            };
+            desc.write_volatile(Descriptor {
+                addr: (base + HEADER_OFFSET) as u64,
+                len: core::mem::size_of::<RequestHeader>() as u32,
+                flags: DESC_NEXT,
+                next: 1,
+            });
+            desc.add(1).write_volatile(Descriptor {
+                addr: (base + DATA_OFFSET) as u64,
+                len: BLOCK_SZ as u32,
+                flags: data_flags | DESC_NEXT,
+                next: 2,
+            });
+            desc.add(2).write_volatile(Descriptor {
+                addr: (base + STATUS_OFFSET) as u64,
+                len: 1,
+                flags: DESC_WRITE,
+                next: 0,
+            });
+            let avail = queue.at::<AvailRing>(QUEUE_SIZE * core::mem::size_of::<Descriptor>());
+            let idx = (*avail).idx;
+            (*avail).ring[idx as usize % QUEUE_SIZE] = 0;
+            fence(Ordering::SeqCst);
+            core::ptr::addr_of_mut!((*avail).idx).write_volatile(idx.wrapping_add(1));
+            fence(Ordering::SeqCst);
+            self.write_reg(QUEUE_NOTIFY, 0);
+            let used = queue.at::<UsedRing>(USED_OFFSET);
+            while core::ptr::addr_of!((*used).idx).read_volatile() == queue.used_idx {
+                core::hint::spin_loop();
+            }
+            fence(Ordering::SeqCst);
+            queue.used_idx = queue.used_idx.wrapping_add(1);
+            self.write_reg(INTERRUPT_ACK, self.read_reg(INTERRUPT_STATUS));
+            let status = queue.at::<u8>(STATUS_OFFSET).read_volatile();
+            assert_eq!(
+                status, 0,
+                "virtio-blk: error {} on block {}",
+                status, block_id
+            );
+        }
+    }
+}
+
+impl BlockDevice for VirtIOBlock {
+    fn read_block(&self, block_id: usize, buf: &mut [u8]) {
+        assert_eq!(buf.len(), BLOCK_SZ);
+        let mut queue = self.queue.exclusive_access();
+        self.request(&mut queue, REQUEST_IN, block_id);
+        let data = queue.at::<u8>(DATA_OFFSET);
+        unsafe { core::ptr::copy_nonoverlapping(data, buf.as_mut_ptr(), BLOCK_SZ) };
+    }
+
+    fn write_block(&self, block_id: usize, buf: &[u8]) {
+        assert_eq!(buf.len(), BLOCK_SZ);
+        let mut queue = self.queue.exclusive_access();
+        let data = queue.at::<u8>(DATA_OFFSET);
+        unsafe { core::ptr::copy_nonoverlapping(buf.as_ptr(), data, BLOCK_SZ) };
+        self.request(&mut queue, REQUEST_OUT, block_id);
+    }
+
+    fn num_blocks(&self) -> usize {
+        self.capacity
+    }
+}
@@ -0,0 +1,311 @@
+//! `/dev`, devices opened like files
+//!
+//! - `/dev/console`: the terminal on the UART, standard input and output of
+//!   every process
+//! - `/dev/null`: reads nothing, discards writes
+//! - `/dev/zero`: reads zeros, discards writes
+//! - `/dev/urandom`: reads pseudo-random bytes, seeded from `mtime` at boot
+//! - block devices registered by [`register_block_device`]
+//!
+//! Devices have no file offset except block devices. They are all created at
+//! boot, so their times are 0.
+
+use super::vfs::{DirEntry, FileSystem, Inode, InodeType, Metadata, S_IFBLK, S_IFCHR, S_IFDIR};
+use crate::console::{console_read, TERMINAL};
+use crate::drivers::block::{BlockDevice, BLOCK_SZ};
+use crate::sbi::console_putchar;
+use crate::syscall::errno::{EINTR, EINVAL, EISDIR, EROFS};
+use crate::task::signal::{current_has_deliverable_signal, send_signal_to_group, SIGTTIN};
+use crate::task::*;
+use crate::timer::get_time;
+use crate::UPSafeCell;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+
+const CHAR_DEVICES: [&str; 4] = ["console", "null", "zero", "urandom"];
+
+/// A block device in `/dev`
+struct BlockEntry {
+    device: Arc<dyn BlockDevice>,
+    writable: bool,
+    ino: usize,
+}
+
+lazy_static! {
+    static ref BLOCK_DEVICES: UPSafeCell<BTreeMap<String, BlockEntry>> =
+        UPSafeCell::new(BTreeMap::new());
+    /// state of the xorshift generator of `/dev/urandom`, never 0
+    static ref RANDOM_STATE: UPSafeCell<u64> = UPSafeCell::new(get_time() as u64 | 1);
+}
+
+/// Show `device` as `/dev/<name>`, read-only unless `writable`.
+pub fn register_block_device(name: &str, device: Arc<dyn BlockDevice>, writable: bool) {
+    let mut devices = BLOCK_DEVICES.exclusive_access();
+    // after the root and the character devices
+    let ino = 2 + CHAR_DEVICES.len() + devices.len();
+    devices.insert(
+        String::from(name),
+        BlockEntry {
+            device,
+            writable,
+            ino,
+        },
+    );
+}
+
+/// Read one character from the console, blocking until there is one.
+///
+/// Only the foreground process group of the terminal may read, a background
+/// process of its session is sent SIGTTIN.
+fn console_read_char() -> Result<u8, isize> {
+    loop {
+        let (pgid, sid) = {
+            let process = current_process();
+            let inner = process.inner_exclusive_access();
+            (inner.pgid, inner.sid)
+        };
+        let terminal = TERMINAL.exclusive_access();
+        let background = sid == terminal.session && pgid != terminal.foreground;
+        drop(terminal);
+        if background {
+            send_signal_to_group(pgid, SIGTTIN);
+            return Err(-EINTR);
+        }
+        if let Some(c) = console_read() {
+            return Ok(c);
+        }
+        suspend_current_and_run_next();
+        exit_current_if_killed();
+        if current_has_deliverable_signal() {
+            return Err(-EINTR);
+        }
+    }
+}
+
+fn random_u64() -> u64 {
+    let mut state = RANDOM_STATE.exclusive_access();
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+enum DevInode {
+    Root,
+    Console,
+    Null,
+    Zero,
+    Urandom,
+    Block {
+        device: Arc<dyn BlockDevice>,
+        writable: bool,
+        ino: usize,
+    },
+}
+
+impl DevInode {
+    /// Visit bytes `offset..offset + len` of the device block by block, return
+    /// the number of bytes visited. `io(block_id, block, start, n)` is given
+    /// each block read, of which bytes `start..start + n` are in the range.
+    fn block_io(
+        device: &Arc<dyn BlockDevice>,
+        offset: usize,
+        len: usize,
+        mut io: impl FnMut(usize, &mut [u8], usize, usize),
+    ) -> usize {
+        let size = device.num_blocks() * BLOCK_SZ;
+        let end = (offset + len).min(size);
+        let mut block = [0u8; BLOCK_SZ];
+        let mut pos = offset;
+        while pos < end {
+            let block_id = pos / BLOCK_SZ;
+            let start = pos % BLOCK_SZ;
+            let n = (BLOCK_SZ - start).min(end - pos);
+            device.read_block(block_id, &mut block);
+            io(block_id, &mut block, start, n);
+            pos += n;
+        }
+        end.saturating_sub(offset)
+    }
+}
+
+impl Inode for DevInode {
+    fn inode_type(&self) -> InodeType {
+        match self {
+            DevInode::Root => InodeType::Dir,
+            _ => InodeType::File,
+        }
+    }
+
+    fn metadata(&self) -> Metadata {
+        // the root, then the character devices in the order of `CHAR_DEVICES`
+        let (ino, mode) = match self {
+            DevInode::Root => (1, S_IFDIR | 0o755),
+            DevInode::Console => (2, S_IFCHR | 0o620),
+            DevInode::Null => (3, S_IFCHR | 0o666),
+            DevInode::Zero => (4, S_IFCHR | 0o666),
+            DevInode::Urandom => (5, S_IFCHR | 0o666),
+            DevInode::Block { writable, ino, .. } => {
+                (*ino, S_IFBLK | if *writable { 0o660 } else { 0o440 })
+            }
+        };
+        Metadata {
+            ino,
+            mode,
+            nlink: if matches!(self, DevInode::Root) { 2 } else { 1 },
+            size: self.size(),
+            atime: 0,
+            mtime: 0,
+            ctime: 0,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            DevInode::Block { device, .. } => device.num_blocks() * BLOCK_SZ,
+            _ => 0,
+        }
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> isize {
+        match self {
+            DevInode::Root => -EISDIR,
+            // one character at a time
+            DevInode::Console if buf.is_empty() => 0,
+            DevInode::Console => match console_read_char() {
+                Ok(c) => {
+                    buf[0] = c;
+                    1
+                }
+                Err(errno) => errno,
+            },
+            DevInode::Null => 0,
+            DevInode::Zero => {
+                buf.fill(0);
+                buf.len() as isize
+            }
+            DevInode::Urandom => {
+                for chunk in buf.chunks_mut(8) {
+                    let bytes = random_u64().to_le_bytes();
+                    chunk.copy_from_slice(&bytes[..chunk.len()]);
+                }
+                buf.len() as isize
+            }
+            DevInode::Block { device, .. } => {
+                let mut read = 0;
+                let n = Self::block_io(device, offset, buf.len(), |_, block, start, n| {
+                    buf[read..read + n].copy_from_slice(&block[start..start + n]);
+                    read += n;
+                });
+                n as isize
+            }
+        }
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> isize {
+        match self {
+            DevInode::Root => -EISDIR,
+            DevInode::Console => {
+                for &c in buf {
+                    console_putchar(c);
+                }
+                buf.len() as isize
+            }
+            DevInode::Null | DevInode::Zero | DevInode::Urandom => buf.len() as isize,
+            DevInode::Block {
+                writable: false, ..
+            } => -EROFS,
+            DevInode::Block {
+                device,
+                writable: true,
+                ..
+            } => {
+                let mut written = 0;
+                let n = Self::block_io(device, offset, buf.len(), |block_id, block, start, n| {
+                    block[start..start + n].copy_from_slice(&buf[written..written + n]);
+                    device.write_block(block_id, block);
+                    written += n;
+                });
+                n as isize
+            }
+        }
+    }
+
+    /// `O_TRUNC` is ignored, e.g. for `> /dev/null` in the shell.
+    fn truncate(&self, _size: usize) -> isize {
+        match self {
+            DevInode::Root => -EISDIR,
+            _ => 0,
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        if !matches!(self, DevInode::Root) {
+            return None;
+        }
+        let inode = match name {
+            "console" => DevInode::Console,
+            "null" => DevInode::Null,
+            "zero" => DevInode::Zero,
+            "urandom" => DevInode::Urandom,
+            _ => {
+                let devices = BLOCK_DEVICES.exclusive_access();
+                let entry = devices.get(name)?;
+                DevInode::Block {
+                    device: entry.device.clone(),
+                    writable: entry.writable,
+                    ino: entry.ino,
+                }
+            }
+        };
+        Some(Arc::new(inode))
+    }
+
+    fn create(&self, _name: &str, _inode_type: InodeType) -> Result<Arc<dyn Inode>, isize> {
+        Err(-EROFS)
+    }
+
+    fn list(&self) -> Result<Vec<DirEntry>, isize> {
+        let devices = BLOCK_DEVICES.exclusive_access();
+        let names = CHAR_DEVICES
+            .iter()
+            .copied()
+            .chain(devices.keys().map(String::as_str));
+        Ok(names
+            .map(|name| DirEntry {
+                name: String::from(name),
+                inode_type: InodeType::File,
+            })
+            .collect())
+    }
+
+    fn unlink(&self, _name: &str, _dir: bool) -> isize {
+        -EROFS
+    }
+
+    fn sync(&self) -> isize {
+        match self {
+            DevInode::Root => 0,
+            DevInode::Block { device, .. } => {
+                device.flush();
+                0
+            }
+            _ => -EINVAL,
+        }
+    }
+
+    fn is_terminal(&self) -> bool {
+        matches!(self, DevInode::Console)
+    }
+}
+
+pub struct DevFs;
+
+impl FileSystem for DevFs {
+    fn root(&self) -> Arc<dyn Inode> {
+        Arc::new(DevInode::Root)
+    }
+}
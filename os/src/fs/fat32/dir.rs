@@ -0,0 +1,332 @@
+//! Directory entries of FAT32, with VFAT long names
+//!
+//! A directory is an array of 32-byte entries. A file has a short entry, with
+//! an 8.3 upper-case name, its attributes, first cluster and size, which may
+//! be preceded by long name entries holding up to 13 UTF-16 units each, the
+//! last part first.
+
+use crate::syscall::errno::{EINVAL, ENAMETOOLONG};
+use alloc::string::String;
+use alloc::vec::Vec;
+
+pub const ENTRY_SIZE: usize = 32;
+
+pub const ATTR_READ_ONLY: u8 = 0x01;
+const ATTR_VOLUME_ID: u8 = 0x08;
+pub const ATTR_DIRECTORY: u8 = 0x10;
+pub const ATTR_ARCHIVE: u8 = 0x20;
+/// attributes of a long name entry
+const ATTR_LONG_NAME: u8 = 0x0f;
+
+/// first byte of a deleted entry
+pub const DELETED: u8 = 0xe5;
+/// first byte of the entry after the last one
+pub const END: u8 = 0x00;
+
+/// `ord` of the last part of a long name, stored first
+const LAST_LONG_ENTRY: u8 = 0x40;
+/// offsets of the 13 UTF-16 units in a long name entry
+const LONG_NAME_UNITS: [usize; 13] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+const MAX_NAME_UNITS: usize = 255;
+
+/// bits of byte 12 of a short entry, set by Windows NT for names in lower case
+const LOWER_BASE: u8 = 0x08;
+const LOWER_EXT: u8 = 0x10;
+
+/// 1980-01-01, the first DOS date, as there is no clock
+const DOS_EPOCH_DATE: u16 = (1 << 5) | 1;
+
+/// A short entry
+#[derive(Clone)]
+pub struct ShortEntry {
+    pub raw: [u8; ENTRY_SIZE],
+}
+
+impl ShortEntry {
+    pub fn new(name: [u8; 11], attr: u8, first_cluster: u32) -> Self {
+        let mut raw = [0u8; ENTRY_SIZE];
+        raw[..11].copy_from_slice(&name);
+        raw[11] = attr;
+        // creation, access and modification dates
+        for offset in [16, 18, 24] {
+            raw[offset..offset + 2].copy_from_slice(&DOS_EPOCH_DATE.to_le_bytes());
+        }
+        let mut entry = Self { raw };
+        entry.set_first_cluster(first_cluster);
+        entry
+    }
+
+    pub fn name(&self) -> [u8; 11] {
+        self.raw[..11].try_into().unwrap()
+    }
+
+    pub fn set_name(&mut self, name: [u8; 11]) {
+        self.raw[..11].copy_from_slice(&name);
+        self.raw[12] &= !(LOWER_BASE | LOWER_EXT);
+    }
+
+    pub fn attr(&self) -> u8 {
+        self.raw[11]
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.attr() & ATTR_DIRECTORY != 0
+    }
+
+    pub fn first_cluster(&self) -> u32 {
+        let high = u16::from_le_bytes([self.raw[20], self.raw[21]]) as u32;
+        let low = u16::from_le_bytes([self.raw[26], self.raw[27]]) as u32;
+        (high << 16) | low
+    }
+
+    pub fn set_first_cluster(&mut self, cluster: u32) {
+        self.raw[20..22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+        self.raw[26..28].copy_from_slice(&(cluster as u16).to_le_bytes());
+    }
+
+    pub fn size(&self) -> u32 {
+        u32::from_le_bytes(self.raw[28..32].try_into().unwrap())
+    }
+
+    pub fn set_size(&mut self, size: u32) {
+        self.raw[28..32].copy_from_slice(&size.to_le_bytes());
+    }
+
+    /// The 8.3 name as `NAME.EXT`, in lower case where NT flags say so
+    fn display_name(&self) -> String {
+        let case = self.raw[12];
+        let part = |bytes: &[u8], lower: bool| {
+            let text = String::from_utf8_lossy(bytes);
+            let text = text.trim_end_matches(' ');
+            if lower {
+                text.to_ascii_lowercase()
+            } else {
+                String::from(text)
+            }
+        };
+        let mut name = part(&self.raw[..8], case & LOWER_BASE != 0);
+        // 0x05 stands for a first byte 0xe5
+        if name.starts_with('\u{5}') {
+            name.replace_range(..1, "\u{e5}");
+        }
+        let ext = part(&self.raw[8..11], case & LOWER_EXT != 0);
+        if !ext.is_empty() {
+            name.push('.');
+            name.push_str(&ext);
+        }
+        name
+    }
+}
+
+/// Checksum of a short name, stored in its long name entries
+fn checksum(name: &[u8; 11]) -> u8 {
+    name.iter()
+        .fold(0u8, |sum, &c| (sum >> 1 | sum << 7).wrapping_add(c))
+}
+
+/// A file found in a directory
+pub struct DirItem {
+    pub name: String,
+    pub entry: ShortEntry,
+    /// index of its first entry, a long name entry if it has one
+    pub first_slot: usize,
+    /// index of its short entry
+    pub slot: usize,
+}
+
+impl DirItem {
+    pub fn is_dot(&self) -> bool {
+        self.name == "." || self.name == ".."
+    }
+}
+
+/// Parse the entries in `data`, without deleted entries and the volume label.
+pub fn parse(data: &[u8]) -> Vec<DirItem> {
+    let mut items = Vec::new();
+    // long name being read: (first slot, checksum, next ord, units)
+    let mut long: Option<(usize, u8, u8, Vec<u16>)> = None;
+    for (slot, raw) in data.chunks_exact(ENTRY_SIZE).enumerate() {
+        if raw[0] == END {
+            break;
+        }
+        if raw[0] == DELETED {
+            long = None;
+            continue;
+        }
+        if raw[11] & 0x3f == ATTR_LONG_NAME {
+            let ord = raw[0] & !LAST_LONG_ENTRY;
+            let units: Vec<u16> = LONG_NAME_UNITS
+                .iter()
+                .map(|&offset| u16::from_le_bytes([raw[offset], raw[offset + 1]]))
+                .collect();
+            long = match long.take() {
+                _ if raw[0] & LAST_LONG_ENTRY != 0 && ord > 0 => {
+                    Some((slot, raw[13], ord - 1, units))
+                }
+                Some((first, sum, next, mut parts)) if ord == next && ord > 0 && sum == raw[13] => {
+                    // parts are stored backwards
+                    let mut all = units;
+                    all.append(&mut parts);
+                    Some((first, sum, ord - 1, all))
+                }
+                _ => None,
+            };
+            continue;
+        }
+        let entry = ShortEntry {
+            raw: raw.try_into().unwrap(),
+        };
+        let long = long.take();
+        if entry.attr() & ATTR_VOLUME_ID != 0 {
+            continue;
+        }
+        let (name, first_slot) = match long {
+            Some((first, sum, 0, units)) if sum == checksum(&entry.name()) => {
+                let len = units
+                    .iter()
+                    .position(|&unit| unit == 0)
+                    .unwrap_or(units.len());
+                let name = char::decode_utf16(units[..len].iter().copied())
+                    .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                    .collect();
+                (name, first)
+            }
+            _ => (entry.display_name(), slot),
+        };
+        items.push(DirItem {
+            name,
+            entry,
+            first_slot,
+            slot,
+        });
+    }
+    items
+}
+
+/// Whether other systems take `name` for the file `item`, they ignore the case
+/// and accept the short name. Lookups match the name exactly, as the dentry
+/// cache does.
+pub fn name_conflicts(item: &DirItem, name: &str) -> bool {
+    item.name.eq_ignore_ascii_case(name) || item.entry.display_name().eq_ignore_ascii_case(name)
+}
+
+/// Characters allowed in short names besides letters and digits
+const SHORT_NAME_SPECIAL: &[u8] = b"!#$%&'()-@^_`{}~";
+
+fn is_short_char(c: u8) -> bool {
+    c.is_ascii_uppercase() || c.is_ascii_digit() || SHORT_NAME_SPECIAL.contains(&c)
+}
+
+/// Check that `name` may be stored in a directory.
+pub fn check_name(name: &str) -> Result<(), isize> {
+    if name.encode_utf16().count() > MAX_NAME_UNITS {
+        return Err(-ENAMETOOLONG);
+    }
+    if name.ends_with(' ') || name.ends_with('.') {
+        return Err(-EINVAL);
+    }
+    if name
+        .chars()
+        .any(|c| (c as u32) < 0x20 || "\"*/:<>?\\|".contains(c))
+    {
+        return Err(-EINVAL);
+    }
+    Ok(())
+}
+
+/// The short name of `name` if it is a valid upper-case 8.3 name, which then
+/// needs no long name
+fn exact_short_name(name: &str) -> Option<[u8; 11]> {
+    let (base, ext) = match name.rfind('.') {
+        Some(dot) => (&name[..dot], &name[dot + 1..]),
+        None => (name, ""),
+    };
+    let valid = |part: &str, max: usize| part.len() <= max && part.bytes().all(is_short_char);
+    if base.is_empty() || !valid(base, 8) || !valid(ext, 3) {
+        return None;
+    }
+    let mut short = [b' '; 11];
+    short[..base.len()].copy_from_slice(base.as_bytes());
+    short[8..8 + ext.len()].copy_from_slice(ext.as_bytes());
+    Some(short)
+}
+
+/// Generate a short name `BASE~N.EXT` for `name`, not in `taken`.
+fn generate_short_name(name: &str, taken: &[[u8; 11]]) -> [u8; 11] {
+    let name = name.trim_start_matches('.');
+    let (base, ext) = match name.rfind('.') {
+        Some(dot) => (&name[..dot], &name[dot + 1..]),
+        None => (name, ""),
+    };
+    let convert = |part: &str, max: usize| -> Vec<u8> {
+        part.chars()
+            .filter(|&c| c != ' ' && c != '.')
+            .map(|c| {
+                let c = c.to_ascii_uppercase();
+                if c.is_ascii() && is_short_char(c as u8) {
+                    c as u8
+                } else {
+                    b'_'
+                }
+            })
+            .take(max)
+            .collect()
+    };
+    let base = convert(base, 8);
+    let ext = convert(ext, 3);
+    let mut short = [b' '; 11];
+    short[8..8 + ext.len()].copy_from_slice(&ext);
+    for n in 1.. {
+        let tail = alloc::format!("~{}", n);
+        let keep = base.len().min(8 - tail.len());
+        short[..8].fill(b' ');
+        short[..keep].copy_from_slice(&base[..keep]);
+        short[keep..keep + tail.len()].copy_from_slice(tail.as_bytes());
+        if !taken.contains(&short) {
+            break;
+        }
+    }
+    short
+}
+
+/// Entries storing `name` for `entry`, the short entry last. Its short name is
+/// chosen not to be in `taken`.
+pub fn encode(name: &str, mut entry: ShortEntry, taken: &[[u8; 11]]) -> Vec<[u8; ENTRY_SIZE]> {
+    if let Some(short) = exact_short_name(name) {
+        if !taken.contains(&short) {
+            entry.set_name(short);
+            return Vec::from([entry.raw]);
+        }
+    }
+    let short = generate_short_name(name, taken);
+    entry.set_name(short);
+    let sum = checksum(&short);
+    let mut units: Vec<u16> = name.encode_utf16().collect();
+    // ended by 0 unless it fills the last entry, padded with 0xffff
+    if units.len() % 13 != 0 {
+        units.push(0);
+        while units.len() % 13 != 0 {
+            units.push(0xffff);
+        }
+    }
+    let count = units.len() / 13;
+    let mut entries = Vec::new();
+    for i in (0..count).rev() {
+        let mut raw = [0u8; ENTRY_SIZE];
+        raw[0] = (i + 1) as u8;
+        if i == count - 1 {
+            raw[0] |= LAST_LONG_ENTRY;
+        }
+        raw[11] = ATTR_LONG_NAME;
+        raw[13] = sum;
+        for (unit, &offset) in units[i * 13..(i + 1) * 13]
+            .iter()
+            .zip(LONG_NAME_UNITS.iter())
+        {
+            raw[offset..offset + 2].copy_from_slice(&unit.to_le_bytes());
+        }
+        entries.push(raw);
+    }
+    entries.push(entry.raw);
+    entries
+}
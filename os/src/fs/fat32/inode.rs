@@ -0,0 +1,768 @@
+//! Files and directories of a FAT32 volume
+//!
+//! The first cluster and the size of a file are kept in its short entry, which
+//! is written back whenever they change. There is no clock, so times on disk
+//! are left at the DOS epoch, and the times shown by `stat` are those of
+//! changes since boot, or 0.
+//!
+//! Every method changing the volume is an operation of the journal, see
+//! [`Volume::begin`].
+
+use super::dir::{
+    self, DirItem, ShortEntry, ATTR_ARCHIVE, ATTR_DIRECTORY, ATTR_READ_ONLY, DELETED, END,
+    ENTRY_SIZE,
+};
+use super::volume::{EntryPos, Volume};
+use crate::drivers::block::BLOCK_SZ;
+use crate::fs::vfs::{DirEntry, Inode, InodeType, Metadata, S_IFDIR, S_IFREG};
+use crate::syscall::errno::{
+    EEXIST, EFBIG, EIO, EISDIR, ENOENT, ENOSPC, ENOTDIR, ENOTEMPTY, EXDEV,
+};
+use crate::timer::get_time_ms;
+use crate::UPSafeCell;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// A directory has at most 65536 entries.
+const MAX_DIR_ENTRIES: usize = 65536;
+
+struct State {
+    /// position of the short entry, None for the root directory or once unlinked
+    pos: Option<EntryPos>,
+    ino: usize,
+    first_cluster: u32,
+    /// of a file, the entry of a directory has size 0
+    size: usize,
+    read_only: bool,
+    /// clusters from `first_cluster`, read when first needed
+    chain: Option<Vec<u32>>,
+    /// removed from its directory, the clusters are freed when dropped
+    unlinked: bool,
+    atime: usize,
+    mtime: usize,
+    ctime: usize,
+}
+
+pub struct FatInode {
+    volume: Arc<Volume>,
+    is_dir: bool,
+    state: UPSafeCell<State>,
+}
+
+/// Inode number from the position of the short entry, which is unique on the
+/// volume, the root is 1.
+fn ino_of(pos: Option<EntryPos>) -> usize {
+    pos.map_or(1, |pos| (pos.sector * BLOCK_SZ + pos.offset) / ENTRY_SIZE)
+}
+
+impl FatInode {
+    fn new(
+        volume: &Arc<Volume>,
+        is_dir: bool,
+        pos: Option<EntryPos>,
+        entry: Option<&ShortEntry>,
+    ) -> Arc<Self> {
+        let (first_cluster, size, read_only) = match entry {
+            Some(entry) => (
+                entry.first_cluster(),
+                entry.size() as usize,
+                entry.attr() & ATTR_READ_ONLY != 0,
+            ),
+            None => (volume.root_cluster, 0, false),
+        };
+        Arc::new(Self {
+            volume: volume.clone(),
+            is_dir,
+            state: UPSafeCell::new(State {
+                pos,
+                ino: ino_of(pos),
+                first_cluster,
+                size: if is_dir { 0 } else { size },
+                read_only,
+                chain: None,
+                unlinked: false,
+                atime: 0,
+                mtime: 0,
+                ctime: 0,
+            }),
+        })
+    }
+
+    pub fn root(volume: &Arc<Volume>) -> Arc<Self> {
+        Self::new(volume, true, None, None)
+    }
+
+    /// The clusters of this file, read from the FAT the first time.
+    fn clusters<'a>(&self, state: &'a mut State) -> Result<&'a mut Vec<u32>, isize> {
+        if state.chain.is_none() {
+            state.chain = Some(self.volume.chain(state.first_cluster)?);
+        }
+        Ok(state.chain.as_mut().unwrap())
+    }
+
+    /// The sector holding byte `offset` of the file
+    fn sector_of(&self, chain: &[u32], offset: usize) -> usize {
+        let cluster_size = self.volume.cluster_size();
+        self.volume.cluster_sector(chain[offset / cluster_size]) + offset % cluster_size / BLOCK_SZ
+    }
+
+    /// Make the file `count` clusters long at least, all or nothing.
+    ///
+    /// `count` must be small, see [`FatInode::grow`] for more clusters.
+    fn ensure_clusters(&self, state: &mut State, count: usize) -> Result<(), isize> {
+        let unlinked = state.unlinked;
+        let chain = self.clusters(state)?;
+        let old_len = chain.len();
+        while chain.len() < count {
+            match self.volume.alloc_cluster(chain.last().copied()) {
+                Ok(cluster) => chain.push(cluster),
+                Err(err) => {
+                    self.volume.free_clusters(chain, old_len);
+                    chain.truncate(old_len);
+                    return Err(err);
+                }
+            }
+        }
+        // the chain of a removed file is an orphan
+        if unlinked && old_len == 0 && !chain.is_empty() {
+            if let Err(err) = self.volume.add_orphan(chain[0]) {
+                self.volume.free_clusters(chain, 0);
+                chain.clear();
+                return Err(err);
+            }
+        }
+        let first = chain.first().copied();
+        state.first_cluster = first.unwrap_or(0);
+        Ok(())
+    }
+
+    /// Extend the file to `count` clusters with zeroed ones, and its size with
+    /// them, a step at a time, see [`Volume::checkpoint`].
+    fn grow(&self, state: &mut State, count: usize) -> Result<(), isize> {
+        let cluster_size = self.volume.cluster_size();
+        loop {
+            let len = self.clusters(state)?.len();
+            if len >= count {
+                return Ok(());
+            }
+            if self.volume.needs_checkpoint() {
+                // whole clusters, as many as the size needs
+                state.size = state.size.max(len * cluster_size);
+                self.save_entry(state);
+                self.volume.checkpoint();
+            }
+            self.ensure_clusters(state, len + 1)?;
+        }
+    }
+
+    /// Cut the chain of the file to `count` clusters, the rest is freed as an
+    /// orphan when the operation ends.
+    fn cut(&self, state: &mut State, count: usize) -> Result<(), isize> {
+        let unlinked = state.unlinked;
+        let chain = self.clusters(state)?;
+        if count >= chain.len() {
+            return Ok(());
+        }
+        let rest = chain[count];
+        // the whole chain of a removed file is an orphan already
+        if count > 0 || !unlinked {
+            self.volume.add_orphan(rest)?;
+        }
+        if count > 0 {
+            self.volume.end_chain(chain[count - 1]);
+        }
+        chain.truncate(count);
+        self.volume.free_later(rest);
+        if count == 0 {
+            state.first_cluster = 0;
+        }
+        Ok(())
+    }
+
+    /// Write the first cluster and the size to the short entry.
+    fn save_entry(&self, state: &State) {
+        let pos = match state.pos {
+            Some(pos) => pos,
+            None => return,
+        };
+        let mut entry = ShortEntry {
+            raw: [0u8; ENTRY_SIZE],
+        };
+        self.volume
+            .read_bytes(pos.sector, pos.offset, &mut entry.raw);
+        entry.set_first_cluster(state.first_cluster);
+        if !self.is_dir {
+            entry.set_size(state.size as u32);
+        }
+        self.volume.write_bytes(pos.sector, pos.offset, &entry.raw);
+    }
+
+    fn modified(state: &mut State) {
+        state.mtime = get_time_ms();
+        state.ctime = state.mtime;
+    }
+
+    /// Cut or extend the file to `size` bytes, filled with zeros.
+    fn resize(&self, state: &mut State, size: usize) -> Result<(), isize> {
+        if size > u32::MAX as usize {
+            return Err(-EFBIG);
+        }
+        let cluster_size = self.volume.cluster_size();
+        let old_size = state.size;
+        if size > old_size {
+            // new clusters are zeroed, but not the end of the old last one
+            let chain = self.clusters(state)?;
+            let zero_end = size.min(old_size.div_ceil(cluster_size) * cluster_size);
+            let zeros = [0u8; BLOCK_SZ];
+            let mut pos = old_size;
+            while pos < zero_end {
+                let start = pos % BLOCK_SZ;
+                let n = (BLOCK_SZ - start).min(zero_end - pos);
+                self.volume
+                    .write_data(self.sector_of(chain, pos), start, &zeros[..n]);
+                pos += n;
+            }
+            self.grow(state, size.div_ceil(cluster_size))?;
+        } else {
+            self.cut(state, size.div_ceil(cluster_size))?;
+        }
+        state.size = size;
+        self.save_entry(state);
+        Self::modified(state);
+        Ok(())
+    }
+
+    /// All the entries of this directory
+    fn dir_data(&self, state: &mut State) -> Result<Vec<u8>, isize> {
+        let chain = self.clusters(state)?;
+        let sectors = self.volume.sectors_per_cluster();
+        let mut data = vec![0u8; chain.len() * self.volume.cluster_size()];
+        let mut blocks = data.chunks_exact_mut(BLOCK_SZ);
+        for &cluster in chain.iter() {
+            let first = self.volume.cluster_sector(cluster);
+            for sector in first..first + sectors {
+                let block = blocks.next().unwrap();
+                self.volume.read_sector(sector, block.try_into().unwrap());
+            }
+        }
+        Ok(data)
+    }
+
+    /// Position of entry `slot` of this directory
+    fn slot_pos(&self, chain: &[u32], slot: usize) -> EntryPos {
+        let offset = slot * ENTRY_SIZE;
+        EntryPos {
+            sector: self.sector_of(chain, offset),
+            offset: offset % BLOCK_SZ,
+        }
+    }
+
+    fn items(&self) -> Result<Vec<DirItem>, isize> {
+        let mut state = self.state.exclusive_access();
+        Ok(dir::parse(&self.dir_data(&mut state)?))
+    }
+
+    /// The file `name` of this directory, not `.` or `..`
+    fn find(&self, name: &str) -> Result<Option<DirItem>, isize> {
+        let items = self.items()?;
+        Ok(items
+            .into_iter()
+            .find(|item| !item.is_dot() && item.name == name))
+    }
+
+    /// The file other systems would take for `name`, but `except`
+    fn find_conflict(
+        &self,
+        name: &str,
+        except: Option<&DirItem>,
+    ) -> Result<Option<DirItem>, isize> {
+        let items = self.items()?;
+        Ok(items.into_iter().find(|item| {
+            !item.is_dot()
+                && dir::name_conflicts(item, name)
+                && except.map_or(true, |except| except.slot != item.slot)
+        }))
+    }
+
+    /// The inode of `item` in this directory, the one in use if any.
+    fn child(&self, item: &DirItem) -> Result<Arc<FatInode>, isize> {
+        let pos = {
+            let mut state = self.state.exclusive_access();
+            let chain = self.clusters(&mut state)?;
+            self.slot_pos(chain, item.slot)
+        };
+        let mut inodes = self.volume.inodes.exclusive_access();
+        if let Some(inode) = inodes.get(&pos).and_then(|inode| inode.upgrade()) {
+            return Ok(inode);
+        }
+        let inode = Self::new(
+            &self.volume,
+            item.entry.is_dir(),
+            Some(pos),
+            Some(&item.entry),
+        );
+        inodes.insert(pos, Arc::downgrade(&inode));
+        Ok(inode)
+    }
+
+    /// Store `name` for `entry` in this directory, extending it if needed,
+    /// return the position of the short entry.
+    fn add_entries(&self, name: &str, entry: ShortEntry) -> Result<EntryPos, isize> {
+        dir::check_name(name)?;
+        let mut state = self.state.exclusive_access();
+        let data = self.dir_data(&mut state)?;
+        let taken: Vec<[u8; 11]> = dir::parse(&data)
+            .iter()
+            .map(|item| item.entry.name())
+            .collect();
+        let raws = dir::encode(name, entry, &taken);
+        // a run of free entries, all entries after the end are free
+        let slots = data.len() / ENTRY_SIZE;
+        let mut run = 0;
+        let mut ended = false;
+        let mut start = None;
+        for slot in 0..slots {
+            let first = data[slot * ENTRY_SIZE];
+            ended |= first == END;
+            run = if ended || first == DELETED {
+                run + 1
+            } else {
+                0
+            };
+            if run == raws.len() {
+                start = Some(slot + 1 - run);
+                break;
+            }
+        }
+        // or in new clusters, after the free entries at the end
+        let start = start.unwrap_or(slots - run);
+        if start + raws.len() > MAX_DIR_ENTRIES {
+            return Err(-ENOSPC);
+        }
+        let entries_per_cluster = self.volume.cluster_size() / ENTRY_SIZE;
+        self.ensure_clusters(
+            &mut state,
+            (start + raws.len()).div_ceil(entries_per_cluster),
+        )?;
+        let chain = self.clusters(&mut state)?;
+        for (i, raw) in raws.iter().enumerate() {
+            let pos = self.slot_pos(chain, start + i);
+            self.volume.write_bytes(pos.sector, pos.offset, raw);
+        }
+        let pos = self.slot_pos(chain, start + raws.len() - 1);
+        Self::modified(&mut state);
+        Ok(pos)
+    }
+
+    /// Mark the entries of `item` in this directory deleted.
+    fn remove_entries(&self, item: &DirItem) -> Result<(), isize> {
+        let mut state = self.state.exclusive_access();
+        let chain = self.clusters(&mut state)?;
+        for slot in item.first_slot..=item.slot {
+            let pos = self.slot_pos(chain, slot);
+            self.volume.write_bytes(pos.sector, pos.offset, &[DELETED]);
+        }
+        Self::modified(&mut state);
+        Ok(())
+    }
+
+    /// `item` is being removed from this directory: its chain becomes an
+    /// orphan, freed when the operation ends, or when its inode is no longer
+    /// used.
+    fn release(&self, item: &DirItem) -> Result<(), isize> {
+        let pos = {
+            let mut state = self.state.exclusive_access();
+            let chain = self.clusters(&mut state)?;
+            self.slot_pos(chain, item.slot)
+        };
+        let inode = self
+            .volume
+            .inodes
+            .exclusive_access()
+            .get(&pos)
+            .and_then(|inode| inode.upgrade());
+        match inode {
+            Some(inode) => {
+                let mut state = inode.state.exclusive_access();
+                if state.first_cluster != 0 {
+                    self.volume.add_orphan(state.first_cluster)?;
+                }
+                state.pos = None;
+                state.unlinked = true;
+                state.ctime = get_time_ms();
+                drop(state);
+                self.volume.inodes.exclusive_access().remove(&pos);
+            }
+            None => {
+                let first = item.entry.first_cluster();
+                if first != 0 {
+                    self.volume.add_orphan(first)?;
+                    self.volume.free_later(first);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Cluster of this directory as written in `..` of its subdirectories,
+    /// 0 for the root
+    fn dotdot_cluster(&self) -> u32 {
+        let state = self.state.exclusive_access();
+        if state.pos.is_none() && !state.unlinked {
+            0
+        } else {
+            state.first_cluster
+        }
+    }
+
+    /// Check that `item` may replace the existing `old` of a directory.
+    fn check_replace(&self, item: &DirItem, old: &DirItem) -> Result<(), isize> {
+        match (item.entry.is_dir(), old.entry.is_dir()) {
+            (true, true) => {
+                let old = self.child(old)?;
+                if old.items()?.iter().any(|item| !item.is_dot()) {
+                    return Err(-ENOTEMPTY);
+                }
+                Ok(())
+            }
+            (true, false) => Err(-ENOTDIR),
+            (false, true) => Err(-EISDIR),
+            (false, false) => Ok(()),
+        }
+    }
+}
+
+impl Drop for FatInode {
+    fn drop(&mut self) {
+        let _op = self.volume.begin();
+        let state = self.state.exclusive_access();
+        if state.unlinked {
+            if state.first_cluster != 0 {
+                self.volume.free_later(state.first_cluster);
+            }
+        } else if let Some(pos) = state.pos {
+            let mut inodes = self.volume.inodes.exclusive_access();
+            if inodes
+                .get(&pos)
+                .map_or(false, |inode| inode.strong_count() == 0)
+            {
+                inodes.remove(&pos);
+            }
+        }
+    }
+}
+
+impl Inode for FatInode {
+    fn inode_type(&self) -> InodeType {
+        if self.is_dir {
+            InodeType::Dir
+        } else {
+            InodeType::File
+        }
+    }
+
+    fn metadata(&self) -> Metadata {
+        // `.` and the entry in the parent, and `..` of subdirectories
+        let subdirs = if self.is_dir {
+            self.items().map_or(0, |items| {
+                items
+                    .iter()
+                    .filter(|item| !item.is_dot() && item.entry.is_dir())
+                    .count()
+            })
+        } else {
+            0
+        };
+        let mut state = self.state.exclusive_access();
+        let (mode, nlink) = if self.is_dir {
+            (S_IFDIR, 2 + subdirs)
+        } else {
+            (S_IFREG, 1)
+        };
+        let perm = if state.read_only { 0o555 } else { 0o755 };
+        let size = if self.is_dir {
+            let cluster_size = self.volume.cluster_size();
+            self.clusters(&mut state).map_or(0, |chain| chain.len()) * cluster_size
+        } else {
+            state.size
+        };
+        Metadata {
+            ino: state.ino,
+            mode: mode | perm,
+            nlink: if state.unlinked { 0 } else { nlink },
+            size,
+            atime: state.atime,
+            mtime: state.mtime,
+            ctime: state.ctime,
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.state.exclusive_access().size
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> isize {
+        if self.is_dir {
+            return -EISDIR;
+        }
+        let mut state = self.state.exclusive_access();
+        if offset >= state.size {
+            return 0;
+        }
+        let end = (offset + buf.len()).min(state.size);
+        let cluster_size = self.volume.cluster_size();
+        let chain = match self.clusters(&mut state) {
+            Ok(chain) if chain.len() * cluster_size >= end => chain,
+            // shorter than its size
+            Ok(_) => return -EIO,
+            Err(err) => return err,
+        };
+        let mut block = [0u8; BLOCK_SZ];
+        let mut pos = offset;
+        while pos < end {
+            let start = pos % BLOCK_SZ;
+            let n = (BLOCK_SZ - start).min(end - pos);
+            self.volume
+                .read_sector(self.sector_of(chain, pos), &mut block);
+            buf[pos - offset..pos - offset + n].copy_from_slice(&block[start..start + n]);
+            pos += n;
+        }
+        state.atime = get_time_ms();
+        (end - offset) as isize
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> isize {
+        if self.is_dir {
+            return -EISDIR;
+        }
+        let end = offset + buf.len();
+        if end > u32::MAX as usize {
+            return -EFBIG;
+        }
+        let _op = self.volume.begin();
+        let mut state = self.state.exclusive_access();
+        if offset > state.size {
+            if let Err(err) = self.resize(&mut state, offset) {
+                return err;
+            }
+        }
+        // a cluster at a time, what is written is committed when the log is
+        // full, see `Volume::checkpoint`
+        let cluster_size = self.volume.cluster_size();
+        let mut pos = offset;
+        let mut result = Ok(());
+        while pos < end {
+            if self.volume.needs_checkpoint() {
+                self.save_entry(&state);
+                self.volume.checkpoint();
+            }
+            result = self.ensure_clusters(&mut state, pos / cluster_size + 1);
+            if result.is_err() {
+                break;
+            }
+            let chunk_end = end.min((pos / cluster_size + 1) * cluster_size);
+            let chain = state.chain.as_ref().unwrap();
+            while pos < chunk_end {
+                let start = pos % BLOCK_SZ;
+                let n = (BLOCK_SZ - start).min(chunk_end - pos);
+                let data = &buf[pos - offset..pos - offset + n];
+                self.volume
+                    .write_data(self.sector_of(chain, pos), start, data);
+                pos += n;
+            }
+            state.size = state.size.max(pos);
+        }
+        self.save_entry(&state);
+        Self::modified(&mut state);
+        match result {
+            Err(err) if pos == offset => err,
+            _ => (pos - offset) as isize,
+        }
+    }
+
+    fn truncate(&self, size: usize) -> isize {
+        if self.is_dir {
+            return -EISDIR;
+        }
+        let _op = self.volume.begin();
+        let mut state = self.state.exclusive_access();
+        match self.resize(&mut state, size) {
+            Ok(()) => 0,
+            Err(err) => err,
+        }
+    }
+
+    fn sync(&self) -> isize {
+        self.volume.sync();
+        0
+    }
+
+    fn mappable(&self) -> bool {
+        !self.is_dir
+    }
+
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        if !self.is_dir {
+            return None;
+        }
+        let item = self.find(name).ok()??;
+        Some(self.child(&item).ok()?)
+    }
+
+    fn create(&self, name: &str, inode_type: InodeType) -> Result<Arc<dyn Inode>, isize> {
+        if !self.is_dir {
+            return Err(-ENOTDIR);
+        }
+        if self.find_conflict(name, None)?.is_some() {
+            return Err(-EEXIST);
+        }
+        let _op = self.volume.begin();
+        let entry = match inode_type {
+            InodeType::File => ShortEntry::new([b' '; 11], ATTR_ARCHIVE, 0),
+            InodeType::Dir => {
+                let cluster = self.volume.alloc_cluster(None)?;
+                let dot = ShortEntry::new(*b".          ", ATTR_DIRECTORY, cluster);
+                let dotdot =
+                    ShortEntry::new(*b"..         ", ATTR_DIRECTORY, self.dotdot_cluster());
+                let sector = self.volume.cluster_sector(cluster);
+                self.volume.write_bytes(sector, 0, &dot.raw);
+                self.volume.write_bytes(sector, ENTRY_SIZE, &dotdot.raw);
+                ShortEntry::new([b' '; 11], ATTR_DIRECTORY, cluster)
+            }
+        };
+        let pos = match self.add_entries(name, entry.clone()) {
+            Ok(pos) => pos,
+            Err(err) => {
+                if entry.first_cluster() != 0 {
+                    self.volume.free_clusters(&[entry.first_cluster()], 0);
+                }
+                return Err(err);
+            }
+        };
+        let inode = Self::new(
+            &self.volume,
+            inode_type == InodeType::Dir,
+            Some(pos),
+            Some(&entry),
+        );
+        Self::modified(&mut inode.state.exclusive_access());
+        self.volume
+            .inodes
+            .exclusive_access()
+            .insert(pos, Arc::downgrade(&inode));
+        Ok(inode)
+    }
+
+    fn list(&self) -> Result<Vec<DirEntry>, isize> {
+        if !self.is_dir {
+            return Err(-ENOTDIR);
+        }
+        Ok(self
+            .items()?
+            .into_iter()
+            .filter(|item| !item.is_dot())
+            .map(|item| DirEntry {
+                inode_type: if item.entry.is_dir() {
+                    InodeType::Dir
+                } else {
+                    InodeType::File
+                },
+                name: item.name,
+            })
+            .collect())
+    }
+
+    fn unlink(&self, name: &str, dir: bool) -> isize {
+        if !self.is_dir {
+            return -ENOTDIR;
+        }
+        let item = match self.find(name) {
+            Ok(Some(item)) => item,
+            Ok(None) => return -ENOENT,
+            Err(err) => return err,
+        };
+        let _op = self.volume.begin();
+        match (item.entry.is_dir(), dir) {
+            (true, false) => return -EISDIR,
+            (false, true) => return -ENOTDIR,
+            _ => {}
+        }
+        if item.entry.is_dir() {
+            match self.child(&item).and_then(|child| child.items()) {
+                Ok(items) if items.iter().any(|item| !item.is_dot()) => return -ENOTEMPTY,
+                Ok(_) => {}
+                Err(err) => return err,
+            }
+        }
+        match self.release(&item).and_then(|_| self.remove_entries(&item)) {
+            Ok(()) => 0,
+            Err(err) => err,
+        }
+    }
+
+    fn rename(&self, old_name: &str, new_dir: &dyn Inode, new_name: &str) -> isize {
+        let new_dir = match new_dir.as_any().downcast_ref::<FatInode>() {
+            Some(new_dir) if Arc::ptr_eq(&self.volume, &new_dir.volume) => new_dir,
+            _ => return -EXDEV,
+        };
+        let _op = self.volume.begin();
+        let result = (|| {
+            let item = self.find(old_name)?.ok_or(-ENOENT)?;
+            let same_dir = core::ptr::eq(self, new_dir);
+            if same_dir && old_name == new_name {
+                return Ok(());
+            }
+            let old = match new_dir.find(new_name)? {
+                Some(old) => Some(old),
+                // `Foo` may become `foo`, but not replace another `foo`
+                None => match new_dir.find_conflict(new_name, same_dir.then_some(&item))? {
+                    Some(_) => return Err(-EEXIST),
+                    None => None,
+                },
+            };
+            if let Some(old) = &old {
+                new_dir.check_replace(&item, old)?;
+            }
+            let inode = self.child(&item)?;
+            let old_pos = inode.state.exclusive_access().pos;
+            if let Some(old) = &old {
+                new_dir.release(old)?;
+                new_dir.remove_entries(old)?;
+            }
+            let pos = new_dir.add_entries(new_name, item.entry.clone())?;
+            self.remove_entries(&item)?;
+            let mut inodes = self.volume.inodes.exclusive_access();
+            if let Some(old_pos) = old_pos {
+                inodes.remove(&old_pos);
+            }
+            inodes.insert(pos, Arc::downgrade(&inode));
+            drop(inodes);
+            let mut state = inode.state.exclusive_access();
+            state.pos = Some(pos);
+            state.ino = ino_of(Some(pos));
+            state.ctime = get_time_ms();
+            drop(state);
+            // `..` of a directory moved to another one
+            if inode.is_dir && !same_dir {
+                let mut state = inode.state.exclusive_access();
+                let chain = inode.clusters(&mut state)?;
+                let dotdot = inode.slot_pos(chain, 1);
+                let mut entry = ShortEntry {
+                    raw: [0u8; ENTRY_SIZE],
+                };
+                self.volume
+                    .read_bytes(dotdot.sector, dotdot.offset, &mut entry.raw);
+                drop(state);
+                entry.set_first_cluster(new_dir.dotdot_cluster());
+                self.volume
+                    .write_bytes(dotdot.sector, dotdot.offset, &entry.raw);
+            }
+            Ok(())
+        })();
+        match result {
+            Ok(()) => 0,
+            Err(err) => err,
+        }
+    }
+}
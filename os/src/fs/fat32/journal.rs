@@ -0,0 +1,252 @@
+//! Write-ahead log of the metadata of a FAT32 volume
+//!
+//! FAT has no journal: a crash in the middle of an operation, e.g. after a
+//! cluster is allocated but before it is linked to its file, leaves the volume
+//! inconsistent. Sectors of the FATs, directories and the FSInfo sector changed
+//! by an operation are kept in memory, and committed together when it ends:
+//!
+//! 1. they are written to the log and flushed, with the file data written
+//!    before, such as the content of new clusters,
+//! 2. the header of the log, with their home sectors, is written and flushed,
+//!    from then on the operation is done even after a crash,
+//! 3. they are written to their home sectors and flushed,
+//! 4. the header is cleared and flushed.
+//!
+//! A header left by a crash is replayed at mount, before the FATs are read.
+//!
+//! The log is in the reserved sectors after the boot sector, from sector
+//! [`LOG_START`], followed by the orphan list, which `mkfs.vfat` leaves unused,
+//! so other systems ignore them. A volume with fewer than 19 reserved sectors
+//! has no log. A log which cannot hold the largest operation is not used,
+//! with a warning at mount: the Makefile formats with 64 reserved sectors,
+//! the 32 `mkfs.vfat` gives by default are too few.
+//!
+//! Operations changing a number of sectors not known in advance, such as a
+//! large write, are made of steps committed one at a time, each leaving the
+//! volume consistent, see [`Journal::checkpoint`].
+//!
+//! The header holds [`LOG_MAGIC`], the number of sectors in the log as a u32
+//! at offset 8, and their home sectors from offset 16 as u32, relative to the
+//! start of the volume like the log itself.
+//!
+//! The sector after the log holds the orphan list: the first clusters of chains
+//! removed from their directory but not freed yet, such as those of files
+//! removed while open, written through the log like other metadata. The chains
+//! are freed from their end, a step at a time, then removed from the list, or
+//! at mount after a crash. It holds [`ORPHAN_MAGIC`], the number of chains as
+//! a u32 at offset 8, and their first clusters from offset 16 as u32.
+//!
+//! `fsck` reads the same format.
+
+use crate::drivers::block::{BlockDevice, BLOCK_SZ};
+use crate::warn;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+/// First sector of the log, after the boot code of other systems in sector 12
+pub const LOG_START: usize = 16;
+pub const LOG_MAGIC: &[u8; 8] = b"ACORELOG";
+/// home sectors the header holds
+const MAX_LOG_BLOCKS: usize = (BLOCK_SZ - 16) / 4;
+pub const ORPHAN_MAGIC: &[u8; 8] = b"ACOREORP";
+/// chains the orphan list holds
+pub const MAX_ORPHANS: usize = (BLOCK_SZ - 16) / 4;
+
+pub struct Journal {
+    device: Arc<dyn BlockDevice>,
+    /// absolute sector of the header, the log follows it
+    header: usize,
+    /// sector of the start of the volume
+    volume_start: usize,
+    /// sectors of the volume on the device
+    volume_sectors: usize,
+    /// sectors the log holds, 0 without a log
+    capacity: usize,
+    /// nested operations in progress
+    depth: usize,
+    /// sectors changed by the operation in progress, by absolute home sector
+    blocks: BTreeMap<usize, Box<[u8; BLOCK_SZ]>>,
+}
+
+impl Journal {
+    /// The log of the volume of `total_sectors` starting at sector
+    /// `volume_start` with `reserved_sectors`, replay what a crash left in it.
+    ///
+    /// The log is not used if it holds fewer than `max_op_sectors`.
+    pub fn open(
+        device: Arc<dyn BlockDevice>,
+        volume_start: usize,
+        total_sectors: usize,
+        reserved_sectors: usize,
+        max_op_sectors: usize,
+    ) -> Self {
+        let volume_sectors = device
+            .num_blocks()
+            .saturating_sub(volume_start)
+            .min(total_sectors);
+        let capacity = reserved_sectors
+            .saturating_sub(LOG_START + 2)
+            .min(MAX_LOG_BLOCKS);
+        let mut journal = Self {
+            device,
+            header: volume_start + LOG_START,
+            volume_start,
+            volume_sectors,
+            capacity,
+            depth: 0,
+            blocks: BTreeMap::new(),
+        };
+        if capacity > 0 {
+            journal.replay();
+        }
+        if capacity > 0 && capacity < max_op_sectors {
+            warn!(
+                "[kernel] FAT32 log of {} sectors, an operation may need {}, not used.",
+                capacity, max_op_sectors
+            );
+            journal.capacity = 0;
+        }
+        journal
+    }
+
+    /// Absolute sector of the orphan list, None without a log
+    pub fn orphan_sector(&self) -> Option<usize> {
+        (self.capacity > 0).then_some(self.header + 1 + self.capacity)
+    }
+
+    /// Install the sectors of a committed operation, if any.
+    ///
+    /// A log with a home sector in the log or past the volume is not ours, or
+    /// corrupt, and is left alone.
+    fn replay(&self) {
+        let mut header = [0u8; BLOCK_SZ];
+        self.device.read_block(self.header, &mut header);
+        if &header[..8] != LOG_MAGIC {
+            return;
+        }
+        let count = u32::from_le_bytes(header[8..12].try_into().unwrap()) as usize;
+        if count == 0 || count > self.capacity {
+            return;
+        }
+        let homes: Vec<usize> = (0..count)
+            .map(|i| {
+                let offset = 16 + i * 4;
+                u32::from_le_bytes(header[offset..offset + 4].try_into().unwrap()) as usize
+            })
+            .collect();
+        let log_end = LOG_START + 1 + self.capacity;
+        if let Some(home) = homes
+            .iter()
+            .find(|&&home| (LOG_START..log_end).contains(&home) || home >= self.volume_sectors)
+        {
+            warn!(
+                "[kernel] FAT32 log with invalid home sector {}, not replayed.",
+                home
+            );
+            return;
+        }
+        let mut data = [0u8; BLOCK_SZ];
+        for (i, home) in homes.into_iter().enumerate() {
+            self.device.read_block(self.header + 1 + i, &mut data);
+            self.device.write_block(self.volume_start + home, &data);
+        }
+        self.device.flush();
+        self.clear_header();
+    }
+
+    fn clear_header(&self) {
+        let mut header = [0u8; BLOCK_SZ];
+        header[..8].copy_from_slice(LOG_MAGIC);
+        self.device.write_block(self.header, &header);
+        self.device.flush();
+    }
+
+    /// The change of `sector` in the operation in progress, if any
+    pub fn get(&self, sector: usize) -> Option<&[u8; BLOCK_SZ]> {
+        self.blocks.get(&sector).map(|data| &**data)
+    }
+
+    /// Write metadata `data` to `sector`, in the log if in an operation.
+    pub fn write(&mut self, sector: usize, data: &[u8; BLOCK_SZ]) {
+        if self.depth == 0 || self.capacity == 0 {
+            self.device.write_block(sector, data);
+            return;
+        }
+        if let Some(block) = self.blocks.get_mut(&sector) {
+            block.copy_from_slice(data);
+            return;
+        }
+        if self.blocks.len() == self.capacity {
+            // not to happen, operations and their steps fit in the log
+            warn!("[kernel] FAT32 operation larger than the log, committed in parts.");
+            self.commit();
+        }
+        self.blocks.insert(sector, Box::new(*data));
+    }
+
+    /// Sectors the operation in progress may still change
+    pub fn room(&self) -> usize {
+        if self.capacity == 0 {
+            usize::MAX
+        } else {
+            self.capacity - self.blocks.len()
+        }
+    }
+
+    /// Write file data `data` to `sector`, not logged, but not to be undone by
+    /// a change in the operation in progress either.
+    pub fn write_data(&mut self, sector: usize, data: &[u8; BLOCK_SZ]) {
+        match self.blocks.get_mut(&sector) {
+            Some(block) => block.copy_from_slice(data),
+            None => self.device.write_block(sector, data),
+        }
+    }
+
+    pub fn begin(&mut self) {
+        self.depth += 1;
+    }
+
+    /// End an operation, commit it if it is the outermost one, return whether
+    /// it was.
+    pub fn end(&mut self) -> bool {
+        self.depth -= 1;
+        if self.depth == 0 {
+            self.commit();
+        }
+        self.depth == 0
+    }
+
+    /// Commit a step of the operation in progress, after which the volume is
+    /// consistent, unless it is nested in another one, which may not be.
+    pub fn checkpoint(&mut self) {
+        if self.depth == 1 {
+            self.commit();
+        }
+    }
+
+    fn commit(&mut self) {
+        if self.blocks.is_empty() {
+            return;
+        }
+        let mut header = [0u8; BLOCK_SZ];
+        header[..8].copy_from_slice(LOG_MAGIC);
+        header[8..12].copy_from_slice(&(self.blocks.len() as u32).to_le_bytes());
+        for (i, (&sector, data)) in self.blocks.iter().enumerate() {
+            self.device.write_block(self.header + 1 + i, &**data);
+            let offset = 16 + i * 4;
+            let home = (sector - self.volume_start) as u32;
+            header[offset..offset + 4].copy_from_slice(&home.to_le_bytes());
+        }
+        self.device.flush();
+        self.device.write_block(self.header, &header);
+        self.device.flush();
+        for (&sector, data) in self.blocks.iter() {
+            self.device.write_block(sector, &**data);
+        }
+        self.device.flush();
+        self.clear_header();
+        self.blocks.clear();
+    }
+}
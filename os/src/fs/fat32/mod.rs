@@ -0,0 +1,44 @@
+//! FAT32 on a block device, to share files with the host
+//!
+//! An image made with `mkfs.vfat -F 32` and filled with `mcopy` is given to
+//! QEMU as a virtio disk, see the Makefile. Files have long names, and may be
+//! created, extended, truncated, renamed and removed. Names are matched
+//! exactly, but a name differing only in case from another one may not be
+//! created, as other systems could not tell them apart.
+//!
+//! There are no owners, permissions or links: everything has mode 0755, or
+//! 0555 with the read-only attribute.
+//!
+//! Changes of the FATs and directories are written through a log in reserved
+//! sectors, so that a crash leaves the volume consistent, see [`journal`].
+
+mod dir;
+mod inode;
+mod journal;
+mod volume;
+
+use super::vfs::{FileSystem, Inode};
+use crate::drivers::block::BlockDevice;
+use alloc::sync::Arc;
+use inode::FatInode;
+use volume::Volume;
+
+pub struct Fat32 {
+    root: Arc<FatInode>,
+}
+
+impl Fat32 {
+    /// Open the FAT32 volume on `device`, return -EINVAL if there is none.
+    pub fn open(device: Arc<dyn BlockDevice>) -> Result<Self, isize> {
+        let volume = Arc::new(Volume::open(device)?);
+        Ok(Self {
+            root: FatInode::root(&volume),
+        })
+    }
+}
+
+impl FileSystem for Fat32 {
+    fn root(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+}
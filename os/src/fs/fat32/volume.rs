@@ -0,0 +1,507 @@
+//! Layout of a FAT32 volume: the boot sector, the FATs and the clusters
+
+use super::inode::FatInode;
+use super::journal::{Journal, MAX_ORPHANS, ORPHAN_MAGIC};
+use crate::drivers::block::{BlockDevice, BLOCK_SZ};
+use crate::syscall::errno::{EINVAL, EIO, ENOSPC};
+use crate::warn;
+use crate::UPSafeCell;
+use alloc::collections::BTreeMap;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+
+/// Entries of the FAT are 28 bits, the top 4 bits are reserved.
+const FAT_MASK: u32 = 0x0fff_ffff;
+/// Entries from this value end a cluster chain.
+const END_OF_CHAIN: u32 = 0x0fff_fff8;
+/// Written to end a chain
+const END_MARK: u32 = 0x0fff_ffff;
+
+// signatures of the FSInfo sector
+const FSINFO_LEAD: u32 = 0x4161_5252;
+const FSINFO_STRUCT: u32 = 0x6141_7272;
+/// free count or next free cluster not known
+const FSINFO_UNKNOWN: u32 = 0xffff_ffff;
+
+/// Partition types of FAT32 in an MBR
+const MBR_FAT32: [u8; 3] = [0x0b, 0x0c, 0x1c];
+
+fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+/// Position of a directory entry on the device
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub struct EntryPos {
+    pub sector: usize,
+    /// in bytes, in the sector
+    pub offset: usize,
+}
+
+/// Free clusters, see the FSInfo sector
+struct AllocState {
+    free_count: Option<usize>,
+    /// where to start looking for a free cluster
+    next_free: u32,
+}
+
+/// Chains removed from their directory, see the orphan list in [`Journal`]
+struct Orphans {
+    /// first clusters, as in the orphan list
+    recorded: Vec<u32>,
+    /// no longer used, to be freed when the operation in progress ends
+    unused: Vec<u32>,
+}
+
+/// The most sectors an operation changes: a rename with long names replacing
+/// an entry and extending the directory by two clusters, or a `mkdir` doing so.
+/// Its entries may span 3 sectors of each directory.
+fn max_op_sectors(num_fats: usize) -> usize {
+    (3 * num_fats + 12).max(4 * num_fats + 6)
+}
+
+pub struct Volume {
+    device: Arc<dyn BlockDevice>,
+    sectors_per_cluster: usize,
+    /// sectors are absolute on the device, after a partition table
+    fat_start: usize,
+    fat_sectors: usize,
+    num_fats: usize,
+    data_start: usize,
+    /// number of data clusters, numbered from 2
+    cluster_count: usize,
+    pub root_cluster: u32,
+    fsinfo_sector: Option<usize>,
+    alloc: UPSafeCell<AllocState>,
+    journal: UPSafeCell<Journal>,
+    /// None without a log
+    orphan_sector: Option<usize>,
+    orphans: UPSafeCell<Orphans>,
+    /// inodes in use by their directory entry, so that an entry has a single
+    /// inode, which `rename` can move
+    pub inodes: UPSafeCell<BTreeMap<EntryPos, Weak<FatInode>>>,
+}
+
+impl Volume {
+    /// Read the boot sector of the FAT32 volume on `device`, which may be the
+    /// first FAT32 partition of an MBR partition table.
+    pub fn open(device: Arc<dyn BlockDevice>) -> Result<Self, isize> {
+        let mut sector = [0u8; BLOCK_SZ];
+        device.read_block(0, &mut sector);
+        let mut start = 0;
+        if !Self::is_fat32_boot_sector(&sector) {
+            if sector[510..512] != [0x55, 0xaa] {
+                return Err(-EINVAL);
+            }
+            let partition = (0..4)
+                .map(|i| &sector[446 + i * 16..446 + (i + 1) * 16])
+                .find(|entry| MBR_FAT32.contains(&entry[4]))
+                .ok_or(-EINVAL)?;
+            start = read_u32(partition, 8) as usize;
+            device.read_block(start, &mut sector);
+            if !Self::is_fat32_boot_sector(&sector) {
+                return Err(-EINVAL);
+            }
+        }
+        let sectors_per_cluster = sector[13] as usize;
+        let reserved_sectors = read_u16(&sector, 14) as usize;
+        let num_fats = sector[16] as usize;
+        let total_sectors = read_u32(&sector, 32) as usize;
+        let fat_sectors = read_u32(&sector, 36) as usize;
+        let root_cluster = read_u32(&sector, 44);
+        let fsinfo = read_u16(&sector, 48) as usize;
+        if !sectors_per_cluster.is_power_of_two() || num_fats == 0 || fat_sectors == 0 {
+            return Err(-EINVAL);
+        }
+        // before anything is read from the FATs
+        let journal = Journal::open(
+            device.clone(),
+            start,
+            total_sectors,
+            reserved_sectors,
+            max_op_sectors(num_fats),
+        );
+        let orphan_sector = journal.orphan_sector();
+        let fat_start = start + reserved_sectors;
+        let data_start = fat_start + num_fats * fat_sectors;
+        let data_sectors = (start + total_sectors)
+            .min(device.num_blocks())
+            .checked_sub(data_start)
+            .ok_or(-EINVAL)?;
+        // the FAT may be too small for all sectors
+        let cluster_count =
+            (data_sectors / sectors_per_cluster).min(fat_sectors * BLOCK_SZ / 4 - 2);
+        let mut volume = Self {
+            device,
+            sectors_per_cluster,
+            fat_start,
+            fat_sectors,
+            num_fats,
+            data_start,
+            cluster_count,
+            root_cluster,
+            fsinfo_sector: None,
+            alloc: UPSafeCell::new(AllocState {
+                free_count: None,
+                next_free: 2,
+            }),
+            journal: UPSafeCell::new(journal),
+            orphan_sector,
+            orphans: UPSafeCell::new(Orphans {
+                recorded: Vec::new(),
+                unused: Vec::new(),
+            }),
+            inodes: UPSafeCell::new(BTreeMap::new()),
+        };
+        if !volume.is_cluster(root_cluster) {
+            return Err(-EINVAL);
+        }
+        if fsinfo != 0 && fsinfo != 0xffff && fsinfo < reserved_sectors {
+            volume.device.read_block(start + fsinfo, &mut sector);
+            if read_u32(&sector, 0) == FSINFO_LEAD && read_u32(&sector, 484) == FSINFO_STRUCT {
+                volume.fsinfo_sector = Some(start + fsinfo);
+                let mut alloc = volume.alloc.exclusive_access();
+                let free_count = read_u32(&sector, 488);
+                if free_count as usize <= cluster_count {
+                    alloc.free_count = Some(free_count as usize);
+                }
+                let next_free = read_u32(&sector, 492);
+                if volume.is_cluster(next_free) {
+                    alloc.next_free = next_free;
+                }
+            }
+        }
+        // chains left by a crash, nothing uses them now
+        if let Some(orphan_sector) = orphan_sector {
+            volume.device.read_block(orphan_sector, &mut sector);
+            let count = read_u32(&sector, 8) as usize;
+            if &sector[..8] == ORPHAN_MAGIC && count <= MAX_ORPHANS {
+                let recorded: Vec<u32> =
+                    (0..count).map(|i| read_u32(&sector, 16 + i * 4)).collect();
+                *volume.orphans.exclusive_access() = Orphans {
+                    unused: recorded.clone(),
+                    recorded,
+                };
+            }
+            volume.free_orphans();
+        }
+        Ok(volume)
+    }
+
+    /// A FAT32 boot sector has no root directory entries and no 16-bit FAT
+    /// size, and 512-byte sectors like the block layer.
+    fn is_fat32_boot_sector(sector: &[u8]) -> bool {
+        sector[510..512] == [0x55, 0xaa]
+            && read_u16(sector, 11) as usize == BLOCK_SZ
+            && read_u16(sector, 17) == 0
+            && read_u16(sector, 22) == 0
+            && read_u32(sector, 36) != 0
+    }
+
+    pub fn cluster_size(&self) -> usize {
+        self.sectors_per_cluster * BLOCK_SZ
+    }
+
+    pub fn sectors_per_cluster(&self) -> usize {
+        self.sectors_per_cluster
+    }
+
+    fn is_cluster(&self, cluster: u32) -> bool {
+        cluster >= 2 && (cluster as usize) < self.cluster_count + 2
+    }
+
+    /// First sector of `cluster`
+    pub fn cluster_sector(&self, cluster: u32) -> usize {
+        self.data_start + (cluster as usize - 2) * self.sectors_per_cluster
+    }
+
+    /// Start an operation, whose changes of metadata are committed together
+    /// when it is dropped, see [`Journal`].
+    pub fn begin(&self) -> Operation<'_> {
+        self.journal.exclusive_access().begin();
+        Operation { volume: self }
+    }
+
+    /// Whether the log may not hold another step of the operation in progress,
+    /// which allocates or frees a cluster and changes an entry.
+    pub fn needs_checkpoint(&self) -> bool {
+        self.journal.exclusive_access().room() < 2 * self.num_fats + 2
+    }
+
+    /// Commit the steps of the operation in progress, the volume must be
+    /// consistent.
+    pub fn checkpoint(&self) {
+        self.journal.exclusive_access().checkpoint();
+    }
+
+    pub fn read_sector(&self, sector: usize, buf: &mut [u8; BLOCK_SZ]) {
+        match self.journal.exclusive_access().get(sector) {
+            Some(data) => buf.copy_from_slice(data),
+            None => self.device.read_block(sector, buf),
+        }
+    }
+
+    /// Read `buf.len()` bytes at `offset` of `sector`, all in the sector.
+    pub fn read_bytes(&self, sector: usize, offset: usize, buf: &mut [u8]) {
+        let mut data = [0u8; BLOCK_SZ];
+        self.read_sector(sector, &mut data);
+        buf.copy_from_slice(&data[offset..offset + buf.len()]);
+    }
+
+    /// Change `buf.len()` bytes at `offset` of `sector`, all in the sector.
+    fn modify_sector(&self, sector: usize, offset: usize, buf: &[u8]) -> [u8; BLOCK_SZ] {
+        let mut data = [0u8; BLOCK_SZ];
+        if buf.len() < BLOCK_SZ {
+            self.read_sector(sector, &mut data);
+        }
+        data[offset..offset + buf.len()].copy_from_slice(buf);
+        data
+    }
+
+    /// Write metadata `buf` at `offset` of `sector`, all in the sector.
+    pub fn write_bytes(&self, sector: usize, offset: usize, buf: &[u8]) {
+        let data = self.modify_sector(sector, offset, buf);
+        self.journal.exclusive_access().write(sector, &data);
+    }
+
+    /// Write file data `buf` at `offset` of `sector`, all in the sector.
+    pub fn write_data(&self, sector: usize, offset: usize, buf: &[u8]) {
+        let data = self.modify_sector(sector, offset, buf);
+        self.journal.exclusive_access().write_data(sector, &data);
+    }
+
+    /// Write the blocks changed in the block cache to the device.
+    pub fn sync(&self) {
+        self.device.flush();
+    }
+
+    fn fat_entry(&self, cluster: u32) -> u32 {
+        let offset = cluster as usize * 4;
+        let mut entry = [0u8; 4];
+        self.read_bytes(
+            self.fat_start + offset / BLOCK_SZ,
+            offset % BLOCK_SZ,
+            &mut entry,
+        );
+        u32::from_le_bytes(entry) & FAT_MASK
+    }
+
+    /// Set the entry of `cluster` in every FAT, keeping the reserved bits.
+    fn set_fat_entry(&self, cluster: u32, value: u32) {
+        let offset = cluster as usize * 4;
+        for fat in 0..self.num_fats {
+            let sector = self.fat_start + fat * self.fat_sectors + offset / BLOCK_SZ;
+            let mut entry = [0u8; 4];
+            self.read_bytes(sector, offset % BLOCK_SZ, &mut entry);
+            let old = u32::from_le_bytes(entry);
+            let new = (old & !FAT_MASK) | (value & FAT_MASK);
+            self.write_bytes(sector, offset % BLOCK_SZ, &new.to_le_bytes());
+        }
+    }
+
+    /// The clusters of the chain starting at `first`, empty if `first` is 0.
+    ///
+    /// Return -EIO if the chain is broken or loops.
+    pub fn chain(&self, first: u32) -> Result<Vec<u32>, isize> {
+        let mut chain = Vec::new();
+        let mut cluster = first;
+        while cluster != 0 {
+            if !self.is_cluster(cluster) || chain.len() >= self.cluster_count {
+                return Err(-EIO);
+            }
+            chain.push(cluster);
+            cluster = match self.fat_entry(cluster) {
+                next if next >= END_OF_CHAIN => 0,
+                0 => return Err(-EIO),
+                next => next,
+            };
+        }
+        Ok(chain)
+    }
+
+    /// Allocate a zeroed cluster at the end of the chain ending with `last`.
+    pub fn alloc_cluster(&self, last: Option<u32>) -> Result<u32, isize> {
+        let mut alloc = self.alloc.exclusive_access();
+        if alloc.free_count == Some(0) {
+            return Err(-ENOSPC);
+        }
+        let start = alloc.next_free;
+        let mut cluster = start;
+        loop {
+            if self.fat_entry(cluster) == 0 {
+                break;
+            }
+            cluster += 1;
+            if !self.is_cluster(cluster) {
+                cluster = 2;
+            }
+            if cluster == start {
+                alloc.free_count = Some(0);
+                return Err(-ENOSPC);
+            }
+        }
+        self.set_fat_entry(cluster, END_MARK);
+        if let Some(last) = last {
+            self.set_fat_entry(last, cluster);
+        }
+        alloc.next_free = cluster;
+        if let Some(free_count) = alloc.free_count.as_mut() {
+            *free_count -= 1;
+        }
+        drop(alloc);
+        let zeros = [0u8; BLOCK_SZ];
+        let first_sector = self.cluster_sector(cluster);
+        for sector in first_sector..first_sector + self.sectors_per_cluster {
+            self.write_data(sector, 0, &zeros);
+        }
+        self.write_fsinfo();
+        Ok(cluster)
+    }
+
+    /// Free the clusters of `chain` from `from`, end the chain before it.
+    ///
+    /// Only for a few clusters, such as those just allocated, others are freed
+    /// as orphans, see [`Volume::free_later`].
+    pub fn free_clusters(&self, chain: &[u32], from: usize) {
+        if from >= chain.len() {
+            return;
+        }
+        if from > 0 {
+            self.set_fat_entry(chain[from - 1], END_MARK);
+        }
+        for &cluster in &chain[from..] {
+            self.set_fat_entry(cluster, 0);
+        }
+        let mut alloc = self.alloc.exclusive_access();
+        if let Some(free_count) = alloc.free_count.as_mut() {
+            *free_count += chain.len() - from;
+        }
+        alloc.next_free = alloc.next_free.min(chain[from]);
+        drop(alloc);
+        self.write_fsinfo();
+    }
+
+    /// End the chain at `last`, the clusters after it must be freed as an orphan.
+    pub fn end_chain(&self, last: u32) {
+        self.set_fat_entry(last, END_MARK);
+    }
+
+    /// Add the chain from `first` to the orphan list, as it is removed from its
+    /// directory, so that it is freed at mount after a crash.
+    ///
+    /// Return -ENOSPC if the list is full.
+    pub fn add_orphan(&self, first: u32) -> Result<(), isize> {
+        if self.orphan_sector.is_none() {
+            return Ok(());
+        }
+        let mut orphans = self.orphans.exclusive_access();
+        if orphans.recorded.len() == MAX_ORPHANS {
+            return Err(-ENOSPC);
+        }
+        orphans.recorded.push(first);
+        drop(orphans);
+        self.write_orphans();
+        Ok(())
+    }
+
+    /// Free the orphan chain from `first` when the operation in progress ends.
+    pub fn free_later(&self, first: u32) {
+        self.orphans.exclusive_access().unused.push(first);
+    }
+
+    /// Free the chains of unused orphans and remove them from the orphan list,
+    /// each from its end a step at a time, so that it stays a chain.
+    fn free_orphans(&self) {
+        loop {
+            let first = match self.orphans.exclusive_access().unused.pop() {
+                Some(first) => first,
+                None => return,
+            };
+            self.journal.exclusive_access().begin();
+            match self.chain(first) {
+                Ok(mut chain) => {
+                    while let Some(cluster) = chain.pop() {
+                        if self.needs_checkpoint() {
+                            self.write_fsinfo();
+                            self.checkpoint();
+                        }
+                        if let Some(&last) = chain.last() {
+                            self.set_fat_entry(last, END_MARK);
+                        }
+                        self.set_fat_entry(cluster, 0);
+                        let mut alloc = self.alloc.exclusive_access();
+                        if let Some(free_count) = alloc.free_count.as_mut() {
+                            *free_count += 1;
+                        }
+                        alloc.next_free = alloc.next_free.min(cluster);
+                    }
+                }
+                Err(_) => {
+                    warn!(
+                        "[kernel] FAT32 orphan chain from cluster {} is broken, not freed.",
+                        first
+                    );
+                }
+            }
+            let mut orphans = self.orphans.exclusive_access();
+            if let Some(index) = orphans.recorded.iter().position(|&c| c == first) {
+                orphans.recorded.remove(index);
+                drop(orphans);
+                self.write_orphans();
+            }
+            self.write_fsinfo();
+            self.journal.exclusive_access().end();
+        }
+    }
+
+    fn write_orphans(&self) {
+        let sector = match self.orphan_sector {
+            Some(sector) => sector,
+            None => return,
+        };
+        let orphans = self.orphans.exclusive_access();
+        let mut data = [0u8; BLOCK_SZ];
+        data[..8].copy_from_slice(ORPHAN_MAGIC);
+        data[8..12].copy_from_slice(&(orphans.recorded.len() as u32).to_le_bytes());
+        for (i, first) in orphans.recorded.iter().enumerate() {
+            data[16 + i * 4..20 + i * 4].copy_from_slice(&first.to_le_bytes());
+        }
+        drop(orphans);
+        self.write_bytes(sector, 0, &data);
+    }
+
+    /// Save the free count and next free cluster for other systems.
+    fn write_fsinfo(&self) {
+        let sector = match self.fsinfo_sector {
+            Some(sector) => sector,
+            None => return,
+        };
+        let alloc = self.alloc.exclusive_access();
+        let free_count = alloc
+            .free_count
+            .map_or(FSINFO_UNKNOWN, |count| count as u32);
+        let mut fields = [0u8; 8];
+        fields[..4].copy_from_slice(&free_count.to_le_bytes());
+        fields[4..].copy_from_slice(&alloc.next_free.to_le_bytes());
+        drop(alloc);
+        self.write_bytes(sector, 488, &fields);
+    }
+}
+
+/// An operation in progress on a volume, see [`Volume::begin`]
+pub struct Operation<'a> {
+    volume: &'a Volume,
+}
+
+impl Drop for Operation<'_> {
+    fn drop(&mut self) {
+        let outermost = self.volume.journal.exclusive_access().end();
+        if outermost {
+            self.volume.free_orphans();
+        }
+    }
+}
@@ -0,0 +1,143 @@
+//! Opened files of the VFS
+
+use super::vfs::{Dentry, DirEntry, Inode, InodeType, Metadata};
+use super::{File, WriteAccess};
+use crate::mm::page_cache;
+use crate::syscall::errno::EINVAL;
+use crate::UPSafeCell;
+use alloc::string::String;
+use alloc::sync::Arc;
+
+/// A regular file or a directory opened by `sys_open`
+pub struct InodeFile {
+    readable: bool,
+    /// held while the file is open for writing
+    write_access: Option<WriteAccess>,
+    append: bool,
+    offset: UPSafeCell<usize>,
+    dentry: Arc<Dentry>,
+}
+
+impl InodeFile {
+    pub fn new(
+        readable: bool,
+        write_access: Option<WriteAccess>,
+        append: bool,
+        dentry: Arc<Dentry>,
+    ) -> Self {
+        Self {
+            readable,
+            write_access,
+            append,
+            offset: UPSafeCell::new(0),
+            dentry,
+        }
+    }
+}
+
+impl File for InodeFile {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn writable(&self) -> bool {
+        self.write_access.is_some()
+    }
+
+    /// The offset is not borrowed while reading, since devices may block and
+    /// the file may be shared with other processes.
+    fn read(&self, buf: &mut [u8]) -> isize {
+        let offset = *self.offset.exclusive_access();
+        let n = self.dentry.inode.read_at(offset, buf);
+        if n > 0 {
+            *self.offset.exclusive_access() = offset + n as usize;
+        }
+        n
+    }
+
+    fn write(&self, buf: &[u8]) -> isize {
+        let offset = if self.append {
+            self.dentry.inode.size()
+        } else {
+            *self.offset.exclusive_access()
+        };
+        let n = self.dentry.inode.write_at(offset, buf);
+        if n > 0 {
+            *self.offset.exclusive_access() = offset + n as usize;
+            // seen by mappings of the file
+            page_cache::write(&self.dentry.inode, offset, &buf[..n as usize]);
+        }
+        n
+    }
+
+    fn metadata(&self) -> Metadata {
+        self.dentry.inode.metadata()
+    }
+
+    fn sync(&self) -> isize {
+        self.dentry.inode.sync()
+    }
+
+    fn is_terminal(&self) -> bool {
+        self.dentry.inode.is_terminal()
+    }
+
+    fn inode(&self) -> Option<Arc<dyn Inode>> {
+        Some(self.dentry.inode.clone())
+    }
+
+    /// The offset is the index of the next entry, from `.` and `..`.
+    fn read_dir(&self, buf: &mut [u8]) -> isize {
+        let mut entries = match self.dentry.inode.list() {
+            Ok(entries) => entries,
+            Err(err) => return err,
+        };
+        for name in ["..", "."] {
+            entries.insert(
+                0,
+                DirEntry {
+                    name: String::from(name),
+                    inode_type: InodeType::Dir,
+                },
+            );
+        }
+        let mut offset = self.offset.exclusive_access();
+        let mut len = 0;
+        for (index, entry) in entries.iter().enumerate().skip(*offset) {
+            match write_dirent(&mut buf[len..], entry, index + 1) {
+                Some(n) => len += n,
+                // too small for a single entry
+                None if len == 0 => return -EINVAL,
+                None => break,
+            }
+            *offset = index + 1;
+        }
+        len as isize
+    }
+}
+
+const DT_DIR: u8 = 4;
+const DT_REG: u8 = 8;
+
+/// Write `entry` as a `linux_dirent64` record, return its length, or None if
+/// `buf` is too small. `next` is the offset of the next entry.
+fn write_dirent(buf: &mut [u8], entry: &DirEntry, next: usize) -> Option<usize> {
+    // d_ino: u64, d_off: i64, d_reclen: u16, d_type: u8, d_name
+    const NAME_OFFSET: usize = 19;
+    let reclen = (NAME_OFFSET + entry.name.len() + 1 + 7) & !7;
+    if buf.len() < reclen {
+        return None;
+    }
+    let record = &mut buf[..reclen];
+    record.fill(0);
+    // no inode numbers yet
+    record[0..8].copy_from_slice(&1u64.to_le_bytes());
+    record[8..16].copy_from_slice(&(next as i64).to_le_bytes());
+    record[16..18].copy_from_slice(&(reclen as u16).to_le_bytes());
+    record[18] = match entry.inode_type {
+        InodeType::Dir => DT_DIR,
+        InodeType::File => DT_REG,
+    };
+    record[NAME_OFFSET..NAME_OFFSET + entry.name.len()].copy_from_slice(entry.name.as_bytes());
+    Some(reclen)
+}
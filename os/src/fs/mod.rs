@@ -0,0 +1,218 @@
+//! Files which processes access through file descriptors
+//!
+//! Every process has a table of opened files indexed by file descriptor, see
+//! `ProcessControlBlockInner::fd_table`. A file may be shared by several
+//! descriptors after `dup` or `fork`, and is closed when the last one is closed.
+//!
+//! Named files are found through the VFS, see [`vfs`]: a memory filesystem is
+//! mounted on `/`, with the applications linked into the kernel in `/bin`, the
+//! state of processes and the kernel is shown in `/proc`, and devices in `/dev`.
+//! Standard input and output of every process are `/dev/console`. A FAT32 disk
+//! is mounted on `/mnt`, or on `/` when built with `ROOT_FS=fat`.
+
+mod devfs;
+mod fat32;
+mod inode_file;
+mod pipe;
+mod procfs;
+mod ramfs;
+pub mod vfs;
+mod write_access;
+
+use crate::drivers::block::virtio_block_devices;
+use crate::loader::{app_data, app_names};
+use crate::mm::page_cache;
+use crate::syscall::errno::{EEXIST, EINVAL, EISDIR, ENOENT, ENOTDIR};
+use alloc::sync::Arc;
+use bitflags::*;
+pub use devfs::register_block_device;
+use devfs::DevFs;
+use fat32::Fat32;
+pub use inode_file::InodeFile;
+pub use pipe::make_pipe;
+use procfs::ProcFs;
+use ramfs::{RamFs, RamInode};
+pub use vfs::{Dentry, Metadata};
+use vfs::{FileSystem, Inode, InodeType};
+pub use write_access::WriteAccess;
+
+/// An opened file
+///
+/// `read` and `write` work on kernel buffers, and may block the current thread.
+/// They return the number of bytes transferred, or a negated errno.
+pub trait File: Send + Sync {
+    fn readable(&self) -> bool;
+    fn writable(&self) -> bool;
+    /// Return 0 at the end of file.
+    fn read(&self, buf: &mut [u8]) -> isize;
+    fn write(&self, buf: &[u8]) -> isize;
+    fn metadata(&self) -> Metadata;
+    /// Write the changes of the file to its device, for `sys_fsync`, return
+    /// -EINVAL if it has none.
+    fn sync(&self) -> isize {
+        -EINVAL
+    }
+    /// Whether it is the console, for `sys_tcsetpgrp` and `sys_tcgetpgrp`
+    fn is_terminal(&self) -> bool {
+        false
+    }
+    /// The inode of a file opened by name, for `sys_mmap`
+    fn inode(&self) -> Option<Arc<dyn Inode>> {
+        None
+    }
+    /// Fill `buf` with the next entries of a directory as `linux_dirent64`
+    /// records, return the number of bytes filled, 0 at the end.
+    fn read_dir(&self, _buf: &mut [u8]) -> isize {
+        -ENOTDIR
+    }
+}
+
+bitflags! {
+    /// `flags` of `sys_open`, following Linux, read only if neither `WRONLY` nor `RDWR`
+    pub struct OpenFlags: u32 {
+        const WRONLY = 1 << 0;
+        const RDWR = 1 << 1;
+        const CREATE = 1 << 6;
+        const TRUNC = 1 << 9;
+        const APPEND = 1 << 10;
+        /// fail if it is not a directory
+        const DIRECTORY = 1 << 16;
+    }
+}
+
+impl OpenFlags {
+    /// Return (readable, writable).
+    pub fn read_write(&self) -> (bool, bool) {
+        if self.contains(Self::WRONLY) {
+            (false, true)
+        } else if self.contains(Self::RDWR) {
+            (true, true)
+        } else {
+            (true, false)
+        }
+    }
+}
+
+/// Open the file `path`, relative to `cwd`, return a negated errno on failure.
+pub fn open_file(cwd: &Arc<Dentry>, path: &str, flags: OpenFlags) -> Result<Arc<dyn File>, isize> {
+    let (readable, writable) = flags.read_write();
+    let dentry = match vfs::lookup(cwd, path) {
+        Ok(dentry) => {
+            if dentry.is_dir() && writable {
+                return Err(-EISDIR);
+            }
+            if !dentry.is_dir() && flags.contains(OpenFlags::DIRECTORY) {
+                return Err(-ENOTDIR);
+            }
+            dentry
+        }
+        Err(err) if err == -ENOENT && flags.contains(OpenFlags::CREATE) => {
+            let (dir, name) = vfs::lookup_parent(cwd, path)?;
+            dir.create(name, InodeType::File)?
+        }
+        Err(err) => return Err(err),
+    };
+    // -ETXTBSY for the file of a running program
+    let write_access = if writable {
+        Some(WriteAccess::write(&dentry.inode)?)
+    } else {
+        None
+    };
+    if writable && flags.contains(OpenFlags::TRUNC) {
+        let ret = dentry.inode.truncate(0);
+        if ret < 0 {
+            return Err(ret);
+        }
+        page_cache::truncate(&dentry.inode, 0);
+    }
+    Ok(Arc::new(InodeFile::new(
+        readable,
+        write_access,
+        flags.contains(OpenFlags::APPEND),
+        dentry,
+    )))
+}
+
+/// Metadata of `path`, relative to `cwd`.
+pub fn stat(cwd: &Arc<Dentry>, path: &str) -> Result<Metadata, isize> {
+    Ok(vfs::lookup(cwd, path)?.inode.metadata())
+}
+
+/// Create the directory `path`, relative to `cwd`.
+pub fn mkdir(cwd: &Arc<Dentry>, path: &str) -> isize {
+    let (dir, name) = match vfs::lookup_parent(cwd, path) {
+        Ok(parent) => parent,
+        Err(err) => return err,
+    };
+    if matches!(name, "" | "." | "..") {
+        return -EEXIST;
+    }
+    match dir.create(name, InodeType::Dir) {
+        Ok(_) => 0,
+        Err(err) => err,
+    }
+}
+
+/// Remove `path`, relative to `cwd`, an empty directory if `dir`, or a file
+/// otherwise.
+pub fn unlink(cwd: &Arc<Dentry>, path: &str, dir: bool) -> isize {
+    let (parent, name) = match vfs::lookup_parent(cwd, path) {
+        Ok(parent) => parent,
+        Err(err) => return err,
+    };
+    if matches!(name, "" | "." | "..") {
+        return -EINVAL;
+    }
+    parent.unlink(name, dir)
+}
+
+/// Add the applications linked into the kernel to `dir`.
+fn add_apps(dir: &RamInode) {
+    for name in app_names() {
+        dir.add_static(name, app_data(name).unwrap()).unwrap();
+    }
+}
+
+/// Mount a memory filesystem on `/`, seeded with the applications in `/bin`,
+/// procfs on `/proc` and devfs on `/dev`, and the FAT32 volume of the first
+/// virtio disk, if any, on `/mnt`.
+///
+/// With `ROOT_FS=fat` at build time, the FAT32 volume is mounted on `/`
+/// instead, and the applications on `/bin` in memory.
+pub fn init() {
+    let disks = virtio_block_devices();
+    for (i, disk) in disks.iter().enumerate() {
+        let name = alloc::format!("vd{}", (b'a' + i as u8) as char);
+        register_block_device(&name, disk.clone(), true);
+    }
+    let fat = disks
+        .first()
+        .and_then(|disk| Fat32::open(disk.clone()).ok());
+    match fat {
+        Some(fat) if option_env!("ROOT_FS") == Some("fat") => {
+            let root = fat.root();
+            for dir in ["bin", "proc", "dev"] {
+                if root.lookup(dir).is_none() {
+                    root.create(dir, InodeType::Dir).unwrap();
+                }
+            }
+            vfs::mount("/", Arc::new(fat)).unwrap();
+            let apps = RamFs::new();
+            add_apps(apps.root_dir());
+            vfs::mount("/bin", Arc::new(apps)).unwrap();
+        }
+        fat => {
+            let ramfs = RamFs::new();
+            add_apps(&ramfs.root_dir().mkdir("bin").unwrap());
+            for dir in ["proc", "dev", "mnt"] {
+                ramfs.root_dir().mkdir(dir).unwrap();
+            }
+            vfs::mount("/", Arc::new(ramfs)).unwrap();
+            if let Some(fat) = fat {
+                vfs::mount("/mnt", Arc::new(fat)).unwrap();
+            }
+        }
+    }
+    vfs::mount("/proc", Arc::new(ProcFs)).unwrap();
+    vfs::mount("/dev", Arc::new(DevFs)).unwrap();
+}
@@ -0,0 +1,133 @@
+//! Pipes, a ring buffer with a read end and a write end
+
+use super::vfs::{Metadata, S_IFIFO};
+use super::File;
+use crate::syscall::errno::{EINTR, EPIPE};
+use crate::task::signal::{current_has_deliverable_signal, send_signal, SIGPIPE};
+use crate::task::*;
+use crate::UPSafeCell;
+use alloc::collections::VecDeque;
+use alloc::sync::{Arc, Weak};
+
+const PIPE_CAPACITY: usize = 4096;
+
+/// One end of a pipe
+pub struct Pipe {
+    readable: bool,
+    writable: bool,
+    buffer: Arc<UPSafeCell<PipeBuffer>>,
+}
+
+struct PipeBuffer {
+    data: VecDeque<u8>,
+    /// the ends are closed when all their descriptors are closed
+    read_end: Weak<Pipe>,
+    write_end: Weak<Pipe>,
+}
+
+/// Create a pipe, return (read end, write end).
+pub fn make_pipe() -> (Arc<Pipe>, Arc<Pipe>) {
+    let buffer = Arc::new(UPSafeCell::new(PipeBuffer {
+        data: VecDeque::new(),
+        read_end: Weak::new(),
+        write_end: Weak::new(),
+    }));
+    let read_end = Arc::new(Pipe {
+        readable: true,
+        writable: false,
+        buffer: buffer.clone(),
+    });
+    let write_end = Arc::new(Pipe {
+        readable: false,
+        writable: true,
+        buffer: buffer.clone(),
+    });
+    let mut inner = buffer.exclusive_access();
+    inner.read_end = Arc::downgrade(&read_end);
+    inner.write_end = Arc::downgrade(&write_end);
+    drop(inner);
+    (read_end, write_end)
+}
+
+/// Wait for the other end, return false if interrupted by a signal.
+fn wait_other_end() -> bool {
+    suspend_current_and_run_next();
+    exit_current_if_killed();
+    !current_has_deliverable_signal()
+}
+
+impl File for Pipe {
+    fn readable(&self) -> bool {
+        self.readable
+    }
+
+    fn writable(&self) -> bool {
+        self.writable
+    }
+
+    /// Block until there is some data, return 0 if the write end is closed.
+    fn read(&self, buf: &mut [u8]) -> isize {
+        assert!(self.readable);
+        loop {
+            let mut inner = self.buffer.exclusive_access();
+            if !inner.data.is_empty() || buf.is_empty() {
+                let n = buf.len().min(inner.data.len());
+                for (dst, src) in buf.iter_mut().zip(inner.data.drain(..n)) {
+                    *dst = src;
+                }
+                return n as isize;
+            }
+            if inner.write_end.upgrade().is_none() {
+                return 0;
+            }
+            drop(inner);
+            if !wait_other_end() {
+                return -EINTR;
+            }
+        }
+    }
+
+    /// Block until all data is written.
+    ///
+    /// If the read end is closed, SIGPIPE is sent to the current process and
+    /// -EPIPE is returned.
+    fn write(&self, buf: &[u8]) -> isize {
+        assert!(self.writable);
+        let mut written = 0;
+        loop {
+            let mut inner = self.buffer.exclusive_access();
+            if inner.read_end.upgrade().is_none() {
+                drop(inner);
+                send_signal(&current_process(), SIGPIPE);
+                return -EPIPE;
+            }
+            let n = (buf.len() - written).min(PIPE_CAPACITY - inner.data.len());
+            inner.data.extend(&buf[written..written + n]);
+            written += n;
+            drop(inner);
+            if written == buf.len() {
+                return written as isize;
+            }
+            if !wait_other_end() {
+                return if written > 0 {
+                    written as isize
+                } else {
+                    -EINTR
+                };
+            }
+        }
+    }
+
+    /// Pipes are not in any filesystem, and have size 0 like in Linux.
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            ino: 0,
+            mode: S_IFIFO | 0o600,
+            nlink: 1,
+            size: 0,
+            atime: 0,
+            mtime: 0,
+            ctime: 0,
+        }
+    }
+}
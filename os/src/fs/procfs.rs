@@ -0,0 +1,251 @@
+//! `/proc`, files generated from the state of processes and the kernel
+//!
+//! - `/proc/<pid>/status`: state, parent, children, exit code and memory areas
+//! - `/proc/self`: the directory of the current process
+//! - `/proc/meminfo`: usage of physical frames and the kernel heap
+//! - `/proc/uptime`: seconds since boot
+//!
+//! A file is generated again on every read, and has size 0 like in Linux. The
+//! times of every entry are the current time.
+//! Parents, children and exit codes are asked to the process manager.
+
+use super::vfs::{DirEntry, FileSystem, Inode, InodeType, Metadata, S_IFDIR, S_IFREG};
+use crate::config::PAGE_SIZE;
+use crate::mm::frame_allocator::frame_allocator_stats;
+use crate::mm::heap_allocator::heap_stats;
+use crate::mm::map_area::MapPermission;
+use crate::syscall::errno::{EISDIR, ENOTDIR, EROFS, ESRCH};
+use crate::task::service::{self, ProcessInfo};
+use crate::task::{current_pid, current_process, has_ready_task, pid2process};
+use crate::timer::get_time_ms;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+enum ProcInode {
+    Root,
+    MemInfo,
+    Uptime,
+    /// `/proc/<pid>`
+    Process(usize),
+    /// `/proc/<pid>/status`
+    Status(usize),
+}
+
+fn status(pid: usize) -> Option<String> {
+    let ProcessInfo {
+        parent,
+        children,
+        zombie,
+        exit_code,
+    } = service::proc_info(pid)?;
+    let mut text = format!("Pid:\t{}\n", pid);
+    // exited processes are only known by the process manager
+    let process = pid2process(pid).filter(|_| !zombie);
+    let state = match &process {
+        None => "Z (zombie)",
+        Some(process) if process.inner_exclusive_access().stopped => "T (stopped)",
+        Some(_) if pid == current_pid() => "R (running)",
+        Some(process) if has_ready_task(process) => "R (ready)",
+        Some(_) => "S (sleeping)",
+    };
+    writeln!(text, "State:\t{}", state).unwrap();
+    writeln!(text, "PPid:\t{}", parent).unwrap();
+    let children: Vec<String> = children.iter().map(|child| child.to_string()).collect();
+    writeln!(text, "Children:\t{}", children.join(" ")).unwrap();
+    if zombie {
+        // wait status, see `exited_status`
+        if exit_code & 0x7f == 0 {
+            writeln!(text, "ExitCode:\t{}", (exit_code >> 8) & 0xff).unwrap();
+        } else {
+            writeln!(text, "ExitSignal:\t{}", exit_code & 0x7f).unwrap();
+        }
+    }
+    let process = match process {
+        Some(process) => process,
+        None => return Some(text),
+    };
+    let inner = process.inner_exclusive_access();
+    writeln!(text, "Threads:\t{}", inner.thread_count()).unwrap();
+    writeln!(text, "Pgid:\t{}", inner.pgid).unwrap();
+    writeln!(text, "Sid:\t{}", inner.sid).unwrap();
+    writeln!(
+        text,
+        "VmResident:\t{} kB",
+        inner.memory_set.resident_count() * PAGE_SIZE / 1024
+    )
+    .unwrap();
+    // start-end, permissions, pages resident and swapped out of all pages
+    writeln!(text, "Areas:").unwrap();
+    for area in inner.memory_set.areas.iter() {
+        let range = area.vpn_range;
+        let perm = area.map_perm();
+        let flag = |bit, c| if perm.contains(bit) { c } else { '-' };
+        writeln!(
+            text,
+            "\t{:#x}-{:#x} {}{}{}{} {}+{}/{}",
+            range.start.0 * PAGE_SIZE,
+            range.end.0 * PAGE_SIZE,
+            flag(MapPermission::R, 'r'),
+            flag(MapPermission::W, 'w'),
+            flag(MapPermission::X, 'x'),
+            flag(MapPermission::U, 'u'),
+            area.resident_count(),
+            area.swapped_count(),
+            range.end.0 - range.start.0,
+        )
+        .unwrap();
+    }
+    Some(text)
+}
+
+fn meminfo() -> String {
+    let frames = frame_allocator_stats();
+    let heap = heap_stats();
+    let kb = |frames: usize| frames * PAGE_SIZE / 1024;
+    let mut text = String::new();
+    writeln!(text, "MemTotal:\t{} kB", kb(frames.total)).unwrap();
+    writeln!(text, "MemFree:\t{} kB", kb(frames.total - frames.allocated)).unwrap();
+    writeln!(text, "MemUsed:\t{} kB", kb(frames.allocated)).unwrap();
+    writeln!(text, "MemLargestFree:\t{} kB", kb(frames.largest_free)).unwrap();
+    writeln!(text, "HeapTotal:\t{} kB", heap.total / 1024).unwrap();
+    writeln!(text, "HeapUsed:\t{} kB", heap.allocated / 1024).unwrap();
+    text
+}
+
+fn uptime() -> String {
+    let ms = get_time_ms();
+    format!("{}.{:02}\n", ms / 1000, ms % 1000 / 10)
+}
+
+impl ProcInode {
+    fn text(&self) -> Option<String> {
+        match self {
+            ProcInode::MemInfo => Some(meminfo()),
+            ProcInode::Uptime => Some(uptime()),
+            ProcInode::Status(pid) => status(*pid),
+            ProcInode::Root | ProcInode::Process(_) => None,
+        }
+    }
+
+    /// Fixed numbers for the root and the files in it, and a range of 16
+    /// numbers for each process
+    fn ino(&self) -> usize {
+        match self {
+            ProcInode::Root => 1,
+            ProcInode::MemInfo => 2,
+            ProcInode::Uptime => 3,
+            ProcInode::Process(pid) => (pid + 1) * 16,
+            ProcInode::Status(pid) => (pid + 1) * 16 + 1,
+        }
+    }
+}
+
+impl Inode for ProcInode {
+    fn inode_type(&self) -> InodeType {
+        match self {
+            ProcInode::Root | ProcInode::Process(_) => InodeType::Dir,
+            _ => InodeType::File,
+        }
+    }
+
+    fn metadata(&self) -> Metadata {
+        let (mode, nlink) = match self.inode_type() {
+            InodeType::Dir => (S_IFDIR | 0o555, 2),
+            InodeType::File => (S_IFREG | 0o444, 1),
+        };
+        let now = get_time_ms();
+        Metadata {
+            ino: self.ino(),
+            mode,
+            nlink,
+            size: 0,
+            atime: now,
+            mtime: now,
+            ctime: now,
+        }
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> isize {
+        if self.inode_type() == InodeType::Dir {
+            return -EISDIR;
+        }
+        let text = match self.text() {
+            Some(text) => text,
+            None => return -ESRCH,
+        };
+        let data = text.as_bytes();
+        let start = offset.min(data.len());
+        let n = buf.len().min(data.len() - start);
+        buf[..n].copy_from_slice(&data[start..start + n]);
+        n as isize
+    }
+
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> isize {
+        -EROFS
+    }
+
+    fn truncate(&self, _size: usize) -> isize {
+        -EROFS
+    }
+
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        let inode = match (self, name) {
+            (ProcInode::Root, "meminfo") => ProcInode::MemInfo,
+            (ProcInode::Root, "uptime") => ProcInode::Uptime,
+            (ProcInode::Root, "self") => ProcInode::Process(current_process().pid),
+            (ProcInode::Root, _) => {
+                let pid = name.parse().ok()?;
+                service::proc_info(pid)?;
+                ProcInode::Process(pid)
+            }
+            (ProcInode::Process(pid), "status") => ProcInode::Status(*pid),
+            _ => return None,
+        };
+        Some(Arc::new(inode))
+    }
+
+    fn create(&self, _name: &str, _inode_type: InodeType) -> Result<Arc<dyn Inode>, isize> {
+        Err(-EROFS)
+    }
+
+    fn list(&self) -> Result<Vec<DirEntry>, isize> {
+        let entry = |name: String, inode_type| DirEntry { name, inode_type };
+        match self {
+            ProcInode::Root => {
+                let mut entries = Vec::from([
+                    entry(String::from("meminfo"), InodeType::File),
+                    entry(String::from("uptime"), InodeType::File),
+                    entry(String::from("self"), InodeType::Dir),
+                ]);
+                for pid in service::list_pids() {
+                    entries.push(entry(pid.to_string(), InodeType::Dir));
+                }
+                Ok(entries)
+            }
+            ProcInode::Process(_) => {
+                Ok(Vec::from([entry(String::from("status"), InodeType::File)]))
+            }
+            _ => Err(-ENOTDIR),
+        }
+    }
+
+    fn unlink(&self, _name: &str, _dir: bool) -> isize {
+        -EROFS
+    }
+
+    fn cache_entries(&self) -> bool {
+        // processes come and go
+        false
+    }
+}
+
+pub struct ProcFs;
+
+impl FileSystem for ProcFs {
+    fn root(&self) -> Arc<dyn Inode> {
+        Arc::new(ProcInode::Root)
+    }
+}
@@ -0,0 +1,355 @@
+//! A filesystem kept in kernel memory, lost on shutdown
+//!
+//! Files may start with static data, such as the application images linked
+//! into the kernel, which is copied to the heap only when first modified.
+
+use super::vfs::{DirEntry, FileSystem, Inode, InodeType, Metadata, S_IFDIR, S_IFREG};
+use crate::syscall::errno::{EEXIST, EISDIR, ENOENT, ENOTDIR, ENOTEMPTY, EXDEV};
+use crate::timer::get_time_ms;
+use crate::UPSafeCell;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+
+lazy_static! {
+    /// inode number of the next inode, the root is 1
+    static ref NEXT_INO: UPSafeCell<usize> = UPSafeCell::new(1);
+}
+
+/// Data of a regular file
+enum Data {
+    Static(&'static [u8]),
+    Owned(Vec<u8>),
+}
+
+impl Data {
+    fn as_slice(&self) -> &[u8] {
+        match self {
+            Data::Static(data) => data,
+            Data::Owned(data) => data,
+        }
+    }
+
+    /// Copy static data before it is modified.
+    fn to_mut(&mut self) -> &mut Vec<u8> {
+        if let Data::Static(data) = self {
+            *self = Data::Owned(Vec::from(*data));
+        }
+        match self {
+            Data::Owned(data) => data,
+            Data::Static(_) => unreachable!(),
+        }
+    }
+}
+
+enum Content {
+    File(Data),
+    Dir(BTreeMap<String, Arc<RamInode>>),
+}
+
+/// Metadata kept with the content, see [`Metadata`]
+struct Attr {
+    /// permission bits of `Metadata::mode`
+    perm: u32,
+    /// false once removed from its directory
+    linked: bool,
+    atime: usize,
+    mtime: usize,
+    ctime: usize,
+}
+
+pub struct RamInode {
+    ino: usize,
+    content: UPSafeCell<Content>,
+    attr: UPSafeCell<Attr>,
+}
+
+impl RamInode {
+    fn new(content: Content) -> Arc<Self> {
+        let mut next_ino = NEXT_INO.exclusive_access();
+        let ino = *next_ino;
+        *next_ino += 1;
+        let perm = match content {
+            Content::File(_) => 0o644,
+            Content::Dir(_) => 0o755,
+        };
+        let now = get_time_ms();
+        Arc::new(Self {
+            ino,
+            content: UPSafeCell::new(content),
+            attr: UPSafeCell::new(Attr {
+                perm,
+                linked: true,
+                atime: now,
+                mtime: now,
+                ctime: now,
+            }),
+        })
+    }
+
+    fn accessed(&self) {
+        self.attr.exclusive_access().atime = get_time_ms();
+    }
+
+    /// The content has changed, which changes the metadata too.
+    fn modified(&self) {
+        let mut attr = self.attr.exclusive_access();
+        attr.mtime = get_time_ms();
+        attr.ctime = attr.mtime;
+    }
+
+    /// The inode is removed from its directory, or replaced by `rename`.
+    fn unlinked(&self) {
+        let mut attr = self.attr.exclusive_access();
+        attr.linked = false;
+        attr.ctime = get_time_ms();
+    }
+
+    /// Add the entry `name` to a directory, return -EEXIST if it exists.
+    fn insert(&self, name: &str, content: Content) -> Result<Arc<RamInode>, isize> {
+        match &mut *self.content.exclusive_access() {
+            Content::Dir(entries) => {
+                if entries.contains_key(name) {
+                    return Err(-EEXIST);
+                }
+                let inode = RamInode::new(content);
+                entries.insert(String::from(name), inode.clone());
+                self.modified();
+                Ok(inode)
+            }
+            Content::File(_) => Err(-ENOTDIR),
+        }
+    }
+
+    fn is_empty_dir(&self) -> bool {
+        matches!(&*self.content.exclusive_access(), Content::Dir(entries) if entries.is_empty())
+    }
+
+    fn entry(&self, name: &str) -> Result<Arc<RamInode>, isize> {
+        match &*self.content.exclusive_access() {
+            Content::Dir(entries) => entries.get(name).cloned().ok_or(-ENOENT),
+            Content::File(_) => Err(-ENOTDIR),
+        }
+    }
+
+    /// Check that `inode` may replace the entry `name` of this directory.
+    fn check_replace(&self, name: &str, inode: &RamInode) -> Result<(), isize> {
+        let old = match self.entry(name) {
+            Ok(old) => old,
+            Err(err) if err == -ENOENT => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        match (inode.inode_type(), old.inode_type()) {
+            (InodeType::Dir, InodeType::Dir) if !old.is_empty_dir() => Err(-ENOTEMPTY),
+            (InodeType::Dir, InodeType::File) => Err(-ENOTDIR),
+            (InodeType::File, InodeType::Dir) => Err(-EISDIR),
+            _ => Ok(()),
+        }
+    }
+
+    /// Create a directory `name` in this directory.
+    pub fn mkdir(&self, name: &str) -> Result<Arc<RamInode>, isize> {
+        self.insert(name, Content::Dir(BTreeMap::new()))
+    }
+
+    /// Create an executable file `name` in this directory with static `data`.
+    pub fn add_static(&self, name: &str, data: &'static [u8]) -> Result<Arc<RamInode>, isize> {
+        let inode = self.insert(name, Content::File(Data::Static(data)))?;
+        inode.attr.exclusive_access().perm = 0o755;
+        Ok(inode)
+    }
+}
+
+impl Inode for RamInode {
+    fn inode_type(&self) -> InodeType {
+        match *self.content.exclusive_access() {
+            Content::File(_) => InodeType::File,
+            Content::Dir(_) => InodeType::Dir,
+        }
+    }
+
+    fn metadata(&self) -> Metadata {
+        let (mode, nlink) = match &*self.content.exclusive_access() {
+            Content::File(_) => (S_IFREG, 1),
+            // `.` and the entry in the parent, and `..` of subdirectories
+            Content::Dir(entries) => {
+                let subdirs = entries
+                    .values()
+                    .filter(|inode| inode.inode_type() == InodeType::Dir)
+                    .count();
+                (S_IFDIR, 2 + subdirs)
+            }
+        };
+        let attr = self.attr.exclusive_access();
+        Metadata {
+            ino: self.ino,
+            mode: mode | attr.perm,
+            nlink: if attr.linked { nlink } else { 0 },
+            size: self.size(),
+            atime: attr.atime,
+            mtime: attr.mtime,
+            ctime: attr.ctime,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match &*self.content.exclusive_access() {
+            Content::File(data) => data.as_slice().len(),
+            Content::Dir(_) => 0,
+        }
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> isize {
+        match &*self.content.exclusive_access() {
+            Content::File(data) => {
+                let data = data.as_slice();
+                let start = offset.min(data.len());
+                let n = buf.len().min(data.len() - start);
+                buf[..n].copy_from_slice(&data[start..start + n]);
+                self.accessed();
+                n as isize
+            }
+            Content::Dir(_) => -EISDIR,
+        }
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> isize {
+        match &mut *self.content.exclusive_access() {
+            Content::File(data) => {
+                let data = data.to_mut();
+                let end = offset + buf.len();
+                if data.len() < end {
+                    data.resize(end, 0);
+                }
+                data[offset..end].copy_from_slice(buf);
+                self.modified();
+                buf.len() as isize
+            }
+            Content::Dir(_) => -EISDIR,
+        }
+    }
+
+    fn truncate(&self, size: usize) -> isize {
+        match &mut *self.content.exclusive_access() {
+            Content::File(data) => {
+                if size == 0 {
+                    *data = Data::Owned(Vec::new());
+                } else {
+                    data.to_mut().resize(size, 0);
+                }
+                self.modified();
+                0
+            }
+            Content::Dir(_) => -EISDIR,
+        }
+    }
+
+    fn mappable(&self) -> bool {
+        self.inode_type() == InodeType::File
+    }
+
+    fn lookup(&self, name: &str) -> Option<Arc<dyn Inode>> {
+        match &*self.content.exclusive_access() {
+            Content::Dir(entries) => entries
+                .get(name)
+                .map(|inode| inode.clone() as Arc<dyn Inode>),
+            Content::File(_) => None,
+        }
+    }
+
+    fn create(&self, name: &str, inode_type: InodeType) -> Result<Arc<dyn Inode>, isize> {
+        let content = match inode_type {
+            InodeType::File => Content::File(Data::Owned(Vec::new())),
+            InodeType::Dir => Content::Dir(BTreeMap::new()),
+        };
+        Ok(self.insert(name, content)?)
+    }
+
+    fn list(&self) -> Result<Vec<DirEntry>, isize> {
+        match &*self.content.exclusive_access() {
+            Content::Dir(entries) => Ok(entries
+                .iter()
+                .map(|(name, inode)| DirEntry {
+                    name: name.clone(),
+                    inode_type: inode.inode_type(),
+                })
+                .collect()),
+            Content::File(_) => Err(-ENOTDIR),
+        }
+    }
+
+    fn unlink(&self, name: &str, dir: bool) -> isize {
+        let inode = match self.entry(name) {
+            Ok(inode) => inode,
+            Err(err) => return err,
+        };
+        match (inode.inode_type(), dir) {
+            (InodeType::Dir, false) => return -EISDIR,
+            (InodeType::File, true) => return -ENOTDIR,
+            (InodeType::Dir, true) if !inode.is_empty_dir() => return -ENOTEMPTY,
+            _ => {}
+        }
+        if let Content::Dir(entries) = &mut *self.content.exclusive_access() {
+            entries.remove(name);
+        }
+        self.modified();
+        inode.unlinked();
+        0
+    }
+
+    fn rename(&self, old_name: &str, new_dir: &dyn Inode, new_name: &str) -> isize {
+        // a file cannot move to another filesystem
+        let new_dir = match new_dir.as_any().downcast_ref::<RamInode>() {
+            Some(new_dir) => new_dir,
+            None => return -EXDEV,
+        };
+        let inode = match self.entry(old_name) {
+            Ok(inode) => inode,
+            Err(err) => return err,
+        };
+        if core::ptr::eq(self, new_dir) && old_name == new_name {
+            return 0;
+        }
+        if let Err(err) = new_dir.check_replace(new_name, &inode) {
+            return err;
+        }
+        if let Content::Dir(entries) = &mut *self.content.exclusive_access() {
+            entries.remove(old_name);
+        }
+        let replaced = match &mut *new_dir.content.exclusive_access() {
+            Content::Dir(entries) => entries.insert(String::from(new_name), inode.clone()),
+            Content::File(_) => None,
+        };
+        if let Some(replaced) = replaced {
+            replaced.unlinked();
+        }
+        self.modified();
+        new_dir.modified();
+        inode.attr.exclusive_access().ctime = get_time_ms();
+        0
+    }
+}
+
+pub struct RamFs {
+    root: Arc<RamInode>,
+}
+
+impl RamFs {
+    pub fn new() -> Self {
+        Self {
+            root: RamInode::new(Content::Dir(BTreeMap::new())),
+        }
+    }
+
+    pub fn root_dir(&self) -> &Arc<RamInode> {
+        &self.root
+    }
+}
+
+impl FileSystem for RamFs {
+    fn root(&self) -> Arc<dyn Inode> {
+        self.root.clone()
+    }
+}
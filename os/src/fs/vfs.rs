@@ -0,0 +1,472 @@
+//! Virtual filesystem
+//!
+//! Every filesystem provides a tree of [`Inode`]s. The VFS joins them into one
+//! tree: a filesystem is mounted on a directory, which then stands for the root
+//! of the filesystem. Paths are resolved through a cache of directory entries
+//! ([`Dentry`]), which also remembers the parent of every directory for `..`.
+//!
+//! Entries in use, by a process or as the parent of a cached entry, and mount
+//! points are never dropped from the cache, others are kept up to
+//! [`DENTRY_CACHE_SIZE`], and the least recently used one is dropped first.
+
+use crate::config::DENTRY_CACHE_SIZE;
+use crate::syscall::errno::{EBUSY, EINVAL, ENOENT, ENOTDIR, EROFS};
+use crate::UPSafeCell;
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::any::Any;
+use lazy_static::lazy_static;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InodeType {
+    File,
+    Dir,
+}
+
+/// An entry of a directory listed by [`Inode::list`]
+pub struct DirEntry {
+    pub name: String,
+    pub inode_type: InodeType,
+}
+
+// Type bits of `Metadata::mode`, following Linux
+pub const S_IFIFO: u32 = 0o010000;
+pub const S_IFCHR: u32 = 0o020000;
+pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFBLK: u32 = 0o060000;
+pub const S_IFREG: u32 = 0o100000;
+
+/// Metadata of an inode, shown by `stat`
+pub struct Metadata {
+    /// unique in the filesystem
+    pub ino: usize,
+    /// type and permission bits, such as `S_IFREG | 0o644`
+    pub mode: u32,
+    /// number of directory entries linking to it, 0 once unlinked
+    pub nlink: usize,
+    pub size: usize,
+    /// last access, modification and change of metadata, in ms since boot
+    pub atime: usize,
+    pub mtime: usize,
+    pub ctime: usize,
+}
+
+/// For a filesystem to find its own inodes behind `dyn Inode`
+pub trait AsAny {
+    fn as_any(&self) -> &dyn Any;
+}
+
+impl<T: Any> AsAny for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// A file or directory of a filesystem
+///
+/// Operations not supported by the inode return a negated errno.
+pub trait Inode: AsAny + Send + Sync {
+    fn inode_type(&self) -> InodeType;
+    fn metadata(&self) -> Metadata;
+    /// Size in bytes of a regular file
+    fn size(&self) -> usize {
+        0
+    }
+    /// Read at `offset`, return the number of bytes read, 0 at the end of file.
+    fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> isize {
+        -EINVAL
+    }
+    /// Write at `offset`, extending the file if needed.
+    fn write_at(&self, _offset: usize, _buf: &[u8]) -> isize {
+        -EINVAL
+    }
+    /// Cut or extend a regular file to `size` bytes.
+    fn truncate(&self, _size: usize) -> isize {
+        -EINVAL
+    }
+    /// Find the entry `name` of a directory, which is never `.` or `..`
+    fn lookup(&self, _name: &str) -> Option<Arc<dyn Inode>> {
+        None
+    }
+    /// Create the entry `name` in a directory, which does not exist yet.
+    fn create(&self, _name: &str, _inode_type: InodeType) -> Result<Arc<dyn Inode>, isize> {
+        Err(-ENOTDIR)
+    }
+    /// Entries of a directory, without `.` and `..`
+    fn list(&self) -> Result<Vec<DirEntry>, isize> {
+        Err(-ENOTDIR)
+    }
+    /// Remove the entry `name` from a directory, which must be an empty
+    /// directory if `dir`, or not a directory otherwise.
+    fn unlink(&self, _name: &str, _dir: bool) -> isize {
+        -ENOTDIR
+    }
+    /// Move the entry `old_name` of this directory to `new_name` in `new_dir`,
+    /// replacing an entry of the same type there, but not a non-empty directory.
+    ///
+    /// Return -EXDEV if `new_dir` is in another filesystem.
+    fn rename(&self, _old_name: &str, _new_dir: &dyn Inode, _new_name: &str) -> isize {
+        -EROFS
+    }
+    /// Write the changes of the file to its device, see `File::sync`
+    fn sync(&self) -> isize {
+        0
+    }
+    /// Whether it is the console, see `File::is_terminal`
+    fn is_terminal(&self) -> bool {
+        false
+    }
+    /// Whether a regular file may be mapped by `sys_mmap`, not if its content
+    /// changes by itself, as its pages are kept in the page cache
+    fn mappable(&self) -> bool {
+        false
+    }
+    /// Whether entries of a directory may be kept in the dentry cache, false
+    /// if they come and go by themselves
+    fn cache_entries(&self) -> bool {
+        true
+    }
+}
+
+pub trait FileSystem: Send + Sync {
+    fn root(&self) -> Arc<dyn Inode>;
+}
+
+/// An entry of a directory in the tree of all filesystems
+pub struct Dentry {
+    /// changed by `rename`, with `parent`
+    name: UPSafeCell<String>,
+    pub inode: Arc<dyn Inode>,
+    /// the root is its own parent
+    parent: UPSafeCell<Weak<Dentry>>,
+    /// entries looked up before
+    children: UPSafeCell<BTreeMap<String, Arc<Dentry>>>,
+    /// root of the filesystem mounted on this directory
+    mounted: UPSafeCell<Option<Arc<Dentry>>>,
+    /// `DentryCache::clock` when last looked up
+    last_used: UPSafeCell<usize>,
+}
+
+impl Dentry {
+    fn new(name: &str, inode: Arc<dyn Inode>, parent: Weak<Dentry>) -> Arc<Self> {
+        Arc::new(Self {
+            name: UPSafeCell::new(String::from(name)),
+            inode,
+            parent: UPSafeCell::new(parent),
+            children: UPSafeCell::new(BTreeMap::new()),
+            mounted: UPSafeCell::new(None),
+            last_used: UPSafeCell::new(0),
+        })
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.inode.inode_type() == InodeType::Dir
+    }
+
+    pub fn name(&self) -> String {
+        self.name.exclusive_access().clone()
+    }
+
+    pub fn parent(self: &Arc<Self>) -> Arc<Dentry> {
+        let parent = self.parent.exclusive_access().upgrade();
+        parent.unwrap_or_else(|| self.clone())
+    }
+
+    /// Absolute path of this entry
+    pub fn path(self: &Arc<Self>) -> String {
+        let mut names = Vec::new();
+        let mut dentry = self.clone();
+        loop {
+            let parent = dentry.parent();
+            if Arc::ptr_eq(&parent, &dentry) {
+                break;
+            }
+            names.push(dentry.name());
+            dentry = parent;
+        }
+        let mut path = String::new();
+        for name in names.iter().rev() {
+            path.push('/');
+            path.push_str(name);
+        }
+        if path.is_empty() {
+            path.push('/');
+        }
+        path
+    }
+
+    /// Whether a filesystem is mounted on the entry `name` of this directory
+    fn is_mount_point(&self, name: &str) -> bool {
+        let cached = self.children.exclusive_access().get(name).cloned();
+        cached.map_or(false, |child| child.mounted.exclusive_access().is_some())
+    }
+
+    /// The root of the filesystem mounted here, or itself
+    fn follow_mounts(self: Arc<Self>) -> Arc<Dentry> {
+        let mut dentry = self;
+        loop {
+            let mounted = dentry.mounted.exclusive_access().clone();
+            match mounted {
+                Some(root) => dentry = root,
+                None => return dentry,
+            }
+        }
+    }
+
+    /// The entry `name` of this directory, not `.` or `..`
+    pub fn child(self: &Arc<Self>, name: &str) -> Result<Arc<Dentry>, isize> {
+        if !self.is_dir() {
+            return Err(-ENOTDIR);
+        }
+        let cached = self.children.exclusive_access().get(name).cloned();
+        let child = match cached {
+            Some(child) => {
+                child.touch();
+                child
+            }
+            None => {
+                let inode = self.inode.lookup(name).ok_or(-ENOENT)?;
+                let child = Dentry::new(name, inode, Arc::downgrade(self));
+                self.insert_child(name, &child);
+                child
+            }
+        };
+        Ok(child.follow_mounts())
+    }
+
+    /// Create the entry `name` in this directory.
+    pub fn create(
+        self: &Arc<Self>,
+        name: &str,
+        inode_type: InodeType,
+    ) -> Result<Arc<Dentry>, isize> {
+        if !self.is_dir() {
+            return Err(-ENOTDIR);
+        }
+        let inode = self.inode.create(name, inode_type)?;
+        let child = Dentry::new(name, inode, Arc::downgrade(self));
+        self.insert_child(name, &child);
+        Ok(child)
+    }
+
+    /// Remove the entry `name` of this directory, see [`Inode::unlink`].
+    pub fn unlink(self: &Arc<Self>, name: &str, dir: bool) -> isize {
+        if self.is_mount_point(name) {
+            return -EBUSY;
+        }
+        let ret = self.inode.unlink(name, dir);
+        if ret == 0 {
+            self.remove_child(name);
+        }
+        ret
+    }
+
+    fn touch(&self) {
+        let mut cache = DENTRY_CACHE.exclusive_access();
+        cache.clock += 1;
+        *self.last_used.exclusive_access() = cache.clock;
+    }
+
+    /// Cache `child` as the entry `name` of this directory, unless its entries
+    /// are not cached, and shrink the cache to `DENTRY_CACHE_SIZE` entries.
+    fn insert_child(self: &Arc<Self>, name: &str, child: &Arc<Dentry>) {
+        if !self.inode.cache_entries() {
+            return;
+        }
+        self.children
+            .exclusive_access()
+            .insert(String::from(name), child.clone());
+        child.touch();
+        let mut cache = DENTRY_CACHE.exclusive_access();
+        cache
+            .entries
+            .insert(dentry_key(child), Arc::downgrade(child));
+        let mut evicted = Vec::new();
+        while cache.entries.len() > DENTRY_CACHE_SIZE {
+            match cache.evict() {
+                Some(dentry) => evicted.push(dentry),
+                None => break,
+            }
+        }
+        // the last reference to an inode may be dropped
+        drop(cache);
+        drop(evicted);
+    }
+
+    /// Drop the entry `name` from the cache of this directory, return it.
+    fn remove_child(&self, name: &str) -> Option<Arc<Dentry>> {
+        let child = self.children.exclusive_access().remove(name)?;
+        DENTRY_CACHE
+            .exclusive_access()
+            .entries
+            .remove(&dentry_key(&child));
+        Some(child)
+    }
+
+    /// Whether the entry may be dropped from the cache: it is referenced only
+    /// by its parent and the caller, nothing is mounted on it and none of its
+    /// entries are cached.
+    fn is_unused(self: &Arc<Self>) -> bool {
+        Arc::strong_count(self) == 2
+            && self.mounted.exclusive_access().is_none()
+            && self.children.exclusive_access().is_empty()
+    }
+}
+
+/// Entries cached in the `children` of their parent
+struct DentryCache {
+    /// by address
+    entries: BTreeMap<usize, Weak<Dentry>>,
+    /// counts the lookups of entries
+    clock: usize,
+}
+
+impl DentryCache {
+    /// Drop the least recently used entry which is not in use from the
+    /// `children` of its parent, return it.
+    fn evict(&mut self) -> Option<Arc<Dentry>> {
+        self.entries.retain(|_, entry| entry.strong_count() > 0);
+        let (key, dentry) = self
+            .entries
+            .iter()
+            .filter_map(|(&key, entry)| Some((key, entry.upgrade()?)))
+            .filter(|(_, dentry)| dentry.is_unused())
+            .min_by_key(|(_, dentry)| *dentry.last_used.exclusive_access())?;
+        self.entries.remove(&key);
+        let parent = dentry.parent.exclusive_access().upgrade()?;
+        let name = dentry.name();
+        let removed = parent.children.exclusive_access().remove(&name);
+        removed
+    }
+}
+
+fn dentry_key(dentry: &Arc<Dentry>) -> usize {
+    Arc::as_ptr(dentry) as usize
+}
+
+lazy_static! {
+    static ref ROOT: UPSafeCell<Option<Arc<Dentry>>> = UPSafeCell::new(None);
+    static ref DENTRY_CACHE: UPSafeCell<DentryCache> = UPSafeCell::new(DentryCache {
+        entries: BTreeMap::new(),
+        clock: 0,
+    });
+    /// mounted filesystems, kept alive while their inodes are in use
+    static ref MOUNTS: UPSafeCell<Vec<Arc<dyn FileSystem>>> = UPSafeCell::new(Vec::new());
+}
+
+/// The root directory `/`, a filesystem must be mounted on it first.
+pub fn root() -> Arc<Dentry> {
+    ROOT.exclusive_access()
+        .clone()
+        .expect("no filesystem mounted on /")
+}
+
+/// Mount `fs` on directory `path`, or as the root if `path` is `/`.
+pub fn mount(path: &str, fs: Arc<dyn FileSystem>) -> Result<(), isize> {
+    if path == "/" {
+        *ROOT.exclusive_access() = Some(Dentry::new("/", fs.root(), Weak::new()));
+    } else {
+        let mountpoint = lookup(&root(), path)?;
+        if !mountpoint.is_dir() {
+            return Err(-ENOTDIR);
+        }
+        // `..` of the mounted root goes out of the filesystem
+        let parent = Arc::downgrade(&mountpoint.parent());
+        let root = Dentry::new(&mountpoint.name(), fs.root(), parent);
+        *mountpoint.mounted.exclusive_access() = Some(root);
+    }
+    MOUNTS.exclusive_access().push(fs);
+    Ok(())
+}
+
+/// Resolve `path`, relative to `cwd` unless it starts with `/`.
+pub fn lookup(cwd: &Arc<Dentry>, path: &str) -> Result<Arc<Dentry>, isize> {
+    let mut dentry = if path.starts_with('/') {
+        root()
+    } else {
+        cwd.clone()
+    };
+    for name in path.split('/') {
+        dentry = match name {
+            "" | "." => {
+                if !dentry.is_dir() {
+                    return Err(-ENOTDIR);
+                }
+                dentry
+            }
+            ".." => dentry.parent(),
+            _ => dentry.child(name)?,
+        };
+    }
+    Ok(dentry)
+}
+
+/// Resolve the directory containing `path`, return it with the last name in `path`.
+///
+/// The last name may be empty, `.` or `..`, e.g. for `/`.
+pub fn lookup_parent<'a>(
+    cwd: &Arc<Dentry>,
+    path: &'a str,
+) -> Result<(Arc<Dentry>, &'a str), isize> {
+    let path = path.trim_end_matches('/');
+    let (dir, name) = match path.rfind('/') {
+        Some(0) => ("/", &path[1..]),
+        Some(i) => (&path[..i], &path[i + 1..]),
+        None => (".", path),
+    };
+    let dir = lookup(cwd, dir)?;
+    if !dir.is_dir() {
+        return Err(-ENOTDIR);
+    }
+    Ok((dir, name))
+}
+
+/// Move `old_path` to `new_path`, in the same filesystem.
+pub fn rename(cwd: &Arc<Dentry>, old_path: &str, new_path: &str) -> isize {
+    let (old_dir, old_name) = match lookup_parent(cwd, old_path) {
+        Ok(parent) => parent,
+        Err(err) => return err,
+    };
+    let (new_dir, new_name) = match lookup_parent(cwd, new_path) {
+        Ok(parent) => parent,
+        Err(err) => return err,
+    };
+    if [old_name, new_name]
+        .iter()
+        .any(|name| matches!(*name, "" | "." | ".."))
+    {
+        return -EINVAL;
+    }
+    if old_dir.is_mount_point(old_name) || new_dir.is_mount_point(new_name) {
+        return -EBUSY;
+    }
+    let old = match old_dir.child(old_name) {
+        Ok(old) => old,
+        Err(err) => return err,
+    };
+    // a directory cannot be moved into itself
+    let mut dir = new_dir.clone();
+    loop {
+        if Arc::ptr_eq(&dir, &old) {
+            return -EINVAL;
+        }
+        let parent = dir.parent();
+        if Arc::ptr_eq(&parent, &dir) {
+            break;
+        }
+        dir = parent;
+    }
+    let ret = old_dir.inode.rename(old_name, &*new_dir.inode, new_name);
+    if ret == 0 {
+        let moved = old_dir.remove_child(old_name);
+        new_dir.remove_child(new_name);
+        if let Some(moved) = moved {
+            *moved.name.exclusive_access() = String::from(new_name);
+            *moved.parent.exclusive_access() = Arc::downgrade(&new_dir);
+            new_dir.insert_child(new_name, &moved);
+        }
+    }
+    ret
+}
@@ -0,0 +1,69 @@
+//! Write access to files, denied while they run as programs
+//!
+//! Pages of a running program may be mapped from the page cache, see
+//! `MemorySet::new_from_elf`, so its file must not change under it. Like
+//! `i_writecount` of Linux, a file is either written, through descriptors
+//! opened for writing or shared writable mappings, or run by processes, never
+//! both, and the other one gets -ETXTBSY.
+
+use super::vfs::Inode;
+use crate::syscall::errno::ETXTBSY;
+use crate::UPSafeCell;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use lazy_static::lazy_static;
+
+lazy_static! {
+    /// By address of the inode: writers if positive, running programs if negative
+    static ref WRITE_COUNTS: UPSafeCell<BTreeMap<usize, isize>> =
+        UPSafeCell::new(BTreeMap::new());
+}
+
+fn inode_key(inode: &Arc<dyn Inode>) -> usize {
+    Arc::as_ptr(inode) as *const () as usize
+}
+
+/// Access to write a file, or to run it, given back when dropped
+pub struct WriteAccess {
+    /// kept alive so that its address stays unique
+    inode: Arc<dyn Inode>,
+    /// 1 for a writer, -1 for a program
+    delta: isize,
+}
+
+impl WriteAccess {
+    fn acquire(inode: &Arc<dyn Inode>, delta: isize) -> Result<Self, isize> {
+        let mut counts = WRITE_COUNTS.exclusive_access();
+        let count = counts.entry(inode_key(inode)).or_insert(0);
+        if *count * delta < 0 {
+            return Err(-ETXTBSY);
+        }
+        *count += delta;
+        Ok(Self {
+            inode: inode.clone(),
+            delta,
+        })
+    }
+
+    /// Access to write `inode`, return -ETXTBSY if it runs as a program.
+    pub fn write(inode: &Arc<dyn Inode>) -> Result<Self, isize> {
+        Self::acquire(inode, 1)
+    }
+
+    /// Access to run `inode` as a program, return -ETXTBSY if it is written.
+    pub fn deny_write(inode: &Arc<dyn Inode>) -> Result<Self, isize> {
+        Self::acquire(inode, -1)
+    }
+}
+
+impl Drop for WriteAccess {
+    fn drop(&mut self) {
+        let key = inode_key(&self.inode);
+        let mut counts = WRITE_COUNTS.exclusive_access();
+        let count = counts.get_mut(&key).unwrap();
+        *count -= self.delta;
+        if *count == 0 {
+            counts.remove(&key);
+        }
+    }
+}
@@ -1,8 +1,18 @@
 //! Loading user applications into memory
 
-use crate::{info, println};
+use crate::config::{MMAP_BASE, PAGE_SIZE, USER_STACK_GUARD_SIZE, USER_STACK_MAX_SIZE};
+use crate::fs::vfs::{lookup, Inode};
+use crate::fs::{Dentry, WriteAccess};
+use crate::info;
+use crate::syscall::errno::{EISDIR, ENOEXEC};
+use alloc::format;
+use alloc::sync::Arc;
+use alloc::vec;
 use alloc::vec::Vec;
 use lazy_static::lazy_static;
+use xmas_elf::header::Class;
+use xmas_elf::program::{Flags, Type};
+use xmas_elf::ElfFile;
 
 extern "C" {
     fn _num_app();
@@ -54,12 +64,141 @@ lazy_static! {
     };
 }
 
-pub fn get_app_data_by_name(name: &str) -> Option<&'static [u8]> {
+/// The image of the application `name` linked into the kernel
+pub fn app_data(name: &str) -> Option<&'static [u8]> {
     (0..get_num_app())
         .find(|&i| APP_NAMES[i] == name)
         .map(get_app_data)
 }
 
+/// An application to run: its file, with the ELF header and program headers
+/// read, its segments are mapped from the file, see `MemorySet::new_from_elf`.
+pub struct Executable {
+    pub file: Arc<dyn Inode>,
+    pub entry_point: usize,
+    /// loadable segments, sorted by address
+    pub segments: Vec<Segment>,
+    /// held by the segments mapped from the file, so that it does not change
+    pub write_access: Arc<WriteAccess>,
+}
+
+/// A loadable segment of an [`Executable`], in user space and not overlapping
+/// the other segments
+pub struct Segment {
+    /// virtual address range
+    pub start: usize,
+    pub end: usize,
+    /// `file_size` bytes from `offset` in the file are at `start`, the rest is
+    /// zeroed
+    pub offset: usize,
+    pub file_size: usize,
+    pub flags: Flags,
+}
+
+/// Segments end below here, leaving room for the main stack below `MMAP_BASE`
+const SEGMENTS_END: usize = MMAP_BASE - USER_STACK_GUARD_SIZE - USER_STACK_MAX_SIZE;
+
+/// size of the header of a 64-bit ELF file
+const ELF_HEADER_SIZE: usize = 64;
+/// size of a 64-bit program header
+const PROGRAM_HEADER_SIZE: usize = 56;
+
+impl Executable {
+    /// Open the application `name` through the VFS.
+    ///
+    /// A name without `/` is looked up in `/bin`, a path is relative to `cwd`.
+    /// Return -ENOEXEC if it is not a 64-bit ELF file whose segments can be
+    /// loaded, -ETXTBSY if it is open for writing.
+    pub fn open(cwd: &Arc<Dentry>, name: &str) -> Result<Self, isize> {
+        let dentry = if name.contains('/') {
+            lookup(cwd, name)?
+        } else {
+            lookup(cwd, &format!("/bin/{}", name))?
+        };
+        if dentry.is_dir() {
+            return Err(-EISDIR);
+        }
+        let file = dentry.inode.clone();
+        let write_access = Arc::new(WriteAccess::deny_write(&file)?);
+        // files in /bin may be overwritten with anything
+        let mut headers = vec![0u8; ELF_HEADER_SIZE];
+        if read_file(&file, 0, &mut headers) < ELF_HEADER_SIZE || !headers.starts_with(b"\x7fELF") {
+            return Err(-ENOEXEC);
+        }
+        let header = ElfFile::new(&headers).map_err(|_| -ENOEXEC)?.header;
+        if header.pt1.class() != Class::SixtyFour
+            || (header.pt2.ph_entry_size() as usize) < PROGRAM_HEADER_SIZE
+        {
+            return Err(-ENOEXEC);
+        }
+        let end = header.pt2.ph_offset() as usize
+            + header.pt2.ph_count() as usize * header.pt2.ph_entry_size() as usize;
+        // read into memory, the program headers must be in the first page
+        if end > PAGE_SIZE {
+            return Err(-ENOEXEC);
+        }
+        headers.resize(end.max(ELF_HEADER_SIZE), 0);
+        if read_file(&file, 0, &mut headers) < end {
+            return Err(-ENOEXEC);
+        }
+        let elf = ElfFile::new(&headers).map_err(|_| -ENOEXEC)?;
+        let mut segments = Vec::new();
+        for i in 0..elf.header.pt2.ph_count() {
+            let ph = elf.program_header(i).map_err(|_| -ENOEXEC)?;
+            if ph.get_type() != Ok(Type::Load) || ph.mem_size() == 0 {
+                continue;
+            }
+            let start = ph.virtual_addr() as usize;
+            let file_size = ph.file_size() as usize;
+            let end = start
+                .checked_add(ph.mem_size() as usize)
+                .filter(|&end| end <= SEGMENTS_END && file_size <= end - start)
+                .ok_or(-ENOEXEC)?;
+            let offset = ph.offset() as usize;
+            offset.checked_add(file_size).ok_or(-ENOEXEC)?;
+            segments.push(Segment {
+                start,
+                end,
+                offset,
+                file_size,
+                flags: ph.flags(),
+            });
+        }
+        // segments are mapped by pages, which must not be shared
+        segments.sort_by_key(|segment| segment.start);
+        let overlapping = segments
+            .windows(2)
+            .any(|pair| pair[0].end.div_ceil(PAGE_SIZE) > pair[1].start / PAGE_SIZE);
+        if overlapping {
+            return Err(-ENOEXEC);
+        }
+        Ok(Self {
+            file,
+            entry_point: elf.header.pt2.entry_point() as usize,
+            segments,
+            write_access,
+        })
+    }
+
+    /// Read at `offset` until `buf` is full or the end of the file, return the
+    /// number of bytes read.
+    pub fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        read_file(&self.file, offset, buf)
+    }
+}
+
+fn read_file(file: &Arc<dyn Inode>, offset: usize, buf: &mut [u8]) -> usize {
+    let mut len = 0;
+    while len < buf.len() {
+        let n = file.read_at(offset + len, &mut buf[len..]);
+        if n <= 0 {
+            break;
+        }
+        len += n as usize;
+    }
+    len
+}
+
 pub fn list_apps() {
     info!("[kernel] ----- APPS -----");
     for app in APP_NAMES.iter() {
@@ -71,11 +210,15 @@ pub fn list_apps() {
     info!("[kernel] ----------------");
 }
 
-/// for built-in commands `ls`
-pub fn ls() {
-    for app in APP_NAMES.iter() {
-        if app != &"proc_manager" && app != &"initproc" && app != &"shell" {
-            println!("{}", app);
-        }
-    }
+/// Names of all applications linked into the kernel
+pub fn app_names() -> impl Iterator<Item = &'static str> {
+    APP_NAMES.iter().copied()
+}
+
+/// Apps which can be run from the shell
+pub fn user_apps() -> impl Iterator<Item = &'static str> {
+    APP_NAMES
+        .iter()
+        .copied()
+        .filter(|app| *app != "proc_manager" && *app != "initproc" && *app != "shell")
 }
@@ -5,7 +5,10 @@
 #![feature(asm_const)]
 #![feature(naked_functions)]
 
+mod board;
 mod config;
+mod drivers;
+mod fs;
 mod lang_items;
 mod loader;
 mod mm;
@@ -29,8 +32,10 @@ global_asm!(include_str!("entry.s"));
 global_asm!(include_str!("link_app.s"));
 
 #[no_mangle]
-pub fn booting() -> ! {
+pub fn booting(_hartid: usize, dtb: usize) -> ! {
     clear_bss();
+    // discover memory and devices before using them
+    board::init(dtb);
     unsafe {
         // set privilege change to supervisor
         mstatus::set_mpp(mstatus::MPP::Supervisor);
@@ -62,6 +67,7 @@ pub fn kernel_main() -> ! {
     print_init_info();
     mm::init();
     info!("[kernel] Hello, MMU!");
+    fs::init();
     task::add_initproc();
     trap::init();
     loader::list_apps();
@@ -72,6 +78,7 @@ pub fn kernel_main() -> ! {
 fn print_init_info() {
     info!("{}", sbi::LOGO);
     info!("[mysbi] Hello, kernel!");
+    board::print_board_info();
 }
 
 fn clear_bss() {
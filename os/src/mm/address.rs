@@ -189,6 +189,10 @@ impl VPNRange {
         Self { start, end }
     }
 
+    pub fn contains(&self, vpn: VirtPageNum) -> bool {
+        self.start <= vpn && vpn < self.end
+    }
+
     pub fn iter(&self) -> Iter {
         Iter {
             cur: self.start,
@@ -1,6 +1,12 @@
 use super::address::PhysPageNum;
+use super::page_cache;
+use super::swap::swap_area;
+use crate::config::SHARED_PAGE;
 use crate::debug;
-use crate::{config::MEMORY_END, mm::address::PhysAddr, UPSafeCell};
+use crate::task::swap_out_victim;
+use crate::{mm::address::PhysAddr, UPSafeCell};
+use alloc::collections::BTreeSet;
+use alloc::vec;
 use alloc::vec::Vec;
 use core::fmt::{self, Debug, Formatter};
 use lazy_static::lazy_static;
@@ -9,6 +15,20 @@ trait FrameAllocator {
     fn new() -> Self;
     fn alloc(&mut self) -> Option<PhysPageNum>;
     fn dealloc(&mut self, ppn: PhysPageNum);
+    /// Allocate `count` physically contiguous frames, return the first one.
+    /// Each of them is deallocated by `dealloc` separately.
+    fn alloc_contiguous(&mut self, count: usize) -> Option<PhysPageNum>;
+    fn stats(&self) -> FrameAllocatorStats;
+}
+
+/// Statistics of a frame allocator (in frames)
+#[allow(unused)]
+#[derive(Debug, Clone, Copy)]
+pub struct FrameAllocatorStats {
+    pub total: usize,
+    pub allocated: usize,
+    /// the largest number of contiguous frames that can be allocated now
+    pub largest_free: usize,
 }
 
 /// an implementation for frame allocator
@@ -17,6 +37,7 @@ pub struct StackFrameAllocator {
     end: usize,
     // [current, end) represents unallocated pages
     recycled: Vec<usize>, // LIFO
+    total: usize,
 }
 
 impl FrameAllocator for StackFrameAllocator {
@@ -25,6 +46,7 @@ impl FrameAllocator for StackFrameAllocator {
             current: 0,
             end: 0,
             recycled: Vec::new(),
+            total: 0,
         }
     }
 
@@ -50,16 +72,203 @@ impl FrameAllocator for StackFrameAllocator {
         // recycle
         self.recycled.push(ppn);
     }
+
+    fn alloc_contiguous(&mut self, count: usize) -> Option<PhysPageNum> {
+        if count == 1 {
+            return self.alloc();
+        }
+        // recycled frames are scattered, only take from the unallocated range
+        if self.current + count > self.end {
+            None
+        } else {
+            self.current += count;
+            Some((self.current - count).into())
+        }
+    }
+
+    fn stats(&self) -> FrameAllocatorStats {
+        let largest_free = if self.current < self.end {
+            self.end - self.current
+        } else {
+            self.recycled.len().min(1)
+        };
+        FrameAllocatorStats {
+            total: self.total,
+            allocated: self.total - (self.end - self.current) - self.recycled.len(),
+            largest_free,
+        }
+    }
 }
 
 impl StackFrameAllocator {
+    #[allow(unused)]
     pub fn init(&mut self, l: PhysPageNum, r: PhysPageNum) {
         self.current = l.0;
         self.end = r.0;
+        self.total = r.0 - l.0;
+    }
+
+    /// Take frame `ppn` out of the allocator forever.
+    #[allow(unused)]
+    pub fn reserve(&mut self, ppn: PhysPageNum) {
+        let ppn = ppn.0;
+        assert!(
+            ppn >= self.current && ppn < self.end,
+            "Frame ppn={:#x} is not free!",
+            ppn
+        );
+        self.recycled.extend(ppn + 1..self.end);
+        self.end = ppn;
     }
 }
 
-type FrameAllocatorImpl = StackFrameAllocator;
+// buddy system with blocks of at most 2^(MAX_ORDER - 1) frames (4MB)
+const MAX_ORDER: usize = 11;
+
+/// Buddy system frame allocator
+///
+/// A block of order `k` has 2^k frames and its first ppn is aligned to 2^k,
+/// so large blocks can also be used as huge pages. A bitmap records which
+/// frames are allocated, making double free detection O(1).
+pub struct BuddyFrameAllocator {
+    start: usize,
+    end: usize,
+    // start ppn of free blocks of each order
+    free_list: [BTreeSet<usize>; MAX_ORDER],
+    // one bit for each frame in [start, end), set if allocated
+    bitmap: Vec<u64>,
+    // statistics
+    allocated: usize,
+}
+
+impl FrameAllocator for BuddyFrameAllocator {
+    fn new() -> Self {
+        Self {
+            start: 0,
+            end: 0,
+            free_list: Default::default(),
+            bitmap: Vec::new(),
+            allocated: 0,
+        }
+    }
+
+    fn alloc(&mut self) -> Option<PhysPageNum> {
+        self.alloc_contiguous(1)
+    }
+
+    fn dealloc(&mut self, ppn: PhysPageNum) {
+        let ppn = ppn.0;
+        // validity check
+        if ppn < self.start || ppn >= self.end || !self.is_allocated(ppn) {
+            panic!("Frame ppn={:#x} has not been allocated!", ppn);
+        }
+        self.set_allocated(ppn, false);
+        self.allocated -= 1;
+        // merge with free buddies
+        let mut block = ppn;
+        let mut order = 0;
+        while order + 1 < MAX_ORDER {
+            let buddy = block ^ (1 << order);
+            if !self.free_list[order].remove(&buddy) {
+                break;
+            }
+            block = block.min(buddy);
+            order += 1;
+        }
+        self.free_list[order].insert(block);
+    }
+
+    fn alloc_contiguous(&mut self, count: usize) -> Option<PhysPageNum> {
+        if count == 0 {
+            return None;
+        }
+        let class = count.next_power_of_two().trailing_zeros() as usize;
+        // find the first non-empty available order
+        let order = (class..MAX_ORDER).find(|&i| !self.free_list[i].is_empty())?;
+        let block = self.free_list[order].pop_first().unwrap();
+        // split it to the required order
+        for j in (class..order).rev() {
+            self.free_list[j].insert(block + (1 << j));
+        }
+        // give back the unused tail frames of the block
+        let mut tail = block + count;
+        let block_end = block + (1 << class);
+        while tail < block_end {
+            let order = tail.trailing_zeros() as usize;
+            self.free_list[order].insert(tail);
+            tail += 1 << order;
+        }
+        for ppn in block..block + count {
+            self.set_allocated(ppn, true);
+        }
+        self.allocated += count;
+        Some(block.into())
+    }
+
+    fn stats(&self) -> FrameAllocatorStats {
+        let largest_free = (0..MAX_ORDER)
+            .rev()
+            .find(|&i| !self.free_list[i].is_empty())
+            .map_or(0, |i| 1 << i);
+        FrameAllocatorStats {
+            total: self.end - self.start,
+            allocated: self.allocated,
+            largest_free,
+        }
+    }
+}
+
+impl BuddyFrameAllocator {
+    pub fn init(&mut self, l: PhysPageNum, r: PhysPageNum) {
+        self.start = l.0;
+        self.end = r.0;
+        self.bitmap = vec![0; (r.0 - l.0 + 63) / 64];
+        // split [l, r) into aligned blocks as large as possible
+        let mut current = l.0;
+        while current < r.0 {
+            let mut order = (current.trailing_zeros() as usize).min(MAX_ORDER - 1);
+            while current + (1 << order) > r.0 {
+                order -= 1;
+            }
+            self.free_list[order].insert(current);
+            current += 1 << order;
+        }
+    }
+
+    /// Take frame `ppn` out of the allocator forever.
+    pub fn reserve(&mut self, ppn: PhysPageNum) {
+        let ppn = ppn.0;
+        for order in 0..MAX_ORDER {
+            let block = ppn & !((1 << order) - 1);
+            if self.free_list[order].remove(&block) {
+                // split the block, the halves without `ppn` are still free
+                for j in (0..order).rev() {
+                    self.free_list[j].insert((ppn & !((1 << j) - 1)) ^ (1 << j));
+                }
+                self.set_allocated(ppn, true);
+                self.allocated += 1;
+                return;
+            }
+        }
+        panic!("Frame ppn={:#x} is not free!", ppn);
+    }
+
+    fn is_allocated(&self, ppn: usize) -> bool {
+        let i = ppn - self.start;
+        self.bitmap[i / 64] & (1 << (i % 64)) != 0
+    }
+
+    fn set_allocated(&mut self, ppn: usize, allocated: bool) {
+        let i = ppn - self.start;
+        if allocated {
+            self.bitmap[i / 64] |= 1 << (i % 64);
+        } else {
+            self.bitmap[i / 64] &= !(1 << (i % 64));
+        }
+    }
+}
+
+type FrameAllocatorImpl = BuddyFrameAllocator;
 
 lazy_static! {
     pub static ref FRAME_ALLOCATOR: UPSafeCell<FrameAllocatorImpl> =
@@ -70,10 +279,15 @@ pub fn init_frame_allocator() {
     extern "C" {
         fn ekernel();
     }
-    FRAME_ALLOCATOR.exclusive_access().init(
-        PhysAddr::from(ekernel as usize).ceil(),
-        PhysAddr::from(MEMORY_END).floor(),
-    );
+    let start = PhysAddr::from(ekernel as usize).ceil();
+    let end = PhysAddr::from(swap_area().0).floor();
+    let mut frame_allocator = FRAME_ALLOCATOR.exclusive_access();
+    frame_allocator.init(start, end);
+    // the shared page has a fixed physical address
+    let shared_page = PhysAddr::from(SHARED_PAGE).floor();
+    if shared_page >= start && shared_page < end {
+        frame_allocator.reserve(shared_page);
+    }
 }
 
 /// ### RAII : Resource Acquisition Is Initialization
@@ -107,11 +321,42 @@ fn frame_dealloc(ppn: PhysPageNum) {
 }
 
 /// allocate a frame (auto-recycle)
+///
+/// When physical memory is exhausted, drop a page of the page cache, or evict a
+/// user page to the swap area, and try again.
 pub fn frame_alloc() -> Option<FrameTracker> {
-    FRAME_ALLOCATOR
+    loop {
+        let ppn = FRAME_ALLOCATOR.exclusive_access().alloc();
+        if let Some(ppn) = ppn {
+            return Some(FrameTracker::new(ppn));
+        }
+        // debug: FRAME_ALLOCATOR must not be borrowed here, evicted frames are deallocated
+        if !page_cache::shrink() && !swap_out_victim() {
+            return None;
+        }
+    }
+}
+
+/// allocate `count` physically contiguous frames (auto-recycle)
+///
+/// Unlike `frame_alloc`, no page is swapped out to make room.
+#[allow(unused)]
+pub fn frame_alloc_contiguous(count: usize) -> Option<Vec<FrameTracker>> {
+    let start = FRAME_ALLOCATOR
         .exclusive_access()
-        .alloc()
-        .map(|ppn| FrameTracker::new(ppn))
+        .alloc_contiguous(count)?
+        .0;
+    Some(
+        (start..start + count)
+            .map(|ppn| FrameTracker::new(ppn.into()))
+            .collect(),
+    )
+}
+
+/// get statistics of the frame allocator
+#[allow(unused)]
+pub fn frame_allocator_stats() -> FrameAllocatorStats {
+    FRAME_ALLOCATOR.exclusive_access().stats()
 }
 
 #[allow(unused)]
@@ -129,6 +374,14 @@ pub fn frame_allocator_test() {
         v.push(frame);
     }
     drop(v);
+    let allocated = frame_allocator_stats().allocated;
+    let frames = frame_alloc_contiguous(5).unwrap();
+    for (i, frame) in frames.iter().enumerate() {
+        assert_eq!(frame.ppn.0, frames[0].ppn.0 + i);
+    }
+    assert_eq!(frame_allocator_stats().allocated, allocated + 5);
+    drop(frames);
+    assert_eq!(frame_allocator_stats().allocated, allocated);
     debug!("[test] frame allocator test passed!");
 }
 
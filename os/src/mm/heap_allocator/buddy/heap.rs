@@ -30,6 +30,16 @@ impl MyHeap {
         }
     }
 
+    /// Bytes allocated, rounded up to the size classes
+    pub fn allocated(&self) -> usize {
+        self.allocated
+    }
+
+    /// Bytes managed by the heap
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
     pub fn init(&mut self, start: usize, size: usize) {
         self.add_to_heap(start, start + size);
     }
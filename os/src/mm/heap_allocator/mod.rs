@@ -17,6 +17,21 @@ pub fn init_heap() {
     }
 }
 
+/// Statistics of the kernel heap (in bytes)
+#[derive(Debug, Clone, Copy)]
+pub struct HeapStats {
+    pub total: usize,
+    pub allocated: usize,
+}
+
+pub fn heap_stats() -> HeapStats {
+    let heap = HEAP_ALLOCATOR.lock();
+    HeapStats {
+        total: heap.total(),
+        allocated: heap.allocated(),
+    }
+}
+
 #[alloc_error_handler]
 pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
     panic!("Heap allocation error, layout = {:?}", layout);
@@ -1,8 +1,14 @@
 use super::address::*;
 use super::frame_allocator::*;
+use super::page_cache::{self, CachedPage};
 use super::page_table::*;
+use super::swap::{swap_out, SwapTracker};
+use super::OutOfMemory;
 use crate::config::PAGE_SIZE;
+use crate::fs::vfs::Inode;
+use crate::fs::WriteAccess;
 use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
 use bitflags::bitflags;
 
 bitflags! {
@@ -18,6 +24,25 @@ bitflags! {
 pub enum MapType {
     Identical,
     Framed,
+    /// pages of a file, mapped on page fault
+    File,
+}
+
+/// The file mapped by a `MapType::File` area
+#[derive(Clone)]
+pub struct MappedFile {
+    pub inode: Arc<dyn Inode>,
+    /// offset in the file of the start of the area, page aligned
+    pub offset: usize,
+    /// bytes of the file in the area, the rest of the area is zero
+    pub len: usize,
+    /// changes are written back to the file and seen by other mappings,
+    /// instead of being private to the area
+    pub shared: bool,
+    /// to write the file for a shared writable mapping, or denying it for the
+    /// segments of a program
+    #[allow(dead_code)]
+    pub write_access: Option<Arc<WriteAccess>>,
 }
 
 /// VM area (RAII)
@@ -25,7 +50,13 @@ pub struct MapArea {
     pub vpn_range: VPNRange,
     map_type: MapType,
     data_frames: BTreeMap<VirtPageNum, FrameTracker>,
+    /// pages evicted to the swap area (not present in page table)
+    swapped_frames: BTreeMap<VirtPageNum, SwapTracker>,
     map_perm: MapPermission,
+    file: Option<MappedFile>,
+    /// pages of the page cache mapped by a `MapType::File` area, the others
+    /// have their own frames
+    cached_pages: BTreeMap<VirtPageNum, Arc<CachedPage>>,
 }
 
 impl MapArea {
@@ -34,8 +65,11 @@ impl MapArea {
         Self {
             vpn_range: another.vpn_range.clone(),
             data_frames: BTreeMap::new(),
+            swapped_frames: BTreeMap::new(),
             map_type: another.map_type,
             map_perm: another.map_perm,
+            file: another.file.clone(),
+            cached_pages: BTreeMap::new(),
         }
     }
 }
@@ -53,24 +87,74 @@ impl MapArea {
             vpn_range: VPNRange::new(start_vpn, end_vpn),
             map_type,
             data_frames: BTreeMap::new(),
+            swapped_frames: BTreeMap::new(),
             map_perm,
+            file: None,
+            cached_pages: BTreeMap::new(),
         }
     }
 
+    /// An area of `MapType::File`, whose pages are mapped on page fault.
+    pub fn new_file(
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        map_perm: MapPermission,
+        file: MappedFile,
+    ) -> Self {
+        let mut area = Self::new(start_va, end_va, MapType::File, map_perm);
+        area.file = Some(file);
+        area
+    }
+
     pub fn new_by_varange(va_range: VARange, map_type: MapType, map_perm: MapPermission) -> Self {
         let vpn_range = VPNRange::new(va_range.start.floor(), va_range.end.ceil());
         Self {
             vpn_range,
             map_type,
             data_frames: BTreeMap::new(),
+            swapped_frames: BTreeMap::new(),
             map_perm,
+            file: None,
+            cached_pages: BTreeMap::new(),
         }
     }
 
-    pub fn map_to(&mut self, page_table: &mut PageTable) {
+    /// On failure, pages already mapped are unmapped again.
+    pub fn map_to(&mut self, page_table: &mut PageTable) -> Result<(), OutOfMemory> {
+        if self.map_type == MapType::File {
+            // mapped on page fault
+            return Ok(());
+        }
         for vpn in self.vpn_range {
-            self.map(page_table, vpn);
+            if let Err(err) = self.map(page_table, vpn) {
+                for mapped_vpn in VPNRange::new(self.vpn_range.start, vpn) {
+                    self.unmap(page_table, mapped_vpn);
+                }
+                return Err(err);
+            }
         }
+        Ok(())
+    }
+
+    /// Map pages `[start, vpn_range.start)` and make them a part of this area.
+    ///
+    /// On failure, the area is left unchanged.
+    pub fn extend_down(
+        &mut self,
+        page_table: &mut PageTable,
+        start: VirtPageNum,
+    ) -> Result<(), OutOfMemory> {
+        let old_start = self.vpn_range.start;
+        for vpn in VPNRange::new(start, old_start) {
+            if let Err(err) = self.map(page_table, vpn) {
+                for mapped_vpn in VPNRange::new(start, vpn) {
+                    self.unmap(page_table, mapped_vpn);
+                }
+                return Err(err);
+            }
+        }
+        self.vpn_range = VPNRange::new(start, self.vpn_range.end);
+        Ok(())
     }
 
     pub fn unmap_to(&mut self, page_table: &mut PageTable) {
@@ -105,20 +189,74 @@ impl MapArea {
 
 impl MapArea {
     /// Map one virtual page to page table.
-    pub fn map(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
-        let ppn: PhysPageNum;
+    pub fn map(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> Result<(), OutOfMemory> {
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
         match self.map_type {
             MapType::Identical => {
-                ppn = PhysPageNum(vpn.0);
+                page_table.map(vpn, PhysPageNum(vpn.0), pte_flags)?;
             }
             MapType::Framed => {
-                let frame = frame_alloc().unwrap();
-                ppn = frame.ppn;
+                let frame = frame_alloc().ok_or(OutOfMemory)?;
+                page_table.map(vpn, frame.ppn, pte_flags)?;
                 self.data_frames.insert(vpn, frame);
             }
+            MapType::File => {
+                let file = self.file.as_ref().unwrap();
+                let start = (vpn.0 - self.vpn_range.start.0) * PAGE_SIZE;
+                let index = (file.offset + start) / PAGE_SIZE;
+                // a private page may be changed, or cleared past `len`
+                if file.shared
+                    || !self.map_perm.contains(MapPermission::W) && start + PAGE_SIZE <= file.len
+                {
+                    let page = page_cache::get(&file.inode, index)?;
+                    page_table.map(vpn, page.ppn(), pte_flags)?;
+                    self.cached_pages.insert(vpn, page);
+                } else {
+                    let frame = frame_alloc().ok_or(OutOfMemory)?;
+                    if start < file.len {
+                        let page = page_cache::get(&file.inode, index)?;
+                        let len = (file.len - start).min(PAGE_SIZE);
+                        frame.ppn.get_bytes_array()[..len]
+                            .copy_from_slice(&page.ppn().get_bytes_array()[..len]);
+                    }
+                    page_table.map(vpn, frame.ppn, pte_flags)?;
+                    self.data_frames.insert(vpn, frame);
+                }
+            }
         }
+        Ok(())
+    }
+
+    /// Map the pages of `another`, a `MapType::File` area of the parent
+    /// process, which it has accessed already, for fork.
+    ///
+    /// Pages of the page cache are shared, the others are copied.
+    pub fn copy_file_pages(
+        &mut self,
+        page_table: &mut PageTable,
+        another: &MapArea,
+    ) -> Result<(), OutOfMemory> {
         let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
-        page_table.map(vpn, ppn, pte_flags);
+        for (&vpn, page) in another.cached_pages.iter() {
+            page_table.map(vpn, page.ppn(), pte_flags)?;
+            self.cached_pages.insert(vpn, page.clone());
+        }
+        for (&vpn, src) in another.data_frames.iter() {
+            let frame = frame_alloc().ok_or(OutOfMemory)?;
+            frame
+                .ppn
+                .get_bytes_array()
+                .copy_from_slice(src.ppn.get_bytes_array());
+            page_table.map(vpn, frame.ppn, pte_flags)?;
+            self.data_frames.insert(vpn, frame);
+        }
+        for (&vpn, swap_tracker) in another.swapped_frames.iter() {
+            let frame = frame_alloc().ok_or(OutOfMemory)?;
+            swap_tracker.read_to(frame.ppn);
+            page_table.map(vpn, frame.ppn, pte_flags)?;
+            self.data_frames.insert(vpn, frame);
+        }
+        Ok(())
     }
 
     /// Unmap one virtual page from page table.
@@ -126,9 +264,168 @@ impl MapArea {
         match self.map_type {
             MapType::Framed => {
                 self.data_frames.remove(&vpn);
+                if self.swapped_frames.remove(&vpn).is_some() {
+                    // swapped page is not present in page table
+                    return;
+                }
+            }
+            MapType::File => {
+                self.swapped_frames.remove(&vpn);
+                if let Some(page) = self.cached_pages.remove(&vpn) {
+                    self.write_back(vpn, &page);
+                } else if self.data_frames.remove(&vpn).is_none() {
+                    // not accessed yet, or swapped
+                    return;
+                }
             }
             _ => {}
         }
         page_table.unmap(vpn);
     }
+
+    pub fn is_file(&self) -> bool {
+        self.map_type == MapType::File
+    }
+
+    /// Bring page `vpn` in on a page fault: a swapped page, or a page of a file
+    /// not accessed yet. Return false if it is present already.
+    pub fn handle_fault(
+        &mut self,
+        page_table: &mut PageTable,
+        vpn: VirtPageNum,
+    ) -> Result<bool, OutOfMemory> {
+        if self.swap_in(page_table, vpn)? {
+            return Ok(true);
+        }
+        if self.map_type != MapType::File
+            || self.cached_pages.contains_key(&vpn)
+            || self.data_frames.contains_key(&vpn)
+        {
+            return Ok(false);
+        }
+        self.map(page_table, vpn)?;
+        Ok(true)
+    }
+}
+
+// shared file mappings ---------------------------------------------
+
+impl MapArea {
+    /// Write page `vpn` of a shared and writable mapping back to its file, but
+    /// not past the end of the file. Return 0 or a negated errno.
+    fn write_back(&self, vpn: VirtPageNum, page: &CachedPage) -> isize {
+        let file = match &self.file {
+            Some(file) if file.shared && self.map_perm.contains(MapPermission::W) => file,
+            _ => return 0,
+        };
+        let offset = file.offset + (vpn.0 - self.vpn_range.start.0) * PAGE_SIZE;
+        let size = file.inode.size();
+        if offset >= size {
+            return 0;
+        }
+        let len = (size - offset).min(PAGE_SIZE);
+        let ret = file
+            .inode
+            .write_at(offset, &page.ppn().get_bytes_array()[..len]);
+        ret.min(0)
+    }
+
+    /// Write the pages in `range` back to the file, see `sys_msync`, and the
+    /// changes of the file to its device if `to_device`. Return 0 or a negated
+    /// errno.
+    pub fn sync(&self, range: VPNRange, to_device: bool) -> isize {
+        let mut ret = 0;
+        for (&vpn, page) in self.cached_pages.range(range.start..range.end) {
+            let err = self.write_back(vpn, page);
+            if ret == 0 {
+                ret = err;
+            }
+        }
+        if let Some(file) = &self.file {
+            if to_device && ret == 0 && file.shared {
+                ret = file.inode.sync();
+            }
+        }
+        ret
+    }
+}
+
+impl Drop for MapArea {
+    /// Pages of a shared mapping are written back when the address space is
+    /// dropped, e.g. on exit.
+    fn drop(&mut self) {
+        for (&vpn, page) in self.cached_pages.iter() {
+            self.write_back(vpn, page);
+        }
+    }
+}
+
+// swapping --------------------------------------------------------
+
+impl MapArea {
+    /// Only user pages with their own frames can be swapped out, not those of
+    /// the page cache.
+    pub fn is_swappable(&self) -> bool {
+        matches!(self.map_type, MapType::Framed | MapType::File)
+            && self.map_perm.contains(MapPermission::U)
+    }
+
+    /// Virtual pages currently backed by physical frames.
+    pub fn resident_vpns(&self) -> impl Iterator<Item = VirtPageNum> + '_ {
+        self.data_frames.keys().copied()
+    }
+
+    pub fn swapped_frame(&self, vpn: VirtPageNum) -> Option<&SwapTracker> {
+        self.swapped_frames.get(&vpn)
+    }
+
+    /// Write one resident page to the swap area and release its frame.
+    ///
+    /// Return false if the swap area is full.
+    pub fn swap_out(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) -> bool {
+        let ppn = self.data_frames.get(&vpn).unwrap().ppn;
+        match swap_out(ppn) {
+            Some(swap_tracker) => {
+                page_table.unmap(vpn);
+                self.data_frames.remove(&vpn);
+                self.swapped_frames.insert(vpn, swap_tracker);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Bring a swapped page back into a new frame.
+    ///
+    /// Return false if the page has not been swapped out.
+    pub fn swap_in(
+        &mut self,
+        page_table: &mut PageTable,
+        vpn: VirtPageNum,
+    ) -> Result<bool, OutOfMemory> {
+        if !self.swapped_frames.contains_key(&vpn) {
+            return Ok(false);
+        }
+        // keep the swap slot until the page is mapped again
+        let frame = frame_alloc().ok_or(OutOfMemory)?;
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+        page_table.map(vpn, frame.ppn, pte_flags)?;
+        let swap_tracker = self.swapped_frames.remove(&vpn).unwrap();
+        swap_tracker.read_to(frame.ppn);
+        self.data_frames.insert(vpn, frame);
+        Ok(true)
+    }
+
+    /// Number of resident frames, used to choose an OOM victim.
+    pub fn resident_count(&self) -> usize {
+        self.data_frames.len()
+    }
+
+    pub fn swapped_count(&self) -> usize {
+        self.swapped_frames.len()
+    }
+
+    pub fn map_perm(&self) -> MapPermission {
+        self.map_perm
+    }
 }
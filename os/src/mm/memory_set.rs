@@ -1,37 +1,74 @@
 use super::address::*;
 use super::map_area::*;
 use super::page_table::*;
+use super::OutOfMemory;
 use crate::asm;
+use crate::board::board_info;
 use crate::config::*;
-use crate::sbi::mmio::MMIO;
+use crate::loader::Executable;
+use crate::sbi::mmio::mmio_ranges;
+use crate::syscall::errno::{EEXIST, EINVAL, ENOMEM};
+use alloc::vec;
 use alloc::vec::Vec;
 use riscv::register::satp;
 
+/// Reasons why a page fault cannot be resolved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageFaultError {
+    /// access outside of any map area
+    InvalidAddress,
+    /// no frame available even after swapping
+    OutOfMemory,
+    /// access below the stack limit or to the guard gap below the user stack, a
+    /// SIGSEGV reported as a stack overflow in the wait status
+    StackOverflow,
+}
+
 /// Address Space (RAII)
 pub struct MemorySet {
     pub page_table: PageTable,
     pub areas: Vec<MapArea>,
+    /// clock hand for page replacement
+    clock_hand: VirtPageNum,
+    /// pages the user stack may grow to within `stack_limit`, the rest of the
+    /// `USER_STACK_MAX_SIZE` reserved and `USER_STACK_GUARD_SIZE` below are never
+    /// mapped
+    stack_range: VPNRange,
+    /// bytes the user stack may grow to, kept across exec
+    stack_limit: usize,
 }
 
 impl MemorySet {
-    pub fn from_existed_user(user_space: &MemorySet) -> MemorySet {
-        let mut memory_set = Self::new_bare();
+    pub fn from_existed_user(user_space: &MemorySet) -> Result<MemorySet, OutOfMemory> {
+        let mut memory_set = Self::new_bare()?;
+        memory_set.stack_range = user_space.stack_range;
+        memory_set.stack_limit = user_space.stack_limit;
         // map trampoline
-        memory_set.map_trampoline();
+        memory_set.map_trampoline()?;
         // copy data sections/trap_context/user_stack
         for area in user_space.areas.iter() {
-            let new_area = MapArea::from_another(area);
-            memory_set.push(new_area, None);
+            let mut new_area = MapArea::from_another(area);
+            if area.is_file() {
+                // pages not accessed yet are mapped on page fault
+                new_area.copy_file_pages(&mut memory_set.page_table, area)?;
+                memory_set.areas.push(new_area);
+                continue;
+            }
+            memory_set.push(new_area, None)?;
             // copy data from another space
             for vpn in area.vpn_range {
-                let src_ppn = user_space.translate_to_ppn(vpn).unwrap();
                 let dst_ppn = memory_set.translate_to_ppn(vpn).unwrap();
+                if let Some(swap_tracker) = area.swapped_frame(vpn) {
+                    swap_tracker.read_to(dst_ppn);
+                    continue;
+                }
+                let src_ppn = user_space.translate_to_ppn(vpn).unwrap();
                 dst_ppn
                     .get_bytes_array()
                     .copy_from_slice(src_ppn.get_bytes_array());
             }
         }
-        memory_set
+        Ok(memory_set)
     }
 
     #[allow(unused)]
@@ -65,25 +102,257 @@ impl MemorySet {
     }
 }
 
+// --------------------------- page replacement --------------------------------
+
+impl MemorySet {
+    /// Handle a page fault at `va` (e.g. bring back a swapped out page, or read
+    /// a page of a mapped file).
+    pub fn handle_page_fault(&mut self, va: VirtAddr) -> Result<(), PageFaultError> {
+        let vpn = va.floor();
+        if self.stack_range.contains(vpn) {
+            self.grow_stack(vpn)?;
+        }
+        // a frame may skip pages of the gap
+        if self.stack_guard().contains(vpn) {
+            return Err(PageFaultError::StackOverflow);
+        }
+        let area = self
+            .areas
+            .iter_mut()
+            .find(|area| area.vpn_range.contains(vpn))
+            .ok_or(PageFaultError::InvalidAddress)?;
+        match area.handle_fault(&mut self.page_table, vpn) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(PageFaultError::InvalidAddress),
+            Err(OutOfMemory) => Err(PageFaultError::OutOfMemory),
+        }
+    }
+
+    /// Reserved pages and guard gap below the reach of the main user stack.
+    fn stack_guard(&self) -> VPNRange {
+        let pages = (USER_STACK_MAX_SIZE + USER_STACK_GUARD_SIZE) / PAGE_SIZE;
+        let end = self.stack_range.end;
+        VPNRange::new(
+            VirtPageNum(end.0.saturating_sub(pages)),
+            self.stack_range.start,
+        )
+    }
+
+    pub fn stack_limit(&self) -> usize {
+        self.stack_limit
+    }
+
+    /// Let the main user stack grow to `limit` bytes (at most `USER_STACK_MAX_SIZE`).
+    ///
+    /// Pages already mapped below the new limit stay mapped.
+    pub fn set_stack_limit(&mut self, limit: usize) {
+        assert!(limit <= USER_STACK_MAX_SIZE);
+        let top: usize = VirtAddr::from(self.stack_range.end).into();
+        self.stack_range.start = VirtAddr::from(top - limit).floor();
+        self.stack_limit = limit;
+    }
+
+    /// Top of the main user stack, stacks of other threads are placed above it.
+    pub fn user_stack_top(&self) -> usize {
+        VirtAddr::from(self.stack_range.end).into()
+    }
+
+    /// Extend the user stack area down to `vpn` (inside `stack_range`).
+    fn grow_stack(&mut self, vpn: VirtPageNum) -> Result<(), PageFaultError> {
+        let stack_top = self.stack_range.end;
+        let stack = self
+            .areas
+            .iter_mut()
+            .find(|area| area.vpn_range.end == stack_top)
+            .ok_or(PageFaultError::InvalidAddress)?;
+        if vpn < stack.vpn_range.start {
+            stack
+                .extend_down(&mut self.page_table, vpn)
+                .map_err(|_| PageFaultError::OutOfMemory)?;
+        }
+        Ok(())
+    }
+
+    /// Number of resident frames of user areas.
+    pub fn resident_count(&self) -> usize {
+        self.areas.iter().map(|area| area.resident_count()).sum()
+    }
+
+    /// Evict one resident user page to the swap area (clock algorithm).
+    ///
+    /// Pages are scanned from the clock hand: an accessed page gets its `A` bit
+    /// cleared and a second chance, the first one not accessed is evicted.
+    pub fn swap_out_one(&mut self) -> bool {
+        let mut candidates: Vec<(usize, VirtPageNum)> = Vec::new();
+        for (idx, area) in self.areas.iter().enumerate() {
+            if area.is_swappable() {
+                candidates.extend(area.resident_vpns().map(|vpn| (idx, vpn)));
+            }
+        }
+        let start = candidates
+            .iter()
+            .position(|(_, vpn)| *vpn >= self.clock_hand)
+            .unwrap_or(0);
+        candidates.rotate_left(start);
+        // all accessed bits are cleared after the first round
+        for _ in 0..2 {
+            for &(idx, vpn) in candidates.iter() {
+                if self.page_table.test_and_clear_accessed(vpn) {
+                    continue;
+                }
+                if !self.areas[idx].swap_out(&mut self.page_table, vpn) {
+                    // swap area is full
+                    return false;
+                }
+                self.clock_hand = VirtPageNum(vpn.0 + 1);
+                return true;
+            }
+        }
+        false
+    }
+}
+
+// --------------------------- memory mappings --------------------------------
+
+/// User addresses are below, the upper half of Sv39 is for the kernel
+const USER_SPACE_END: VirtPageNum = VirtPageNum(1 << (38 - PAGE_SIZE_BITS));
+
+fn overlaps(a: VPNRange, b: VPNRange) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+impl MemorySet {
+    /// End of the last area, or of the reach of the main stack, overlapping
+    /// `range`, if any.
+    fn conflict(&self, range: VPNRange) -> Option<VirtPageNum> {
+        let stack = VPNRange::new(self.stack_guard().start, self.stack_range.end);
+        self.areas
+            .iter()
+            .map(|area| area.vpn_range)
+            .chain([stack])
+            .filter(|other| overlaps(range, *other))
+            .map(|other| other.end)
+            .max()
+    }
+
+    /// Whether `range` overlaps neither an area nor the reach of the main stack.
+    pub fn is_free(&self, range: VPNRange) -> bool {
+        self.conflict(range).is_none()
+    }
+
+    /// Map `pages` pages at `start` if they are free, or else at the lowest
+    /// free address from `MMAP_BASE`, for `sys_mmap`. Return the start of the
+    /// mapping, or a negated errno.
+    ///
+    /// With `fixed` the pages must be at `start`. Below `MMAP_BASE` are the
+    /// program and the stacks of threads, a start there is no hint. Pages of
+    /// `file` are mapped on page fault, anonymous ones at once.
+    pub fn mmap(
+        &mut self,
+        start: VirtPageNum,
+        pages: usize,
+        perm: MapPermission,
+        file: Option<MappedFile>,
+        fixed: bool,
+    ) -> Result<VirtPageNum, isize> {
+        let base = VirtAddr::from(MMAP_BASE).floor();
+        let mut range = VPNRange::new(start, VirtPageNum(start.0 + pages));
+        let valid = start >= base && range.end <= USER_SPACE_END;
+        if !valid || self.conflict(range).is_some() {
+            if fixed {
+                // mappings are not replaced
+                return Err(if valid { -EEXIST } else { -EINVAL });
+            }
+            let mut start = base;
+            loop {
+                range = VPNRange::new(start, VirtPageNum(start.0 + pages));
+                if range.end > USER_SPACE_END {
+                    return Err(-ENOMEM);
+                }
+                match self.conflict(range) {
+                    Some(end) => start = end,
+                    None => break,
+                }
+            }
+        }
+        let area = match file {
+            Some(file) => MapArea::new_file(range.start.into(), range.end.into(), perm, file),
+            None => MapArea::new(range.start.into(), range.end.into(), MapType::Framed, perm),
+        };
+        self.push(area, None).map_err(|_| -ENOMEM)?;
+        Ok(range.start)
+    }
+
+    /// Remove the areas in `range`, for `sys_munmap`, pages of shared mappings
+    /// are written back. Areas are not split: return -EINVAL if one is partly
+    /// in `range`.
+    pub fn munmap(&mut self, range: VPNRange) -> isize {
+        if self.areas.iter().any(|area| {
+            overlaps(range, area.vpn_range)
+                && (area.vpn_range.start < range.start || area.vpn_range.end > range.end)
+        }) {
+            return -EINVAL;
+        }
+        let (removed, kept): (Vec<MapArea>, Vec<MapArea>) = self
+            .areas
+            .drain(..)
+            .partition(|area| overlaps(range, area.vpn_range));
+        self.areas = kept;
+        for mut area in removed {
+            area.unmap_to(&mut self.page_table);
+        }
+        0
+    }
+
+    /// Write the pages of shared mappings in `range` back to their files, for
+    /// `sys_msync`, and the files to their devices if `to_device`. Return 0,
+    /// -ENOMEM if part of `range` is not mapped, or another negated errno.
+    pub fn msync(&self, range: VPNRange, to_device: bool) -> isize {
+        let mut mapped = 0;
+        let mut ret = 0;
+        for area in self
+            .areas
+            .iter()
+            .filter(|area| overlaps(range, area.vpn_range))
+        {
+            let start = area.vpn_range.start.max(range.start);
+            let end = area.vpn_range.end.min(range.end);
+            mapped += end.0 - start.0;
+            let err = area.sync(VPNRange::new(start, end), to_device);
+            if ret == 0 {
+                ret = err;
+            }
+        }
+        if mapped < range.end.0 - range.start.0 {
+            return -ENOMEM;
+        }
+        ret
+    }
+}
+
 // --------------------------- MemorySet construct methods --------------------------------
 
 impl MemorySet {
     /// Create a new address space.
-    pub fn new_bare() -> Self {
-        Self {
-            page_table: PageTable::new(),
+    pub fn new_bare() -> Result<Self, OutOfMemory> {
+        Ok(Self {
+            page_table: PageTable::new()?,
             areas: Vec::new(),
-        }
+            clock_hand: VirtPageNum(0),
+            stack_range: VPNRange::new(VirtPageNum(0), VirtPageNum(0)),
+            stack_limit: 0,
+        })
     }
 
     /// Push a new map area into the address space.
-    fn push(&mut self, mut area: MapArea, data: Option<&[u8]>) {
-        area.map_to(&mut self.page_table);
+    fn push(&mut self, mut area: MapArea, data: Option<&[u8]>) -> Result<(), OutOfMemory> {
+        area.map_to(&mut self.page_table)?;
         // write initial data (optional)
         if let Some(data) = data {
             area.copy_data(&mut self.page_table, data);
         }
         self.areas.push(area);
+        Ok(())
     }
 
     /// Assume that no conflicts.
@@ -92,11 +361,11 @@ impl MemorySet {
         start_va: VirtAddr,
         end_va: VirtAddr,
         permission: MapPermission,
-    ) {
+    ) -> Result<(), OutOfMemory> {
         self.push(
             MapArea::new(start_va, end_va, MapType::Framed, permission),
             None,
-        );
+        )
     }
 
     /// Enable address space.
@@ -110,20 +379,20 @@ impl MemorySet {
     }
 
     /// Mention that trampoline is not collected by areas.
-    fn map_trampoline(&mut self) {
+    fn map_trampoline(&mut self) -> Result<(), OutOfMemory> {
         self.page_table.map(
             VirtAddr::from(TRAMPOLINE).into(),
             PhysAddr::from(strampoline as usize).into(),
             PTEFlags::R | PTEFlags::X,
-        );
+        )
     }
 
-    fn map_shared_page(&mut self) {
+    fn map_shared_page(&mut self) -> Result<(), OutOfMemory> {
         self.page_table.map(
             VirtAddr::from(SHARED_PAGE).into(),
             PhysAddr::from(SHARED_PAGE).into(),
             PTEFlags::R | PTEFlags::W | PTEFlags::U,
-        );
+        )
     }
 }
 
@@ -153,10 +422,10 @@ macro_rules! print_kernel_init_info {
 
 impl MemorySet {
     /// Without kernel stacks.
-    pub fn new_kernel() -> Self {
-        let mut memory_set = Self::new_bare();
+    pub fn new_kernel() -> Result<Self, OutOfMemory> {
+        let mut memory_set = Self::new_bare()?;
         // map trampoline
-        memory_set.map_trampoline();
+        memory_set.map_trampoline()?;
         // map kernel sections
         print_kernel_init_info!(".text [{:#x}, {:#x})", stext as usize, etext as usize);
         print_kernel_init_info!(".rodata [{:#x}, {:#x})", srodata as usize, erodata as usize);
@@ -175,7 +444,7 @@ impl MemorySet {
                 MapPermission::R | MapPermission::X,
             ),
             None,
-        );
+        )?;
         print_kernel_init_info!("mapping .rodata section");
         memory_set.push(
             MapArea::new(
@@ -185,7 +454,7 @@ impl MemorySet {
                 MapPermission::R,
             ),
             None,
-        );
+        )?;
         print_kernel_init_info!("mapping .data section");
         memory_set.push(
             MapArea::new(
@@ -195,7 +464,7 @@ impl MemorySet {
                 MapPermission::R | MapPermission::W,
             ),
             None,
-        );
+        )?;
         print_kernel_init_info!("mapping .bss section");
         memory_set.push(
             MapArea::new(
@@ -205,28 +474,28 @@ impl MemorySet {
                 MapPermission::R | MapPermission::W,
             ),
             None,
-        );
+        )?;
         print_kernel_init_info!("mapping physical memory");
         memory_set.push(
             MapArea::new(
                 (ekernel as usize).into(),
-                MEMORY_END.into(),
+                board_info().memory_end().into(),
                 MapType::Identical,
                 MapPermission::R | MapPermission::W,
             ),
             None,
-        );
+        )?;
         // debug: Don't forget to map MMIO!
         print_kernel_init_info!("mapping MMIO");
-        for va_range in MMIO {
+        for va_range in mmio_ranges() {
             let ma = MapArea::new_by_varange(
                 va_range,
                 MapType::Identical,
                 MapPermission::R | MapPermission::W,
             );
-            memory_set.push(ma, None);
+            memory_set.push(ma, None)?;
         }
-        memory_set
+        Ok(memory_set)
     }
 }
 
@@ -235,71 +504,80 @@ impl MemorySet {
 impl MemorySet {
     /// Include sections in elf and trampoline and TrapContext and user stack,
     /// also returns user_sp and entry point.
-    pub fn new_from_elf(elf_data: &[u8]) -> (Self, usize, usize) {
-        let mut memory_set = Self::new_bare();
+    ///
+    /// Segments are mapped from the file of `exe`, and read on page fault, unless
+    /// they are not page aligned as in the file. The user stack may grow to
+    /// `stack_limit` bytes.
+    pub fn new_from_elf(
+        exe: &Executable,
+        stack_limit: usize,
+    ) -> Result<(Self, usize, usize), OutOfMemory> {
+        let mut memory_set = Self::new_bare()?;
         // map trampoline
-        memory_set.map_trampoline();
+        memory_set.map_trampoline()?;
         // map shared page
-        memory_set.map_shared_page();
+        memory_set.map_shared_page()?;
         // debug!("new_from_elf0");
         // map program headers of elf, with U flag
-        let elf = xmas_elf::ElfFile::new(elf_data).unwrap();
-        // debug!("new_from_elf2");
-        let elf_header = elf.header;
-        // check magic number
-        let magic = elf_header.pt1.magic;
-        assert_eq!(magic, [0x7f, 0x45, 0x4c, 0x46], "invalid elf!");
-        // get program header count
-        let ph_count = elf_header.pt2.ph_count();
         let mut max_end_vpn = VirtPageNum(0);
-        // traverse program headers
-        for i in 0..ph_count {
-            let ph = elf.program_header(i).unwrap();
-            if ph.get_type().unwrap() == xmas_elf::program::Type::Load {
-                let start_va: VirtAddr = (ph.virtual_addr() as usize).into();
-                let end_va: VirtAddr = ((ph.virtual_addr() + ph.mem_size()) as usize).into();
-                let mut map_perm = MapPermission::U;
-                let ph_flags = ph.flags();
-                if ph_flags.is_read() {
-                    map_perm |= MapPermission::R;
-                }
-                if ph_flags.is_write() {
-                    map_perm |= MapPermission::W;
-                }
-                if ph_flags.is_execute() {
-                    map_perm |= MapPermission::X;
-                }
+        // segments are checked and sorted by `Executable::open`
+        for segment in exe.segments.iter() {
+            let start_va: VirtAddr = segment.start.into();
+            let end_va: VirtAddr = segment.end.into();
+            let mut map_perm = MapPermission::U;
+            let ph_flags = segment.flags;
+            if ph_flags.is_read() {
+                map_perm |= MapPermission::R;
+            }
+            if ph_flags.is_write() {
+                map_perm |= MapPermission::W;
+            }
+            if ph_flags.is_execute() {
+                map_perm |= MapPermission::X;
+            }
+            let offset = segment.offset;
+            let file_size = segment.file_size;
+            if offset % PAGE_SIZE == start_va.page_offset() {
+                let file = MappedFile {
+                    inode: exe.file.clone(),
+                    offset: offset - start_va.page_offset(),
+                    len: start_va.page_offset() + file_size,
+                    shared: false,
+                    write_access: Some(exe.write_access.clone()),
+                };
+                let map_area = MapArea::new_file(start_va, end_va, map_perm, file);
+                max_end_vpn = map_area.vpn_range.end;
+                memory_set.push(map_area, None)?;
+            } else {
+                let mut data = vec![0u8; file_size];
+                exe.read_at(offset, &mut data);
                 let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
                 max_end_vpn = map_area.vpn_range.end;
-                memory_set.push(
-                    map_area,
-                    Some(&elf.input[ph.offset() as usize..(ph.offset() + ph.file_size()) as usize]),
-                );
+                memory_set.push(map_area, Some(&data))?;
             }
         }
         // map user stack with U flags
         let max_end_va: VirtAddr = max_end_vpn.into();
         let mut user_stack_bottom: usize = max_end_va.into();
-        // guard page
-        user_stack_bottom += PAGE_SIZE;
-        let user_stack_top = user_stack_bottom + USER_STACK_SIZE;
-        // map user stack
+        // guard gap
+        user_stack_bottom += USER_STACK_GUARD_SIZE;
+        let user_stack_top = user_stack_bottom + USER_STACK_MAX_SIZE;
+        // map user stack, it grows down to `stack_limit` below the top on page fault
         memory_set.insert_empty_framed_area(
-            user_stack_bottom.into(),
+            (user_stack_top - USER_STACK_SIZE).into(),
             user_stack_top.into(),
             MapPermission::R | MapPermission::W | MapPermission::U,
-        );
+        )?;
+        let top = VirtAddr::from(user_stack_top).floor();
+        memory_set.stack_range = VPNRange::new(top, top);
+        memory_set.set_stack_limit(stack_limit);
         // map TrapContext
         memory_set.insert_empty_framed_area(
             (TRAMPOLINE - PAGE_SIZE).into(),
             TRAMPOLINE.into(),
             MapPermission::R | MapPermission::W,
-        );
+        )?;
         // return user_space, user_sp, entry_point
-        (
-            memory_set,
-            user_stack_top,
-            elf.header.pt2.entry_point() as usize,
-        )
+        Ok((memory_set, user_stack_top, exe.entry_point))
     }
 }
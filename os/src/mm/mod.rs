@@ -3,20 +3,28 @@ pub mod frame_allocator;
 pub mod heap_allocator;
 pub mod map_area;
 pub mod memory_set;
+pub mod page_cache;
 pub mod page_table;
+pub mod swap;
 
 use crate::debug;
+use crate::task::{current_task, handle_out_of_memory};
 use crate::UPSafeCell;
-use address::VirtAddr;
+use address::{VirtAddr, VirtPageNum};
 use alloc::string::String;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 use lazy_static::lazy_static;
-use memory_set::MemorySet;
-use page_table::PageTable;
+use memory_set::{MemorySet, PageFaultError};
+use page_table::{PTEFlags, PageTable};
+
+/// Physical frames are exhausted (even after swapping).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutOfMemory;
 
 lazy_static! {
     pub static ref KERNEL_SPACE: Arc<UPSafeCell<MemorySet>> =
-        Arc::new(UPSafeCell::new(MemorySet::new_kernel()));
+        Arc::new(UPSafeCell::new(MemorySet::new_kernel().unwrap()));
 }
 
 pub fn init() {
@@ -28,11 +36,40 @@ pub fn init() {
     KERNEL_SPACE.exclusive_access().activate();
 }
 
+/// Bring a swapped out page of current user space back,
+/// so that it can be accessed in kernel space directly.
+pub fn ensure_user_page(token: usize, vpn: VirtPageNum) {
+    loop {
+        let page_table = PageTable::from_token(token);
+        if let Some(pte) = page_table.translate_to_pte(vpn) {
+            if pte.is_valid() {
+                return;
+            }
+        }
+        let process = match current_task() {
+            Some(task) => task.process.upgrade().unwrap(),
+            None => return,
+        };
+        let mut inner = process.inner_exclusive_access();
+        if inner.get_user_token() != token {
+            return;
+        }
+        let result = inner.memory_set.handle_page_fault(vpn.into());
+        drop(inner);
+        drop(process);
+        match result {
+            Err(PageFaultError::OutOfMemory) => handle_out_of_memory(),
+            _ => return,
+        }
+    }
+}
+
 pub fn translated_str(token: usize, ptr: *const u8) -> String {
     let page_table = PageTable::from_token(token);
     let mut string = String::new();
     let mut va = ptr as usize;
     loop {
+        ensure_user_page(token, VirtAddr::from(va).floor());
         let ch: u8 = *(page_table
             .translate_va(VirtAddr::from(va))
             .unwrap()
@@ -47,16 +84,48 @@ pub fn translated_str(token: usize, ptr: *const u8) -> String {
     string
 }
 
+/// Strings of a null-terminated array of string pointers in user space, like `argv`.
+pub fn translated_strings(token: usize, mut ptr: *const usize) -> Vec<String> {
+    let mut strings = Vec::new();
+    loop {
+        let str_ptr = *translated_refmut(token, ptr as *mut usize);
+        if str_ptr == 0 {
+            break;
+        }
+        strings.push(translated_str(token, str_ptr as *const u8));
+        ptr = unsafe { ptr.add(1) };
+    }
+    strings
+}
+
 /// translate a ptr through page table and return a mutable reference
 pub fn translated_refmut<T>(token: usize, ptr: *mut T) -> &'static mut T {
     let page_table = PageTable::from_token(token);
     let va = ptr as usize;
+    ensure_user_page(token, VirtAddr::from(va).floor());
     page_table
         .translate_va(VirtAddr::from(va))
         .unwrap()
         .get_mut()
 }
 
+/// Like `translated_refmut`, but return None instead of panicking if `ptr` is not
+/// in a readable user page. `T` must not cross a page boundary.
+pub fn checked_translated_ref<T>(token: usize, ptr: *const T) -> Option<&'static T> {
+    let page_table = PageTable::from_token(token);
+    let va = VirtAddr::from(ptr as usize);
+    if va.0 != ptr as usize {
+        return None;
+    }
+    ensure_user_page(token, va.floor());
+    let pte = page_table.translate_to_pte(va.floor())?;
+    if !pte.is_valid() || !pte.flags().contains(PTEFlags::R | PTEFlags::U) {
+        return None;
+    }
+    let pa = page_table.translate_va(va)?;
+    Some(pa.get_mut())
+}
+
 #[allow(unused)]
 pub fn remap_test() {
     extern "C" {
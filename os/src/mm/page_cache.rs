@@ -0,0 +1,184 @@
+//! Cache of the pages of files
+//!
+//! Pages of files mapped by `sys_mmap`, and of the programs loaded by `exec`,
+//! are read into frames kept here, so that a page is read from its file once,
+//! and shared by all mappings of it. Pages still mapped are never dropped,
+//! others are kept up to [`PAGE_CACHE_SIZE`], and the least recently used one
+//! is dropped first, also when frames run out, see `frame_alloc`.
+//!
+//! Cached pages are never newer than their file: `write` through a file
+//! descriptor changes both, see [`write`], and shared mappings, the only ones
+//! writing to cached pages, write them back to the file when they are synced
+//! or unmapped.
+
+use super::address::PhysPageNum;
+use super::frame_allocator::{frame_alloc, FrameTracker};
+use super::OutOfMemory;
+use crate::config::{PAGE_CACHE_SIZE, PAGE_SIZE};
+use crate::fs::vfs::Inode;
+use crate::UPSafeCell;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use lazy_static::lazy_static;
+
+/// A page of a file, mapped as long as there are other references to it
+pub struct CachedPage {
+    frame: FrameTracker,
+}
+
+impl CachedPage {
+    pub fn ppn(&self) -> PhysPageNum {
+        self.frame.ppn
+    }
+}
+
+struct Entry {
+    /// kept alive so that its address stays unique
+    #[allow(dead_code)]
+    inode: Arc<dyn Inode>,
+    page: Arc<CachedPage>,
+    /// `PageCache::clock` when last used
+    last_used: usize,
+}
+
+impl Entry {
+    fn is_mapped(&self) -> bool {
+        Arc::strong_count(&self.page) > 1
+    }
+}
+
+struct PageCache {
+    /// by address of the inode and index of the page in the file
+    pages: BTreeMap<(usize, usize), Entry>,
+    /// counts the uses of pages
+    clock: usize,
+}
+
+impl PageCache {
+    /// Remove the least recently used page which is not mapped.
+    fn evict(&mut self) -> Option<Entry> {
+        let key = *self
+            .pages
+            .iter()
+            .filter(|(_, entry)| !entry.is_mapped())
+            .min_by_key(|(_, entry)| entry.last_used)?
+            .0;
+        self.pages.remove(&key)
+    }
+
+    /// Cached pages of the inode at `key`, with their offset in the file
+    fn pages_of(&mut self, key: usize) -> impl Iterator<Item = (usize, &mut Entry)> + '_ {
+        self.pages
+            .range_mut((key, 0)..=(key, usize::MAX))
+            .map(|(&(_, index), entry)| (index * PAGE_SIZE, entry))
+    }
+}
+
+lazy_static! {
+    static ref PAGE_CACHE: UPSafeCell<PageCache> = UPSafeCell::new(PageCache {
+        pages: BTreeMap::new(),
+        clock: 0,
+    });
+}
+
+fn inode_key(inode: &Arc<dyn Inode>) -> usize {
+    Arc::as_ptr(inode) as *const () as usize
+}
+
+/// Page `index` of the file `inode`, read from it unless cached. The part past
+/// the end of the file is zero.
+pub fn get(inode: &Arc<dyn Inode>, index: usize) -> Result<Arc<CachedPage>, OutOfMemory> {
+    let key = (inode_key(inode), index);
+    {
+        let mut cache = PAGE_CACHE.exclusive_access();
+        cache.clock += 1;
+        let clock = cache.clock;
+        if let Some(entry) = cache.pages.get_mut(&key) {
+            entry.last_used = clock;
+            return Ok(entry.page.clone());
+        }
+    }
+    // the cache may be shrunk to allocate the frame
+    let frame = frame_alloc().ok_or(OutOfMemory)?;
+    let data = frame.ppn.get_bytes_array();
+    let mut len = 0;
+    while len < PAGE_SIZE {
+        let n = inode.read_at(index * PAGE_SIZE + len, &mut data[len..]);
+        if n <= 0 {
+            break;
+        }
+        len += n as usize;
+    }
+    let page = Arc::new(CachedPage { frame });
+    let mut cache = PAGE_CACHE.exclusive_access();
+    let last_used = cache.clock;
+    cache.pages.insert(
+        key,
+        Entry {
+            inode: inode.clone(),
+            page: page.clone(),
+            last_used,
+        },
+    );
+    let mut evicted = Vec::new();
+    while cache.pages.len() > PAGE_CACHE_SIZE {
+        match cache.evict() {
+            Some(entry) => evicted.push(entry),
+            None => break,
+        }
+    }
+    // the last reference to an inode may be dropped
+    drop(cache);
+    drop(evicted);
+    Ok(page)
+}
+
+/// Copy `data` written at `offset` of the file `inode` to its cached pages.
+pub fn write(inode: &Arc<dyn Inode>, offset: usize, data: &[u8]) {
+    let end = offset + data.len();
+    let mut cache = PAGE_CACHE.exclusive_access();
+    for (start, entry) in cache.pages_of(inode_key(inode)) {
+        if start + PAGE_SIZE <= offset || start >= end {
+            continue;
+        }
+        let from = offset.max(start);
+        let to = end.min(start + PAGE_SIZE);
+        entry.page.ppn().get_bytes_array()[from - start..to - start]
+            .copy_from_slice(&data[from - offset..to - offset]);
+    }
+}
+
+/// Clear the cached pages of the file `inode` past its new `size`, drop those
+/// not mapped.
+pub fn truncate(inode: &Arc<dyn Inode>, size: usize) {
+    let key = inode_key(inode);
+    let mut cache = PAGE_CACHE.exclusive_access();
+    let mut dropped = Vec::new();
+    for (start, entry) in cache.pages_of(key) {
+        if start + PAGE_SIZE <= size {
+            continue;
+        }
+        entry.page.ppn().get_bytes_array()[size.max(start) - start..].fill(0);
+        if start >= size && !entry.is_mapped() {
+            dropped.push((key, start / PAGE_SIZE));
+        }
+    }
+    let dropped: Vec<Entry> = dropped
+        .iter()
+        .filter_map(|key| cache.pages.remove(key))
+        .collect();
+    drop(cache);
+    drop(dropped);
+}
+
+/// Drop a page not mapped, return false if there is none.
+///
+/// Called when frames run out, maybe while a page is being read into the cache.
+pub fn shrink() -> bool {
+    let evicted = match PAGE_CACHE.try_borrow_mut() {
+        Ok(mut cache) => cache.evict(),
+        Err(_) => None,
+    };
+    evicted.is_some()
+}
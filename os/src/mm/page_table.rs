@@ -1,5 +1,6 @@
 use super::address::*;
 use super::frame_allocator::*;
+use super::{ensure_user_page, OutOfMemory};
 use alloc::vec;
 use alloc::vec::Vec;
 use bitflags::*;
@@ -70,21 +71,27 @@ pub struct PageTable {
 }
 
 impl PageTable {
-    pub fn new() -> Self {
+    pub fn new() -> Result<Self, OutOfMemory> {
         // for root
-        let frame = frame_alloc().unwrap();
-        PageTable {
+        let frame = frame_alloc().ok_or(OutOfMemory)?;
+        Ok(PageTable {
             root_ppn: frame.ppn,
             frames: vec![frame],
-        }
+        })
     }
 
-    pub fn map(&mut self, vpn: VirtPageNum, ppn: PhysPageNum, flags: PTEFlags) {
-        let pte = self.find_pte_create(vpn).unwrap();
+    pub fn map(
+        &mut self,
+        vpn: VirtPageNum,
+        ppn: PhysPageNum,
+        flags: PTEFlags,
+    ) -> Result<(), OutOfMemory> {
+        let pte = self.find_pte_create(vpn)?;
         if pte.is_valid() {
             panic!("vpn {:?} is mapped before mapping", vpn);
         }
         *pte = PageTableEntry::new(ppn, flags | PTEFlags::V);
+        Ok(())
     }
 
     pub fn unmap(&mut self, vpn: VirtPageNum) {
@@ -98,21 +105,21 @@ impl PageTable {
 
 impl PageTable {
     /// find pte with create
-    fn find_pte_create(&mut self, vpn: VirtPageNum) -> Option<&mut PageTableEntry> {
+    fn find_pte_create(&mut self, vpn: VirtPageNum) -> Result<&mut PageTableEntry, OutOfMemory> {
         let idxs = vpn.indexes();
         let mut ppn = self.root_ppn;
         for i in 0..2 {
             let pte = &mut ppn.get_pte_array()[idxs[i]];
             // if invalid, create a new pte node
             if !pte.is_valid() {
-                let frame = frame_alloc().unwrap();
+                let frame = frame_alloc().ok_or(OutOfMemory)?;
                 *pte = PageTableEntry::new(frame.ppn, PTEFlags::V);
                 self.frames.push(frame);
             }
             ppn = pte.ppn();
         }
         let pte = &mut ppn.get_pte_array()[idxs[2]];
-        Some(pte)
+        Ok(pte)
     }
 
     /// find pte without create
@@ -147,6 +154,17 @@ impl PageTable {
         self.find_pte(vpn).map(|pte| pte.ppn())
     }
 
+    /// Clear the accessed bit of a valid pte, return whether it was set.
+    pub fn test_and_clear_accessed(&mut self, vpn: VirtPageNum) -> bool {
+        match self.find_pte(vpn) {
+            Some(pte) if pte.is_valid() && pte.flags().contains(PTEFlags::A) => {
+                pte.bits &= !(PTEFlags::A.bits() as usize);
+                true
+            }
+            _ => false,
+        }
+    }
+
     /// Temporarily used to get arguments from user space.
     pub fn from_token(satp: usize) -> Self {
         Self {
@@ -175,6 +193,7 @@ pub fn translated_byte_buffer(token: usize, ptr: *const u8, len: usize) -> Vec<&
     while start < end {
         let start_va = VirtAddr::from(start);
         let mut vpn = start_va.floor();
+        ensure_user_page(token, vpn);
         let ppn = page_table.translate_to_ppn(vpn).unwrap();
         vpn.step();
         let mut end_va: VirtAddr = vpn.into();
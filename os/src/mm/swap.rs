@@ -0,0 +1,125 @@
+//! Page swapping to a backing block device
+//!
+//! Evicted user pages are written to page-sized slots of the swap device and
+//! read back on page fault. A slot is owned by a [`SwapTracker`] (RAII), just
+//! like a physical frame is owned by a `FrameTracker`.
+
+use super::address::PhysPageNum;
+use crate::board::board_info;
+use crate::config::{PAGE_SIZE, SHARED_PAGE, SWAP_SIZE};
+use crate::drivers::block::{BlockDevice, RamDisk, BLOCK_SZ};
+use crate::UPSafeCell;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::fmt::{self, Debug, Formatter};
+use lazy_static::lazy_static;
+
+const BLOCKS_PER_PAGE: usize = PAGE_SIZE / BLOCK_SZ;
+
+pub struct SwapManager {
+    device: Arc<dyn BlockDevice>,
+    current: usize,
+    end: usize,
+    // [current, end) represents unused slots
+    recycled: Vec<usize>,
+}
+
+impl SwapManager {
+    pub fn new(device: Arc<dyn BlockDevice>) -> Self {
+        let end = device.num_blocks() / BLOCKS_PER_PAGE;
+        Self {
+            device,
+            current: 0,
+            end,
+            recycled: Vec::new(),
+        }
+    }
+
+    fn alloc(&mut self) -> Option<usize> {
+        if let Some(slot) = self.recycled.pop() {
+            Some(slot)
+        } else if self.current == self.end {
+            None
+        } else {
+            self.current += 1;
+            Some(self.current - 1)
+        }
+    }
+
+    fn dealloc(&mut self, slot: usize) {
+        assert!(
+            slot < self.current,
+            "Swap slot {} has not been allocated!",
+            slot
+        );
+        self.recycled.push(slot);
+    }
+
+    fn write_page(&self, slot: usize, ppn: PhysPageNum) {
+        let page = ppn.get_bytes_array();
+        for (i, block) in page.chunks(BLOCK_SZ).enumerate() {
+            self.device.write_block(slot * BLOCKS_PER_PAGE + i, block);
+        }
+    }
+
+    fn read_page(&self, slot: usize, ppn: PhysPageNum) {
+        let page = ppn.get_bytes_array();
+        for (i, block) in page.chunks_mut(BLOCK_SZ).enumerate() {
+            self.device.read_block(slot * BLOCKS_PER_PAGE + i, block);
+        }
+    }
+}
+
+lazy_static! {
+    /// Swap area on a RAM disk reserved at the top of physical memory.
+    pub static ref SWAP_MANAGER: UPSafeCell<SwapManager> = {
+        let (start, end) = swap_area();
+        UPSafeCell::new(SwapManager::new(Arc::new(RamDisk::new(start, end - start))))
+    };
+}
+
+/// Physical range `[start, end)` of the swap area: the top `SWAP_SIZE` of
+/// memory, but never covering the shared page.
+pub fn swap_area() -> (usize, usize) {
+    let end = board_info().memory_end();
+    assert!(end > SHARED_PAGE + PAGE_SIZE, "Memory is too small!");
+    let start = (end - SWAP_SIZE).max(SHARED_PAGE + PAGE_SIZE);
+    (start, end)
+}
+
+/// ### RAII
+///
+/// - get resource : `swap_out` -> `SwapTracker`
+/// - release resource automatically : `drop` -> `swap_dealloc`
+pub struct SwapTracker {
+    pub slot: usize,
+}
+
+impl SwapTracker {
+    /// Copy the swapped page into frame `ppn`.
+    pub fn read_to(&self, ppn: PhysPageNum) {
+        SWAP_MANAGER.exclusive_access().read_page(self.slot, ppn);
+    }
+}
+
+impl Drop for SwapTracker {
+    fn drop(&mut self) {
+        SWAP_MANAGER.exclusive_access().dealloc(self.slot);
+    }
+}
+
+impl Debug for SwapTracker {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!("SwapTracker:slot={}", self.slot))
+    }
+}
+
+/// Write the content of frame `ppn` to a free swap slot.
+///
+/// Return `None` if the swap area is full.
+pub fn swap_out(ppn: PhysPageNum) -> Option<SwapTracker> {
+    let mut swap_manager = SWAP_MANAGER.exclusive_access();
+    let slot = swap_manager.alloc()?;
+    swap_manager.write_page(slot, ppn);
+    Some(SwapTracker { slot })
+}
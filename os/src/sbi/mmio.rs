@@ -1,22 +1,26 @@
+use crate::board::{board_info, Region};
 use crate::mm::address::*;
-use crate::sbi::timer::CLINT;
-use crate::sbi::uart::UART0;
+use alloc::vec::Vec;
 
-pub const VIRT_TEST: usize = 0x10_0000;
+fn to_varange(region: Region) -> VARange {
+    VARange {
+        start: VirtAddr(region.base),
+        end: VirtAddr(region.end()),
+    }
+}
 
-const VIRT_MMIO: VARange = VARange {
-    start: VirtAddr(VIRT_TEST),
-    end: VirtAddr(VIRT_TEST + 0x2000),
-};
-
-const UART_MMIO: VARange = VARange {
-    start: VirtAddr(UART0),
-    end: VirtAddr(UART0 + 0x9000),
-};
-
-const CLINT_MMIO: VARange = VARange {
-    start: VirtAddr(CLINT),
-    end: VirtAddr(CLINT + 0x10000),
-};
-
-pub const MMIO: [VARange; 3] = [VIRT_MMIO, UART_MMIO, CLINT_MMIO];
+/// MMIO ranges to be mapped identically in kernel space
+pub fn mmio_ranges() -> Vec<VARange> {
+    let board_info = board_info();
+    let mut ranges = Vec::new();
+    ranges.push(to_varange(board_info.test));
+    ranges.push(to_varange(board_info.uart));
+    ranges.push(to_varange(board_info.clint));
+    if let Some(plic) = board_info.plic {
+        ranges.push(to_varange(plic));
+    }
+    for &virtio in board_info.virtio() {
+        ranges.push(to_varange(virtio));
+    }
+    ranges
+}
@@ -1,3 +1,4 @@
+use crate::board::board_info;
 use crate::error;
 use crate::info;
 
@@ -13,7 +14,6 @@ pub fn console_getchar() -> u8 {
     uart::uart_getchar().unwrap_or(0)
 }
 
-const VIRT_TEST: *mut u32 = mmio::VIRT_TEST as *mut u32;
 const TEST_PASS: u32 = 0x5555;
 
 pub fn shutdown(failure: bool) -> ! {
@@ -22,7 +22,10 @@ pub fn shutdown(failure: bool) -> ! {
     } else {
         info!("[mysbi] Normal shutdown...");
     }
-    unsafe { VIRT_TEST.write_volatile(TEST_PASS) };
+    // disks keep what was written
+    crate::drivers::block::sync_all();
+    let virt_test = board_info().test.base as *mut u32;
+    unsafe { virt_test.write_volatile(TEST_PASS) };
     unreachable!()
 }
 
@@ -1,4 +1,5 @@
 use crate::asm;
+use crate::board::board_info;
 use crate::config::CLOCK_FREQ;
 use core::ptr::addr_of;
 use riscv::register::{mie, mscratch, mstatus, mtvec};
@@ -8,16 +9,20 @@ pub const MSEC_PER_SEC: usize = 1000; // 1s = 1000ms
 const TICKS_PER_SEC: usize = 100; // 100 ticks/s = 10 ms/tick
 const INTERVAL: usize = CLOCK_FREQ / TICKS_PER_SEC; // timer interval (clock cycle)
 
-// CLINT : Core Local Interruptor
-pub const CLINT: usize = 0x200_0000;
-const CLINT_MTIME: usize = CLINT + 0xbff8; // current time
-const CLINT_MTIMECMP: usize = CLINT + 0x4000; // next interrupt time
+// CLINT : Core Local Interruptor (base address is detected from device tree)
+const CLINT_MTIME: usize = 0xbff8; // current time
+const CLINT_MTIMECMP: usize = 0x4000; // next interrupt time
+
+fn clint_reg(offset: usize) -> *mut usize {
+    (board_info().clint.base + offset) as *mut usize
+}
 
 static mut TEMP: [usize; 4] = [0; 4];
 
 pub fn init() {
     unsafe {
         // use mscratch to pass some data
+        TEMP[3] = clint_reg(CLINT_MTIMECMP) as usize;
         mscratch::write(addr_of!(TEMP) as usize);
 
         // set initial trigger
@@ -50,7 +55,7 @@ pub extern "C" fn timervec() -> ! {
         sd a2, 16(sp)
 
         # set next time
-        li a0, {mtimecmp}
+        ld a0, 24(sp) # a0 = address of mtimecmp
         ld a1, 0(a0) # a1 = mtimecmp
         li a2, {interval}
         add a1, a1, a2
@@ -68,7 +73,6 @@ pub extern "C" fn timervec() -> ! {
 
         mret
         "#, 
-        mtimecmp = const CLINT_MTIMECMP,
         interval = const INTERVAL,
         options(noreturn))
     }
@@ -76,7 +80,7 @@ pub extern "C" fn timervec() -> ! {
 
 /// get current time in clock cycle
 pub fn get_time() -> usize {
-    unsafe { (CLINT_MTIME as *const usize).read_volatile() }
+    unsafe { clint_reg(CLINT_MTIME).read_volatile() }
 }
 
 /// get current time in ms
@@ -85,7 +89,7 @@ pub fn get_time_ms() -> usize {
 }
 
 fn set_time_cmp(time: usize) {
-    unsafe { (CLINT_MTIMECMP as *mut usize).write_volatile(time) }
+    unsafe { clint_reg(CLINT_MTIMECMP).write_volatile(time) }
 }
 
 /// set next timer interrupt
@@ -1,7 +1,7 @@
 // 16550 UART (simulated by qemu)
 #![allow(unused)]
 
-pub const UART0: usize = 0x1000_0000; // base address of the UART
+use crate::board::board_info;
 
 // UART control registers
 const RHR: usize = 0; // receive holding register (for input bytes)
@@ -21,7 +21,8 @@ const LSR_RX_READY: u8 = 1 << 0; // input is waiting to be read from RHR
 const LSR_TX_IDLE: u8 = 1 << 5; // THR can accept another character to send
 
 fn reg(reg_offset: usize) -> *mut u8 {
-    unsafe { (UART0 + reg_offset) as *mut u8 }
+    // base address of the UART is detected from device tree
+    (board_info().uart.base + reg_offset) as *mut u8
 }
 
 fn read_reg(reg_offset: usize) -> u8 {
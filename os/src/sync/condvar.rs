@@ -0,0 +1,66 @@
+//! Condition variable for user threads
+
+use super::up::UPSafeCell;
+use super::Mutex;
+use crate::syscall::errno::EINTR;
+use crate::task::{block_current_and_run_next, current_task, wakeup_task, TaskControlBlock};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+pub struct Condvar {
+    inner: UPSafeCell<CondvarInner>,
+}
+
+pub struct CondvarInner {
+    wait_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl Condvar {
+    pub fn new() -> Self {
+        Self {
+            inner: UPSafeCell::new(CondvarInner {
+                wait_queue: VecDeque::new(),
+            }),
+        }
+    }
+
+    /// Wake up one waiting thread (if any).
+    pub fn signal(&self) {
+        let mut inner = self.inner.exclusive_access();
+        if let Some(task) = inner.wait_queue.pop_front() {
+            wakeup_task(task);
+        }
+    }
+
+    /// Release `mutex` and block until signaled, then lock `mutex` again.
+    ///
+    /// Return -1 if `mutex` is not locked, or -EINTR if the process is killed
+    /// while waiting (see [`block_current_and_run_next`]), `mutex` is not locked
+    /// again then.
+    pub fn wait(&self, mutex: Arc<dyn Mutex>) -> Result<(), isize> {
+        if !mutex.unlock() {
+            return Err(-1);
+        }
+        let mut inner = self.inner.exclusive_access();
+        inner.wait_queue.push_back(current_task().unwrap());
+        drop(inner);
+        if !block_current_and_run_next() {
+            self.remove_waiter(&current_task().unwrap());
+            return Err(-EINTR);
+        }
+        if !mutex.lock() {
+            return Err(-EINTR);
+        }
+        Ok(())
+    }
+
+    /// Remove `task` from the wait queue, return false if it is not there.
+    pub fn remove_waiter(&self, task: &TaskControlBlock) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let len = inner.wait_queue.len();
+        inner
+            .wait_queue
+            .retain(|waiting| !core::ptr::eq(waiting.as_ref(), task));
+        inner.wait_queue.len() != len
+    }
+}
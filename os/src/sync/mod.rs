@@ -1 +1,8 @@
-pub mod up;
\ No newline at end of file
+mod condvar;
+mod mutex;
+mod semaphore;
+pub mod up;
+
+pub use condvar::Condvar;
+pub use mutex::{Mutex, MutexBlocking};
+pub use semaphore::Semaphore;
@@ -0,0 +1,78 @@
+//! Mutex for user threads
+
+use super::up::UPSafeCell;
+use crate::task::{block_current_and_run_next, current_task, wakeup_task, TaskControlBlock};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+pub trait Mutex: Sync + Send {
+    /// Return false if the process is killed while waiting, see
+    /// [`block_current_and_run_next`].
+    fn lock(&self) -> bool;
+    /// Return false if the mutex is not locked.
+    fn unlock(&self) -> bool;
+    /// Remove `task` from the wait queue, return false if it is not there.
+    fn remove_waiter(&self, task: &TaskControlBlock) -> bool;
+}
+
+/// A mutex that blocks the waiting threads instead of spinning
+pub struct MutexBlocking {
+    inner: UPSafeCell<MutexBlockingInner>,
+}
+
+pub struct MutexBlockingInner {
+    locked: bool,
+    wait_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl MutexBlocking {
+    pub fn new() -> Self {
+        Self {
+            inner: UPSafeCell::new(MutexBlockingInner {
+                locked: false,
+                wait_queue: VecDeque::new(),
+            }),
+        }
+    }
+}
+
+impl Mutex for MutexBlocking {
+    fn lock(&self) -> bool {
+        let mut mutex_inner = self.inner.exclusive_access();
+        if mutex_inner.locked {
+            mutex_inner.wait_queue.push_back(current_task().unwrap());
+            drop(mutex_inner);
+            // the lock is handed over to us when we are woken up, unless killed
+            if !block_current_and_run_next() {
+                self.remove_waiter(&current_task().unwrap());
+                return false;
+            }
+        } else {
+            mutex_inner.locked = true;
+        }
+        true
+    }
+
+    fn unlock(&self) -> bool {
+        let mut mutex_inner = self.inner.exclusive_access();
+        if !mutex_inner.locked {
+            return false;
+        }
+        if let Some(waking_task) = mutex_inner.wait_queue.pop_front() {
+            // keep it locked for the waking task
+            wakeup_task(waking_task);
+        } else {
+            mutex_inner.locked = false;
+        }
+        true
+    }
+
+    fn remove_waiter(&self, task: &TaskControlBlock) -> bool {
+        let mut mutex_inner = self.inner.exclusive_access();
+        let len = mutex_inner.wait_queue.len();
+        mutex_inner
+            .wait_queue
+            .retain(|waiting| !core::ptr::eq(waiting.as_ref(), task));
+        mutex_inner.wait_queue.len() != len
+    }
+}
@@ -0,0 +1,68 @@
+//! Counting semaphore for user threads
+
+use super::up::UPSafeCell;
+use crate::task::{block_current_and_run_next, current_task, wakeup_task, TaskControlBlock};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+pub struct Semaphore {
+    inner: UPSafeCell<SemaphoreInner>,
+}
+
+pub struct SemaphoreInner {
+    /// a negative count is the number of waiting threads
+    count: isize,
+    wait_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl Semaphore {
+    pub fn new(count: usize) -> Self {
+        Self {
+            inner: UPSafeCell::new(SemaphoreInner {
+                count: count as isize,
+                wait_queue: VecDeque::new(),
+            }),
+        }
+    }
+
+    pub fn up(&self) {
+        let mut inner = self.inner.exclusive_access();
+        inner.count += 1;
+        if inner.count <= 0 {
+            if let Some(task) = inner.wait_queue.pop_front() {
+                wakeup_task(task);
+            }
+        }
+    }
+
+    /// Return false if the process is killed while waiting, see
+    /// [`block_current_and_run_next`].
+    pub fn down(&self) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        inner.count -= 1;
+        if inner.count < 0 {
+            inner.wait_queue.push_back(current_task().unwrap());
+            drop(inner);
+            if !block_current_and_run_next() {
+                self.remove_waiter(&current_task().unwrap());
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Remove `task` from the wait queue, return false if it is not there.
+    pub fn remove_waiter(&self, task: &TaskControlBlock) -> bool {
+        let mut inner = self.inner.exclusive_access();
+        let len = inner.wait_queue.len();
+        inner
+            .wait_queue
+            .retain(|waiting| !core::ptr::eq(waiting.as_ref(), task));
+        if inner.wait_queue.len() == len {
+            return false;
+        }
+        // it no longer waits for the count
+        inner.count += 1;
+        true
+    }
+}
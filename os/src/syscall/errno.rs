@@ -0,0 +1,58 @@
+//! Error numbers returned (negated) by system calls, following Linux
+
+/// Operation not permitted
+pub const EPERM: isize = 1;
+/// No such file or directory
+pub const ENOENT: isize = 2;
+/// No such process
+pub const ESRCH: isize = 3;
+/// Interrupted system call
+pub const EINTR: isize = 4;
+/// I/O error
+pub const EIO: isize = 5;
+/// Exec format error
+pub const ENOEXEC: isize = 8;
+/// Bad file number
+pub const EBADF: isize = 9;
+/// Try again
+pub const EAGAIN: isize = 11;
+/// Out of memory
+pub const ENOMEM: isize = 12;
+/// Permission denied
+pub const EACCES: isize = 13;
+/// Bad address
+pub const EFAULT: isize = 14;
+/// Device or resource busy
+pub const EBUSY: isize = 16;
+/// File exists
+pub const EEXIST: isize = 17;
+/// Cross-device link
+pub const EXDEV: isize = 18;
+/// No such device
+pub const ENODEV: isize = 19;
+/// Not a directory
+pub const ENOTDIR: isize = 20;
+/// Is a directory
+pub const EISDIR: isize = 21;
+/// Invalid argument
+pub const EINVAL: isize = 22;
+/// Too many open files
+pub const EMFILE: isize = 24;
+/// Not a typewriter
+pub const ENOTTY: isize = 25;
+/// Text file busy
+pub const ETXTBSY: isize = 26;
+/// File too large
+pub const EFBIG: isize = 27;
+/// No space left on device
+pub const ENOSPC: isize = 28;
+/// Read-only file system
+pub const EROFS: isize = 30;
+/// Broken pipe
+pub const EPIPE: isize = 32;
+/// Math result not representable
+pub const ERANGE: isize = 34;
+/// File name too long
+pub const ENAMETOOLONG: isize = 36;
+/// Directory not empty
+pub const ENOTEMPTY: isize = 39;
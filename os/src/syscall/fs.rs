@@ -1,61 +1,422 @@
-use crate::loader::ls;
+use super::errno::{EBADF, EINVAL, EMFILE, ENOTDIR, ENOTTY, EPERM, ERANGE};
+use crate::config::{MAX_FD, PAGE_SIZE};
+use crate::console::TERMINAL;
+use crate::drivers::block::sync_all;
+use crate::fs::vfs::{lookup, rename};
+use crate::fs::{make_pipe, mkdir, open_file, stat, unlink, Dentry, File, Metadata, OpenFlags};
+use crate::loader::user_apps;
 use crate::mm::page_table::translated_byte_buffer;
-use crate::print;
-use crate::sbi::console_getchar;
+use crate::mm::{translated_refmut, translated_str};
 use crate::task::*;
+use alloc::sync::Arc;
+use alloc::vec;
 use alloc::vec::Vec;
-
-const FD_STDIN: usize = 0;
-const FD_STDOUT: usize = 1;
+use core::mem::size_of;
 
 fn translate_buffer(buf: *const u8, len: usize) -> Vec<&'static mut [u8]> {
     translated_byte_buffer(current_user_token(), buf, len)
 }
 
+/// Copy `data` to the user buffer `buf`, which must be large enough.
+fn copy_to_user(buf: *const u8, data: &[u8]) {
+    let mut src = data.iter();
+    for buffer in translate_buffer(buf, data.len()) {
+        for (dst, src) in buffer.iter_mut().zip(&mut src) {
+            *dst = *src;
+        }
+    }
+}
+
+/// The working directory of current process
+fn current_cwd() -> Arc<Dentry> {
+    current_process().inner_exclusive_access().cwd.clone()
+}
+
+/// The file opened as `fd` by current process
+fn get_file(fd: usize) -> Option<Arc<dyn File>> {
+    current_process().inner_exclusive_access().get_file(fd)
+}
+
+/// Write `buf` to file `fd` page by page, stop at the first short write.
+///
+/// Return the number of bytes written, or a negated errno if nothing is written.
 pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
-    match fd {
-        FD_STDOUT => {
-            // debug: user space buffer cannot be accessed directly in kernel space
-            let buffers = translate_buffer(buf, len);
-            for buffer in buffers {
-                let str = core::str::from_utf8(buffer).unwrap();
-                print!("{}", str);
-            }
-            len as isize
+    let file = match get_file(fd) {
+        Some(file) if file.writable() => file,
+        _ => return -EBADF,
+    };
+    let mut written = 0;
+    while written < len {
+        // copy to kernel space first, since the file may block
+        let chunk_len = (len - written).min(PAGE_SIZE);
+        let mut chunk = Vec::with_capacity(chunk_len);
+        for buffer in translate_buffer(unsafe { buf.add(written) }, chunk_len) {
+            chunk.extend_from_slice(buffer);
+        }
+        let ret = file.write(&chunk);
+        if ret < 0 {
+            return if written > 0 { written as isize } else { ret };
         }
-        _ => {
-            panic!("Unsupported fd {} in sys_write!", fd);
+        written += ret as usize;
+        if (ret as usize) < chunk_len {
+            break;
         }
     }
+    written as isize
 }
 
+/// Read at most a page from file `fd` to `buf`.
+///
+/// Return the number of bytes read, 0 at the end of file, or a negated errno.
 pub fn sys_read(fd: usize, buf: *const u8, len: usize) -> isize {
-    match fd {
-        FD_STDIN => {
-            assert_eq!(len, 1, "Only support len = 1 in sys_read!");
-            let mut c: u8;
-            loop {
-                c = console_getchar();
-                if c == 0 {
-                    suspend_current_and_run_next();
-                    continue;
-                } else {
-                    break;
+    let file = match get_file(fd) {
+        Some(file) if file.readable() => file,
+        _ => return -EBADF,
+    };
+    let mut chunk = vec![0u8; len.min(PAGE_SIZE)];
+    let ret = file.read(&mut chunk);
+    if ret <= 0 {
+        return ret;
+    }
+    // the user buffer is translated after blocking, in case it is swapped out
+    copy_to_user(buf, &chunk[..ret as usize]);
+    ret
+}
+
+/// Open file `path`, relative to the working directory, return the lowest
+/// free file descriptor.
+///
+/// Return -ENOENT if there is no such file and `CREATE` is not in `flags`.
+pub fn sys_open(path: *const u8, flags: u32) -> isize {
+    let path = translated_str(current_user_token(), path);
+    let flags = match OpenFlags::from_bits(flags) {
+        Some(flags) => flags,
+        None => return -EINVAL,
+    };
+    match open_file(&current_cwd(), path.as_str(), flags) {
+        Ok(file) => {
+            let process = current_process();
+            let mut inner = process.inner_exclusive_access();
+            match inner.alloc_fd() {
+                Some(fd) => {
+                    inner.fd_table[fd] = Some(file);
+                    fd as isize
                 }
+                None => -EMFILE,
             }
-            let mut buffers = translate_buffer(buf, len);
-            unsafe {
-                buffers[0].as_mut_ptr().write_volatile(c);
-            }
-            1
         }
-        _ => {
-            panic!("Unsupported fd in sys_read!");
+        Err(errno) => errno,
+    }
+}
+
+/// `flags` of `sys_unlink`: remove an empty directory instead of a file
+const AT_REMOVEDIR: u32 = 0x200;
+
+/// Remove the file `path`, which stays readable through opened descriptors,
+/// or the empty directory `path` if `AT_REMOVEDIR` is in `flags`.
+pub fn sys_unlink(path: *const u8, flags: u32) -> isize {
+    if flags & !AT_REMOVEDIR != 0 {
+        return -EINVAL;
+    }
+    let path = translated_str(current_user_token(), path);
+    unlink(&current_cwd(), path.as_str(), flags & AT_REMOVEDIR != 0)
+}
+
+/// Create the directory `path`, return -EEXIST if it exists.
+pub fn sys_mkdir(path: *const u8) -> isize {
+    let path = translated_str(current_user_token(), path);
+    mkdir(&current_cwd(), path.as_str())
+}
+
+/// Move `old_path` to `new_path`, replacing it if it exists.
+///
+/// Return -EXDEV if they are in different filesystems.
+pub fn sys_rename(old_path: *const u8, new_path: *const u8) -> isize {
+    let token = current_user_token();
+    let old_path = translated_str(token, old_path);
+    let new_path = translated_str(token, new_path);
+    rename(&current_cwd(), old_path.as_str(), new_path.as_str())
+}
+
+/// Change the working directory of current process to `path`.
+pub fn sys_chdir(path: *const u8) -> isize {
+    let path = translated_str(current_user_token(), path);
+    let dentry = match lookup(&current_cwd(), path.as_str()) {
+        Ok(dentry) => dentry,
+        Err(errno) => return errno,
+    };
+    if !dentry.is_dir() {
+        return -ENOTDIR;
+    }
+    current_process().inner_exclusive_access().cwd = dentry;
+    0
+}
+
+/// Copy the absolute path of the working directory to `buf`, ended by `\0`.
+///
+/// Return the length including `\0`, or -ERANGE if it is longer than `len`.
+pub fn sys_getcwd(buf: *mut u8, len: usize) -> isize {
+    let mut path = current_cwd().path().into_bytes();
+    path.push(0);
+    if path.len() > len {
+        return -ERANGE;
+    }
+    copy_to_user(buf, &path);
+    path.len() as isize
+}
+
+/// Read the next entries of the directory `fd` to `buf` as `linux_dirent64`
+/// records, at most a page.
+///
+/// Return the number of bytes read, 0 at the end of the directory, or -EINVAL
+/// if `buf` is too small for the next entry.
+pub fn sys_getdents64(fd: usize, buf: *mut u8, len: usize) -> isize {
+    let file = match get_file(fd) {
+        Some(file) => file,
+        None => return -EBADF,
+    };
+    let mut chunk = vec![0u8; len.min(PAGE_SIZE)];
+    let ret = file.read_dir(&mut chunk);
+    if ret > 0 {
+        copy_to_user(buf, &chunk[..ret as usize]);
+    }
+    ret
+}
+
+pub fn sys_close(fd: usize) -> isize {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    match inner.fd_table.get_mut(fd).and_then(|file| file.take()) {
+        Some(file) => {
+            drop(inner);
+            // may close the end of a pipe
+            drop(file);
+            0
+        }
+        None => -EBADF,
+    }
+}
+
+/// Create a pipe, store the file descriptors of its read end and write end
+/// to `pipe[0]` and `pipe[1]`.
+///
+/// Return -EMFILE if the process has `MAX_FD` files open.
+pub fn sys_pipe(pipe: *mut usize) -> isize {
+    let token = current_user_token();
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let (read_end, write_end) = make_pipe();
+    let read_fd = match inner.alloc_fd() {
+        Some(fd) => fd,
+        None => return -EMFILE,
+    };
+    inner.fd_table[read_fd] = Some(read_end);
+    let write_fd = match inner.alloc_fd() {
+        Some(fd) => fd,
+        None => {
+            inner.fd_table[read_fd] = None;
+            return -EMFILE;
+        }
+    };
+    inner.fd_table[write_fd] = Some(write_end);
+    drop(inner);
+    *translated_refmut(token, pipe) = read_fd;
+    *translated_refmut(token, unsafe { pipe.add(1) }) = write_fd;
+    0
+}
+
+/// Duplicate `fd` to the lowest free file descriptor.
+pub fn sys_dup(fd: usize) -> isize {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let file = match inner.get_file(fd) {
+        Some(file) => file,
+        None => return -EBADF,
+    };
+    match inner.alloc_fd() {
+        Some(new_fd) => {
+            inner.fd_table[new_fd] = Some(file);
+            new_fd as isize
+        }
+        None => -EMFILE,
+    }
+}
+
+/// Duplicate `old_fd` to `new_fd`, closing the file `new_fd` refers to.
+///
+/// `flags` must be 0. Return -EINVAL if `old_fd` equals `new_fd`, -EBADF if
+/// `new_fd` is not below `MAX_FD`.
+pub fn sys_dup3(old_fd: usize, new_fd: usize, flags: u32) -> isize {
+    if flags != 0 || old_fd == new_fd {
+        return -EINVAL;
+    }
+    if new_fd >= MAX_FD {
+        return -EBADF;
+    }
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let file = match inner.get_file(old_fd) {
+        Some(file) => file,
+        None => return -EBADF,
+    };
+    while inner.fd_table.len() <= new_fd {
+        inner.fd_table.push(None);
+    }
+    let old_file = inner.fd_table[new_fd].replace(file);
+    drop(inner);
+    drop(old_file);
+    new_fd as isize
+}
+
+/// `struct stat` of Linux on RISC-V
+#[repr(C)]
+#[derive(Default)]
+struct Stat {
+    st_dev: u64,
+    st_ino: u64,
+    st_mode: u32,
+    st_nlink: u32,
+    st_uid: u32,
+    st_gid: u32,
+    st_rdev: u64,
+    __pad1: u64,
+    st_size: i64,
+    st_blksize: i32,
+    __pad2: i32,
+    st_blocks: i64,
+    st_atime: i64,
+    st_atime_nsec: u64,
+    st_mtime: i64,
+    st_mtime_nsec: u64,
+    st_ctime: i64,
+    st_ctime_nsec: u64,
+    __unused: [u32; 2],
+}
+
+/// Unit of `st_blocks`, following Linux
+const STAT_BLOCK_SIZE: usize = 512;
+
+impl From<Metadata> for Stat {
+    /// Only one user and one device, owners and devices are 0.
+    fn from(metadata: Metadata) -> Self {
+        let sec = |ms: usize| (ms / 1000) as i64;
+        let nsec = |ms: usize| (ms % 1000 * 1_000_000) as u64;
+        Self {
+            st_ino: metadata.ino as u64,
+            st_mode: metadata.mode,
+            st_nlink: metadata.nlink as u32,
+            st_size: metadata.size as i64,
+            st_blksize: STAT_BLOCK_SIZE as i32,
+            st_blocks: metadata.size.div_ceil(STAT_BLOCK_SIZE) as i64,
+            st_atime: sec(metadata.atime),
+            st_atime_nsec: nsec(metadata.atime),
+            st_mtime: sec(metadata.mtime),
+            st_mtime_nsec: nsec(metadata.mtime),
+            st_ctime: sec(metadata.ctime),
+            st_ctime_nsec: nsec(metadata.ctime),
+            ..Default::default()
+        }
+    }
+}
+
+/// Copy the metadata to the `struct stat` at `buf`.
+fn copy_stat_to_user(buf: *mut u8, metadata: Metadata) {
+    let stat = Stat::from(metadata);
+    let bytes = unsafe {
+        core::slice::from_raw_parts(&stat as *const Stat as *const u8, size_of::<Stat>())
+    };
+    copy_to_user(buf, bytes);
+}
+
+/// Store the metadata of file `fd` to the `struct stat` at `buf`.
+pub fn sys_fstat(fd: usize, buf: *mut u8) -> isize {
+    match get_file(fd) {
+        Some(file) => {
+            copy_stat_to_user(buf, file.metadata());
+            0
         }
+        None => -EBADF,
     }
 }
 
-pub fn sys_ls() -> isize {
-    ls();
+/// Store the metadata of `path` to the `struct stat` at `buf`, following
+/// mount points.
+pub fn sys_stat(path: *const u8, buf: *mut u8) -> isize {
+    let path = translated_str(current_user_token(), path);
+    match stat(&current_cwd(), path.as_str()) {
+        Ok(metadata) => {
+            copy_stat_to_user(buf, metadata);
+            0
+        }
+        Err(errno) => errno,
+    }
+}
+
+/// Write all changes cached in memory to the disks.
+pub fn sys_sync() -> isize {
+    sync_all();
+    0
+}
+
+/// Write the changes of the file `fd` to its disk, return -EINVAL if it is
+/// not on one, such as a pipe.
+pub fn sys_fsync(fd: usize) -> isize {
+    match get_file(fd) {
+        Some(file) => file.sync(),
+        None => -EBADF,
+    }
+}
+
+/// Copy the names of apps which can be run from the shell to `buf`, each ended by `\n`.
+///
+/// Return the length of the whole list, which is truncated if longer than `len`.
+pub fn sys_apps(buf: *mut u8, len: usize) -> isize {
+    let mut list = Vec::new();
+    for app in user_apps() {
+        list.extend_from_slice(app.as_bytes());
+        list.push(b'\n');
+    }
+    copy_to_user(buf, &list[..len.min(list.len())]);
+    list.len() as isize
+}
+
+/// Check that `fd` is the controlling terminal of current process, return its session.
+fn controlling_terminal(fd: usize) -> Result<usize, isize> {
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    let file = match inner.get_file(fd) {
+        Some(file) => file,
+        None => return Err(-EBADF),
+    };
+    if !file.is_terminal() || inner.sid != TERMINAL.exclusive_access().session {
+        return Err(-ENOTTY);
+    }
+    Ok(inner.sid)
+}
+
+/// Return the foreground process group of the terminal `fd`.
+///
+/// Only the console is a terminal, and it is only the controlling terminal of
+/// its session, return -ENOTTY otherwise.
+pub fn sys_tcgetpgrp(fd: usize) -> isize {
+    match controlling_terminal(fd) {
+        Ok(_) => TERMINAL.exclusive_access().foreground as isize,
+        Err(errno) => errno,
+    }
+}
+
+/// Make `pgid` the foreground process group of the terminal `fd`.
+///
+/// Return -ENOTTY like `sys_tcgetpgrp`, or -EPERM if there is no group `pgid`
+/// in the session.
+pub fn sys_tcsetpgrp(fd: usize, pgid: usize) -> isize {
+    let sid = match controlling_terminal(fd) {
+        Ok(sid) => sid,
+        Err(errno) => return errno,
+    };
+    if !group_in_session(pgid, sid) {
+        return -EPERM;
+    }
+    TERMINAL.exclusive_access().foreground = pgid;
     0
 }
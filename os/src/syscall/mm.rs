@@ -0,0 +1,168 @@
+//! Memory mappings of files and anonymous memory
+
+use super::errno::{EACCES, EBADF, EINVAL, ENODEV, ENOMEM};
+use crate::config::PAGE_SIZE;
+use crate::fs::WriteAccess;
+use crate::mm::address::{VPNRange, VirtAddr};
+use crate::mm::map_area::{MapPermission, MappedFile};
+use crate::task::current_process;
+use alloc::sync::Arc;
+use bitflags::bitflags;
+
+bitflags! {
+    /// `prot` of `sys_mmap`, following Linux
+    pub struct ProtFlags: usize {
+        const READ = 1 << 0;
+        const WRITE = 1 << 1;
+        const EXEC = 1 << 2;
+    }
+}
+
+bitflags! {
+    /// `flags` of `sys_mmap`, following Linux
+    pub struct MapFlags: usize {
+        const SHARED = 1 << 0;
+        const PRIVATE = 1 << 1;
+        const FIXED = 1 << 4;
+        const ANONYMOUS = 1 << 5;
+    }
+}
+
+// `flags` of `sys_msync`
+const MS_ASYNC: usize = 1 << 0;
+const MS_INVALIDATE: usize = 1 << 1;
+const MS_SYNC: usize = 1 << 2;
+
+/// mappings are smaller than the user address space
+const MAX_MAP_LEN: usize = 1 << 38;
+
+/// Pages `[addr, addr + len)`, or None if `addr` is not page aligned.
+fn page_range(addr: usize, len: usize) -> Option<VPNRange> {
+    if addr % PAGE_SIZE != 0 || len > MAX_MAP_LEN || addr > MAX_MAP_LEN {
+        return None;
+    }
+    Some(VPNRange::new(
+        VirtAddr::from(addr).floor(),
+        VirtAddr::from(addr + len).ceil(),
+    ))
+}
+
+/// Map `len` bytes of file `fd` from `offset`, or zeroed memory with
+/// `MAP_ANONYMOUS`, return the address of the mapping, or a negated errno.
+///
+/// `addr` is a hint from `MMAP_BASE`, or the address with `MAP_FIXED`, but
+/// existing mappings are not replaced (-EEXIST). Pages of a file are read on
+/// page fault through the page cache: those of a `MAP_SHARED` mapping are the
+/// pages of the file, changes are written back by `sys_msync` and
+/// `sys_munmap`, or on exit. Anonymous mappings are private, and `PROT_NONE`
+/// is not supported.
+pub fn sys_mmap(
+    addr: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    let (prot, flags) = match (ProtFlags::from_bits(prot), MapFlags::from_bits(flags)) {
+        (Some(prot), Some(flags)) if !prot.is_empty() => (prot, flags),
+        _ => return -EINVAL,
+    };
+    let shared = flags.contains(MapFlags::SHARED);
+    let fixed = flags.contains(MapFlags::FIXED);
+    if len == 0
+        || shared == flags.contains(MapFlags::PRIVATE)
+        || fixed && addr % PAGE_SIZE != 0
+        || offset % PAGE_SIZE != 0
+    {
+        return -EINVAL;
+    }
+    if len > MAX_MAP_LEN {
+        return -ENOMEM;
+    }
+    let pages = (len + PAGE_SIZE - 1) / PAGE_SIZE;
+    // pages cannot be writable without being readable
+    let mut perm = MapPermission::U | MapPermission::R;
+    if prot.contains(ProtFlags::WRITE) {
+        perm |= MapPermission::W;
+    }
+    if prot.contains(ProtFlags::EXEC) {
+        perm |= MapPermission::X;
+    }
+    let process = current_process();
+    let file = if flags.contains(MapFlags::ANONYMOUS) {
+        if shared {
+            return -EINVAL;
+        }
+        None
+    } else {
+        let file = match process.inner_exclusive_access().get_file(fd) {
+            Some(file) => file,
+            None => return -EBADF,
+        };
+        let inode = match file.inode() {
+            Some(inode) if inode.mappable() => inode,
+            _ => return -ENODEV,
+        };
+        let write = shared && prot.contains(ProtFlags::WRITE);
+        if !file.readable() || write && !file.writable() {
+            return -EACCES;
+        }
+        // kept after the file is closed
+        let write_access = if write {
+            match WriteAccess::write(&inode) {
+                Ok(access) => Some(Arc::new(access)),
+                Err(err) => return err,
+            }
+        } else {
+            None
+        };
+        Some(MappedFile {
+            inode,
+            offset,
+            len: pages * PAGE_SIZE,
+            shared,
+            write_access,
+        })
+    };
+    let start = VirtAddr::from(addr).floor();
+    let mut inner = process.inner_exclusive_access();
+    match inner.memory_set.mmap(start, pages, perm, file, fixed) {
+        Ok(start) => VirtAddr::from(start).0 as isize,
+        Err(err) => err,
+    }
+}
+
+/// Remove the mappings in `[addr, addr + len)`, changes of shared mappings are
+/// written back to their files. A mapping partly in the range is not split,
+/// return -EINVAL instead.
+pub fn sys_munmap(addr: usize, len: usize) -> isize {
+    let range = match page_range(addr, len) {
+        Some(range) if len > 0 => range,
+        _ => return -EINVAL,
+    };
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    inner.memory_set.munmap(range)
+}
+
+/// Write changes of shared mappings in `[addr, addr + len)` back to their
+/// files, and to their devices with `MS_SYNC`, return -ENOMEM if part of the
+/// range is not mapped.
+///
+/// Other mappings of the files share their pages, so `MS_INVALIDATE` has
+/// nothing to do.
+pub fn sys_msync(addr: usize, len: usize, flags: usize) -> isize {
+    let range = match page_range(addr, len) {
+        Some(range) => range,
+        None => return -EINVAL,
+    };
+    if flags & !(MS_ASYNC | MS_INVALIDATE | MS_SYNC) != 0
+        || flags & MS_ASYNC != 0 && flags & MS_SYNC != 0
+    {
+        return -EINVAL;
+    }
+    let process = current_process();
+    let inner = process.inner_exclusive_access();
+    inner.memory_set.msync(range, flags & MS_SYNC != 0)
+}
@@ -1,9 +1,14 @@
-use crate::info;
-use crate::loader::get_app_data_by_name;
+use super::errno::{EINVAL, ENOEXEC, ENOMEM, EPERM, ESRCH, ETXTBSY};
+use crate::config::USER_STACK_MAX_SIZE;
+use crate::loader::Executable;
 use crate::mm::*;
 use crate::task::switch::check_proc_manager_service;
 use crate::task::*;
 use crate::timer::get_time_ms;
+use crate::{info, warn};
+use alloc::vec;
+use alloc::vec::Vec;
+use service::WUNTRACED;
 use switch::set_proc_manager_service_off;
 
 /// task exits and submit an exit code
@@ -32,42 +37,83 @@ pub fn sys_time() -> isize {
 }
 
 pub fn sys_getpid() -> isize {
-    current_task().unwrap().pid as isize
+    current_pid() as isize
 }
 
+/// Only a process with a single thread can fork, otherwise return -1.
 pub fn sys_fork() -> isize {
-    let current_task = current_task().unwrap();
-    let new_pid = service::fork(current_task.pid); // child process
-    let new_task = current_task.fork(new_pid);
+    let current_process = current_process();
+    if current_process.inner_exclusive_access().thread_count() > 1 {
+        return -1;
+    }
+    let new_pid = service::fork(current_process.pid); // child process
+    let new_process = match current_process.fork(new_pid) {
+        Ok(process) => process,
+        Err(OutOfMemory) => {
+            // the child never runs, let process manager forget it
+            service::exit(new_pid, -ENOMEM as i32);
+            service::waitpid(current_process.pid, new_pid as isize, 0);
+            warn!("[kernel] Out of memory, fork failed.");
+            return -ENOMEM;
+        }
+    };
+    let new_task = new_process.main_task();
 
     // for child process, fork returns 0 to u-mode when it's scheduled
     // so modify trap context of new_task, it will not go back to trap_return
     let trap_cx = new_task.inner_exclusive_access().get_trap_cx();
     // we do not have to move to next instruction since we have done it before
     trap_cx.x[10] = 0; // x[10] is a0 reg
+    insert_into_pid2process(new_pid, new_process);
     add_task(new_task); // add child process to scheduler
 
     new_pid as isize // for parent process, fork returns pid of child process
 }
 
-pub fn sys_exec(path: *const u8) -> isize {
+/// `args` is a null-terminated array of argument strings, or null if the only
+/// argument is `path`. `envp` is a null-terminated array of `NAME=value`
+/// strings, or null for an empty environment.
+///
+/// Only a process with a single thread can exec, otherwise return -1.
+/// Return -ENOEXEC if `path` is not an ELF file, -ETXTBSY if it is open for
+/// writing.
+pub fn sys_exec(path: *const u8, args: *const usize, envp: *const usize) -> isize {
     let token = current_user_token();
     let path = translated_str(token, path);
-    // crate::debug!("sys_exec: path = {:?}", path);
-    if let Some(data) = get_app_data_by_name(path.as_str()) {
-        let task = current_task().unwrap();
-        task.exec(data);
-        0
+    // without `args`, the path is the only argument
+    let args_vec = if args.is_null() {
+        vec![path.clone()]
     } else {
-        -1
+        translated_strings(token, args)
+    };
+    let envs_vec = if envp.is_null() {
+        Vec::new()
+    } else {
+        translated_strings(token, envp)
+    };
+    // crate::debug!("sys_exec: path = {:?}", path);
+    let process = current_process();
+    if process.inner_exclusive_access().thread_count() > 1 {
+        return -1;
+    }
+    let cwd = process.inner_exclusive_access().cwd.clone();
+    match Executable::open(&cwd, path.as_str()) {
+        Ok(exe) => match process.exec(&exe, args_vec, envs_vec) {
+            Ok(()) => 0,
+            Err(OutOfMemory) => -ENOMEM,
+        },
+        Err(err) if err == -ENOEXEC || err == -ETXTBSY => err,
+        Err(_) => -1,
     }
 }
 
 /// If there is not a child process whose pid is same as given, return -1.
 /// Else if there is a child process but it is still running, return -2.
 /// Else return found_pid.
-pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
-    let (found_pid, exit_code) = service::waitpid(current_pid(), pid);
+///
+/// With `WUNTRACED` in `options`, a stopped child is also reported.
+pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32, options: usize) -> isize {
+    let (found_pid, exit_code) = service::waitpid(current_pid(), pid, options & WUNTRACED);
 
     match found_pid {
         0 => {
@@ -90,3 +136,132 @@ pub fn sys_shutdown() -> isize {
     info!("[kernel] Shutdown by user.");
     crate::shutdown(false)
 }
+
+pub fn sys_getppid() -> isize {
+    service::getppid(current_pid()) as isize
+}
+
+/// Move process `pid` (0 for the caller) into group `pgid` (0 for `pid`).
+///
+/// Return -ESRCH if `pid` is neither the caller nor its child.
+/// Return -EPERM if `pid` is a session leader or in another session, or group
+/// `pgid` is in another session.
+pub fn sys_setpgid(pid: usize, pgid: usize) -> isize {
+    let process = current_process();
+    let pid = if pid == 0 { process.pid } else { pid };
+    let pgid = if pgid == 0 { pid } else { pgid };
+    if pid != process.pid && service::getppid(pid) != process.pid {
+        return -ESRCH;
+    }
+    let sid = process.inner_exclusive_access().sid;
+    drop(process);
+    let target = match pid2process(pid) {
+        Some(target) => target,
+        None => return -ESRCH,
+    };
+    let target_sid = target.inner_exclusive_access().sid;
+    if target_sid != sid || target_sid == pid {
+        return -EPERM;
+    }
+    if pgid != pid && !group_in_session(pgid, sid) {
+        return -EPERM;
+    }
+    target.inner_exclusive_access().pgid = pgid;
+    0
+}
+
+/// Return the process group of `pid` (0 for the caller), or -ESRCH.
+pub fn sys_getpgid(pid: usize) -> isize {
+    let process = match pid {
+        0 => current_process(),
+        _ => match pid2process(pid) {
+            Some(process) => process,
+            None => return -ESRCH,
+        },
+    };
+    let pgid = process.inner_exclusive_access().pgid;
+    pgid as isize
+}
+
+/// Create a new session and process group led by the caller, return the session id.
+///
+/// The new session has no controlling terminal. Return -EPERM if the caller is
+/// already a process group leader.
+pub fn sys_setsid() -> isize {
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    if inner.pgid == process.pid {
+        return -EPERM;
+    }
+    inner.pgid = process.pid;
+    inner.sid = process.pid;
+    process.pid as isize
+}
+
+/// Return the session of `pid` (0 for the caller), or -ESRCH.
+pub fn sys_getsid(pid: usize) -> isize {
+    let process = match pid {
+        0 => current_process(),
+        _ => match pid2process(pid) {
+            Some(process) => process,
+            None => return -ESRCH,
+        },
+    };
+    let sid = process.inner_exclusive_access().sid;
+    sid as isize
+}
+
+/// Limits of a resource, following Linux
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct RLimit {
+    /// soft limit, enforced
+    pub rlim_cur: usize,
+    /// hard limit, the soft limit may be raised up to
+    pub rlim_max: usize,
+}
+
+/// Bytes the main user stack may grow to
+const RLIMIT_STACK: usize = 3;
+
+/// Write the limits of `resource` to `rlim`.
+///
+/// Only `RLIMIT_STACK` is supported, otherwise return -EINVAL.
+pub fn sys_getrlimit(resource: usize, rlim: *mut RLimit) -> isize {
+    if resource != RLIMIT_STACK {
+        return -EINVAL;
+    }
+    let token = current_user_token();
+    let rlim_cur = current_process()
+        .inner_exclusive_access()
+        .memory_set
+        .stack_limit();
+    *translated_refmut(token, rlim) = RLimit {
+        rlim_cur,
+        rlim_max: USER_STACK_MAX_SIZE,
+    };
+    0
+}
+
+/// Set the limits of `resource` from `rlim`, they are inherited by children
+/// and kept across exec.
+///
+/// Only `RLIMIT_STACK` is supported, whose hard limit is the space reserved for
+/// the stack and cannot be changed. Return -EINVAL for another resource, a soft
+/// limit above the hard one, or another hard limit.
+pub fn sys_setrlimit(resource: usize, rlim: *const RLimit) -> isize {
+    if resource != RLIMIT_STACK {
+        return -EINVAL;
+    }
+    let token = current_user_token();
+    // user pages may be faulted in, which borrows the process
+    let rlim = *translated_refmut(token, rlim as *mut RLimit);
+    if rlim.rlim_max != USER_STACK_MAX_SIZE || rlim.rlim_cur > rlim.rlim_max {
+        return -EINVAL;
+    }
+    current_process()
+        .inner_exclusive_access()
+        .memory_set
+        .set_stack_limit(rlim.rlim_cur);
+    0
+}
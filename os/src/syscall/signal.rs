@@ -0,0 +1,109 @@
+use super::errno::{EINVAL, EPERM, ESRCH};
+use crate::mm::translated_refmut;
+use crate::task::signal::*;
+use crate::task::*;
+
+/// Send signal `signum` to process `pid`, `signum` 0 only checks that `pid` exists.
+///
+/// If `pid` is 0, send it to the process group of the caller, if `pid` is less
+/// than -1, send it to process group `-pid`. Sending to all processes (-1) is
+/// not supported.
+///
+/// The process manager and the init process cannot be signaled.
+pub fn sys_kill(pid: isize, signum: usize) -> isize {
+    if signum != 0 && SignalFlags::from_signum(signum).is_none() {
+        return -EINVAL;
+    }
+    if pid <= 0 {
+        let pgid = match pid {
+            0 => current_process().inner_exclusive_access().pgid,
+            -1 => return -EINVAL,
+            _ => (-pid) as usize,
+        };
+        let found = match signum {
+            0 => PID2PROCESS
+                .exclusive_access()
+                .values()
+                .any(|process| process.pid > 1 && process.inner_exclusive_access().pgid == pgid),
+            _ => send_signal_to_group(pgid, signum),
+        };
+        return if found { 0 } else { -ESRCH };
+    }
+    let pid = pid as usize;
+    let process = match pid2process(pid) {
+        Some(process) => process,
+        None => return -ESRCH,
+    };
+    if pid <= 1 {
+        return -EPERM;
+    }
+    if signum != 0 {
+        send_signal(&process, signum);
+    }
+    0
+}
+
+/// Set the action for `signum` if `action` is not null, and store the old one to
+/// `old_action` if it is not null.
+///
+/// Return -EINVAL if `signum` is invalid, or it is SIGKILL or SIGSTOP.
+pub fn sys_sigaction(
+    signum: usize,
+    action: *const SignalAction,
+    old_action: *mut SignalAction,
+) -> isize {
+    let flag = match SignalFlags::from_signum(signum) {
+        Some(flag) => flag,
+        None => return -EINVAL,
+    };
+    if SignalFlags::unmaskable().contains(flag) {
+        return -EINVAL;
+    }
+    let token = current_user_token();
+    // user pages may be faulted in, which borrows the process
+    let action = if action.is_null() {
+        None
+    } else {
+        Some(*translated_refmut(token, action as *mut SignalAction))
+    };
+    let process = current_process();
+    let mut inner = process.inner_exclusive_access();
+    let old = inner.signal_actions.table[signum];
+    if let Some(action) = action {
+        inner.signal_actions.table[signum] = SignalAction {
+            handler: action.handler,
+            mask: action.mask - SignalFlags::unmaskable(),
+        };
+    }
+    drop(inner);
+    if !old_action.is_null() {
+        *translated_refmut(token, old_action) = old;
+    }
+    0
+}
+
+/// Set the signal mask of current thread, return the old one.
+pub fn sys_sigprocmask(mask: u32) -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let old_mask = inner.signals.mask;
+    inner.signals.mask = SignalFlags::from_bits_truncate(mask) - SignalFlags::unmaskable();
+    old_mask.bits() as isize
+}
+
+/// Return from a signal handler to where the thread was interrupted.
+///
+/// Return -1 if no handler is running.
+pub fn sys_sigreturn() -> isize {
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    let trap_cx_backup = match inner.signals.trap_cx_backup.take() {
+        Some(trap_cx) => trap_cx,
+        None => return -1,
+    };
+    inner.signals.handling = None;
+    let trap_cx = inner.get_trap_cx();
+    *trap_cx = trap_cx_backup;
+    // keep a0 of the interrupted context
+    trap_cx.x[10] as isize
+}
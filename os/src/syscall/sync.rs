@@ -0,0 +1,193 @@
+use super::errno::{EAGAIN, EFAULT, EINTR, EINVAL};
+use crate::mm::checked_translated_ref;
+use crate::sync::{Condvar, MutexBlocking, Semaphore};
+use crate::task::*;
+use alloc::sync::Arc;
+
+const FUTEX_WAIT: usize = 0;
+const FUTEX_WAKE: usize = 1;
+
+/// Create a mutex in current process, return its id.
+pub fn sys_mutex_create() -> isize {
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let mutex = Arc::new(MutexBlocking::new());
+    ProcessControlBlockInner::insert_into_list(&mut process_inner.mutex_list, mutex) as isize
+}
+
+/// Return -1 if there is no such mutex, or -EINTR if the process is killed
+/// while waiting, the mutex is not locked then.
+pub fn sys_mutex_lock(mutex_id: usize) -> isize {
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    let mutex = match process_inner.mutex_list.get(mutex_id) {
+        Some(Some(mutex)) => mutex.clone(),
+        _ => return -1,
+    };
+    drop(process_inner);
+    drop(process);
+    if mutex.lock() {
+        0
+    } else {
+        -EINTR
+    }
+}
+
+/// Return -1 if there is no such mutex or it is not locked.
+pub fn sys_mutex_unlock(mutex_id: usize) -> isize {
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    let mutex = match process_inner.mutex_list.get(mutex_id) {
+        Some(Some(mutex)) => mutex.clone(),
+        _ => return -1,
+    };
+    drop(process_inner);
+    drop(process);
+    if mutex.unlock() {
+        0
+    } else {
+        -1
+    }
+}
+
+/// Create a semaphore with initial `count` in current process, return its id.
+pub fn sys_semaphore_create(count: usize) -> isize {
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let semaphore = Arc::new(Semaphore::new(count));
+    ProcessControlBlockInner::insert_into_list(&mut process_inner.semaphore_list, semaphore)
+        as isize
+}
+
+/// Return -1 if there is no such semaphore.
+pub fn sys_semaphore_up(sem_id: usize) -> isize {
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    let semaphore = match process_inner.semaphore_list.get(sem_id) {
+        Some(Some(semaphore)) => semaphore.clone(),
+        _ => return -1,
+    };
+    drop(process_inner);
+    drop(process);
+    semaphore.up();
+    0
+}
+
+/// Return -1 if there is no such semaphore, or -EINTR if the process is killed
+/// while waiting.
+pub fn sys_semaphore_down(sem_id: usize) -> isize {
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    let semaphore = match process_inner.semaphore_list.get(sem_id) {
+        Some(Some(semaphore)) => semaphore.clone(),
+        _ => return -1,
+    };
+    drop(process_inner);
+    drop(process);
+    if semaphore.down() {
+        0
+    } else {
+        -EINTR
+    }
+}
+
+/// Create a condition variable in current process, return its id.
+pub fn sys_condvar_create() -> isize {
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let condvar = Arc::new(Condvar::new());
+    ProcessControlBlockInner::insert_into_list(&mut process_inner.condvar_list, condvar) as isize
+}
+
+/// Return -1 if there is no such condition variable.
+pub fn sys_condvar_signal(condvar_id: usize) -> isize {
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    let condvar = match process_inner.condvar_list.get(condvar_id) {
+        Some(Some(condvar)) => condvar.clone(),
+        _ => return -1,
+    };
+    drop(process_inner);
+    drop(process);
+    condvar.signal();
+    0
+}
+
+/// Return -1 if there is no such condition variable or mutex, or the mutex is not locked,
+/// see [`Condvar::wait`] for -EINTR.
+pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
+    let process = current_process();
+    let process_inner = process.inner_exclusive_access();
+    let (condvar, mutex) = match (
+        process_inner.condvar_list.get(condvar_id),
+        process_inner.mutex_list.get(mutex_id),
+    ) {
+        (Some(Some(condvar)), Some(Some(mutex))) => (condvar.clone(), mutex.clone()),
+        _ => return -1,
+    };
+    drop(process_inner);
+    drop(process);
+    match condvar.wait(mutex) {
+        Ok(()) => 0,
+        Err(err) => err,
+    }
+}
+
+/// Fast user-space locking, `uaddr` is a 32-bit word shared by the threads of current process.
+///
+/// - `FUTEX_WAIT`: block until woken if `*uaddr == val`, else return -EAGAIN.
+/// - `FUTEX_WAKE`: wake up at most `val` threads waiting on `uaddr`, return the number woken.
+///
+/// Return -EINVAL if `uaddr` is not aligned or `op` is unknown. `FUTEX_WAIT`
+/// returns -EFAULT if `uaddr` cannot be read, or -EINTR if the process is killed
+/// while waiting.
+pub fn sys_futex(uaddr: *mut u32, op: usize, val: u32) -> isize {
+    let addr = uaddr as usize;
+    if addr % core::mem::size_of::<u32>() != 0 {
+        return -EINVAL;
+    }
+    match op {
+        FUTEX_WAIT => {
+            // no other thread can run between the check and blocking
+            match checked_translated_ref(current_user_token(), uaddr as *const u32) {
+                Some(&word) if word == val => {}
+                Some(_) => return -EAGAIN,
+                None => return -EFAULT,
+            }
+            let process = current_process();
+            process
+                .inner_exclusive_access()
+                .futex_queues
+                .entry(addr)
+                .or_default()
+                .push_back(current_task().unwrap());
+            drop(process);
+            if !block_current_and_run_next() {
+                current_process()
+                    .inner_exclusive_access()
+                    .remove_futex_waiter(&current_task().unwrap());
+                return -EINTR;
+            }
+            0
+        }
+        FUTEX_WAKE => {
+            let process = current_process();
+            let mut process_inner = process.inner_exclusive_access();
+            let mut woken = 0;
+            if let Some(queue) = process_inner.futex_queues.get_mut(&addr) {
+                while woken < val as usize {
+                    match queue.pop_front() {
+                        Some(task) => wakeup_task(task),
+                        None => break,
+                    }
+                    woken += 1;
+                }
+                if queue.is_empty() {
+                    process_inner.futex_queues.remove(&addr);
+                }
+            }
+            woken as isize
+        }
+        _ => -EINVAL,
+    }
+}
@@ -0,0 +1,69 @@
+use super::errno::ENOMEM;
+use crate::mm::*;
+use crate::task::*;
+use alloc::sync::Arc;
+
+/// Create a thread running `entry(arg)` in current process, return its tid.
+///
+/// Return -ENOMEM without memory or room below `MMAP_BASE` for its stack.
+pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
+    let process = current_process();
+    let tid = process.inner_exclusive_access().alloc_tid();
+    let new_task = match TaskControlBlock::new(&process, tid) {
+        Ok(task) => Arc::new(task),
+        Err(OutOfMemory) => {
+            process.inner_exclusive_access().dealloc_tid(tid);
+            return -ENOMEM;
+        }
+    };
+    let ustack_top = new_task
+        .inner_exclusive_access()
+        .res
+        .as_ref()
+        .unwrap()
+        .ustack_top();
+    // the new thread inherits the signal mask
+    new_task.inner_exclusive_access().signals.mask = current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .signals
+        .mask;
+    let trap_cx = new_task.init_trap_cx(entry, ustack_top);
+    trap_cx.x[10] = arg; // x[10] is a0 reg
+    process
+        .inner_exclusive_access()
+        .insert_task(tid, new_task.clone());
+    add_task(new_task);
+    tid as isize
+}
+
+pub fn sys_gettid() -> isize {
+    current_tid() as isize
+}
+
+/// If there is not a thread whose tid is same as given (or it is the caller), return -1.
+/// Else if the thread is still running, return -2.
+/// Else return tid.
+pub fn sys_waittid(tid: usize, exit_code_ptr: *mut i32) -> isize {
+    if tid == current_tid() {
+        return -1;
+    }
+    let process = current_process();
+    let mut process_inner = process.inner_exclusive_access();
+    let waited_task = match process_inner.tasks.get(tid) {
+        Some(Some(task)) => task.clone(),
+        _ => return -1,
+    };
+    let exit_code = match waited_task.inner_exclusive_access().exit_code {
+        Some(exit_code) => exit_code,
+        None => return -2,
+    };
+    // recycle the exited thread (its kernel stack)
+    process_inner.tasks[tid] = None;
+    process_inner.dealloc_tid(tid);
+    drop(process_inner);
+    drop(waited_task);
+    // store exit code to user space
+    *translated_refmut(current_user_token(), exit_code_ptr) = exit_code;
+    tid as isize
+}
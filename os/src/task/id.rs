@@ -0,0 +1,130 @@
+//! Allocators for ids (kernel stack ids, tids) and per-thread user resources
+
+use super::ProcessControlBlock;
+use crate::config::{thread_stack_position, trap_cx_position, MMAP_BASE, PAGE_SIZE};
+use crate::mm::address::{PhysPageNum, VPNRange, VirtAddr};
+use crate::mm::map_area::MapPermission;
+use crate::mm::OutOfMemory;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+
+/// Allocate the smallest unused ids first (LIFO for recycled ones)
+pub struct RecycleAllocator {
+    current: usize,
+    recycled: Vec<usize>,
+}
+
+impl RecycleAllocator {
+    pub fn new() -> Self {
+        Self {
+            current: 0,
+            recycled: Vec::new(),
+        }
+    }
+
+    pub fn alloc(&mut self) -> usize {
+        if let Some(id) = self.recycled.pop() {
+            id
+        } else {
+            self.current += 1;
+            self.current - 1
+        }
+    }
+
+    pub fn dealloc(&mut self, id: usize) {
+        assert!(id < self.current, "id {} has not been allocated!", id);
+        assert!(
+            !self.recycled.contains(&id),
+            "id {} has been deallocated!",
+            id
+        );
+        self.recycled.push(id);
+    }
+}
+
+/// User stack and trap context of a thread (RAII)
+///
+/// Those of the main thread (tid 0) are mapped together with the ELF, and
+/// released with the whole address space.
+pub struct TaskUserRes {
+    pub tid: usize,
+    pub process: Weak<ProcessControlBlock>,
+}
+
+impl TaskUserRes {
+    /// Map user stack and trap context for thread `tid` of `process`.
+    ///
+    /// The stack must be below `MMAP_BASE` and not overlap another area,
+    /// otherwise there is no room for it as for missing memory.
+    pub fn new(process: &Arc<ProcessControlBlock>, tid: usize) -> Result<Self, OutOfMemory> {
+        if tid != 0 {
+            let mut process_inner = process.inner_exclusive_access();
+            let memory_set = &mut process_inner.memory_set;
+            let (ustack_bottom, ustack_top) =
+                thread_stack_position(memory_set.user_stack_top(), tid);
+            let range = VPNRange::new(
+                VirtAddr::from(ustack_bottom).floor(),
+                VirtAddr::from(ustack_top).ceil(),
+            );
+            if ustack_top > MMAP_BASE || !memory_set.is_free(range) {
+                return Err(OutOfMemory);
+            }
+            memory_set.insert_empty_framed_area(
+                ustack_bottom.into(),
+                ustack_top.into(),
+                MapPermission::R | MapPermission::W | MapPermission::U,
+            )?;
+            let trap_cx_bottom = trap_cx_position(tid);
+            let result = memory_set.insert_empty_framed_area(
+                trap_cx_bottom.into(),
+                (trap_cx_bottom + PAGE_SIZE).into(),
+                MapPermission::R | MapPermission::W,
+            );
+            if let Err(err) = result {
+                memory_set.remove_area_with_start_vpn(VirtAddr::from(ustack_bottom).into());
+                return Err(err);
+            }
+        }
+        Ok(Self {
+            tid,
+            process: Arc::downgrade(process),
+        })
+    }
+
+    /// Virtual address of trap context in user space
+    pub fn trap_cx_user_va(&self) -> usize {
+        trap_cx_position(self.tid)
+    }
+
+    pub fn trap_cx_ppn(&self) -> PhysPageNum {
+        let process = self.process.upgrade().unwrap();
+        let process_inner = process.inner_exclusive_access();
+        process_inner
+            .memory_set
+            .translate_to_ppn(VirtAddr::from(self.trap_cx_user_va()).into())
+            .unwrap()
+    }
+
+    /// Top of user stack (initial sp) of a new thread
+    pub fn ustack_top(&self) -> usize {
+        let process = self.process.upgrade().unwrap();
+        let process_inner = process.inner_exclusive_access();
+        thread_stack_position(process_inner.memory_set.user_stack_top(), self.tid).1
+    }
+}
+
+impl Drop for TaskUserRes {
+    fn drop(&mut self) {
+        if self.tid == 0 {
+            return;
+        }
+        // nothing to do if the whole address space is gone
+        if let Some(process) = self.process.upgrade() {
+            let mut process_inner = process.inner_exclusive_access();
+            let memory_set = &mut process_inner.memory_set;
+            let (ustack_bottom, _) = thread_stack_position(memory_set.user_stack_top(), self.tid);
+            memory_set.remove_area_with_start_vpn(VirtAddr::from(ustack_bottom).into());
+            memory_set.remove_area_with_start_vpn(VirtAddr::from(self.trap_cx_user_va()).into());
+        }
+    }
+}
@@ -1,24 +1,36 @@
+use super::id::RecycleAllocator;
 use crate::config::kernel_stack_position;
 use crate::mm::address::VirtAddr;
 use crate::mm::map_area::MapPermission;
-use crate::mm::KERNEL_SPACE;
+use crate::mm::{OutOfMemory, KERNEL_SPACE};
+use crate::UPSafeCell;
+use lazy_static::lazy_static;
 
-/// Kernel stack for app
+lazy_static! {
+    static ref KSTACK_ALLOCATOR: UPSafeCell<RecycleAllocator> =
+        UPSafeCell::new(RecycleAllocator::new());
+}
+
+/// Kernel stack for a thread
 pub struct KernelStack {
-    pid: usize,
+    id: usize,
 }
 
 impl KernelStack {
-    /// Create a kernel stack from pid
-    pub fn new(pid: usize) -> Self {
-        // let pid = pid.0;
-        let (kernel_stack_bottom, kernel_stack_top) = kernel_stack_position(pid);
-        KERNEL_SPACE.exclusive_access().insert_empty_framed_area(
+    /// Create a kernel stack with a new id
+    pub fn new() -> Result<Self, OutOfMemory> {
+        let id = KSTACK_ALLOCATOR.exclusive_access().alloc();
+        let (kernel_stack_bottom, kernel_stack_top) = kernel_stack_position(id);
+        let result = KERNEL_SPACE.exclusive_access().insert_empty_framed_area(
             kernel_stack_bottom.into(),
             kernel_stack_top.into(),
             MapPermission::R | MapPermission::W,
         );
-        KernelStack { pid }
+        if let Err(err) = result {
+            KSTACK_ALLOCATOR.exclusive_access().dealloc(id);
+            return Err(err);
+        }
+        Ok(KernelStack { id })
     }
 
     #[allow(unused)]
@@ -37,17 +49,18 @@ impl KernelStack {
 
     /// Get the value on the top of kernel stack
     pub fn get_top(&self) -> usize {
-        let (_, kernel_stack_top) = kernel_stack_position(self.pid);
+        let (_, kernel_stack_top) = kernel_stack_position(self.id);
         kernel_stack_top
     }
 }
 
 impl Drop for KernelStack {
     fn drop(&mut self) {
-        let (kernel_stack_bottom, _) = kernel_stack_position(self.pid);
+        let (kernel_stack_bottom, _) = kernel_stack_position(self.id);
         let kernel_stack_bottom_va: VirtAddr = kernel_stack_bottom.into();
         KERNEL_SPACE
             .exclusive_access()
             .remove_area_with_start_vpn(kernel_stack_bottom_va.into());
+        KSTACK_ALLOCATOR.exclusive_access().dealloc(self.id);
     }
 }
@@ -2,10 +2,10 @@
 //!
 //! A simple FIFO scheduler.
 
-use super::{TaskControlBlock, PROC_MANAGER};
+use super::{ProcessControlBlock, TaskControlBlock, PROC_MANAGER};
 use crate::task::switch::check_proc_manager_service;
 use crate::UPSafeCell;
-use alloc::collections::VecDeque;
+use alloc::collections::{BTreeMap, VecDeque};
 use alloc::sync::Arc;
 use lazy_static::lazy_static;
 
@@ -17,7 +17,7 @@ pub fn add_task(task: Arc<TaskControlBlock>) {
 /// Interface offered to pop the first task
 pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
     if check_proc_manager_service() {
-        Some(PROC_MANAGER.clone())
+        Some(PROC_MANAGER.main_task())
     } else {
         TASK_MANAGER.exclusive_access().fetch()
     }
@@ -27,6 +27,93 @@ pub fn add_task_front(task: Arc<TaskControlBlock>) {
     TASK_MANAGER.exclusive_access().add_front(task);
 }
 
+/// Remove a task from the ready queue (if it is there)
+pub fn remove_task(task: &Arc<TaskControlBlock>) {
+    TASK_MANAGER.exclusive_access().remove(task);
+}
+
+/// Whether a thread of `process` is waiting in the ready queue
+pub fn has_ready_task(process: &Arc<ProcessControlBlock>) -> bool {
+    TASK_MANAGER
+        .exclusive_access()
+        .ready_queue
+        .iter()
+        .any(|task| {
+            task.process
+                .upgrade()
+                .map_or(false, |p| Arc::ptr_eq(&p, process))
+        })
+}
+
+/// Register a process so that it can be found by pid
+pub fn insert_into_pid2process(pid: usize, process: Arc<ProcessControlBlock>) {
+    PID2PROCESS.exclusive_access().insert(pid, process);
+}
+
+pub fn pid2process(pid: usize) -> Option<Arc<ProcessControlBlock>> {
+    PID2PROCESS.exclusive_access().get(&pid).cloned()
+}
+
+/// Whether there is a process group `pgid` in session `sid`
+pub fn group_in_session(pgid: usize, sid: usize) -> bool {
+    PID2PROCESS.exclusive_access().values().any(|process| {
+        let inner = process.inner_exclusive_access();
+        inner.pgid == pgid && inner.sid == sid
+    })
+}
+
+pub fn remove_from_pid2process(pid: usize) {
+    if PID2PROCESS.exclusive_access().remove(&pid).is_none() {
+        panic!("cannot find pid {} in pid2process!", pid);
+    }
+}
+
+/// Pick victim processes in a round-robin way and evict one page to the swap area.
+///
+/// Processes whose PCB is being accessed (e.g. the faulting process) are skipped.
+pub fn swap_out_victim() -> bool {
+    let pid2process = PID2PROCESS.exclusive_access();
+    let mut cursor = SWAP_CURSOR.exclusive_access();
+    let victims = pid2process
+        .range(*cursor..)
+        .chain(pid2process.range(..*cursor));
+    for (&pid, process) in victims {
+        if let Ok(mut inner) = process.try_borrow_mut() {
+            if inner.memory_set.swap_out_one() {
+                *cursor = pid + 1;
+                return true;
+            }
+        }
+    }
+    false
+}
+
+/// Choose a process to be killed when memory is exhausted: the one with the most
+/// resident pages (the youngest one on ties).
+///
+/// Processes already chosen are skipped, they are woken up when chosen and free
+/// their memory as soon as they run. So are processes without resident pages,
+/// killing them reclaims nothing. `initproc` and the process manager are never
+/// chosen.
+pub fn select_oom_victim() -> Option<Arc<ProcessControlBlock>> {
+    let pid2process = PID2PROCESS.exclusive_access();
+    let mut victim = None;
+    let mut max_resident = 0;
+    for (_, process) in pid2process.range(2..) {
+        if let Ok(inner) = process.try_borrow_mut() {
+            if inner.killed {
+                continue;
+            }
+            let resident = inner.memory_set.resident_count();
+            if resident > 0 && resident >= max_resident {
+                max_resident = resident;
+                victim = Some(process.clone());
+            }
+        }
+    }
+    victim
+}
+
 // implementation ---------------------------------------------------
 
 /// A queue of `TaskControlBlock` that is thread-safe
@@ -52,8 +139,17 @@ impl TaskManager {
     fn add_front(&mut self, task: Arc<TaskControlBlock>) {
         self.ready_queue.push_front(task);
     }
+
+    fn remove(&mut self, task: &Arc<TaskControlBlock>) {
+        self.ready_queue.retain(|t| !Arc::ptr_eq(t, task));
+    }
 }
 
 lazy_static! {
     pub static ref TASK_MANAGER: UPSafeCell<TaskManager> = UPSafeCell::new(TaskManager::new());
+    /// All alive processes
+    pub static ref PID2PROCESS: UPSafeCell<BTreeMap<usize, Arc<ProcessControlBlock>>> =
+        UPSafeCell::new(BTreeMap::new());
+    /// Next pid to look for a victim page
+    static ref SWAP_CURSOR: UPSafeCell<usize> = UPSafeCell::new(0);
 }
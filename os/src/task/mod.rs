@@ -1,34 +1,37 @@
 mod context;
+mod id;
 mod kernel_stack;
 mod manager;
+mod process;
 mod scheduler;
 pub mod service;
+pub mod signal;
 pub mod switch;
 mod task;
 
-use crate::loader::get_app_data_by_name;
+use crate::fs::vfs::root;
+use crate::loader::Executable;
 use alloc::sync::Arc;
+use alloc::vec::Vec;
 pub use context::TaskContext;
 use lazy_static::lazy_static;
 pub use manager::*;
+pub use process::{ProcessControlBlock, ProcessControlBlockInner};
 pub use scheduler::*;
 use switch::check_proc_manager_service;
-use task::*;
+pub use task::TaskControlBlock;
 
 lazy_static! {
-    pub static ref INITPROC: Arc<TaskControlBlock> = Arc::new(TaskControlBlock::new(
-        get_app_data_by_name("initproc").unwrap(),
-        1
-    ));
-    pub static ref PROC_MANAGER: Arc<TaskControlBlock> = Arc::new(TaskControlBlock::new(
-        get_app_data_by_name("proc_manager").unwrap(),
-        0
-    ));
+    pub static ref INITPROC: Arc<ProcessControlBlock> =
+        ProcessControlBlock::new(&Executable::open(&root(), "initproc").unwrap(), 1).unwrap();
+    pub static ref PROC_MANAGER: Arc<ProcessControlBlock> =
+        ProcessControlBlock::new(&Executable::open(&root(), "proc_manager").unwrap(), 0).unwrap();
 }
 
 pub fn add_initproc() {
-    let initproc = INITPROC.clone();
-    add_task(initproc);
+    insert_into_pid2process(INITPROC.pid, INITPROC.clone());
+    insert_into_pid2process(PROC_MANAGER.pid, PROC_MANAGER.clone());
+    add_task(INITPROC.main_task());
 }
 
 /// Suspend the current 'Running' task and run the next task in task list.
@@ -51,22 +54,194 @@ pub fn suspend_current_and_run_next() {
     schedule(task_cx_ptr);
 }
 
+/// Block the current 'Running' task and run the next task in task list.
+///
+/// The task is not pushed back to the ready queue, whoever holds it (e.g. the
+/// wait queue of a mutex) must call `wakeup_task` later.
+///
+/// Return false if the process is being killed (see
+/// [`signal::current_is_killed`]), either before blocking or when woken up by
+/// [`interrupt_blocked_tasks`]. The task may still be in the wait queue then.
+pub fn block_current_and_run_next() -> bool {
+    if signal::current_is_killed() {
+        return false;
+    }
+    let current_task = take_current_task().unwrap();
+    let mut current_inner = current_task.inner_exclusive_access();
+    let task_cx_ptr = current_inner.get_task_cx_ptr();
+    drop(current_inner);
+    drop(current_task);
+
+    // jump to scheduling cycle
+    schedule(task_cx_ptr);
+    !signal::current_is_killed()
+}
+
+/// Make a blocked task ready again.
+pub fn wakeup_task(task: Arc<TaskControlBlock>) {
+    add_task(task);
+}
+
+/// Wake up the threads of `process` blocked on its mutexes, semaphores,
+/// condition variables and futexes for which `interrupted` returns true, so
+/// that they see the process being killed instead of waiting forever.
+///
+/// They are removed from the wait queues first, so nothing is handed over to
+/// them any more.
+pub fn interrupt_blocked_tasks(
+    process: &ProcessControlBlock,
+    interrupted: impl Fn(&TaskControlBlock) -> bool,
+) {
+    let process_inner = process.inner_exclusive_access();
+    let tasks: Vec<_> = process_inner.tasks.iter().flatten().cloned().collect();
+    let mutexes: Vec<_> = process_inner.mutex_list.iter().flatten().cloned().collect();
+    let semaphores: Vec<_> = process_inner
+        .semaphore_list
+        .iter()
+        .flatten()
+        .cloned()
+        .collect();
+    let condvars: Vec<_> = process_inner
+        .condvar_list
+        .iter()
+        .flatten()
+        .cloned()
+        .collect();
+    drop(process_inner);
+    for task in tasks {
+        if !interrupted(&task) {
+            continue;
+        }
+        // a thread waits in one queue at a time
+        let blocked = mutexes.iter().any(|mutex| mutex.remove_waiter(&task))
+            || semaphores
+                .iter()
+                .any(|semaphore| semaphore.remove_waiter(&task))
+            || condvars.iter().any(|condvar| condvar.remove_waiter(&task))
+            || process.inner_exclusive_access().remove_futex_waiter(&task);
+        if blocked {
+            wakeup_task(task);
+        }
+    }
+}
+
 // ---------------------------------------------------------------------
 
+/// Wait status of a process exited with `exit_code`, following Linux
+pub fn exited_status(exit_code: i32) -> i32 {
+    (exit_code & 0xff) << 8
+}
+
+/// Wait status of a process killed by signal `signum`, following Linux
+pub fn signaled_status(signum: usize) -> i32 {
+    signum as i32
+}
+
+/// Added to the wait status of a process killed by SIGSEGV for overflowing its
+/// stack, above the bits used by Linux
+pub const STACK_OVERFLOW: i32 = 0x10000;
+
 /// Exit the current 'Running' task and run the next task in task list.
+///
+/// If it is the main thread, the whole process exits.
 pub fn exit_current_and_run_next(exit_code: i32) {
-    service::exit(current_pid(), exit_code);
+    if current_tid() == 0 {
+        exit_current_process_and_run_next(exited_status(exit_code));
+        return;
+    }
+    // take current task from Processor
+    let current_task = take_current_task().unwrap();
+    let mut current_inner = current_task.inner_exclusive_access();
+    // the task is kept in its process until waited
+    current_inner.exit_code = Some(exit_code);
+    // deallocate user stack and trap context
+    current_inner.res = None;
+    drop(current_inner);
+    drop(current_task);
+
+    // we do not have to save task context, just run next
+    let mut _unused = TaskContext::empty();
+    schedule(&mut _unused as *mut _);
+}
+
+/// Exit the current process with all its threads and run the next task in task list.
+///
+/// `status` is reported to the parent by `sys_waitpid`.
+pub fn exit_current_process_and_run_next(status: i32) {
+    service::exit(current_pid(), status);
 
     // take current task from Processor
     let current_task = take_current_task().unwrap();
-    // confirm that current task will be deallocated
-    if current_task.pid > 1 {
-        assert_eq!(Arc::strong_count(&current_task), 1);
-    }
-    // recycle current task resources
+    let process = current_task.process.upgrade().unwrap();
     drop(current_task);
+    remove_from_pid2process(process.pid);
+    // other threads will never run again
+    let mut process_inner = process.inner_exclusive_access();
+    let tasks: Vec<_> = process_inner.tasks.drain(..).flatten().collect();
+    // blocked threads are only referenced by wait queues
+    let sync_lists = (
+        core::mem::take(&mut process_inner.mutex_list),
+        core::mem::take(&mut process_inner.semaphore_list),
+        core::mem::take(&mut process_inner.condvar_list),
+        core::mem::take(&mut process_inner.futex_queues),
+    );
+    // close all files, e.g. so that readers of its pipes see the end of file
+    let fd_table = core::mem::take(&mut process_inner.fd_table);
+    drop(process_inner);
+    for task in tasks.iter() {
+        remove_task(task);
+    }
+    // recycle resources of all threads
+    drop(sync_lists);
+    drop(fd_table);
+    drop(tasks);
+    // confirm that current process will be deallocated
+    if process.pid > 1 {
+        assert_eq!(Arc::strong_count(&process), 1);
+    }
+    // recycle current process resources
+    drop(process);
 
     // we do not have to save task context, just run next
     let mut _unused = TaskContext::empty();
     schedule(&mut _unused as *mut _);
 }
+
+// ---------------------------------------------------------------------
+
+/// Called when the current task needs a frame that cannot be allocated and
+/// there is no caller to return an error to (e.g. on page fault).
+///
+/// The OOM killer marks the process with the most resident pages as killed,
+/// wakes its blocked threads and yields, so the victim can exit and free its
+/// memory before the current task retries. If the victim is the current process,
+/// or no other process can be killed, the current process exits right away.
+///
+/// A process killed by the OOM killer is reported as killed by SIGKILL.
+pub fn handle_out_of_memory() {
+    let pid = current_pid();
+    match select_oom_victim() {
+        Some(victim) if victim.pid != pid => {
+            crate::warn!("[kernel] Out of memory, kill process {}.", victim.pid);
+            victim.inner_exclusive_access().killed = true;
+            interrupt_blocked_tasks(&victim, |_| true);
+            drop(victim);
+            suspend_current_and_run_next();
+        }
+        _ => {
+            if pid <= 1 {
+                panic!("Out of memory in process {}!", pid);
+            }
+            crate::warn!("[kernel] Out of memory, kill process {}.", pid);
+            exit_current_process_and_run_next(signaled_status(signal::SIGKILL));
+        }
+    }
+}
+
+/// Exit the current process if it has been chosen by the OOM killer.
+pub fn exit_current_if_killed() {
+    let killed = current_process().inner_exclusive_access().killed;
+    if killed {
+        exit_current_process_and_run_next(signaled_status(signal::SIGKILL));
+    }
+}
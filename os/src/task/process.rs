@@ -0,0 +1,318 @@
+//! Implementation of [`ProcessControlBlock`]
+
+use super::id::RecycleAllocator;
+use super::signal::{SignalActions, SignalFlags};
+use super::TaskControlBlock;
+use crate::config::{MAX_FD, TRAP_CONTEXT, USER_STACK_LIMIT};
+use crate::fs::vfs::root;
+use crate::fs::{open_file, Dentry, File, OpenFlags};
+use crate::loader::Executable;
+use crate::mm::address::VirtAddr;
+use crate::mm::memory_set::MemorySet;
+use crate::mm::{translated_refmut, OutOfMemory};
+use crate::sync::{Condvar, Mutex, Semaphore};
+use crate::UPSafeCell;
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::String;
+use alloc::sync::Arc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::RefMut;
+use core::mem::size_of;
+
+/// Process Control Block, the threads of a process share its address space
+pub struct ProcessControlBlock {
+    // immutable
+    pub pid: usize,
+    // mutable
+    inner: UPSafeCell<ProcessControlBlockInner>,
+}
+
+pub struct ProcessControlBlockInner {
+    pub memory_set: MemorySet,
+    /// threads indexed by tid, an exited thread is kept until it is waited
+    pub tasks: Vec<Option<Arc<TaskControlBlock>>>,
+    tid_allocator: RecycleAllocator,
+    /// chosen by the OOM killer, exit before returning to user space
+    pub killed: bool,
+    // synchronization primitives for threads, indexed by id
+    pub mutex_list: Vec<Option<Arc<dyn Mutex>>>,
+    pub semaphore_list: Vec<Option<Arc<Semaphore>>>,
+    pub condvar_list: Vec<Option<Arc<Condvar>>>,
+    /// threads blocked in `sys_futex`, keyed by user virtual address
+    pub futex_queues: BTreeMap<usize, VecDeque<Arc<TaskControlBlock>>>,
+    /// signals sent to the process but not delivered yet
+    pub pending_signals: SignalFlags,
+    pub signal_actions: SignalActions,
+    /// stopped by a signal until SIGCONT
+    pub stopped: bool,
+    /// process group
+    pub pgid: usize,
+    /// session
+    pub sid: usize,
+    /// opened files indexed by file descriptor
+    pub fd_table: Vec<Option<Arc<dyn File>>>,
+    /// current working directory, where relative paths start
+    pub cwd: Arc<Dentry>,
+}
+
+impl ProcessControlBlockInner {
+    pub fn get_user_token(&self) -> usize {
+        self.memory_set.satp_token()
+    }
+
+    pub fn alloc_tid(&mut self) -> usize {
+        self.tid_allocator.alloc()
+    }
+
+    pub fn dealloc_tid(&mut self, tid: usize) {
+        self.tid_allocator.dealloc(tid)
+    }
+
+    /// Number of threads not exited yet
+    pub fn thread_count(&self) -> usize {
+        self.tasks
+            .iter()
+            .flatten()
+            .filter(|task| task.inner_exclusive_access().exit_code.is_none())
+            .count()
+    }
+
+    pub fn get_task(&self, tid: usize) -> Arc<TaskControlBlock> {
+        self.tasks[tid].as_ref().unwrap().clone()
+    }
+
+    /// Put `item` into the first free slot of `list`, return its id.
+    pub fn insert_into_list<T: ?Sized>(list: &mut Vec<Option<Arc<T>>>, item: Arc<T>) -> usize {
+        if let Some(id) = list.iter().position(|slot| slot.is_none()) {
+            list[id] = Some(item);
+            id
+        } else {
+            list.push(Some(item));
+            list.len() - 1
+        }
+    }
+
+    /// Return the lowest free file descriptor, None if there are `MAX_FD` files
+    /// open.
+    pub fn alloc_fd(&mut self) -> Option<usize> {
+        if let Some(fd) = self.fd_table.iter().position(|file| file.is_none()) {
+            Some(fd)
+        } else if self.fd_table.len() < MAX_FD {
+            self.fd_table.push(None);
+            Some(self.fd_table.len() - 1)
+        } else {
+            None
+        }
+    }
+
+    pub fn get_file(&self, fd: usize) -> Option<Arc<dyn File>> {
+        self.fd_table.get(fd).cloned().flatten()
+    }
+
+    pub fn insert_task(&mut self, tid: usize, task: Arc<TaskControlBlock>) {
+        while self.tasks.len() <= tid {
+            self.tasks.push(None);
+        }
+        self.tasks[tid] = Some(task);
+    }
+
+    /// Remove `task` from the futex queues, return false if it is not there.
+    pub fn remove_futex_waiter(&mut self, task: &TaskControlBlock) -> bool {
+        let addr = self.futex_queues.iter_mut().find_map(|(&addr, queue)| {
+            let index = queue
+                .iter()
+                .position(|waiting| core::ptr::eq(waiting.as_ref(), task))?;
+            queue.remove(index);
+            Some(addr)
+        });
+        match addr {
+            Some(addr) => {
+                if self.futex_queues[&addr].is_empty() {
+                    self.futex_queues.remove(&addr);
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+impl ProcessControlBlock {
+    pub fn inner_exclusive_access(&self) -> RefMut<'_, ProcessControlBlockInner> {
+        match self.try_borrow_mut() {
+            Ok(process_inner) => {
+                drop(process_inner);
+            }
+            Err(_) => {
+                panic!("PCB inner is already borrowed for pid {}!", self.pid);
+            }
+        }
+        self.inner.exclusive_access()
+    }
+
+    pub fn try_borrow_mut(
+        &self,
+    ) -> Result<RefMut<'_, ProcessControlBlockInner>, core::cell::BorrowMutError> {
+        self.inner.try_borrow_mut()
+    }
+
+    fn new_with_memory_set(pid: usize, memory_set: MemorySet) -> Arc<Self> {
+        Arc::new(Self {
+            pid,
+            inner: UPSafeCell::new(ProcessControlBlockInner {
+                memory_set,
+                tasks: Vec::new(),
+                tid_allocator: RecycleAllocator::new(),
+                killed: false,
+                mutex_list: Vec::new(),
+                semaphore_list: Vec::new(),
+                condvar_list: Vec::new(),
+                futex_queues: BTreeMap::new(),
+                pending_signals: SignalFlags::empty(),
+                signal_actions: SignalActions::default(),
+                stopped: false,
+                pgid: pid,
+                sid: pid,
+                fd_table: vec![
+                    // 0 -> stdin
+                    Some(open_file(&root(), "/dev/console", OpenFlags::empty()).unwrap()),
+                    // 1 -> stdout
+                    Some(open_file(&root(), "/dev/console", OpenFlags::WRONLY).unwrap()),
+                    // 2 -> stderr
+                    Some(open_file(&root(), "/dev/console", OpenFlags::WRONLY).unwrap()),
+                ],
+                cwd: root(),
+            }),
+        })
+    }
+
+    /// Create the main thread (tid 0) of a new process.
+    fn create_main_task(self: &Arc<Self>) -> Result<Arc<TaskControlBlock>, OutOfMemory> {
+        let tid = self.inner_exclusive_access().alloc_tid();
+        assert_eq!(tid, 0);
+        let task = Arc::new(TaskControlBlock::new(self, tid)?);
+        self.inner_exclusive_access().tasks = vec![Some(task.clone())];
+        Ok(task)
+    }
+
+    pub fn new(exe: &Executable, pre_alloc_pid: usize) -> Result<Arc<Self>, OutOfMemory> {
+        // 从elf文件中解析出内存布局
+        let (memory_set, user_sp, entry_point) = MemorySet::new_from_elf(exe, USER_STACK_LIMIT)?;
+        let process = Self::new_with_memory_set(pre_alloc_pid, memory_set);
+        let task = process.create_main_task()?;
+        // prepare Trap Context in user space
+        task.init_trap_cx(entry_point, user_sp);
+        Ok(process)
+    }
+
+    /// Only a process with a single thread can fork, the thread becomes the
+    /// main thread of the child process.
+    ///
+    /// Opened files are shared with the child, which starts in the same working
+    /// directory. Synchronization primitives and pending signals are not inherited.
+    pub fn fork(self: &Arc<Self>, new_pid: usize) -> Result<Arc<Self>, OutOfMemory> {
+        // ---- access parent PCB exclusively
+        let parent_inner = self.inner_exclusive_access();
+        assert_eq!(parent_inner.thread_count(), 1);
+        // copy user space (include trap context)
+        let memory_set = MemorySet::from_existed_user(&parent_inner.memory_set);
+        let signal_actions = parent_inner.signal_actions;
+        let (pgid, sid) = (parent_inner.pgid, parent_inner.sid);
+        let fd_table = parent_inner.fd_table.clone();
+        let cwd = parent_inner.cwd.clone();
+        let signal_mask = parent_inner
+            .get_task(0)
+            .inner_exclusive_access()
+            .signals
+            .mask;
+        drop(parent_inner);
+        let child = Self::new_with_memory_set(new_pid, memory_set?);
+        let mut child_inner = child.inner_exclusive_access();
+        child_inner.signal_actions = signal_actions;
+        // in the same process group and session as the parent
+        child_inner.pgid = pgid;
+        child_inner.sid = sid;
+        child_inner.fd_table = fd_table;
+        child_inner.cwd = cwd;
+        drop(child_inner);
+        let task = child.create_main_task()?;
+        task.inner_exclusive_access().signals.mask = signal_mask;
+        // modify kernel_sp in new trap_cx
+        let trap_cx = task.inner_exclusive_access().get_trap_cx();
+        trap_cx.kernel_sp = task.kernel_stack.get_top();
+        Ok(child)
+    }
+
+    /// Only a process with a single thread can exec.
+    ///
+    /// `args` are pushed onto the new user stack, the program is entered with
+    /// `argc` in a0 and `argv` (terminated by a null pointer) in a1.
+    /// The old address space is kept if the new one cannot be built.
+    pub fn exec(
+        &self,
+        exe: &Executable,
+        args: Vec<String>,
+        envs: Vec<String>,
+    ) -> Result<(), OutOfMemory> {
+        // memory_set with elf program headers/trampoline/trap context/user stack
+        let stack_limit = self.inner_exclusive_access().memory_set.stack_limit();
+        let (memory_set, mut user_sp, entry_point) = MemorySet::new_from_elf(exe, stack_limit)?;
+        let trap_cx_ppn = memory_set
+            .translate_to_ppn(VirtAddr::from(TRAP_CONTEXT).into())
+            .unwrap();
+        // ---- access inner exclusively
+        let mut inner = self.inner_exclusive_access();
+        assert_eq!(inner.thread_count(), 1);
+        // substitute memory_set
+        inner.memory_set = memory_set;
+        inner.mutex_list.clear();
+        inner.semaphore_list.clear();
+        inner.condvar_list.clear();
+        inner.futex_queues.clear();
+        inner.signal_actions.reset_handlers();
+        let task = inner.get_task(0);
+        drop(inner);
+        // update trap_cx ppn and initialize trap_cx
+        let mut task_inner = task.inner_exclusive_access();
+        task_inner.trap_cx_ppn = trap_cx_ppn;
+        task_inner.signals.handling = None;
+        task_inner.signals.trap_cx_backup = None;
+        let token = self.inner_exclusive_access().get_user_token();
+        drop(task_inner);
+        // push environment then arguments on the user stack
+        let envp_base = push_strings(token, &mut user_sp, &envs);
+        let argv_base = push_strings(token, &mut user_sp, &args);
+        let trap_cx = task.init_trap_cx(entry_point, user_sp);
+        trap_cx.x[10] = args.len();
+        trap_cx.x[11] = argv_base;
+        trap_cx.x[12] = envp_base;
+        Ok(())
+    }
+
+    /// The main thread of the process
+    pub fn main_task(&self) -> Arc<TaskControlBlock> {
+        self.inner_exclusive_access().get_task(0)
+    }
+}
+
+/// Push `strings` and a null-terminated array of pointers to them on the user
+/// stack, the array below the strings. Return the address of the array.
+fn push_strings(token: usize, user_sp: &mut usize, strings: &[String]) -> usize {
+    let mut pointers = Vec::with_capacity(strings.len() + 1);
+    for string in strings.iter() {
+        *user_sp -= string.len() + 1;
+        for (j, &c) in string.as_bytes().iter().chain(&[0]).enumerate() {
+            *translated_refmut(token, (*user_sp + j) as *mut u8) = c;
+        }
+        pointers.push(*user_sp);
+    }
+    pointers.push(0);
+    // keep the stack pointer aligned
+    *user_sp -= *user_sp % size_of::<usize>();
+    *user_sp -= pointers.len() * size_of::<usize>();
+    for (i, &pointer) in pointers.iter().enumerate() {
+        *translated_refmut(token, (*user_sp + i * size_of::<usize>()) as *mut usize) = pointer;
+    }
+    *user_sp
+}
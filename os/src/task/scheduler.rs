@@ -1,6 +1,6 @@
 //! Implementation of [`Scheduler`] and Intersection of control flow
 
-use super::{TaskContext, TaskControlBlock};
+use super::{ProcessControlBlock, TaskContext, TaskControlBlock};
 use crate::info;
 use crate::shutdown;
 use crate::task::manager::fetch_task;
@@ -58,14 +58,21 @@ pub fn current_task() -> Option<Arc<TaskControlBlock>> {
     SCHEDULER.exclusive_access().clone_current()
 }
 
+/// Get the process of current task
+pub fn current_process() -> Arc<ProcessControlBlock> {
+    current_task().unwrap().process.upgrade().unwrap()
+}
+
 pub fn current_pid() -> usize {
-    current_task().unwrap().pid
+    current_process().pid
+}
+
+pub fn current_tid() -> usize {
+    current_task().unwrap().inner_exclusive_access().tid()
 }
 
 pub fn current_user_token() -> usize {
-    let task = current_task().unwrap();
-    let token = task.inner_exclusive_access().get_user_token();
-    token
+    current_task().unwrap().get_user_token()
 }
 
 pub fn current_trap_cx() -> &'static mut TrapContext {
@@ -75,6 +82,17 @@ pub fn current_trap_cx() -> &'static mut TrapContext {
         .get_trap_cx()
 }
 
+/// Virtual address of the trap context of current task in user space
+pub fn current_trap_cx_user_va() -> usize {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .res
+        .as_ref()
+        .unwrap()
+        .trap_cx_user_va()
+}
+
 // key interface ---------------------------------------
 
 /// The main part of process execution and scheduling
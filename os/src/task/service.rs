@@ -1,9 +1,18 @@
 use crate::config::*;
 use crate::task::switch::switch_to_proc_manager;
+use alloc::vec::Vec;
 
 const EXIT: i32 = 1;
 const WAIT: i32 = 2;
 const FORK: i32 = 3;
+const STOP: i32 = 4;
+const CONT: i32 = 5;
+const GETPPID: i32 = 6;
+const PROC_INFO: i32 = 7;
+const LIST_PIDS: i32 = 8;
+
+/// `options` of `waitpid`: also report stopped children
+pub const WUNTRACED: usize = 2;
 
 pub fn exit(pid: usize, exit_code: i32) {
     write_to_shared_page(0, EXIT);
@@ -14,11 +23,14 @@ pub fn exit(pid: usize, exit_code: i32) {
 
 /// If there is not a child process whose pid is same as given, return (0, _).
 /// Else if there is a child process but it is still running, return (1, _).
-/// Else return (found_pid, exit_code).
-pub fn waitpid(parent_pid: usize, pid: isize) -> (usize, i32) {
+/// Else return (found_pid, status).
+///
+/// With `WUNTRACED` in `options`, a stopped child is also reported (once per stop).
+pub fn waitpid(parent_pid: usize, pid: isize, options: usize) -> (usize, i32) {
     write_to_shared_page(0, WAIT);
     write_to_shared_page(1, parent_pid as i32);
     write_to_shared_page(2, pid as i32);
+    write_to_shared_page(3, options as i32);
     switch_to_proc_manager();
 
     let found_pid = read_from_shared_page(3);
@@ -34,3 +46,69 @@ pub fn fork(parent_pid: usize) -> usize {
     let new_pid = read_from_shared_page(2);
     new_pid as usize
 }
+
+/// Tell the process manager that `pid` is stopped by signal `signum`.
+pub fn stop(pid: usize, signum: usize) {
+    write_to_shared_page(0, STOP);
+    write_to_shared_page(1, pid as i32);
+    write_to_shared_page(2, signum as i32);
+    switch_to_proc_manager();
+}
+
+/// Tell the process manager that `pid` is continued.
+pub fn cont(pid: usize) {
+    write_to_shared_page(0, CONT);
+    write_to_shared_page(1, pid as i32);
+    switch_to_proc_manager();
+}
+
+/// Return the parent of `pid`, or 0 if it has no parent.
+pub fn getppid(pid: usize) -> usize {
+    write_to_shared_page(0, GETPPID);
+    write_to_shared_page(1, pid as i32);
+    switch_to_proc_manager();
+
+    read_from_shared_page(2) as usize
+}
+
+/// What the process manager knows about a process
+pub struct ProcessInfo {
+    /// 0 if it has no parent
+    pub parent: usize,
+    pub children: Vec<usize>,
+    /// exited but not waited yet
+    pub zombie: bool,
+    /// wait status, if `zombie`
+    pub exit_code: i32,
+}
+
+/// Return None if `pid` does not exist or has been waited.
+pub fn proc_info(pid: usize) -> Option<ProcessInfo> {
+    write_to_shared_page(0, PROC_INFO);
+    write_to_shared_page(1, pid as i32);
+    switch_to_proc_manager();
+
+    if read_from_shared_page(2) == 0 {
+        return None;
+    }
+    let count = read_from_shared_page(6) as usize;
+    Some(ProcessInfo {
+        parent: read_from_shared_page(3) as usize,
+        children: (0..count)
+            .map(|i| read_from_shared_page(7 + i) as usize)
+            .collect(),
+        zombie: read_from_shared_page(4) != 0,
+        exit_code: read_from_shared_page(5),
+    })
+}
+
+/// Pids of all processes, including zombies
+pub fn list_pids() -> Vec<usize> {
+    write_to_shared_page(0, LIST_PIDS);
+    switch_to_proc_manager();
+
+    let count = read_from_shared_page(1) as usize;
+    (0..count)
+        .map(|i| read_from_shared_page(2 + i) as usize)
+        .collect()
+}
@@ -0,0 +1,423 @@
+//! POSIX-style signals
+//!
+//! A signal sent to a process is pending until one of its threads, with the
+//! signal not blocked, returns to user space. Then either the default action is
+//! taken or the user handler is called, see [`handle_signals`].
+
+use super::*;
+use crate::trap::TrapContext;
+use bitflags::*;
+
+pub const MAX_SIG: usize = 31;
+
+pub const SIGINT: usize = 2;
+pub const SIGQUIT: usize = 3;
+pub const SIGILL: usize = 4;
+pub const SIGTRAP: usize = 5;
+pub const SIGBUS: usize = 7;
+pub const SIGKILL: usize = 9;
+pub const SIGSEGV: usize = 11;
+pub const SIGPIPE: usize = 13;
+pub const SIGCHLD: usize = 17;
+pub const SIGCONT: usize = 18;
+pub const SIGSTOP: usize = 19;
+pub const SIGTSTP: usize = 20;
+pub const SIGTTIN: usize = 21;
+pub const SIGTTOU: usize = 22;
+pub const SIGURG: usize = 23;
+pub const SIGWINCH: usize = 28;
+
+bitflags! {
+    /// A set of signals, bit `n` stands for signal `n`
+    pub struct SignalFlags: u32 {
+        const SIGHUP = 1 << 1;
+        const SIGINT = 1 << 2;
+        const SIGQUIT = 1 << 3;
+        const SIGILL = 1 << 4;
+        const SIGTRAP = 1 << 5;
+        const SIGABRT = 1 << 6;
+        const SIGBUS = 1 << 7;
+        const SIGFPE = 1 << 8;
+        const SIGKILL = 1 << 9;
+        const SIGUSR1 = 1 << 10;
+        const SIGSEGV = 1 << 11;
+        const SIGUSR2 = 1 << 12;
+        const SIGPIPE = 1 << 13;
+        const SIGALRM = 1 << 14;
+        const SIGTERM = 1 << 15;
+        const SIGSTKFLT = 1 << 16;
+        const SIGCHLD = 1 << 17;
+        const SIGCONT = 1 << 18;
+        const SIGSTOP = 1 << 19;
+        const SIGTSTP = 1 << 20;
+        const SIGTTIN = 1 << 21;
+        const SIGTTOU = 1 << 22;
+        const SIGURG = 1 << 23;
+        const SIGXCPU = 1 << 24;
+        const SIGXFSZ = 1 << 25;
+        const SIGVTALRM = 1 << 26;
+        const SIGPROF = 1 << 27;
+        const SIGWINCH = 1 << 28;
+        const SIGIO = 1 << 29;
+        const SIGPWR = 1 << 30;
+        const SIGSYS = 1 << 31;
+    }
+}
+
+impl SignalFlags {
+    /// Return None if `signum` is not a valid signal.
+    pub fn from_signum(signum: usize) -> Option<Self> {
+        if (1..=MAX_SIG).contains(&signum) {
+            Self::from_bits(1 << signum)
+        } else {
+            None
+        }
+    }
+
+    /// Signals that can be neither caught, blocked nor ignored
+    pub fn unmaskable() -> Self {
+        Self::SIGKILL | Self::SIGSTOP
+    }
+}
+
+/// `handler` of the default action
+pub const SIG_DFL: usize = 0;
+/// `handler` to ignore the signal
+pub const SIG_IGN: usize = 1;
+
+/// Action for a signal, shared with user space
+#[derive(Clone, Copy)]
+#[repr(C)]
+pub struct SignalAction {
+    /// `SIG_DFL`, `SIG_IGN` or the entry of `fn(signum, addr)` in user space,
+    /// which must end with `sys_sigreturn`. `addr` is the faulting address for
+    /// SIGSEGV, SIGILL, SIGTRAP and SIGBUS caused by an exception, or 0.
+    pub handler: usize,
+    /// signals blocked while the handler runs
+    pub mask: SignalFlags,
+}
+
+impl Default for SignalAction {
+    fn default() -> Self {
+        Self {
+            handler: SIG_DFL,
+            mask: SignalFlags::empty(),
+        }
+    }
+}
+
+/// Actions of a process, indexed by signum
+#[derive(Clone, Copy)]
+pub struct SignalActions {
+    pub table: [SignalAction; MAX_SIG + 1],
+}
+
+impl Default for SignalActions {
+    fn default() -> Self {
+        Self {
+            table: [SignalAction::default(); MAX_SIG + 1],
+        }
+    }
+}
+
+impl SignalActions {
+    /// Actions after exec: handlers are gone with the old address space, but
+    /// ignored signals stay ignored.
+    pub fn reset_handlers(&mut self) {
+        for action in self.table.iter_mut() {
+            if action.handler != SIG_IGN {
+                *action = SignalAction::default();
+            }
+        }
+    }
+}
+
+/// A signal raised by an exception of a thread
+#[derive(Clone, Copy)]
+pub struct Fault {
+    pub signum: usize,
+    /// faulting address (or instruction)
+    pub addr: usize,
+    /// SIGSEGV for growing the user stack beyond its limit
+    pub stack_overflow: bool,
+}
+
+/// Signal state of a thread
+pub struct TaskSignals {
+    /// signals not delivered to this thread
+    pub mask: SignalFlags,
+    /// the signal whose user handler is running
+    pub handling: Option<usize>,
+    /// trap context interrupted by the user handler, restored by `sys_sigreturn`
+    pub trap_cx_backup: Option<TrapContext>,
+    /// raised by the last exception, delivered before returning to user space
+    pub fault: Option<Fault>,
+}
+
+impl TaskSignals {
+    pub fn new(mask: SignalFlags) -> Self {
+        Self {
+            mask,
+            handling: None,
+            trap_cx_backup: None,
+            fault: None,
+        }
+    }
+}
+
+enum DefaultAction {
+    Terminate,
+    Ignore,
+    Stop,
+    Continue,
+}
+
+fn default_action(signum: usize) -> DefaultAction {
+    match signum {
+        SIGCHLD | SIGURG | SIGWINCH => DefaultAction::Ignore,
+        SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => DefaultAction::Stop,
+        SIGCONT => DefaultAction::Continue,
+        _ => DefaultAction::Terminate,
+    }
+}
+
+/// Whether `signum` terminates the process when it is delivered.
+fn is_fatal(process_inner: &ProcessControlBlockInner, signum: usize) -> bool {
+    signum == SIGKILL
+        || (process_inner.signal_actions.table[signum].handler == SIG_DFL
+            && matches!(default_action(signum), DefaultAction::Terminate))
+}
+
+/// Make `signum` pending for `process`.
+pub fn send_signal(process: &ProcessControlBlock, signum: usize) {
+    let flag = SignalFlags::from_signum(signum).unwrap();
+    let mut inner = process.inner_exclusive_access();
+    let mut continued = false;
+    match signum {
+        // continue at once, even if SIGCONT is blocked or handled
+        SIGCONT => {
+            continued = inner.stopped;
+            inner.stopped = false;
+            inner.pending_signals -= SignalFlags::SIGSTOP
+                | SignalFlags::SIGTSTP
+                | SignalFlags::SIGTTIN
+                | SignalFlags::SIGTTOU;
+        }
+        SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => {
+            inner.pending_signals -= SignalFlags::SIGCONT;
+        }
+        _ => {}
+    }
+    inner.pending_signals |= flag;
+    let fatal = is_fatal(&inner, signum);
+    drop(inner);
+    if continued {
+        service::cont(process.pid);
+    }
+    if fatal {
+        // threads blocked on a lock would never return to user space otherwise
+        interrupt_blocked_tasks(process, |task| {
+            !blocked_signals(task, process).contains(flag)
+        });
+    }
+}
+
+/// Send `signum` to every process in group `pgid`, return false if there is none.
+///
+/// The process manager and the init process are never signaled.
+pub fn send_signal_to_group(pgid: usize, signum: usize) -> bool {
+    let processes: Vec<_> = PID2PROCESS
+        .exclusive_access()
+        .values()
+        .filter(|process| process.pid > 1)
+        .cloned()
+        .collect();
+    let mut found = false;
+    for process in processes {
+        if process.inner_exclusive_access().pgid == pgid {
+            send_signal(&process, signum);
+            found = true;
+        }
+    }
+    found
+}
+
+/// Raise signal `signum` for the exception of current thread at `addr`.
+///
+/// Unlike signals sent by `sys_kill`, it cannot be blocked or ignored, since the
+/// faulting instruction would run again. The process is terminated if there is
+/// no user handler.
+pub fn raise_fault(signum: usize, addr: usize) {
+    let task = current_task().unwrap();
+    task.inner_exclusive_access().signals.fault = Some(Fault {
+        signum,
+        addr,
+        stack_overflow: false,
+    });
+}
+
+/// Raise SIGSEGV for a stack overflow of current thread at `addr`.
+///
+/// It is handled like other faults, but the process terminated by it has
+/// `STACK_OVERFLOW` in its wait status.
+pub fn raise_stack_overflow(addr: usize) {
+    let task = current_task().unwrap();
+    task.inner_exclusive_access().signals.fault = Some(Fault {
+        signum: SIGSEGV,
+        addr,
+        stack_overflow: true,
+    });
+}
+
+/// Whether current thread has a pending signal which is not ignored, so that
+/// a blocking syscall should be interrupted.
+pub fn current_has_deliverable_signal() -> bool {
+    let task = current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    let blocked = blocked_signals(&task, &process);
+    let process_inner = process.inner_exclusive_access();
+    let deliverable = process_inner.pending_signals - blocked;
+    (1..=MAX_SIG).any(|signum| {
+        if !deliverable.contains(SignalFlags::from_signum(signum).unwrap()) {
+            return false;
+        }
+        match process_inner.signal_actions.table[signum].handler {
+            SIG_IGN => false,
+            SIG_DFL => matches!(
+                default_action(signum),
+                DefaultAction::Terminate | DefaultAction::Stop
+            ),
+            _ => true,
+        }
+    })
+}
+
+/// Whether current process is going to exit before current thread returns to
+/// user space: it is killed by the OOM killer, or a signal terminating it is
+/// pending and not blocked by current thread.
+pub fn current_is_killed() -> bool {
+    let task = current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    let blocked = blocked_signals(&task, &process);
+    let process_inner = process.inner_exclusive_access();
+    let deliverable = process_inner.pending_signals - blocked;
+    process_inner.killed
+        || (1..=MAX_SIG).any(|signum| {
+            deliverable.contains(SignalFlags::from_signum(signum).unwrap())
+                && is_fatal(&process_inner, signum)
+        })
+}
+
+/// Signals current thread should not receive now.
+fn blocked_signals(task: &TaskControlBlock, process: &ProcessControlBlock) -> SignalFlags {
+    let task_inner = task.inner_exclusive_access();
+    let mut blocked = task_inner.signals.mask;
+    if let Some(signum) = task_inner.signals.handling {
+        blocked |= process.inner_exclusive_access().signal_actions.table[signum].mask;
+        blocked |= SignalFlags::from_signum(signum).unwrap();
+    }
+    blocked - SignalFlags::unmaskable()
+}
+
+/// Deliver pending signals to current thread, called before returning to user space.
+///
+/// A stopped process yields here until it is continued or killed.
+pub fn handle_signals() {
+    loop {
+        deliver_pending_signals();
+        let stopped = current_process().inner_exclusive_access().stopped;
+        if !stopped {
+            break;
+        }
+        suspend_current_and_run_next();
+        exit_current_if_killed();
+    }
+}
+
+fn deliver_pending_signals() {
+    let task = current_task().unwrap();
+    let process = task.process.upgrade().unwrap();
+    let blocked = blocked_signals(&task, &process);
+    // no nested user handlers, there is only one backup of trap context
+    let handling = task.inner_exclusive_access().signals.handling.is_some();
+    let fault = task.inner_exclusive_access().signals.fault.take();
+    if let Some(Fault {
+        signum,
+        addr,
+        stack_overflow,
+    }) = fault
+    {
+        let handler = process.inner_exclusive_access().signal_actions.table[signum].handler;
+        let flag = SignalFlags::from_signum(signum).unwrap();
+        if handler == SIG_DFL || handler == SIG_IGN || blocked.contains(flag) || handling {
+            drop(process);
+            drop(task);
+            terminate_current_process(signum, stack_overflow);
+        }
+        call_user_handler(&task, signum, handler, addr);
+        return;
+    }
+    for signum in 1..=MAX_SIG {
+        let flag = SignalFlags::from_signum(signum).unwrap();
+        let mut process_inner = process.inner_exclusive_access();
+        if !process_inner.pending_signals.contains(flag) || blocked.contains(flag) {
+            continue;
+        }
+        let handler = match signum {
+            SIGKILL | SIGSTOP => SIG_DFL,
+            _ => process_inner.signal_actions.table[signum].handler,
+        };
+        if handling && handler != SIG_DFL && handler != SIG_IGN {
+            continue;
+        }
+        process_inner.pending_signals -= flag;
+        match handler {
+            SIG_DFL => match default_action(signum) {
+                DefaultAction::Terminate => {
+                    drop(process_inner);
+                    drop(process);
+                    drop(task);
+                    terminate_current_process(signum, false);
+                }
+                DefaultAction::Stop => {
+                    if !process_inner.stopped {
+                        process_inner.stopped = true;
+                        drop(process_inner);
+                        // so that the parent can find it by waitpid
+                        service::stop(process.pid, signum);
+                    }
+                }
+                DefaultAction::Continue => process_inner.stopped = false,
+                DefaultAction::Ignore => {}
+            },
+            SIG_IGN => {}
+            _ => {
+                drop(process_inner);
+                call_user_handler(&task, signum, handler, 0);
+                // one handler at a time
+                return;
+            }
+        }
+    }
+}
+
+/// Run `handler(signum, addr)` when `task` returns to user space, it is resumed by `sys_sigreturn`.
+fn call_user_handler(task: &TaskControlBlock, signum: usize, handler: usize, addr: usize) {
+    let mut task_inner = task.inner_exclusive_access();
+    let trap_cx = task_inner.get_trap_cx();
+    task_inner.signals.trap_cx_backup = Some(*trap_cx);
+    task_inner.signals.handling = Some(signum);
+    trap_cx.sepc = handler;
+    trap_cx.x[10] = signum;
+    trap_cx.x[11] = addr;
+}
+
+fn terminate_current_process(signum: usize, stack_overflow: bool) -> ! {
+    crate::info!("[kernel] Killed by signal {}.", signum);
+    let mut status = signaled_status(signum);
+    if stack_overflow {
+        status |= STACK_OVERFLOW;
+    }
+    exit_current_process_and_run_next(status);
+    unreachable!();
+}
@@ -1,17 +1,18 @@
-use super::TaskContext;
-use crate::config::TRAP_CONTEXT;
+use super::id::TaskUserRes;
+use super::signal::{SignalFlags, TaskSignals};
+use super::{ProcessControlBlock, TaskContext};
 use crate::mm::address::*;
-use crate::mm::memory_set::MemorySet;
-use crate::mm::KERNEL_SPACE;
+use crate::mm::{OutOfMemory, KERNEL_SPACE};
 use crate::task::kernel_stack::KernelStack;
 use crate::trap::*;
 use crate::UPSafeCell;
-use alloc::sync::Arc;
+use alloc::sync::{Arc, Weak};
 use core::cell::RefMut;
 
+/// Thread Control Block
 pub struct TaskControlBlock {
     // immutable
-    pub pid: usize,
+    pub process: Weak<ProcessControlBlock>,
     pub kernel_stack: KernelStack,
     // mutable
     inner: UPSafeCell<TaskControlBlockInner>,
@@ -19,61 +20,57 @@ pub struct TaskControlBlock {
 
 impl TaskControlBlock {
     pub fn inner_exclusive_access(&self) -> RefMut<'_, TaskControlBlockInner> {
-        match self.try_borrow_mut() {
-            Ok(task_inner) => {
-                drop(task_inner);
-            }
-            Err(_) => {
-                panic!("TCB inner is already borrowed for pid {}!", self.pid);
-            }
-        }
         self.inner.exclusive_access()
     }
 
-    fn try_borrow_mut(
-        &self,
-    ) -> Result<RefMut<'_, TaskControlBlockInner>, core::cell::BorrowMutError> {
-        self.inner.try_borrow_mut()
-    }
-
-    pub fn new(elf_data: &[u8], pre_alloc_pid: usize) -> Self {
-        // 从elf文件中解析出内存布局
-        let (memory_set, user_sp, entry_point) = MemorySet::new_from_elf(elf_data);
-        // 获取trap_context的物理页号
-        let trap_cx_ppn = memory_set
-            .translate_to_ppn(VirtAddr::from(TRAP_CONTEXT).into())
-            .unwrap();
-        // 分配 pid 和 kernel stack
-        let pid = pre_alloc_pid;
-        let kernel_stack = KernelStack::new(pid);
+    /// Create a thread `tid` of `process`, its trap context is not initialized.
+    pub fn new(process: &Arc<ProcessControlBlock>, tid: usize) -> Result<Self, OutOfMemory> {
+        let res = TaskUserRes::new(process, tid)?;
+        let trap_cx_ppn = res.trap_cx_ppn();
+        let kernel_stack = KernelStack::new()?;
         let kernel_stack_top = kernel_stack.get_top();
         // push a task context which goes to trap_return to the top of kernel stack
-        let task_control_block = Self {
-            pid,
+        Ok(Self {
+            process: Arc::downgrade(process),
             kernel_stack,
             inner: UPSafeCell::new(TaskControlBlockInner {
+                res: Some(res),
                 trap_cx_ppn,
                 task_cx: TaskContext::new(trap_return as usize, kernel_stack_top),
-                memory_set,
+                exit_code: None,
+                signals: TaskSignals::new(SignalFlags::empty()),
             }),
-        };
-        // prepare Trap Context in user space
-        let trap_cx = task_control_block.inner_exclusive_access().get_trap_cx();
+        })
+    }
+
+    /// Initialize trap context to run from `entry` with stack pointer `sp`.
+    pub fn init_trap_cx(&self, entry: usize, sp: usize) -> &'static mut TrapContext {
+        let trap_cx = self.inner_exclusive_access().get_trap_cx();
         *trap_cx = TrapContext::app_init_context(
-            entry_point,
-            user_sp,
+            entry,
+            sp,
             KERNEL_SPACE.exclusive_access().satp_token(),
-            kernel_stack_top,
+            self.kernel_stack.get_top(),
             trap_handler as usize,
         );
-        task_control_block
+        trap_cx
+    }
+
+    pub fn get_user_token(&self) -> usize {
+        let process = self.process.upgrade().unwrap();
+        let token = process.inner_exclusive_access().get_user_token();
+        token
     }
 }
 
 pub struct TaskControlBlockInner {
-    pub task_cx: TaskContext,
-    pub memory_set: MemorySet,
+    /// released when the thread exits
+    pub res: Option<TaskUserRes>,
     pub trap_cx_ppn: PhysPageNum,
+    pub task_cx: TaskContext,
+    /// Some after the thread exits, until it is waited
+    pub exit_code: Option<i32>,
+    pub signals: TaskSignals,
 }
 
 impl TaskControlBlockInner {
@@ -81,67 +78,11 @@ impl TaskControlBlockInner {
         self.trap_cx_ppn.get_mut()
     }
 
-    pub fn get_user_token(&self) -> usize {
-        self.memory_set.satp_token()
-    }
-
     pub fn get_task_cx_ptr(&mut self) -> *mut TaskContext {
         &mut self.task_cx
     }
-}
 
-impl TaskControlBlock {
-    pub fn fork(self: &Arc<TaskControlBlock>, new_pid: usize) -> Arc<TaskControlBlock> {
-        // ---- access parent PCB exclusively
-        let parent_inner = self.inner_exclusive_access();
-        // copy user space (include trap context)
-        let memory_set = MemorySet::from_existed_user(&parent_inner.memory_set);
-        drop(parent_inner);
-        let trap_cx_ppn = memory_set
-            .translate_to_ppn(VirtAddr::from(TRAP_CONTEXT).into())
-            .unwrap();
-
-        // alloc kernel stack in kernel space
-        let kernel_stack = KernelStack::new(new_pid);
-        let kernel_stack_top = kernel_stack.get_top();
-        let new_tcb = Arc::new(TaskControlBlock {
-            pid: new_pid,
-            kernel_stack,
-            inner: UPSafeCell::new(TaskControlBlockInner {
-                trap_cx_ppn,
-                task_cx: TaskContext::new(trap_return as usize, kernel_stack_top),
-                memory_set,
-            }),
-        });
-        // modify kernel_sp in new trap_cx
-        // ---- access children PCB exclusively
-        let trap_cx = new_tcb.inner_exclusive_access().get_trap_cx();
-        trap_cx.kernel_sp = kernel_stack_top;
-        // return
-        new_tcb
-    }
-
-    pub fn exec(&self, elf_data: &[u8]) {
-        // memory_set with elf program headers/trampoline/trap context/user stack
-        let (memory_set, user_sp, entry_point) = MemorySet::new_from_elf(elf_data);
-        let trap_cx_ppn = memory_set
-            .translate_to_ppn(VirtAddr::from(TRAP_CONTEXT).into())
-            .unwrap();
-        // ---- access inner exclusively
-        let mut inner = self.inner_exclusive_access();
-        // substitute memory_set
-        inner.memory_set = memory_set;
-        // update trap_cx ppn
-        inner.trap_cx_ppn = trap_cx_ppn;
-        // initialize trap_cx
-        let trap_cx = inner.get_trap_cx();
-        *trap_cx = TrapContext::app_init_context(
-            entry_point,
-            user_sp,
-            KERNEL_SPACE.exclusive_access().satp_token(),
-            self.kernel_stack.get_top(),
-            trap_handler as usize,
-        );
-        // ---- stop exclusively accessing inner automatically
+    pub fn tid(&self) -> usize {
+        self.res.as_ref().unwrap().tid
     }
 }
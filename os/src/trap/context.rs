@@ -2,6 +2,7 @@
 
 use riscv::register::sstatus::{self, Sstatus, SPP};
 
+#[derive(Clone, Copy)]
 #[repr(C)]
 pub struct TrapContext {
     /// general registers
@@ -8,7 +8,12 @@ pub mod context;
 
 use crate::asm;
 use crate::config::*;
+use crate::console::poll_console_input;
+use crate::mm::memory_set::PageFaultError;
 use crate::syscall::syscall;
+use crate::task::signal::{
+    handle_signals, raise_fault, raise_stack_overflow, SIGBUS, SIGILL, SIGSEGV, SIGTRAP,
+};
 use crate::task::*;
 use crate::warn;
 pub use context::TrapContext;
@@ -22,6 +27,9 @@ use switch::check_proc_manager_service;
 
 global_asm!(include_str!("trampoline.s"));
 
+/// `scause` of a misaligned load
+const LOAD_MISALIGNED: usize = 4;
+
 extern "C" {
     fn __save_trap_ctx();
     fn __restore_ctx();
@@ -62,23 +70,77 @@ pub fn trap_handler() -> ! {
             let mut cx = current_trap_cx();
             cx.sepc += 4;
             // get system call return value
-            let result = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]);
+            let result = syscall(
+                cx.x[17],
+                [cx.x[10], cx.x[11], cx.x[12], cx.x[13], cx.x[14], cx.x[15]],
+            );
             // cx is changed during sys_exec, so we have to call it again
             cx = current_trap_cx();
             cx.x[10] = result as usize;
         }
+        Trap::Exception(Exception::StorePageFault)
+        | Trap::Exception(Exception::LoadPageFault)
+        | Trap::Exception(Exception::InstructionPageFault) => {
+            match handle_user_page_fault(stval) {
+                Ok(()) => {}
+                // the faulting instruction is retried when the task runs again
+                Err(PageFaultError::OutOfMemory) => handle_out_of_memory(),
+                Err(PageFaultError::InvalidAddress) => {
+                    warn!("[kernel] PageFault in application, bad addr = {:#x}, bad instruction = {:#x}.", stval, current_trap_cx().sepc);
+                    raise_fault(SIGSEGV, stval);
+                }
+                // SIGSEGV as on Linux, told from a bad address by the wait status
+                Err(PageFaultError::StackOverflow) => {
+                    warn!("[kernel] Stack overflow in application, bad addr = {:#x}, bad instruction = {:#x}.", stval, current_trap_cx().sepc);
+                    raise_stack_overflow(stval);
+                }
+            }
+        }
         Trap::Exception(Exception::StoreFault)
-        | Trap::Exception(Exception::StorePageFault)
         | Trap::Exception(Exception::LoadFault)
-        | Trap::Exception(Exception::LoadPageFault) => {
-            warn!("[kernel] PageFault in application, bad addr = {:#x}, bad instruction = {:#x}, kernel killed it.", stval, current_trap_cx().sepc);
-            // page fault exit code = -2
-            exit_current_and_run_next(-2);
+        | Trap::Exception(Exception::InstructionFault) => {
+            warn!(
+                "[kernel] PageFault in application, bad addr = {:#x}, bad instruction = {:#x}.",
+                stval,
+                current_trap_cx().sepc
+            );
+            raise_fault(SIGSEGV, stval);
+        }
+        Trap::Exception(Exception::StoreMisaligned)
+        | Trap::Exception(Exception::InstructionMisaligned) => {
+            warn!(
+                "[kernel] Misaligned access in application, bad addr = {:#x}, bad instruction = {:#x}.",
+                stval,
+                current_trap_cx().sepc
+            );
+            raise_fault(SIGBUS, stval);
+        }
+        // the riscv crate does not know load address misaligned
+        Trap::Exception(Exception::Unknown) if scause.code() == LOAD_MISALIGNED => {
+            warn!(
+                "[kernel] Misaligned access in application, bad addr = {:#x}, bad instruction = {:#x}.",
+                stval,
+                current_trap_cx().sepc
+            );
+            raise_fault(SIGBUS, stval);
         }
         Trap::Exception(Exception::IllegalInstruction) => {
-            warn!("[kernel] IllegalInstruction in application, kernel killed it.");
-            // illegal instruction exit code = -3
-            exit_current_and_run_next(-3);
+            warn!(
+                "[kernel] IllegalInstruction in application, bad instruction = {:#x}.",
+                current_trap_cx().sepc
+            );
+            raise_fault(SIGILL, current_trap_cx().sepc);
+        }
+        Trap::Exception(Exception::Breakpoint) => {
+            raise_fault(SIGTRAP, current_trap_cx().sepc);
+        }
+        Trap::Exception(_) => {
+            warn!(
+                "[kernel] Unsupported exception {} in application, bad instruction = {:#x}.",
+                scause.code(),
+                current_trap_cx().sepc
+            );
+            raise_fault(SIGILL, current_trap_cx().sepc);
         }
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
             panic!("A strange supervisor timer interrupt occurs! Are you using rustsbi?");
@@ -92,6 +154,7 @@ pub fn trap_handler() -> ! {
 
             // if waiting process manager, ignore timer interrupt
             if !check_proc_manager_service() {
+                poll_console_input();
                 suspend_current_and_run_next();
             }
         }
@@ -103,15 +166,25 @@ pub fn trap_handler() -> ! {
             );
         }
     }
+    exit_current_if_killed();
+    handle_signals();
     trap_return()
 }
 
+/// Try to resolve a page fault of current task (e.g. swapped out page).
+fn handle_user_page_fault(stval: usize) -> Result<(), PageFaultError> {
+    current_process()
+        .inner_exclusive_access()
+        .memory_set
+        .handle_page_fault(stval.into())
+}
+
 /// jump to `__restore_ctx` while passing `trap_cx_ptr` and `user_satp`
 pub fn trap_return() -> ! {
     // reset stvec to __save_trap_ctx
     set_user_trap_entry();
     // prepare for __restore_ctx
-    let trap_cx_ptr = TRAP_CONTEXT;
+    let trap_cx_ptr = current_trap_cx_user_va();
     let user_satp = current_user_token();
     // crate::debug!("user token: {:#x}", user_satp);
     // compute the virtual address of __restore_ctx
@@ -14,7 +14,7 @@
 __save_trap_ctx:
     # swap sp, sscratch
     # sscratch is set to where trap_ctx will be saved in user space 
-    # (actually on the second highest page, just below the trampoline page)
+    # (a page below the trampoline page, one for each thread)
     csrrw sp, sscratch, sp
     # now sp -> trap_ctx, sscratch -> user stack
     # save general-purpose registers
@@ -56,10 +56,10 @@ __save_trap_ctx:
     # which does not work in this case
 
     # no more input argument needs to be set
-    # trap_ctx is always saved at same place in user space
+    # trap_ctx of each thread is always saved at same place in user space
 
 # execute after trap_handler returns
-# a0: *trap_ctx in user space (constant)
+# a0: *trap_ctx in user space (constant for each thread)
 # a1: user space token
 __restore_ctx:
     # switch to user space
@@ -0,0 +1,638 @@
+//! Check a FAT32 disk image on the host, such as `os/fs.img`
+//!
+//! Usage: `fsck [--replay] <image>`
+//!
+//! The image may be a bare volume or have an MBR partition table, like the
+//! kernel accepts. An operation committed to the log of the kernel but not
+//! installed, after a crash, is applied in memory before checking, as the
+//! kernel does at mount, and written to the image with `--replay`. Chains in
+//! the orphan list of the kernel are not lost, it frees them at mount.
+//!
+//! It checks that both FATs are the same, that every chain is valid and used
+//! once, by a single file, with as many clusters as its size needs, that
+//! directories have valid entries, `.` and `..` and no duplicate names, and
+//! that no cluster is lost. Problems are printed with the path of the file,
+//! and the exit status is 1 if there are any. The FSInfo free count is only a
+//! hint, a wrong one is a warning.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::env;
+use std::fs;
+use std::process::ExitCode;
+
+const SECTOR_SIZE: usize = 512;
+const ENTRY_SIZE: usize = 32;
+
+const FAT_MASK: u32 = 0x0fff_ffff;
+const BAD_CLUSTER: u32 = 0x0fff_fff7;
+const END_OF_CHAIN: u32 = 0x0fff_fff8;
+
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_LONG_NAME: u8 = 0x0f;
+const DELETED: u8 = 0xe5;
+const END: u8 = 0x00;
+const LAST_LONG_ENTRY: u8 = 0x40;
+const LONG_NAME_UNITS: [usize; 13] = [1, 3, 5, 7, 9, 14, 16, 18, 20, 22, 24, 28, 30];
+
+const FSINFO_LEAD: u32 = 0x4161_5252;
+const FSINFO_STRUCT: u32 = 0x6141_7272;
+const FSINFO_UNKNOWN: u32 = 0xffff_ffff;
+
+const MBR_FAT32: [u8; 3] = [0x0b, 0x0c, 0x1c];
+
+// the log of the kernel, see `os/src/fs/fat32/journal.rs`
+const LOG_START: usize = 16;
+const LOG_MAGIC: &[u8; 8] = b"ACORELOG";
+const ORPHAN_MAGIC: &[u8; 8] = b"ACOREORP";
+
+fn read_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+struct Volume<'a> {
+    /// the volume, without what comes before a partition
+    data: &'a [u8],
+    sectors_per_cluster: usize,
+    fat_start: usize,
+    fat_sectors: usize,
+    num_fats: usize,
+    data_start: usize,
+    cluster_count: usize,
+    root_cluster: u32,
+    fsinfo: Option<usize>,
+}
+
+impl Volume<'_> {
+    fn cluster_size(&self) -> usize {
+        self.sectors_per_cluster * SECTOR_SIZE
+    }
+
+    fn is_cluster(&self, cluster: u32) -> bool {
+        cluster >= 2 && (cluster as usize) < self.cluster_count + 2
+    }
+
+    fn fat_entry(&self, fat: usize, cluster: u32) -> u32 {
+        let offset = (self.fat_start + fat * self.fat_sectors) * SECTOR_SIZE + cluster as usize * 4;
+        read_u32(self.data, offset) & FAT_MASK
+    }
+
+    fn cluster_data(&self, cluster: u32) -> &[u8] {
+        let start =
+            (self.data_start + (cluster as usize - 2) * self.sectors_per_cluster) * SECTOR_SIZE;
+        &self.data[start..start + self.cluster_size()]
+    }
+}
+
+/// Offset of the FAT32 volume in `image`
+fn find_volume(image: &[u8]) -> Result<usize, String> {
+    if image.len() < SECTOR_SIZE {
+        return Err(String::from("image smaller than a sector"));
+    }
+    if is_boot_sector(&image[..SECTOR_SIZE]) {
+        return Ok(0);
+    }
+    if image[510..512] != [0x55, 0xaa] {
+        return Err(String::from("no FAT32 boot sector or partition table"));
+    }
+    let partition = (0..4)
+        .map(|i| &image[446 + i * 16..446 + (i + 1) * 16])
+        .find(|entry| MBR_FAT32.contains(&entry[4]))
+        .ok_or("no FAT32 partition")?;
+    let start = read_u32(partition, 8) as usize * SECTOR_SIZE;
+    if start + SECTOR_SIZE > image.len() || !is_boot_sector(&image[start..start + SECTOR_SIZE]) {
+        return Err(String::from("no FAT32 boot sector in the partition"));
+    }
+    Ok(start)
+}
+
+fn is_boot_sector(sector: &[u8]) -> bool {
+    sector[510..512] == [0x55, 0xaa]
+        && read_u16(sector, 11) as usize == SECTOR_SIZE
+        && read_u16(sector, 17) == 0
+        && read_u16(sector, 22) == 0
+        && read_u32(sector, 36) != 0
+}
+
+fn parse_volume(data: &[u8]) -> Result<Volume<'_>, String> {
+    let sectors_per_cluster = data[13] as usize;
+    let reserved_sectors = read_u16(data, 14) as usize;
+    let num_fats = data[16] as usize;
+    let total_sectors = read_u32(data, 32) as usize;
+    let fat_sectors = read_u32(data, 36) as usize;
+    let root_cluster = read_u32(data, 44);
+    let fsinfo = read_u16(data, 48) as usize;
+    if !sectors_per_cluster.is_power_of_two() || num_fats == 0 || reserved_sectors == 0 {
+        return Err(String::from("invalid boot sector"));
+    }
+    if total_sectors * SECTOR_SIZE > data.len() {
+        return Err(format!(
+            "{} sectors in the boot sector, but the image has {}",
+            total_sectors,
+            data.len() / SECTOR_SIZE
+        ));
+    }
+    let fat_start = reserved_sectors;
+    let data_start = fat_start + num_fats * fat_sectors;
+    if data_start >= total_sectors {
+        return Err(String::from("FATs larger than the volume"));
+    }
+    let cluster_count =
+        ((total_sectors - data_start) / sectors_per_cluster).min(fat_sectors * SECTOR_SIZE / 4 - 2);
+    let fsinfo = (fsinfo != 0 && fsinfo < reserved_sectors).then_some(fsinfo);
+    let volume = Volume {
+        data,
+        sectors_per_cluster,
+        fat_start,
+        fat_sectors,
+        num_fats,
+        data_start,
+        cluster_count,
+        root_cluster,
+        fsinfo,
+    };
+    if !volume.is_cluster(root_cluster) {
+        return Err(format!("invalid root cluster {}", root_cluster));
+    }
+    Ok(volume)
+}
+
+/// Sectors in the log, 0 without one
+fn log_capacity(data: &[u8]) -> usize {
+    let reserved_sectors = read_u16(data, 14) as usize;
+    reserved_sectors
+        .saturating_sub(LOG_START + 2)
+        .min((SECTOR_SIZE - 16) / 4)
+}
+
+/// First clusters of the chains in the orphan list, after the log
+fn orphans(data: &[u8]) -> Vec<u32> {
+    let capacity = log_capacity(data);
+    if capacity == 0 {
+        return Vec::new();
+    }
+    let sector = (LOG_START + 1 + capacity) * SECTOR_SIZE;
+    let count = read_u32(data, sector + 8) as usize;
+    if &data[sector..sector + 8] != ORPHAN_MAGIC || count > (SECTOR_SIZE - 16) / 4 {
+        return Vec::new();
+    }
+    (0..count)
+        .map(|i| read_u32(data, sector + 16 + i * 4))
+        .collect()
+}
+
+/// Apply an operation left in the log to `data`, return the number of sectors.
+///
+/// A log with a home sector in the log or past the volume is an error, and is
+/// not applied, as the kernel does.
+fn replay_log(data: &mut [u8]) -> Result<usize, String> {
+    let capacity = match log_capacity(data) {
+        0 => return Ok(0),
+        capacity => capacity,
+    };
+    let header = LOG_START * SECTOR_SIZE;
+    if &data[header..header + 8] != LOG_MAGIC {
+        return Ok(0);
+    }
+    let count = read_u32(data, header + 8) as usize;
+    if count == 0 || count > capacity {
+        return Ok(0);
+    }
+    let volume_sectors = (data.len() / SECTOR_SIZE).min(read_u32(data, 32) as usize);
+    let homes: Vec<usize> = (0..count)
+        .map(|i| read_u32(data, header + 16 + i * 4) as usize)
+        .collect();
+    let log_end = LOG_START + 1 + capacity;
+    if let Some(home) = homes
+        .iter()
+        .find(|&&home| (LOG_START..log_end).contains(&home) || home >= volume_sectors)
+    {
+        return Err(format!("log: invalid home sector {}, not replayed", home));
+    }
+    for (i, home) in homes.into_iter().enumerate() {
+        let log = header + (1 + i) * SECTOR_SIZE;
+        data.copy_within(log..log + SECTOR_SIZE, home * SECTOR_SIZE);
+    }
+    data[header + 8..header + 12].fill(0);
+    Ok(count)
+}
+
+/// A file found in a directory
+struct Item {
+    name: String,
+    short_name: [u8; 11],
+    attr: u8,
+    first_cluster: u32,
+    size: u32,
+}
+
+impl Item {
+    fn is_dir(&self) -> bool {
+        self.attr & ATTR_DIRECTORY != 0
+    }
+}
+
+fn checksum(name: &[u8; 11]) -> u8 {
+    name.iter()
+        .fold(0u8, |sum, &c| sum.rotate_right(1).wrapping_add(c))
+}
+
+fn short_display_name(name: &[u8; 11], case: u8) -> String {
+    let part = |bytes: &[u8], lower: bool| {
+        let text = String::from_utf8_lossy(bytes).trim_end().to_string();
+        if lower {
+            text.to_ascii_lowercase()
+        } else {
+            text
+        }
+    };
+    let mut display = part(&name[..8], case & 0x08 != 0);
+    let ext = part(&name[8..], case & 0x10 != 0);
+    if !ext.is_empty() {
+        display.push('.');
+        display.push_str(&ext);
+    }
+    display
+}
+
+/// Parse the entries of a directory, report broken long names to `problems`.
+fn parse_dir(data: &[u8], path: &str, problems: &mut Vec<String>) -> Vec<Item> {
+    let mut items = Vec::new();
+    // long name being read: (checksum, next ord, units)
+    let mut long: Option<(u8, u8, Vec<u16>)> = None;
+    let mut orphans = 0;
+    for raw in data.chunks_exact(ENTRY_SIZE) {
+        if raw[0] == END {
+            break;
+        }
+        if raw[0] == DELETED {
+            orphans += long.take().is_some() as usize;
+            continue;
+        }
+        if raw[11] & 0x3f == ATTR_LONG_NAME {
+            let ord = raw[0] & !LAST_LONG_ENTRY;
+            let units: Vec<u16> = LONG_NAME_UNITS
+                .iter()
+                .map(|&offset| read_u16(raw, offset))
+                .collect();
+            long = match long.take() {
+                previous if raw[0] & LAST_LONG_ENTRY != 0 && ord > 0 => {
+                    orphans += previous.is_some() as usize;
+                    Some((raw[13], ord - 1, units))
+                }
+                Some((sum, next, mut parts)) if ord == next && ord > 0 && sum == raw[13] => {
+                    let mut all = units;
+                    all.append(&mut parts);
+                    Some((sum, ord - 1, all))
+                }
+                previous => {
+                    orphans += 1 + previous.is_some() as usize;
+                    None
+                }
+            };
+            continue;
+        }
+        let short_name: [u8; 11] = raw[..11].try_into().unwrap();
+        let long = long.take();
+        if raw[11] & ATTR_VOLUME_ID != 0 {
+            orphans += long.is_some() as usize;
+            continue;
+        }
+        let name = match long {
+            Some((sum, 0, units)) if sum == checksum(&short_name) => {
+                let len = units
+                    .iter()
+                    .position(|&unit| unit == 0)
+                    .unwrap_or(units.len());
+                char::decode_utf16(units[..len].iter().copied())
+                    .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                    .collect()
+            }
+            other => {
+                orphans += other.is_some() as usize;
+                short_display_name(&short_name, raw[12])
+            }
+        };
+        items.push(Item {
+            name,
+            short_name,
+            attr: raw[11],
+            first_cluster: ((read_u16(raw, 20) as u32) << 16) | read_u16(raw, 26) as u32,
+            size: read_u32(raw, 28),
+        });
+    }
+    if orphans > 0 {
+        problems.push(format!(
+            "{}: {} long names without a valid short entry",
+            path, orphans
+        ));
+    }
+    items
+}
+
+struct Checker<'a> {
+    volume: Volume<'a>,
+    /// path of the file using each cluster
+    owners: Vec<Option<String>>,
+    problems: Vec<String>,
+    warnings: Vec<String>,
+    files: usize,
+    dirs: usize,
+}
+
+impl Checker<'_> {
+    /// Follow the chain from `first` for `path`, return its clusters up to a problem.
+    fn chain(&mut self, first: u32, path: &str) -> Vec<u32> {
+        let mut chain = Vec::new();
+        let mut cluster = first;
+        loop {
+            if !self.volume.is_cluster(cluster) {
+                self.problems.push(format!(
+                    "{}: invalid cluster {} in its chain",
+                    path, cluster
+                ));
+                break;
+            }
+            if let Some(owner) = &self.owners[cluster as usize] {
+                let problem = if owner == path {
+                    format!("{}: its chain loops at cluster {}", path, cluster)
+                } else {
+                    format!("{}: cluster {} is also used by {}", path, cluster, owner)
+                };
+                self.problems.push(problem);
+                break;
+            }
+            self.owners[cluster as usize] = Some(path.to_string());
+            chain.push(cluster);
+            match self.volume.fat_entry(0, cluster) {
+                0 => {
+                    self.problems.push(format!(
+                        "{}: its chain goes to free cluster {}",
+                        path, cluster
+                    ));
+                    break;
+                }
+                next if next >= END_OF_CHAIN => break,
+                BAD_CLUSTER => {
+                    self.problems.push(format!(
+                        "{}: its chain goes to bad cluster {}",
+                        path, cluster
+                    ));
+                    break;
+                }
+                next => cluster = next,
+            }
+        }
+        chain
+    }
+
+    fn dir_data(&self, chain: &[u32]) -> Vec<u8> {
+        chain
+            .iter()
+            .flat_map(|&cluster| self.volume.cluster_data(cluster).iter().copied())
+            .collect()
+    }
+
+    /// Check the tree from the root directory.
+    fn check_tree(&mut self) {
+        // (path, first cluster, cluster of the parent in `..`, or None for the root)
+        let mut queue = VecDeque::new();
+        queue.push_back((String::from("/"), self.volume.root_cluster, None));
+        while let Some((path, first, parent)) = queue.pop_front() {
+            self.dirs += 1;
+            let chain = self.chain(first, &path);
+            let data = self.dir_data(&chain);
+            let items = parse_dir(&data, &path, &mut self.problems);
+            // lower-case long and short names, to the index of their item
+            let mut names: BTreeMap<String, usize> = BTreeMap::new();
+            for (i, item) in items.iter().enumerate() {
+                let child = if path == "/" {
+                    format!("/{}", item.name)
+                } else {
+                    format!("{}/{}", path, item.name)
+                };
+                if item.name == "." || item.name == ".." {
+                    let (index, expected) = if item.name == "." {
+                        (0, Some(first))
+                    } else {
+                        (1, parent)
+                    };
+                    match expected {
+                        None => self.problems.push(format!("{}: `{}` in the root", path, item.name)),
+                        Some(expected) if i != index || item.first_cluster != expected => {
+                            self.problems.push(format!(
+                                "{}: `{}` is entry {} with cluster {}, not entry {} with cluster {}",
+                                path, item.name, i, item.first_cluster, index, expected
+                            ))
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                for name in [
+                    item.name.to_lowercase(),
+                    short_display_name(&item.short_name, 0).to_lowercase(),
+                ] {
+                    if let Some(other) = names.insert(name, i) {
+                        if other != i {
+                            self.problems
+                                .push(format!("{}: same name as {}", child, items[other].name));
+                        }
+                    }
+                }
+                if item.name.is_empty() || item.name.contains('/') {
+                    self.problems
+                        .push(format!("{}: invalid name {:?}", path, item.name));
+                    continue;
+                }
+                if item.is_dir() {
+                    if item.first_cluster == 0 {
+                        self.problems
+                            .push(format!("{}: directory without cluster", child));
+                        continue;
+                    }
+                    // `..` of a subdirectory of the root is 0
+                    let dotdot = if parent.is_none() { 0 } else { first };
+                    queue.push_back((child, item.first_cluster, Some(dotdot)));
+                } else {
+                    self.check_file(&child, item);
+                }
+            }
+            if parent.is_some() && items.len() < 2 {
+                self.problems.push(format!("{}: no `.` and `..`", path));
+            }
+        }
+    }
+
+    fn check_file(&mut self, path: &str, item: &Item) {
+        self.files += 1;
+        let needed = (item.size as usize).div_ceil(self.volume.cluster_size());
+        let clusters = if item.first_cluster == 0 {
+            0
+        } else {
+            self.chain(item.first_cluster, path).len()
+        };
+        if clusters != needed {
+            self.problems.push(format!(
+                "{}: size {} needs {} clusters, its chain has {}",
+                path, item.size, needed, clusters
+            ));
+        }
+    }
+
+    fn check_fats(&mut self) {
+        for fat in 1..self.volume.num_fats {
+            let differ = (2..self.volume.cluster_count as u32 + 2)
+                .filter(|&cluster| {
+                    self.volume.fat_entry(fat, cluster) != self.volume.fat_entry(0, cluster)
+                })
+                .count();
+            if differ > 0 {
+                self.problems.push(format!(
+                    "FAT {} differs from FAT 1 in {} entries",
+                    fat + 1,
+                    differ
+                ));
+            }
+        }
+    }
+
+    /// Follow the chains of the orphan list, return the number of clusters.
+    fn check_orphans(&mut self, orphans: &[u32]) -> usize {
+        orphans
+            .iter()
+            .map(|&first| self.chain(first, &format!("orphan {}", first)).len())
+            .sum()
+    }
+
+    /// Clusters allocated but not used by any file
+    fn check_lost(&mut self) -> usize {
+        let mut lost = 0;
+        let mut used = 0;
+        for cluster in 2..self.volume.cluster_count as u32 + 2 {
+            let entry = self.volume.fat_entry(0, cluster);
+            if entry == 0 || entry == BAD_CLUSTER {
+                continue;
+            }
+            used += 1;
+            if self.owners[cluster as usize].is_none() {
+                lost += 1;
+            }
+        }
+        if lost > 0 {
+            self.problems.push(format!(
+                "{} clusters allocated but not used by any file",
+                lost
+            ));
+        }
+        used
+    }
+
+    fn check_fsinfo(&mut self, free: usize) {
+        let sector = match self.volume.fsinfo {
+            Some(sector) => &self.volume.data[sector * SECTOR_SIZE..(sector + 1) * SECTOR_SIZE],
+            None => return,
+        };
+        if read_u32(sector, 0) != FSINFO_LEAD || read_u32(sector, 484) != FSINFO_STRUCT {
+            self.warnings.push(String::from("invalid FSInfo sector"));
+            return;
+        }
+        let count = read_u32(sector, 488);
+        if count != FSINFO_UNKNOWN && count as usize != free {
+            self.warnings.push(format!(
+                "FSInfo free count is {}, but {} clusters are free",
+                count, free
+            ));
+        }
+    }
+}
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (replay, path) = match args.as_slice() {
+        [path] => (false, path),
+        [flag, path] if flag == "--replay" => (true, path),
+        _ => {
+            eprintln!("usage: fsck [--replay] <image>");
+            return ExitCode::from(2);
+        }
+    };
+    let mut image = match fs::read(path) {
+        Ok(image) => image,
+        Err(err) => {
+            eprintln!("fsck: {}: {}", path, err);
+            return ExitCode::from(2);
+        }
+    };
+    let start = match find_volume(&image) {
+        Ok(start) => start,
+        Err(err) => {
+            eprintln!("fsck: {}: {}", path, err);
+            return ExitCode::from(1);
+        }
+    };
+    let replayed = match replay_log(&mut image[start..]) {
+        Ok(replayed) => replayed,
+        Err(err) => {
+            eprintln!("fsck: {}: {}", path, err);
+            return ExitCode::from(1);
+        }
+    };
+    if replayed > 0 {
+        println!("log: replayed an operation of {} sectors", replayed);
+        if replay {
+            if let Err(err) = fs::write(path, &image) {
+                eprintln!("fsck: {}: {}", path, err);
+                return ExitCode::from(2);
+            }
+        }
+    }
+    let volume = match parse_volume(&image[start..]) {
+        Ok(volume) => volume,
+        Err(err) => {
+            eprintln!("fsck: {}: {}", path, err);
+            return ExitCode::from(1);
+        }
+    };
+    let cluster_count = volume.cluster_count;
+    let mut checker = Checker {
+        owners: vec![None; cluster_count + 2],
+        volume,
+        problems: Vec::new(),
+        warnings: Vec::new(),
+        files: 0,
+        dirs: 0,
+    };
+    checker.check_fats();
+    checker.check_tree();
+    let orphans = orphans(&image[start..]);
+    let orphan_clusters = checker.check_orphans(&orphans);
+    let used = checker.check_lost();
+    checker.check_fsinfo(cluster_count - used);
+    for warning in &checker.warnings {
+        println!("warning: {}", warning);
+    }
+    for problem in &checker.problems {
+        println!("{}", problem);
+    }
+    if !orphans.is_empty() {
+        println!(
+            "orphans: {} chains of {} clusters, freed at mount",
+            orphans.len(),
+            orphan_clusters
+        );
+    }
+    println!(
+        "{}: {} files, {} directories, {}/{} clusters",
+        path, checker.files, checker.dirs, used, cluster_count
+    );
+    if checker.problems.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::from(1)
+    }
+}
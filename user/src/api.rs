@@ -1,4 +1,9 @@
+use crate::errno::ENOSPC;
 use crate::syscall::*;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use bitflags::*;
 
 pub fn write(fd: usize, buffer: &[u8]) -> isize {
     sys_write(fd, buffer)
@@ -12,6 +17,248 @@ pub fn read(fd: usize, buf: &mut [u8]) -> isize {
     sys_read(fd, buf)
 }
 
+// --------------- files ----------------------
+
+bitflags! {
+    /// `flags` of `open`, read only if neither `WRONLY` nor `RDWR`
+    pub struct OpenFlags: u32 {
+        const WRONLY = 1 << 0;
+        const RDWR = 1 << 1;
+        const CREATE = 1 << 6;
+        const TRUNC = 1 << 9;
+        const APPEND = 1 << 10;
+        /// fail if it is not a directory
+        const DIRECTORY = 1 << 16;
+    }
+}
+
+pub fn open(path: &str, flags: OpenFlags) -> isize {
+    sys_open(path, flags.bits())
+}
+
+pub fn unlink(path: &str) -> isize {
+    sys_unlink(path, 0)
+}
+
+// Type bits of `Stat::st_mode`
+pub const S_IFMT: u32 = 0o170000;
+pub const S_IFIFO: u32 = 0o010000;
+pub const S_IFCHR: u32 = 0o020000;
+pub const S_IFDIR: u32 = 0o040000;
+pub const S_IFBLK: u32 = 0o060000;
+pub const S_IFREG: u32 = 0o100000;
+
+/// `struct stat` of Linux on RISC-V, filled by `stat` and `fstat`
+///
+/// Times are since boot. Owners and devices are always 0.
+#[repr(C)]
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Stat {
+    pub st_dev: u64,
+    pub st_ino: u64,
+    /// type and permission bits, such as `S_IFREG | 0o644`
+    pub st_mode: u32,
+    pub st_nlink: u32,
+    pub st_uid: u32,
+    pub st_gid: u32,
+    pub st_rdev: u64,
+    __pad1: u64,
+    pub st_size: i64,
+    pub st_blksize: i32,
+    __pad2: i32,
+    /// number of 512-byte blocks
+    pub st_blocks: i64,
+    pub st_atime: i64,
+    pub st_atime_nsec: u64,
+    pub st_mtime: i64,
+    pub st_mtime_nsec: u64,
+    pub st_ctime: i64,
+    pub st_ctime_nsec: u64,
+    __unused: [u32; 2],
+}
+
+impl Stat {
+    pub fn is_dir(&self) -> bool {
+        self.st_mode & S_IFMT == S_IFDIR
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.st_mode & S_IFMT == S_IFREG
+    }
+
+    /// Last modification in ms since boot
+    pub fn mtime_ms(&self) -> usize {
+        self.st_mtime as usize * 1000 + self.st_mtime_nsec as usize / 1_000_000
+    }
+}
+
+pub fn stat(path: &str, st: &mut Stat) -> isize {
+    sys_stat(path, st as *mut _)
+}
+
+pub fn fstat(fd: usize, st: &mut Stat) -> isize {
+    sys_fstat(fd, st as *mut _)
+}
+
+pub fn sync() -> isize {
+    sys_sync()
+}
+
+pub fn fsync(fd: usize) -> isize {
+    sys_fsync(fd)
+}
+
+/// `flags` of `sys_unlink`: remove an empty directory instead of a file
+const AT_REMOVEDIR: u32 = 0x200;
+
+/// Remove the empty directory `path`.
+pub fn rmdir(path: &str) -> isize {
+    sys_unlink(path, AT_REMOVEDIR)
+}
+
+pub fn mkdir(path: &str) -> isize {
+    sys_mkdir(path)
+}
+
+pub fn rename(old_path: &str, new_path: &str) -> isize {
+    sys_rename(old_path, new_path)
+}
+
+pub fn chdir(path: &str) -> isize {
+    sys_chdir(path)
+}
+
+/// The absolute path of the working directory
+pub fn getcwd() -> String {
+    let mut buf = vec![0u8; 64];
+    loop {
+        let len = sys_getcwd(&mut buf);
+        if len > 0 {
+            buf.truncate(len as usize - 1);
+            return String::from_utf8(buf).unwrap();
+        }
+        let len = buf.len() * 2;
+        buf.resize(len, 0);
+    }
+}
+
+/// An entry of a directory listed by `read_dir`
+pub struct DirEntry {
+    pub name: String,
+    pub is_dir: bool,
+}
+
+/// `d_type` of a directory in `linux_dirent64`
+const DT_DIR: u8 = 4;
+
+/// Fill `buf` with the next entries of the directory `fd`, see `sys_getdents64`.
+pub fn getdents(fd: usize, buf: &mut [u8]) -> isize {
+    sys_getdents64(fd, buf)
+}
+
+/// Entries of the directory `path`, with `.` and `..`
+pub fn read_dir(path: &str) -> Result<Vec<DirEntry>, isize> {
+    let fd = open(path, OpenFlags::DIRECTORY);
+    if fd < 0 {
+        return Err(fd);
+    }
+    let fd = fd as usize;
+    let mut entries = Vec::new();
+    let mut buf = vec![0u8; 512];
+    loop {
+        let len = getdents(fd, &mut buf);
+        if len < 0 {
+            close(fd);
+            return Err(len);
+        }
+        if len == 0 {
+            break;
+        }
+        // struct linux_dirent64 { d_ino: u64, d_off: i64, d_reclen: u16, d_type: u8, d_name }
+        let mut pos = 0;
+        while pos < len as usize {
+            let reclen = u16::from_le_bytes([buf[pos + 16], buf[pos + 17]]) as usize;
+            let name = &buf[pos + 19..pos + reclen];
+            let name_len = name.iter().position(|c| *c == 0).unwrap_or(name.len());
+            entries.push(DirEntry {
+                name: String::from_utf8_lossy(&name[..name_len]).into_owned(),
+                is_dir: buf[pos + 18] == DT_DIR,
+            });
+            pos += reclen;
+        }
+    }
+    close(fd);
+    Ok(entries)
+}
+
+/// Read `fd` until the end of file.
+pub fn read_to_end(fd: usize) -> Result<Vec<u8>, isize> {
+    let mut data = Vec::new();
+    let mut buf = vec![0u8; 512];
+    loop {
+        match read(fd, &mut buf) {
+            0 => return Ok(data),
+            len if len > 0 => data.extend_from_slice(&buf[..len as usize]),
+            err => return Err(err),
+        }
+    }
+}
+
+/// Content of the file `path`
+pub fn read_file(path: &str) -> Result<Vec<u8>, isize> {
+    let fd = open(path, OpenFlags::empty());
+    if fd < 0 {
+        return Err(fd);
+    }
+    let data = read_to_end(fd as usize);
+    close(fd as usize);
+    data
+}
+
+/// Write `data` to the file `path`, created or truncated.
+pub fn write_file(path: &str, data: &[u8]) -> Result<(), isize> {
+    let fd = open(
+        path,
+        OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC,
+    );
+    if fd < 0 {
+        return Err(fd);
+    }
+    let mut written = 0;
+    while written < data.len() {
+        match write(fd as usize, &data[written..]) {
+            len if len > 0 => written += len as usize,
+            err => {
+                close(fd as usize);
+                return Err(if err == 0 { -ENOSPC } else { err });
+            }
+        }
+    }
+    close(fd as usize);
+    Ok(())
+}
+
+pub fn close(fd: usize) -> isize {
+    sys_close(fd)
+}
+
+/// `pipe[0]` is the read end, `pipe[1]` is the write end.
+pub fn pipe(pipe: &mut [usize; 2]) -> isize {
+    sys_pipe(pipe)
+}
+
+pub fn dup(fd: usize) -> isize {
+    sys_dup(fd)
+}
+
+/// Make `new_fd` refer to the file `old_fd` refers to.
+pub fn dup2(old_fd: usize, new_fd: usize) -> isize {
+    if old_fd == new_fd {
+        return new_fd as isize;
+    }
+    sys_dup3(old_fd, new_fd, 0)
+}
+
 pub fn yield_() -> isize {
     sys_yield()
 }
@@ -24,18 +271,48 @@ pub fn getpid() -> isize {
     sys_getpid()
 }
 
+pub fn getppid() -> isize {
+    sys_getppid()
+}
+
 pub fn fork() -> isize {
     sys_fork()
 }
 
 pub fn exec(path: &str) -> isize {
-    sys_exec(path)
+    sys_exec(path, core::ptr::null(), crate::environ())
+}
+
+/// Exec with arguments, `args` must end with a null pointer.
+/// The environment is passed on.
+pub fn execv(path: &str, args: &[*const u8]) -> isize {
+    assert_eq!(args.last(), Some(&core::ptr::null()));
+    sys_exec(path, args.as_ptr(), crate::environ())
+}
+
+/// Exec with arguments and environment `NAME=value` strings, both must end with
+/// a null pointer.
+pub fn execve(path: &str, args: &[*const u8], envs: &[*const u8]) -> isize {
+    assert_eq!(args.last(), Some(&core::ptr::null()));
+    assert_eq!(envs.last(), Some(&core::ptr::null()));
+    sys_exec(path, args.as_ptr(), envs.as_ptr())
 }
 
 // wait for specific
 pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
+    waitpid_options(pid as isize, exit_code, 0)
+}
+
+/// `options` of `waitpid_options`: return 0 at once if no child has changed state
+pub const WNOHANG: usize = 1;
+/// `options` of `waitpid_options`: also report stopped children
+pub const WUNTRACED: usize = 2;
+
+/// Wait for child `pid` (any child if -1) to exit, or to stop with `WUNTRACED`.
+pub fn waitpid_options(pid: isize, exit_code: &mut i32, options: usize) -> isize {
     loop {
-        match sys_waitpid(pid as isize, exit_code as *mut _) {
+        match sys_waitpid(pid, exit_code as *mut _, options & WUNTRACED) {
+            -2 if options & WNOHANG != 0 => return 0,
             -2 => {
                 yield_();
             }
@@ -46,14 +323,48 @@ pub fn waitpid(pid: usize, exit_code: &mut i32) -> isize {
 
 // wait for any
 pub fn wait(exit_code: &mut i32) -> isize {
-    loop {
-        match sys_waitpid(-1, exit_code as *mut _) {
-            -2 => {
-                yield_();
-            }
-            exit_pid => return exit_pid,
-        }
-    }
+    waitpid_options(-1, exit_code, 0)
+}
+
+// `status` of waitpid, following Linux:
+// exit code in bits 8..16 if exited, the signal number if killed by a signal,
+// or the signal number in bits 8..16 and 0x7f in the lowest byte if stopped.
+// A process killed by SIGSEGV for a stack overflow also has `STACK_OVERFLOW`.
+
+/// Not in Linux, see `wstackoverflow`
+pub const STACK_OVERFLOW: i32 = 0x10000;
+
+pub fn wifexited(status: i32) -> bool {
+    status & 0x7f == 0
+}
+
+/// The lowest 8 bits of the exit code, if `wifexited`
+pub fn wexitstatus(status: i32) -> i32 {
+    (status >> 8) & 0xff
+}
+
+pub fn wifsignaled(status: i32) -> bool {
+    status & 0x7f != 0 && status & 0x7f != 0x7f
+}
+
+/// The signal which killed the process, if `wifsignaled`
+pub fn wtermsig(status: i32) -> i32 {
+    status & 0x7f
+}
+
+pub fn wifstopped(status: i32) -> bool {
+    status & 0xff == 0x7f
+}
+
+/// The signal which stopped the process, if `wifstopped`
+pub fn wstopsig(status: i32) -> i32 {
+    (status >> 8) & 0xff
+}
+
+/// Whether the process was killed by SIGSEGV for growing its stack beyond the
+/// limit, `wtermsig` is SIGSEGV then
+pub fn wstackoverflow(status: i32) -> bool {
+    status & STACK_OVERFLOW != 0
 }
 
 pub fn sleep(period_ms: usize) {
@@ -67,8 +378,182 @@ pub fn shutdown() -> ! {
     sys_shutdown()
 }
 
-pub fn ls() {
-    sys_ls();
+/// Names of the apps which can be run from the shell
+pub fn apps() -> Vec<String> {
+    let mut buf = vec![0u8; 256];
+    loop {
+        let len = sys_apps(&mut buf) as usize;
+        if len <= buf.len() {
+            buf.truncate(len);
+            break;
+        }
+        buf.resize(len, 0);
+    }
+    core::str::from_utf8(&buf)
+        .unwrap()
+        .lines()
+        .map(String::from)
+        .collect()
+}
+
+// --------------- memory mappings ----------------------
+
+bitflags! {
+    /// `prot` of `mmap`, `PROT_NONE` is not supported
+    pub struct ProtFlags: usize {
+        const READ = 1 << 0;
+        const WRITE = 1 << 1;
+        const EXEC = 1 << 2;
+    }
+}
+
+bitflags! {
+    /// `flags` of `mmap`, either `SHARED` or `PRIVATE`
+    pub struct MapFlags: usize {
+        const SHARED = 1 << 0;
+        const PRIVATE = 1 << 1;
+        /// at `addr`, which must not be mapped
+        const FIXED = 1 << 4;
+        /// zeroed memory instead of a file, private only
+        const ANONYMOUS = 1 << 5;
+    }
+}
+
+// `flags` of `msync`
+pub const MS_ASYNC: usize = 1 << 0;
+pub const MS_INVALIDATE: usize = 1 << 1;
+/// also write the changes to the disk
+pub const MS_SYNC: usize = 1 << 2;
+
+/// Map `len` bytes of file `fd` from `offset`, return the address of the
+/// mapping, or a negated errno.
+pub fn mmap(
+    addr: usize,
+    len: usize,
+    prot: ProtFlags,
+    flags: MapFlags,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    sys_mmap(addr, len, prot.bits(), flags.bits(), fd, offset)
+}
+
+pub fn munmap(addr: usize, len: usize) -> isize {
+    sys_munmap(addr, len)
+}
+
+pub fn msync(addr: usize, len: usize, flags: usize) -> isize {
+    sys_msync(addr, len, flags)
+}
+
+// --------------- threads ----------------------
+
+/// The thread should call `exit` at the end of `entry`.
+pub fn thread_create(entry: usize, arg: usize) -> isize {
+    sys_thread_create(entry, arg)
+}
+
+pub fn gettid() -> isize {
+    sys_gettid()
+}
+
+// wait for specific thread
+pub fn waittid(tid: usize, exit_code: &mut i32) -> isize {
+    loop {
+        match sys_waittid(tid, exit_code as *mut _) {
+            -2 => {
+                yield_();
+            }
+            exit_tid => return exit_tid,
+        }
+    }
+}
+
+// --------------- signals ----------------------
+
+use crate::signal::{SignalAction, SignalFlags};
+
+pub fn kill(pid: usize, signum: i32) -> isize {
+    sys_kill(pid as isize, signum)
+}
+
+/// Send `signum` to every process in group `pgid`.
+pub fn killpg(pgid: usize, signum: i32) -> isize {
+    sys_kill(-(pgid as isize), signum)
+}
+
+pub fn sigaction(
+    signum: i32,
+    action: Option<&SignalAction>,
+    old_action: Option<&mut SignalAction>,
+) -> isize {
+    sys_sigaction(
+        signum,
+        action.map_or(core::ptr::null(), |action| action as *const _),
+        old_action.map_or(core::ptr::null_mut(), |action| action as *mut _),
+    )
+}
+
+/// Set the signal mask of current thread, return the old one.
+pub fn sigprocmask(mask: SignalFlags) -> SignalFlags {
+    SignalFlags::from_bits_truncate(sys_sigprocmask(mask.bits()) as u32)
+}
+
+/// Must be called at the end of a signal handler.
+pub fn sigreturn() -> isize {
+    sys_sigreturn()
+}
+
+// --------------- process groups and sessions ----------------------
+
+pub fn setpgid(pid: usize, pgid: usize) -> isize {
+    sys_setpgid(pid, pgid)
+}
+
+pub fn getpgid(pid: usize) -> isize {
+    sys_getpgid(pid)
+}
+
+pub fn getpgrp() -> isize {
+    sys_getpgid(0)
+}
+
+pub fn setsid() -> isize {
+    sys_setsid()
+}
+
+pub fn getsid(pid: usize) -> isize {
+    sys_getsid(pid)
+}
+
+pub fn tcgetpgrp(fd: usize) -> isize {
+    sys_tcgetpgrp(fd)
+}
+
+pub fn tcsetpgrp(fd: usize, pgid: usize) -> isize {
+    sys_tcsetpgrp(fd, pgid)
+}
+
+// --------------- resource limits ----------------------
+
+/// Bytes the main stack may grow to, inherited by children and kept across exec
+pub const RLIMIT_STACK: usize = 3;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RLimit {
+    /// soft limit, enforced
+    pub rlim_cur: usize,
+    /// hard limit, fixed for `RLIMIT_STACK`
+    pub rlim_max: usize,
+}
+
+pub fn getrlimit(resource: usize, rlim: &mut RLimit) -> isize {
+    sys_getrlimit(resource, rlim)
+}
+
+pub fn setrlimit(resource: usize, rlim: &RLimit) -> isize {
+    sys_setrlimit(resource, rlim)
 }
 
 // --------------- for user - kernel communication ----------------------
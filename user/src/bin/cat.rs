@@ -0,0 +1,51 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+#[macro_use]
+extern crate user_lib;
+
+use alloc::string::String;
+use user_lib::{args, close, open, read, write, OpenFlags};
+
+const STDIN: usize = 0;
+const STDOUT: usize = 1;
+
+/// Copy `fd` to stdout until the end of file.
+fn copy_to_stdout(fd: usize) -> bool {
+    let mut buf = [0u8; 256];
+    loop {
+        match read(fd, &mut buf) {
+            0 => return true,
+            len if len < 0 => return false,
+            len => {
+                write(STDOUT, &buf[..len as usize]);
+            }
+        }
+    }
+}
+
+/// Print the files given, or stdin if there is none.
+#[no_mangle]
+pub fn main() -> i32 {
+    let args = args();
+    if args.len() <= 1 {
+        return if copy_to_stdout(STDIN) { 0 } else { 1 };
+    }
+    let mut exit_code = 0;
+    for path in &args[1..] {
+        let mut name = String::from(*path);
+        name.push('\0');
+        let fd = open(name.as_str(), OpenFlags::empty());
+        if fd < 0 {
+            eprintln!("cat: {}: No such file", path);
+            exit_code = 1;
+            continue;
+        }
+        if !copy_to_stdout(fd as usize) {
+            exit_code = 1;
+        }
+        close(fd as usize);
+    }
+    exit_code
+}
@@ -0,0 +1,65 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::sync::{Condvar, Mutex};
+use user_lib::{exit, thread_create, waittid};
+
+const ROUNDS: usize = 50;
+
+/// Two threads take turns through a condition variable.
+struct PingPong {
+    turn: Mutex<usize>,
+    changed: Condvar,
+}
+
+struct Arg<'a> {
+    shared: &'a PingPong,
+    me: usize,
+}
+
+fn player(arg: *const Arg) {
+    let arg = unsafe { &*arg };
+    for _ in 0..ROUNDS {
+        let mut turn = arg.shared.turn.lock();
+        while *turn % 2 != arg.me {
+            turn = arg.shared.changed.wait(turn);
+        }
+        *turn += 1;
+        arg.shared.changed.signal();
+    }
+    exit(0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let shared = PingPong {
+        turn: Mutex::new(0),
+        changed: Condvar::new(),
+    };
+    let args = [
+        Arg {
+            shared: &shared,
+            me: 0,
+        },
+        Arg {
+            shared: &shared,
+            me: 1,
+        },
+    ];
+    let mut tids = [0; 2];
+    for (tid, arg) in tids.iter_mut().zip(args.iter()) {
+        *tid = thread_create(player as usize, arg as *const _ as usize);
+        assert!(*tid > 0);
+    }
+    let mut exit_code: i32 = 0;
+    for tid in tids {
+        assert_eq!(waittid(tid as usize, &mut exit_code), tid);
+        assert_eq!(exit_code, 0);
+    }
+    assert_eq!(*shared.turn.lock(), 2 * ROUNDS);
+    println!("condvar passed!");
+    0
+}
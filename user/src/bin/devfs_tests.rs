@@ -0,0 +1,59 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{ENOENT, ENOTTY, EROFS};
+use user_lib::{close, open, read, tcgetpgrp, write, OpenFlags};
+
+fn open_device(path: &str, flags: OpenFlags) -> usize {
+    let fd = open(path, flags);
+    assert!(fd >= 0, "cannot open {}", path);
+    fd as usize
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let null = open_device("/dev/null\0", OpenFlags::RDWR | OpenFlags::TRUNC);
+    assert_eq!(write(null, b"discarded"), 9);
+    assert_eq!(read(null, &mut [0u8; 16]), 0);
+    assert_eq!(tcgetpgrp(null), -ENOTTY);
+    close(null);
+
+    let zero = open_device("/dev/zero\0", OpenFlags::empty());
+    let mut buf = [0xffu8; 100];
+    assert_eq!(read(zero, &mut buf), 100);
+    assert!(buf.iter().all(|b| *b == 0));
+    close(zero);
+
+    let urandom = open_device("/dev/urandom\0", OpenFlags::empty());
+    let mut a = [0u8; 37];
+    let mut b = [0u8; 37];
+    assert_eq!(read(urandom, &mut a), 37);
+    assert_eq!(read(urandom, &mut b), 37);
+    assert_ne!(a, b);
+    assert!(a.iter().any(|x| *x != 0));
+    close(urandom);
+
+    // the console is the terminal, like stdout
+    let console = open_device("/dev/console\0", OpenFlags::WRONLY);
+    assert_eq!(tcgetpgrp(console), tcgetpgrp(1));
+    let message = b"written to /dev/console\n";
+    assert_eq!(write(console, message), message.len() as isize);
+    close(console);
+
+    // block devices are read in place
+    let disk = open("/dev/vda\0", OpenFlags::empty());
+    if disk >= 0 {
+        assert_eq!(read(disk as usize, &mut [0u8; 700]), 700);
+        close(disk as usize);
+    }
+    // the swap area holds pages of other processes
+    assert_eq!(open("/dev/swap\0", OpenFlags::empty()), -ENOENT);
+
+    assert_eq!(open("/dev/none\0", OpenFlags::empty()), -ENOENT);
+    assert_eq!(open("/dev/none\0", OpenFlags::CREATE), -EROFS);
+    println!("devfs_tests passed!");
+    0
+}
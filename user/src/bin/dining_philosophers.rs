@@ -0,0 +1,70 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::sync::Mutex;
+use user_lib::{exit, get_time, thread_create, waittid, yield_};
+
+const N: usize = 5;
+const ROUNDS: usize = 20;
+
+struct Table {
+    forks: [Mutex<()>; N],
+    /// number of meals eaten by each philosopher
+    meals: Mutex<[usize; N]>,
+}
+
+struct Arg<'a> {
+    table: &'a Table,
+    id: usize,
+}
+
+fn think_or_eat() {
+    let start = get_time();
+    while get_time() < start + 2 {
+        yield_();
+    }
+}
+
+fn philosopher(arg: *const Arg) {
+    let arg = unsafe { &*arg };
+    let (left, right) = (arg.id, (arg.id + 1) % N);
+    // always take the lower-numbered fork first to avoid deadlock
+    let (first, second) = if left < right {
+        (left, right)
+    } else {
+        (right, left)
+    };
+    for _ in 0..ROUNDS {
+        think_or_eat();
+        let _first = arg.table.forks[first].lock();
+        let _second = arg.table.forks[second].lock();
+        think_or_eat();
+        arg.table.meals.lock()[arg.id] += 1;
+    }
+    exit(0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let table = Table {
+        forks: core::array::from_fn(|_| Mutex::new(())),
+        meals: Mutex::new([0; N]),
+    };
+    let args: [Arg; N] = core::array::from_fn(|id| Arg { table: &table, id });
+    let mut tids = [0; N];
+    for (tid, arg) in tids.iter_mut().zip(args.iter()) {
+        *tid = thread_create(philosopher as usize, arg as *const _ as usize);
+        assert!(*tid > 0);
+    }
+    let mut exit_code: i32 = 0;
+    for tid in tids {
+        assert_eq!(waittid(tid as usize, &mut exit_code), tid);
+        assert_eq!(exit_code, 0);
+    }
+    assert!(table.meals.lock().iter().all(|&meals| meals == ROUNDS));
+    println!("dining_philosophers passed!");
+    0
+}
@@ -0,0 +1,143 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use user_lib::errno::{EBUSY, EEXIST, EINVAL, EISDIR, ENOENT, ENOTDIR, ENOTEMPTY, EXDEV};
+use user_lib::{
+    chdir, close, fork, getcwd, getdents, mkdir, open, read_dir, rename, rmdir, unlink, wait,
+    wexitstatus, write, OpenFlags,
+};
+
+/// More than the entries kept in the dentry cache
+const FILES: usize = 300;
+
+fn create(path: &str) {
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, b"data"), 4);
+    close(fd as usize);
+}
+
+/// Names in the directory `path`, sorted, directories ended by `/`
+fn names(path: &str) -> Vec<String> {
+    let mut names: Vec<String> = read_dir(path)
+        .unwrap()
+        .into_iter()
+        .map(|entry| {
+            let mut name = entry.name;
+            if entry.is_dir {
+                name.push('/');
+            }
+            name
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(getcwd(), "/");
+    assert_eq!(mkdir("/dir_tests\0"), 0);
+    assert_eq!(mkdir("/dir_tests\0"), -EEXIST);
+    assert_eq!(mkdir("/none/dir\0"), -ENOENT);
+
+    // relative paths start from the working directory
+    assert_eq!(chdir("dir_tests\0"), 0);
+    assert_eq!(getcwd(), "/dir_tests");
+    assert_eq!(mkdir("a\0"), 0);
+    assert_eq!(mkdir("./a/b/\0"), 0);
+    create("a/file\0");
+    assert_eq!(names("a\0"), ["./", "../", "b/", "file"]);
+    assert_eq!(chdir("a/b/..\0"), 0);
+    assert_eq!(getcwd(), "/dir_tests/a");
+    assert_eq!(chdir("file\0"), -ENOTDIR);
+    assert_eq!(open("b\0", OpenFlags::WRONLY), -EISDIR);
+    assert_eq!(open("file\0", OpenFlags::DIRECTORY), -ENOTDIR);
+
+    // getdents reads a directory in pieces, but not a file
+    let fd = open(".\0", OpenFlags::DIRECTORY);
+    assert!(fd >= 0);
+    assert_eq!(getdents(fd as usize, &mut [0u8; 8]), -EINVAL);
+    let mut buf = [0u8; 32];
+    let mut count = 0;
+    loop {
+        match getdents(fd as usize, &mut buf) {
+            0 => break,
+            len => {
+                assert!(len > 0);
+                count += 1;
+            }
+        }
+    }
+    assert_eq!(count, 4);
+    assert_eq!(getdents(fd as usize, &mut [0u8; 8]), 0);
+    close(fd as usize);
+    let fd = open("file\0", OpenFlags::empty());
+    assert_eq!(getdents(fd as usize, &mut buf), -ENOTDIR);
+    close(fd as usize);
+
+    // the working directory is inherited, and follows the directory when moved
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(getcwd(), "/dir_tests/a");
+        assert_eq!(chdir("/\0"), 0);
+        user_lib::exit(0);
+    }
+    let mut exit_code = 0;
+    assert_eq!(wait(&mut exit_code), pid);
+    assert_eq!(wexitstatus(exit_code), 0);
+    assert_eq!(getcwd(), "/dir_tests/a");
+    assert_eq!(rename("/dir_tests/a\0", "/dir_tests/c\0"), 0);
+    assert_eq!(getcwd(), "/dir_tests/c");
+    assert_eq!(rename("file\0", "b/moved\0"), 0);
+    assert_eq!(names("b\0"), ["./", "../", "moved"]);
+    assert_eq!(rename("/dir_tests/c\0", "b/c\0"), -EINVAL);
+    assert_eq!(rename("b/moved\0", "/proc/moved\0"), -EXDEV);
+    assert_eq!(rename("/proc\0", "/proc2\0"), -EBUSY);
+
+    // entries dropped from the cache are looked up again, and the working
+    // directory keeps its parents
+    assert_eq!(mkdir("d\0"), 0);
+    assert_eq!(mkdir("d/e\0"), 0);
+    assert_eq!(chdir("d/e\0"), 0);
+    for i in 0..FILES {
+        create(&format!("f{}\0", i));
+    }
+    for i in 0..FILES {
+        let fd = open(&format!("f{}\0", i), OpenFlags::empty());
+        assert!(fd >= 0);
+        close(fd as usize);
+    }
+    assert_eq!(getcwd(), "/dir_tests/c/d/e");
+    assert_eq!(names("/dir_tests/c/d\0"), ["./", "../", "e/"]);
+    for i in 0..FILES {
+        assert_eq!(unlink(&format!("f{}\0", i)), 0);
+    }
+    assert_eq!(chdir("../..\0"), 0);
+    assert_eq!(getcwd(), "/dir_tests/c");
+    assert_eq!(rmdir("d/e\0"), 0);
+    assert_eq!(rmdir("d\0"), 0);
+
+    // only empty directories are removed, and only by rmdir
+    assert_eq!(rmdir("b\0"), -ENOTEMPTY);
+    assert_eq!(rmdir("b/moved\0"), -ENOTDIR);
+    assert_eq!(unlink("b\0"), -EISDIR);
+    assert_eq!(rmdir("/proc\0"), -EBUSY);
+    assert_eq!(unlink("b/moved\0"), 0);
+    assert_eq!(rmdir("b\0"), 0);
+    assert_eq!(chdir("..\0"), 0);
+    assert_eq!(rmdir("c\0"), 0);
+    assert_eq!(chdir("/\0"), 0);
+    assert_eq!(rmdir("dir_tests\0"), 0);
+    assert_eq!(chdir("/dir_tests\0"), -ENOENT);
+    println!("dir_tests passed!");
+    0
+}
@@ -0,0 +1,21 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::args;
+
+/// Print the arguments separated by spaces.
+#[no_mangle]
+pub fn main() -> i32 {
+    let args = args();
+    for (i, arg) in args.iter().enumerate().skip(1) {
+        if i > 1 {
+            print!(" ");
+        }
+        print!("{}", arg);
+    }
+    println!("");
+    0
+}
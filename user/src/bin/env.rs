@@ -0,0 +1,16 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::vars;
+
+/// Print the environment, a `NAME=value` per line.
+#[no_mangle]
+pub fn main() -> i32 {
+    for (name, value) in vars() {
+        println!("{}={}", name, value);
+    }
+    0
+}
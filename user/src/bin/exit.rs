@@ -3,7 +3,7 @@
 
 #[macro_use]
 extern crate user_lib;
-use user_lib::{exit, fork, wait, waitpid, yield_};
+use user_lib::{exit, fork, wait, waitpid, wexitstatus, wifexited, yield_};
 
 const MAGIC: i32 = -0x10384;
 
@@ -22,7 +22,9 @@ pub fn main() -> i32 {
     }
     println!("I am the parent, waiting now..");
     let mut xstate: i32 = 0;
-    assert!(waitpid(pid as usize, &mut xstate) == pid && xstate == MAGIC);
+    assert!(waitpid(pid as usize, &mut xstate) == pid && wifexited(xstate));
+    // only the lowest 8 bits of the exit code are kept
+    assert_eq!(wexitstatus(xstate), MAGIC & 0xff);
     assert!(waitpid(pid as usize, &mut xstate) < 0 && wait(&mut xstate) <= 0);
     println!("waitpid {} ok.", pid);
     println!("exit pass.");
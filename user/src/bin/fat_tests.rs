@@ -0,0 +1,129 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use user_lib::errno::{EEXIST, ENOENT, ENOTEMPTY, EXDEV};
+use user_lib::{
+    close, fstat, fsync, mkdir, open, read, read_dir, read_file, rename, rmdir, stat, sync, unlink,
+    write, write_file, OpenFlags, Stat, S_IFREG,
+};
+
+/// The FAT32 root and the memory filesystem root are both inode 1, the
+/// directory `/mnt` is not otherwise.
+fn fat_mounted() -> bool {
+    let mut st = Stat::default();
+    stat("/mnt\0", &mut st) == 0 && st.st_ino == 1
+}
+
+fn names(path: &str) -> Vec<String> {
+    let mut names: Vec<String> = read_dir(path)
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.name)
+        .collect();
+    names.sort();
+    names
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    if !fat_mounted() {
+        println!("fat_tests: no FAT32 disk on /mnt, skipped");
+        return 0;
+    }
+    assert_eq!(mkdir("/mnt/fat_tests\0"), 0);
+
+    // long names, and data over several clusters
+    let data: Vec<u8> = (0..10000u32).map(|i| (i * 7 % 251) as u8).collect();
+    let long = "/mnt/fat_tests/A file with a rather long name.data\0";
+    write_file(long, &data).unwrap();
+    assert_eq!(read_file(long).unwrap(), data);
+    let mut st = Stat::default();
+    assert_eq!(stat(long, &mut st), 0);
+    assert_eq!(st.st_mode, S_IFREG | 0o755);
+    assert_eq!(st.st_size, 10000);
+    write_file("/mnt/fat_tests/short.txt\0", b"short").unwrap();
+    assert_eq!(
+        names("/mnt/fat_tests\0"),
+        [
+            ".",
+            "..",
+            "A file with a rather long name.data",
+            "short.txt"
+        ]
+    );
+    // names differing in case only would be the same file elsewhere
+    assert_eq!(
+        open("/mnt/fat_tests/SHORT.TXT\0", OpenFlags::CREATE),
+        -EEXIST
+    );
+
+    // more clusters than the log holds are written and freed in steps
+    let big: Vec<u8> = (0..100_000u32).map(|i| (i * 13 % 251) as u8).collect();
+    write_file("/mnt/fat_tests/big\0", &big).unwrap();
+    assert_eq!(read_file("/mnt/fat_tests/big\0").unwrap(), big);
+    assert_eq!(unlink("/mnt/fat_tests/big\0"), 0);
+
+    // truncate, then extend
+    write_file(long, b"new").unwrap();
+    assert_eq!(read_file(long).unwrap(), b"new");
+    let fd = open(long, OpenFlags::WRONLY | OpenFlags::APPEND);
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, &data[..2000]), 2000);
+    assert_eq!(fstat(fd as usize, &mut st), 0);
+    assert_eq!(st.st_size, 2003);
+    assert_eq!(fsync(fd as usize), 0);
+    close(fd as usize);
+
+    // directories and rename
+    assert_eq!(mkdir("/mnt/fat_tests/sub\0"), 0);
+    assert_eq!(
+        rename(
+            "/mnt/fat_tests/short.txt\0",
+            "/mnt/fat_tests/sub/Renamed File\0"
+        ),
+        0
+    );
+    assert_eq!(
+        read_file("/mnt/fat_tests/sub/Renamed File\0").unwrap(),
+        b"short"
+    );
+    assert_eq!(stat("/mnt/fat_tests/short.txt\0", &mut st), -ENOENT);
+    assert_eq!(rename("/mnt/fat_tests/sub\0", "/mnt/fat_tests/moved\0"), 0);
+    assert_eq!(names("/mnt/fat_tests/moved/..\0").len(), 4);
+    assert_eq!(rmdir("/mnt/fat_tests/moved\0"), -ENOTEMPTY);
+    assert_eq!(rename(long, "/fat_tests\0"), -EXDEV);
+
+    // an unlinked file stays readable while it is open
+    let fd = open(long, OpenFlags::empty());
+    assert!(fd >= 0);
+    assert_eq!(unlink(long), 0);
+    let mut buf = [0u8; 3];
+    assert_eq!(read(fd as usize, &mut buf), 3);
+    assert_eq!(&buf, b"new");
+    close(fd as usize);
+    // and writable, even from empty
+    let fd = open(
+        "/mnt/fat_tests/empty\0",
+        OpenFlags::CREATE | OpenFlags::RDWR,
+    );
+    assert!(fd >= 0);
+    assert_eq!(unlink("/mnt/fat_tests/empty\0"), 0);
+    assert_eq!(write(fd as usize, &data), data.len() as isize);
+    assert_eq!(fstat(fd as usize, &mut st), 0);
+    assert_eq!(st.st_size, data.len() as i64);
+    close(fd as usize);
+
+    assert_eq!(unlink("/mnt/fat_tests/moved/Renamed File\0"), 0);
+    assert_eq!(rmdir("/mnt/fat_tests/moved\0"), 0);
+    assert_eq!(rmdir("/mnt/fat_tests\0"), 0);
+    assert_eq!(sync(), 0);
+    println!("fat_tests passed!");
+    0
+}
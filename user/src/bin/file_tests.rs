@@ -0,0 +1,58 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{EBADF, EINVAL, ENOENT};
+use user_lib::{close, fsync, open, pipe, read, read_file, write, OpenFlags};
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let path = "file_tests.tmp\0";
+    assert_eq!(open("file_tests.none\0", OpenFlags::empty()), -ENOENT);
+    assert_eq!(open(path, OpenFlags::from_bits_truncate(1 << 20)), -EINVAL);
+
+    let fd = open(
+        path,
+        OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC,
+    );
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    assert_eq!(write(fd, b"hello, "), 7);
+    assert_eq!(write(fd, b"world"), 5);
+    // not readable
+    assert_eq!(read(fd, &mut [0u8; 4]), -EBADF);
+    close(fd);
+    assert_eq!(read_file(path).unwrap(), b"hello, world");
+
+    let fd = open(path, OpenFlags::WRONLY | OpenFlags::APPEND);
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, b"!\n"), 2);
+    close(fd as usize);
+    assert_eq!(read_file(path).unwrap(), b"hello, world!\n");
+
+    // overwrite from the beginning
+    let fd = open(path, OpenFlags::RDWR);
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, b"HELLO"), 5);
+    let mut rest = [0u8; 16];
+    assert_eq!(read(fd as usize, &mut rest), 9);
+    assert_eq!(&rest[..9], b", world!\n");
+    // a file in memory has nothing to write back, a pipe has no device
+    assert_eq!(fsync(fd as usize), 0);
+    close(fd as usize);
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    assert_eq!(fsync(fds[1]), -EINVAL);
+    close(fds[0]);
+    close(fds[1]);
+
+    let fd = open(path, OpenFlags::WRONLY | OpenFlags::TRUNC);
+    close(fd as usize);
+    assert!(read_file(path).unwrap().is_empty());
+    assert_eq!(write(fd as usize, b"closed"), -EBADF);
+    assert_eq!(fsync(fd as usize), -EBADF);
+    println!("file_tests passed!");
+    0
+}
@@ -0,0 +1,80 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use user_lib::errno::{EAGAIN, EFAULT, EINVAL};
+use user_lib::sync::{futex_wait, futex_wake, FutexMutex, Once};
+use user_lib::{exit, mmap, munmap, thread_create, waittid, yield_, MapFlags, ProtFlags};
+
+const NUM: usize = 4;
+const PAGE_SIZE: usize = 4096;
+const ADD_PER_THREAD: usize = 200;
+
+static COUNTER: FutexMutex<usize> = FutexMutex::new(0);
+static INIT: Once = Once::new();
+static INIT_CALLS: AtomicUsize = AtomicUsize::new(0);
+
+fn worker() {
+    INIT.call_once(|| {
+        // give the other threads a chance to block on the Once
+        for _ in 0..10 {
+            yield_();
+        }
+        INIT_CALLS.fetch_add(1, Ordering::Relaxed);
+    });
+    assert!(INIT.is_completed());
+    for i in 0..ADD_PER_THREAD {
+        let mut counter = COUNTER.lock();
+        // a plain read-modify-write, with a yield in the critical section
+        let value = *counter;
+        if i % 16 == 0 {
+            yield_();
+        }
+        *counter = value + 1;
+    }
+    exit(0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // the value has changed, so waiting fails at once
+    let word = AtomicU32::new(1);
+    assert_eq!(futex_wait(&word, 0), -EAGAIN);
+    // nobody is waiting
+    assert_eq!(futex_wake(&word, 1), 0);
+    // nor for a word which is not aligned or cannot be read
+    let unaligned = unsafe { &*((word.as_ptr() as usize + 1) as *const AtomicU32) };
+    assert_eq!(futex_wait(unaligned, 1), -EINVAL);
+    let addr = mmap(
+        0,
+        PAGE_SIZE,
+        ProtFlags::READ,
+        MapFlags::PRIVATE | MapFlags::ANONYMOUS,
+        0,
+        0,
+    );
+    assert!(addr > 0);
+    assert_eq!(munmap(addr as usize, PAGE_SIZE), 0);
+    let unmapped = unsafe { &*(addr as *const AtomicU32) };
+    assert_eq!(futex_wait(unmapped, 0), -EFAULT);
+    let kernel = unsafe { &*((usize::MAX - PAGE_SIZE + 1) as *const AtomicU32) };
+    assert_eq!(futex_wait(kernel, 0), -EFAULT);
+
+    let mut tids = [0; NUM];
+    for tid in tids.iter_mut() {
+        *tid = thread_create(worker as usize, 0);
+        assert!(*tid > 0);
+    }
+    let mut exit_code: i32 = 0;
+    for tid in tids {
+        assert_eq!(waittid(tid as usize, &mut exit_code), tid);
+        assert_eq!(exit_code, 0);
+    }
+    assert_eq!(INIT_CALLS.load(Ordering::Relaxed), 1);
+    assert_eq!(*COUNTER.lock(), NUM * ADD_PER_THREAD);
+    println!("futex passed!");
+    0
+}
@@ -0,0 +1,142 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{ENOTTY, EPERM, ESRCH};
+use user_lib::signal::*;
+use user_lib::{
+    exit, fork, getpgid, getpgrp, getpid, getppid, getsid, killpg, read, setpgid, setsid, sleep,
+    tcgetpgrp, tcsetpgrp, waitpid_options, wifsignaled, wifstopped, wstopsig, wtermsig, yield_,
+    WNOHANG, WUNTRACED,
+};
+
+/// Fork a child which waits to be moved into group `pgid` (0 for its own), then spins.
+fn spawn_in_group(pgid: usize) -> usize {
+    let pid = fork();
+    if pid == 0 {
+        let pgid = if pgid == 0 { getpid() } else { pgid as isize };
+        while getpgrp() != pgid {
+            yield_();
+        }
+        loop {
+            sleep(10);
+        }
+    }
+    let pid = pid as usize;
+    assert_eq!(setpgid(pid, if pgid == 0 { pid } else { pgid }), 0);
+    pid
+}
+
+fn groups_and_sessions() {
+    let pid = getpid() as usize;
+    let sid = getsid(0);
+    assert_eq!(getsid(getppid() as usize), sid);
+    assert_eq!(setpgid(0, 0), 0);
+    assert_eq!(getpgrp(), pid as isize);
+    assert_eq!(getpgid(pid), pid as isize);
+    // a group leader cannot create a session
+    assert_eq!(setsid(), -EPERM);
+    // neither self nor a child
+    assert_eq!(setpgid(1, 0), -ESRCH);
+    assert_eq!(getpgid(0x7fff), -ESRCH);
+    // no such group in the session
+    assert_eq!(setpgid(0, 0x7fff), -EPERM);
+
+    let child = fork();
+    if child == 0 {
+        let new_sid = setsid();
+        assert_eq!(new_sid, getpid());
+        assert_eq!(getsid(0), new_sid);
+        assert_eq!(getpgrp(), new_sid);
+        // the new session has no controlling terminal
+        assert_eq!(tcgetpgrp(0), -ENOTTY);
+        // a session leader cannot change its group
+        assert_eq!(setpgid(0, 0), -EPERM);
+        exit(0);
+    }
+    let mut status = 0;
+    assert_eq!(waitpid_options(child, &mut status, 0), child);
+    assert_eq!(status, 0);
+    println!("groups_and_sessions passed!");
+}
+
+fn stop_and_continue_group() {
+    let leader = spawn_in_group(0);
+    let member = spawn_in_group(leader);
+    let mut status = 0;
+    assert_eq!(waitpid_options(leader as isize, &mut status, WNOHANG), 0);
+    assert_eq!(killpg(leader, SIGSTOP), 0);
+    for pid in [leader, member] {
+        assert_eq!(
+            waitpid_options(pid as isize, &mut status, WUNTRACED),
+            pid as isize
+        );
+        assert!(wifstopped(status));
+        assert_eq!(wstopsig(status), SIGSTOP);
+    }
+    // a stop is reported only once
+    assert_eq!(
+        waitpid_options(leader as isize, &mut status, WUNTRACED | WNOHANG),
+        0
+    );
+    assert_eq!(killpg(leader, SIGCONT), 0);
+    assert_eq!(killpg(leader, SIGTERM), 0);
+    for pid in [leader, member] {
+        assert_eq!(
+            waitpid_options(pid as isize, &mut status, WUNTRACED),
+            pid as isize
+        );
+        assert!(wifsignaled(status));
+        assert_eq!(wtermsig(status), SIGTERM);
+    }
+    assert_eq!(killpg(leader, SIGTERM), -ESRCH);
+    println!("stop_and_continue_group passed!");
+}
+
+fn background_read() {
+    let child = fork();
+    if child == 0 {
+        while getpgrp() != getpid() {
+            yield_();
+        }
+        let mut buf = [0u8; 1];
+        read(0, &mut buf);
+        exit(0);
+    }
+    assert_eq!(setpgid(child as usize, child as usize), 0);
+    let mut status = 0;
+    // only the foreground group may read the terminal
+    assert_eq!(waitpid_options(child, &mut status, WUNTRACED), child);
+    assert!(wifstopped(status));
+    assert_eq!(wstopsig(status), SIGTTIN);
+    assert_eq!(killpg(child as usize, SIGKILL), 0);
+    assert_eq!(waitpid_options(child, &mut status, 0), child);
+    assert_eq!(wtermsig(status), SIGKILL);
+    println!("background_read passed!");
+}
+
+fn foreground_group() {
+    let foreground = tcgetpgrp(0);
+    if foreground < 0 {
+        // not in the session of the console
+        assert_eq!(foreground, -ENOTTY);
+        return;
+    }
+    assert_eq!(tcsetpgrp(0, 0x7fff), -EPERM);
+    assert_eq!(tcsetpgrp(0, getpgrp() as usize), 0);
+    assert_eq!(tcgetpgrp(0), getpgrp());
+    assert_eq!(tcsetpgrp(0, foreground as usize), 0);
+    println!("foreground_group passed!");
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    groups_and_sessions();
+    stop_and_continue_group();
+    background_read();
+    foreground_group();
+    println!("job_control passed!");
+    0
+}
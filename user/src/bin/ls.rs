@@ -0,0 +1,99 @@
+#![no_std]
+#![no_main]
+
+extern crate alloc;
+#[macro_use]
+extern crate user_lib;
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use user_lib::{args, read_dir, stat, Stat, S_IFBLK, S_IFCHR, S_IFDIR, S_IFIFO, S_IFMT};
+
+/// Mode as in `ls -l`, such as `drwxr-xr-x`
+fn mode_string(mode: u32) -> String {
+    let mut text = String::from(match mode & S_IFMT {
+        S_IFDIR => 'd',
+        S_IFCHR => 'c',
+        S_IFBLK => 'b',
+        S_IFIFO => 'p',
+        _ => '-',
+    });
+    for shift in [6, 3, 0] {
+        let bits = (mode >> shift) & 0o7;
+        text.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        text.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        text.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+    }
+    text
+}
+
+/// Print the entries of `path`, one per line, directories ended by `/`.
+///
+/// With `long`, print the mode, links, size and modification time in seconds
+/// since boot before every name.
+fn list(path: &str, long: bool) -> bool {
+    let mut name = String::from(path);
+    name.push('\0');
+    let mut entries = match read_dir(name.as_str()) {
+        Ok(entries) => entries,
+        Err(_) => {
+            eprintln!("ls: {}: No such directory", path);
+            return false;
+        }
+    };
+    entries.retain(|entry| entry.name != "." && entry.name != "..");
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    for entry in entries {
+        let suffix = if entry.is_dir { "/" } else { "" };
+        if !long {
+            println!("{}{}", entry.name, suffix);
+            continue;
+        }
+        let full_path = format!("{}/{}\0", path.trim_end_matches('/'), entry.name);
+        let mut st = Stat::default();
+        if stat(full_path.as_str(), &mut st) < 0 {
+            // removed since listed
+            continue;
+        }
+        let mtime = st.mtime_ms();
+        println!(
+            "{} {:>2} {:>8} {:>6}.{:02} {}{}",
+            mode_string(st.st_mode),
+            st.st_nlink,
+            st.st_size,
+            mtime / 1000,
+            mtime % 1000 / 10,
+            entry.name,
+            suffix
+        );
+    }
+    true
+}
+
+/// `ls [-l] [dir]...`, list the working directory if no directory is given.
+#[no_mangle]
+pub fn main() -> i32 {
+    let args = args();
+    let long = args.get(1) == Some(&"-l");
+    let first = if long { 2 } else { 1 };
+    let paths: Vec<&str> = if args.len() <= first {
+        vec!["."]
+    } else {
+        args[first..].to_vec()
+    };
+    let mut exit_code = 0;
+    for (i, path) in paths.iter().enumerate() {
+        if paths.len() > 1 {
+            if i > 0 {
+                println!("");
+            }
+            println!("{}:", path);
+        }
+        if !list(path, long) {
+            exit_code = 1;
+        }
+    }
+    exit_code
+}
@@ -0,0 +1,225 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::vec;
+use alloc::vec::Vec;
+use user_lib::errno::{EACCES, EBADF, EEXIST, EINVAL, ENODEV, ENOMEM, ETXTBSY};
+use user_lib::signal::SIGSEGV;
+use user_lib::{
+    close, exec, exit, fork, mmap, msync, munmap, open, pipe, read, read_file, unlink, waitpid,
+    wifsignaled, write, write_file, wtermsig, MapFlags, OpenFlags, ProtFlags, MS_ASYNC, MS_SYNC,
+};
+
+const PAGE_SIZE: usize = 4096;
+
+fn memory(addr: isize, len: usize) -> &'static mut [u8] {
+    assert!(addr > 0, "mmap failed: {}", addr);
+    assert_eq!(addr as usize % PAGE_SIZE, 0);
+    unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, len) }
+}
+
+fn rw() -> ProtFlags {
+    ProtFlags::READ | ProtFlags::WRITE
+}
+
+fn anonymous() {
+    let private = MapFlags::PRIVATE | MapFlags::ANONYMOUS;
+    let addr = mmap(0, 3 * PAGE_SIZE, rw(), private, 0, 0);
+    let mem = memory(addr, 3 * PAGE_SIZE);
+    assert!(mem.iter().all(|&b| b == 0));
+    for (i, b) in mem.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+    assert!(mem.iter().enumerate().all(|(i, &b)| b == i as u8));
+
+    // at a given address, but not over another mapping
+    let next = addr as usize + 3 * PAGE_SIZE;
+    let fixed = private | MapFlags::FIXED;
+    assert_eq!(mmap(next, PAGE_SIZE, rw(), fixed, 0, 0), next as isize);
+    assert_eq!(mmap(addr as usize, PAGE_SIZE, rw(), fixed, 0, 0), -EEXIST);
+    assert_eq!(mmap(next + 1, PAGE_SIZE, rw(), fixed, 0, 0), -EINVAL);
+
+    // mappings are not split
+    assert_eq!(munmap(addr as usize, PAGE_SIZE), -EINVAL);
+    assert_eq!(munmap(addr as usize, 4 * PAGE_SIZE), 0);
+    assert_eq!(msync(addr as usize, PAGE_SIZE, MS_ASYNC), -ENOMEM);
+    let pid = fork();
+    if pid == 0 {
+        unsafe { (addr as *const u8).read_volatile() };
+        exit(100);
+    }
+    let mut status = 0;
+    assert_eq!(waitpid(pid as usize, &mut status), pid);
+    assert!(wifsignaled(status) && wtermsig(status) == SIGSEGV);
+    println!("anonymous mappings passed!");
+}
+
+fn shared(path: &str, data: &[u8]) {
+    let len = data.len();
+    let fd = open(path, OpenFlags::RDWR);
+    assert!(fd >= 0);
+    let addr = mmap(0, len, rw(), MapFlags::SHARED, fd as usize, 0);
+    let mem = memory(addr, len);
+    assert_eq!(mem, data);
+    // the rest of the last page is zero
+    assert!(memory(addr, 3 * PAGE_SIZE)[len..].iter().all(|&b| b == 0));
+
+    mem[..5].copy_from_slice(b"hello");
+    assert_eq!(msync(addr as usize, len, MS_SYNC), 0);
+    assert_eq!(&read_file(path).unwrap()[..5], b"hello");
+
+    // writes through a file descriptor are seen by the mapping
+    let other = open(path, OpenFlags::RDWR);
+    assert!(other >= 0);
+    let mut buf = vec![0u8; PAGE_SIZE];
+    assert_eq!(read(other as usize, &mut buf), PAGE_SIZE as isize);
+    assert_eq!(write(other as usize, b"world"), 5);
+    close(other as usize);
+    assert_eq!(&mem[PAGE_SIZE..PAGE_SIZE + 5], b"world");
+
+    // and changes by a child by the parent
+    let pid = fork();
+    if pid == 0 {
+        mem[10..15].copy_from_slice(b"child");
+        exit(0);
+    }
+    let mut status = 0;
+    assert_eq!(waitpid(pid as usize, &mut status), pid);
+    assert_eq!(status, 0);
+    assert_eq!(&mem[10..15], b"child");
+
+    // the mapping is kept after the file is closed, and written back when unmapped
+    close(fd as usize);
+    mem[len - 4..].copy_from_slice(b"last");
+    assert_eq!(munmap(addr as usize, len), 0);
+    let file = read_file(path).unwrap();
+    assert_eq!(file.len(), len);
+    assert_eq!(&file[..5], b"hello");
+    assert_eq!(&file[10..15], b"child");
+    assert_eq!(&file[PAGE_SIZE..PAGE_SIZE + 5], b"world");
+    assert_eq!(&file[len - 4..], b"last");
+    println!("shared mappings passed!");
+}
+
+fn private(path: &str) {
+    let file = read_file(path).unwrap();
+    let fd = open(path, OpenFlags::empty());
+    assert!(fd >= 0);
+    let len = file.len() - PAGE_SIZE;
+    let addr = mmap(0, len, rw(), MapFlags::PRIVATE, fd as usize, PAGE_SIZE);
+    let mem = memory(addr, len);
+    assert_eq!(mem, &file[PAGE_SIZE..]);
+    mem[0] = b'X';
+    // changes are seen by neither the file nor a child
+    let pid = fork();
+    if pid == 0 {
+        assert_eq!(mem[0], b'X');
+        mem[1] = b'Y';
+        exit(0);
+    }
+    let mut status = 0;
+    assert_eq!(waitpid(pid as usize, &mut status), pid);
+    assert_eq!(status, 0);
+    assert_eq!(mem[1], file[PAGE_SIZE + 1]);
+    assert_eq!(msync(addr as usize, len, MS_SYNC), 0);
+    assert_eq!(munmap(addr as usize, len), 0);
+    assert_eq!(read_file(path).unwrap(), file);
+    close(fd as usize);
+    println!("private mappings passed!");
+}
+
+fn errors(path: &str) {
+    let ro = open(path, OpenFlags::empty()) as usize;
+    let shared = MapFlags::SHARED;
+    assert_eq!(mmap(0, PAGE_SIZE, rw(), shared, 99, 0), -EBADF);
+    assert_eq!(mmap(0, PAGE_SIZE, rw(), shared, ro, 0), -EACCES);
+    assert_eq!(mmap(0, 0, ProtFlags::READ, shared, ro, 0), -EINVAL);
+    assert_eq!(mmap(0, PAGE_SIZE, ProtFlags::READ, shared, ro, 1), -EINVAL);
+    assert_eq!(
+        mmap(
+            0,
+            PAGE_SIZE,
+            ProtFlags::READ,
+            shared | MapFlags::PRIVATE,
+            ro,
+            0
+        ),
+        -EINVAL
+    );
+    assert_eq!(
+        mmap(0, PAGE_SIZE, rw(), shared | MapFlags::ANONYMOUS, 0, 0),
+        -EINVAL
+    );
+    close(ro);
+    // neither pipes nor devices
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    assert_eq!(
+        mmap(0, PAGE_SIZE, ProtFlags::READ, shared, fds[0], 0),
+        -ENODEV
+    );
+    close(fds[0]);
+    close(fds[1]);
+    assert_eq!(mmap(0, PAGE_SIZE, ProtFlags::READ, shared, 0, 0), -ENODEV);
+    assert_eq!(munmap(1, PAGE_SIZE), -EINVAL);
+    println!("mmap errors passed!");
+}
+
+/// Pages of a running program are those of its file, which cannot change.
+fn running_program(path: &str) {
+    let image = read_file("/bin/mmap_tests\0").unwrap();
+    assert_eq!(open("/bin/mmap_tests\0", OpenFlags::WRONLY), -ETXTBSY);
+    assert_eq!(
+        open("/bin/mmap_tests\0", OpenFlags::WRONLY | OpenFlags::TRUNC),
+        -ETXTBSY
+    );
+    assert_eq!(read_file("/bin/mmap_tests\0").unwrap(), image);
+    // nor be mapped shared and writable, but be read
+    let fd = open("/bin/mmap_tests\0", OpenFlags::RDWR);
+    assert_eq!(fd, -ETXTBSY);
+    let fd = open("/bin/mmap_tests\0", OpenFlags::empty());
+    assert!(fd >= 0);
+    let addr = mmap(
+        0,
+        PAGE_SIZE,
+        ProtFlags::READ,
+        MapFlags::SHARED,
+        fd as usize,
+        0,
+    );
+    assert_eq!(memory(addr, PAGE_SIZE), &image[..PAGE_SIZE]);
+    assert_eq!(munmap(addr as usize, PAGE_SIZE), 0);
+    close(fd as usize);
+
+    // a file mapped shared and writable cannot run, even after it is closed
+    let fd = open(path, OpenFlags::RDWR);
+    assert!(fd >= 0);
+    let addr = mmap(0, PAGE_SIZE, rw(), MapFlags::SHARED, fd as usize, 0);
+    memory(addr, PAGE_SIZE);
+    close(fd as usize);
+    assert_eq!(exec(path), -ETXTBSY);
+    assert_eq!(munmap(addr as usize, PAGE_SIZE), 0);
+    println!("running program passed!");
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    anonymous();
+    let path = "/mmap_tests.tmp\0";
+    let data: Vec<u8> = (0..2 * PAGE_SIZE + 100)
+        .map(|i| (i * 7 % 251) as u8)
+        .collect();
+    write_file(path, &data).unwrap();
+    shared(path, &data);
+    private(path);
+    errors(path);
+    running_program(path);
+    assert_eq!(unlink(path), 0);
+    println!("mmap_tests passed!");
+    0
+}
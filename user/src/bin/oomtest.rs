@@ -0,0 +1,83 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::errno::ENOMEM;
+use user_lib::signal::SIGKILL;
+use user_lib::{exit, fork, mmap, wait, wifsignaled, wtermsig, MapFlags, ProtFlags};
+
+const PAGE_SIZE: usize = 4096;
+const CHUNK_SIZE: usize = 256 * PAGE_SIZE; // 1MB
+const NUM: usize = 4;
+
+/// Map memory until it runs out, memory and swap area are full then, and
+/// write to all of it again, which needs pages back from the swap area.
+fn work() -> i32 {
+    let mut chunks = Vec::new();
+    loop {
+        let addr = mmap(
+            0,
+            CHUNK_SIZE,
+            ProtFlags::READ | ProtFlags::WRITE,
+            MapFlags::PRIVATE | MapFlags::ANONYMOUS,
+            0,
+            0,
+        );
+        if addr == -ENOMEM {
+            break;
+        }
+        assert!(addr > 0);
+        chunks.push(addr as usize);
+    }
+    for round in 0..2u8 {
+        for &chunk in chunks.iter() {
+            for page in (chunk..chunk + CHUNK_SIZE).step_by(PAGE_SIZE) {
+                unsafe { (page as *mut u8).write_volatile(round) };
+            }
+        }
+    }
+    0
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut children = 0;
+    let mut fork_failed = 0;
+    for _ in 0..NUM {
+        let pid = fork();
+        if pid == 0 {
+            exit(work());
+        }
+        if pid == -ENOMEM {
+            fork_failed += 1;
+        } else {
+            assert!(pid > 0);
+            children += 1;
+        }
+    }
+    let mut exit_code: i32 = 0;
+    let mut killed = 0;
+    for _ in 0..children {
+        assert!(wait(&mut exit_code) > 0);
+        // the OOM killer sends SIGKILL
+        if wifsignaled(exit_code) {
+            assert_eq!(wtermsig(exit_code), SIGKILL);
+            killed += 1;
+        } else {
+            assert_eq!(exit_code, 0);
+        }
+    }
+    assert!(wait(&mut exit_code) < 0);
+    assert!(killed > 0, "the OOM killer did not run");
+    println!(
+        "{} forks failed, {} children killed by OOM killer.",
+        fork_failed, killed
+    );
+    println!("oomtest passed!");
+    0
+}
@@ -0,0 +1,144 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::vec::Vec;
+
+use user_lib::errno::{EBADF, EMFILE, EPIPE};
+use user_lib::signal::SIGPIPE;
+use user_lib::{
+    close, dup, dup2, execv, exit, fork, pipe, read, read_to_end, waitpid, wexitstatus, wifexited,
+    write, wtermsig,
+};
+
+fn parent_and_child() {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let pid = fork();
+    if pid == 0 {
+        close(fds[0]);
+        // larger than the pipe, the writer has to wait for the reader
+        for i in 0..64 {
+            let buf = [i as u8; 100];
+            assert_eq!(write(fds[1], &buf), 100);
+        }
+        exit(0);
+    }
+    close(fds[1]);
+    let buf = read_to_end(fds[0]).unwrap();
+    assert_eq!(buf.len(), 6400);
+    for (i, chunk) in buf.chunks(100).enumerate() {
+        assert!(chunk.iter().all(|&c| c == i as u8));
+    }
+    close(fds[0]);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(exit_code, 0);
+    println!("parent_and_child passed!");
+}
+
+fn exec_with_redirected_stdout() {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let pid = fork();
+    if pid == 0 {
+        // opened files are kept by exec
+        assert_eq!(dup2(fds[1], 1), 1);
+        close(fds[0]);
+        close(fds[1]);
+        let args = [
+            "echo\0".as_ptr(),
+            "hello\0".as_ptr(),
+            "pipe\0".as_ptr(),
+            core::ptr::null(),
+        ];
+        execv("echo\0", &args);
+        exit(1);
+    }
+    close(fds[1]);
+    assert_eq!(read_to_end(fds[0]).unwrap(), b"hello pipe\n");
+    close(fds[0]);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert!(wifexited(exit_code) && wexitstatus(exit_code) == 0);
+    println!("exec_with_redirected_stdout passed!");
+}
+
+fn broken_pipe() {
+    let pid = fork();
+    if pid == 0 {
+        let mut fds = [0usize; 2];
+        assert_eq!(pipe(&mut fds), 0);
+        close(fds[0]);
+        // killed by SIGPIPE on return
+        assert_eq!(write(fds[1], b"lost"), -EPIPE);
+        exit(0);
+    }
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert_eq!(wtermsig(exit_code), SIGPIPE);
+    println!("broken_pipe passed!");
+}
+
+fn duplicated_descriptors() {
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    let write_fd = dup(fds[1]);
+    assert!(write_fd > fds[1] as isize);
+    // the write end is still open through `write_fd`
+    close(fds[1]);
+    assert_eq!(write(write_fd as usize, b"dup"), 3);
+    close(write_fd as usize);
+    assert_eq!(read_to_end(fds[0]).unwrap(), b"dup");
+    close(fds[0]);
+    assert_eq!(close(fds[0]), -EBADF);
+    assert_eq!(read(fds[0], &mut [0u8; 8]), -EBADF);
+    assert_eq!(dup(fds[0]), -EBADF);
+    // the lowest free descriptor is reused
+    let mut new_fds = [0usize; 2];
+    assert_eq!(pipe(&mut new_fds), 0);
+    assert_eq!(new_fds, fds);
+    close(new_fds[0]);
+    close(new_fds[1]);
+    println!("duplicated_descriptors passed!");
+}
+
+fn descriptor_limit() {
+    assert_eq!(dup2(0, 1 << 40), -EBADF);
+    let mut fds = Vec::new();
+    loop {
+        match dup(0) {
+            fd if fd >= 0 => fds.push(fd as usize),
+            err => {
+                assert_eq!(err, -EMFILE);
+                break;
+            }
+        }
+    }
+    let mut pipe_fds = [0usize; 2];
+    assert_eq!(pipe(&mut pipe_fds), -EMFILE);
+    // a free descriptor is not enough for a pipe, nor kept by it
+    let free = *fds.last().unwrap();
+    close(free);
+    assert_eq!(pipe(&mut pipe_fds), -EMFILE);
+    assert_eq!(dup(0), free as isize);
+    for fd in fds {
+        close(fd);
+    }
+    println!("descriptor_limit passed!");
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    parent_and_child();
+    exec_with_redirected_stdout();
+    broken_pipe();
+    duplicated_descriptors();
+    descriptor_limit();
+    println!("pipe_tests passed!");
+    0
+}
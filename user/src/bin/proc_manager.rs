@@ -12,8 +12,18 @@ extern crate user_lib;
 const EXIT: i32 = 1;
 const WAIT: i32 = 2;
 const FORK: i32 = 3;
+const STOP: i32 = 4;
+const CONT: i32 = 5;
+const GETPPID: i32 = 6;
+const PROC_INFO: i32 = 7;
+const LIST_PIDS: i32 = 8;
 const DEBUG: bool = false;
 
+/// children of `PROC_INFO` fitting in the shared page
+const MAX_INFO_CHILDREN: usize = 1000;
+/// pids of `LIST_PIDS` fitting in the shared page
+const MAX_LIST_PIDS: usize = 1000;
+
 fn init_proc_manager() {
     PROC_MANAGER.exclusive_access().init();
 }
@@ -38,8 +48,10 @@ fn main() {
             WAIT => {
                 let parent_pid = read_from_shared_page(1) as usize;
                 let pid = read_from_shared_page(2);
-                let (found_pid, exit_code) =
-                    PROC_MANAGER.exclusive_access().waitpid(parent_pid, pid);
+                let options = read_from_shared_page(3) as usize;
+                let (found_pid, exit_code) = PROC_MANAGER
+                    .exclusive_access()
+                    .waitpid(parent_pid, pid, options);
                 write_to_shared_page(3, found_pid as i32);
                 write_to_shared_page(4, exit_code);
                 yield_();
@@ -53,6 +65,51 @@ fn main() {
                 }
                 yield_();
             }
+            STOP => {
+                let pid = read_from_shared_page(1) as usize;
+                let signum = read_from_shared_page(2);
+                PROC_MANAGER.exclusive_access().stop(pid, signum);
+                yield_();
+            }
+            CONT => {
+                let pid = read_from_shared_page(1) as usize;
+                PROC_MANAGER.exclusive_access().cont(pid);
+                yield_();
+            }
+            GETPPID => {
+                let pid = read_from_shared_page(1) as usize;
+                let ppid = PROC_MANAGER.exclusive_access().getppid(pid);
+                write_to_shared_page(2, ppid as i32);
+                yield_();
+            }
+            PROC_INFO => {
+                let pid = read_from_shared_page(1) as usize;
+                match PROC_MANAGER.exclusive_access().info(pid) {
+                    Some(info) => {
+                        // children beyond the shared page are left out
+                        let count = info.children.len().min(MAX_INFO_CHILDREN);
+                        write_to_shared_page(2, 1);
+                        write_to_shared_page(3, info.parent as i32);
+                        write_to_shared_page(4, info.zombie as i32);
+                        write_to_shared_page(5, info.exit_code);
+                        write_to_shared_page(6, count as i32);
+                        for (i, child) in info.children.iter().take(count).enumerate() {
+                            write_to_shared_page(7 + i, *child as i32);
+                        }
+                    }
+                    None => write_to_shared_page(2, 0),
+                }
+                yield_();
+            }
+            LIST_PIDS => {
+                let pids = PROC_MANAGER.exclusive_access().pids();
+                let count = pids.len().min(MAX_LIST_PIDS);
+                write_to_shared_page(1, count as i32);
+                for (i, pid) in pids.iter().take(count).enumerate() {
+                    write_to_shared_page(2 + i, *pid as i32);
+                }
+                yield_();
+            }
             _ => {
                 panic!("Unknown task: {}", task);
             }
@@ -0,0 +1,83 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::format;
+use alloc::string::String;
+use user_lib::errno::{ENOENT, EROFS};
+use user_lib::{
+    close, exit, fork, getpid, getppid, open, read_file, waitpid, write, yield_, OpenFlags,
+};
+
+/// The text of the file `path`, None if it cannot be opened.
+fn read_text(path: &str) -> Option<String> {
+    let data = read_file(path).ok()?;
+    Some(String::from_utf8(data).unwrap())
+}
+
+/// The value of `key` in a `key:\tvalue` file
+fn field(text: &str, key: &str) -> Option<String> {
+    text.lines()
+        .find_map(|line| line.strip_prefix(key)?.strip_prefix(":\t"))
+        .map(String::from)
+}
+
+fn status(pid: usize) -> Option<String> {
+    read_text(&format!("/proc/{}/status\0", pid))
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let pid = getpid() as usize;
+    let text = read_text("/proc/self/status\0").unwrap();
+    assert_eq!(field(&text, "Pid"), Some(format!("{}", pid)));
+    assert_eq!(field(&text, "State").as_deref(), Some("R (running)"));
+    assert_eq!(field(&text, "PPid"), Some(format!("{}", getppid())));
+    assert_eq!(field(&text, "Threads").as_deref(), Some("1"));
+    // code, data and stack at least
+    assert!(text.lines().filter(|line| line.starts_with("\t0x")).count() >= 3);
+
+    let child = fork();
+    if child == 0 {
+        exit(7);
+    }
+    let child = child as usize;
+    // until the child exits
+    let text = loop {
+        let text = status(child).unwrap();
+        if field(&text, "State").as_deref() == Some("Z (zombie)") {
+            break text;
+        }
+        yield_();
+    };
+    assert_eq!(field(&text, "ExitCode").as_deref(), Some("7"));
+    assert_eq!(field(&text, "PPid"), Some(format!("{}", pid)));
+    let text = read_text("/proc/self/status\0").unwrap();
+    assert_eq!(field(&text, "Children"), Some(format!("{}", child)));
+    let mut exit_code = 0;
+    assert_eq!(waitpid(child, &mut exit_code), child as isize);
+    assert!(status(child).is_none());
+    assert_eq!(
+        open(&format!("/proc/{}/status\0", child), OpenFlags::empty()),
+        -ENOENT
+    );
+
+    let text = read_text("/proc/meminfo\0").unwrap();
+    for key in ["MemTotal", "MemFree", "HeapTotal", "HeapUsed"] {
+        assert!(field(&text, key).unwrap().ends_with(" kB"));
+    }
+    let uptime = read_text("/proc/uptime\0").unwrap();
+    assert!(uptime.trim_end().parse::<f64>().unwrap() > 0.0);
+
+    let fd = open("/proc/meminfo\0", OpenFlags::WRONLY);
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, b"0"), -EROFS);
+    close(fd as usize);
+    assert_eq!(open("/proc/none\0", OpenFlags::CREATE), -EROFS);
+    println!("procfs_tests passed!");
+    0
+}
@@ -0,0 +1,98 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::sync::{Mutex, Semaphore};
+use user_lib::{exit, thread_create, waittid};
+
+const BUFFER_SIZE: usize = 4;
+const PRODUCERS: usize = 3;
+const ITEMS_PER_PRODUCER: usize = 100;
+
+/// Bounded ring buffer guarded by `lock`, `empty` and `full` count the slots.
+struct Shared {
+    lock: Mutex<Ring>,
+    empty: Semaphore,
+    full: Semaphore,
+}
+
+struct Ring {
+    buffer: [usize; BUFFER_SIZE],
+    head: usize,
+    tail: usize,
+}
+
+struct Arg<'a> {
+    shared: &'a Shared,
+    id: usize,
+}
+
+fn producer(arg: *const Arg) {
+    let arg = unsafe { &*arg };
+    for i in 0..ITEMS_PER_PRODUCER {
+        arg.shared.empty.down();
+        {
+            let mut ring = arg.shared.lock.lock();
+            let tail = ring.tail;
+            ring.buffer[tail] = arg.id * ITEMS_PER_PRODUCER + i;
+            ring.tail = (tail + 1) % BUFFER_SIZE;
+        }
+        arg.shared.full.up();
+    }
+    exit(0);
+}
+
+fn consumer(arg: *const Arg) {
+    let arg = unsafe { &*arg };
+    let mut seen = [false; PRODUCERS * ITEMS_PER_PRODUCER];
+    for _ in 0..PRODUCERS * ITEMS_PER_PRODUCER {
+        arg.shared.full.down();
+        let item = {
+            let mut ring = arg.shared.lock.lock();
+            let head = ring.head;
+            ring.head = (head + 1) % BUFFER_SIZE;
+            ring.buffer[head]
+        };
+        arg.shared.empty.up();
+        assert!(!seen[item], "item {} consumed twice", item);
+        seen[item] = true;
+    }
+    assert!(seen.iter().all(|&s| s));
+    exit(0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let shared = Shared {
+        lock: Mutex::new(Ring {
+            buffer: [0; BUFFER_SIZE],
+            head: 0,
+            tail: 0,
+        }),
+        empty: Semaphore::new(BUFFER_SIZE),
+        full: Semaphore::new(0),
+    };
+    let args: [Arg; PRODUCERS + 1] = core::array::from_fn(|id| Arg {
+        shared: &shared,
+        id,
+    });
+    let mut tids = [0; PRODUCERS + 1];
+    for (id, tid) in tids.iter_mut().enumerate() {
+        let entry = if id < PRODUCERS {
+            producer as usize
+        } else {
+            consumer as usize
+        };
+        *tid = thread_create(entry, &args[id] as *const _ as usize);
+        assert!(*tid > 0);
+    }
+    let mut exit_code: i32 = 0;
+    for tid in tids {
+        assert_eq!(waittid(tid as usize, &mut exit_code), tid);
+        assert_eq!(exit_code, 0);
+    }
+    println!("producer_consumer passed!");
+    0
+}
@@ -0,0 +1,113 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+extern crate alloc;
+
+use alloc::vec::Vec;
+use user_lib::errno::{EISDIR, ENOENT, ENOEXEC, ETXTBSY};
+use user_lib::{close, exec, fork, open, read_to_end, unlink, wait, wexitstatus, write, OpenFlags};
+
+/// Run `path` in a child, return its exit code, 100 if exec fails.
+fn run(path: &str) -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        exec(path);
+        user_lib::exit(100);
+    }
+    let mut exit_code = 0;
+    assert_eq!(wait(&mut exit_code), pid);
+    wexitstatus(exit_code)
+}
+
+/// Offsets of the loadable program headers of a 64-bit ELF image
+fn load_headers(image: &[u8]) -> Vec<usize> {
+    let read = |offset: usize, len: usize| {
+        let mut bytes = [0u8; 8];
+        bytes[..len].copy_from_slice(&image[offset..offset + len]);
+        u64::from_le_bytes(bytes) as usize
+    };
+    let (ph_offset, ph_size, ph_count) = (read(0x20, 8), read(0x36, 2), read(0x38, 2));
+    (0..ph_count)
+        .map(|i| ph_offset + i * ph_size)
+        .filter(|&ph| read(ph, 4) == 1)
+        .collect()
+}
+
+/// Write `image` with the 8-byte field at `offset` set to `value` to `path`.
+fn write_patched(path: &str, image: &[u8], offset: usize, value: u64) {
+    let mut patched = image.to_vec();
+    patched[offset..offset + 8].copy_from_slice(&value.to_le_bytes());
+    let fd = open(
+        path,
+        OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC,
+    );
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, &patched), patched.len() as isize);
+    close(fd as usize);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // the applications are seeded in /bin
+    let fd = open("/bin/hello_world\0", OpenFlags::empty());
+    assert!(fd >= 0);
+    let image = read_to_end(fd as usize).unwrap();
+    close(fd as usize);
+    assert_eq!(&image[..4], b"\x7fELF");
+
+    // a copy runs like the original
+    let copy = "/bin/hello_copy\0";
+    let fd = open(
+        copy,
+        OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC,
+    );
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, &image), image.len() as isize);
+    close(fd as usize);
+    assert_eq!(run(copy), 0);
+    assert_eq!(run("hello_copy\0"), 0);
+
+    // modifying the copy leaves the original intact
+    let fd = open(copy, OpenFlags::RDWR);
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, b"junk"), 4);
+    // not while it is open for writing
+    assert_eq!(exec(copy), -ETXTBSY);
+    close(fd as usize);
+    assert_eq!(run(copy), 100);
+    let fd = open(copy, OpenFlags::empty());
+    assert!(fd >= 0);
+    let fd2 = open("/bin/hello_world\0", OpenFlags::empty());
+    assert_eq!(read_to_end(fd2 as usize).unwrap(), image);
+    close(fd2 as usize);
+
+    // an unlinked file stays readable through its descriptor
+    assert_eq!(unlink(copy), 0);
+    assert_eq!(open(copy, OpenFlags::empty()), -ENOENT);
+    assert_eq!(unlink(copy), -ENOENT);
+    assert_eq!(read_to_end(fd as usize).unwrap().len(), image.len() - 4);
+    close(fd as usize);
+
+    // segments which cannot be loaded are refused
+    let bad = "/bin/bad_elf\0";
+    let loads = load_headers(&image);
+    assert!(loads.len() >= 2);
+    let first_vaddr = u64::from_le_bytes(image[loads[0] + 16..loads[0] + 24].try_into().unwrap());
+    // in the trampoline
+    write_patched(bad, &image, loads[0] + 16, 0xffff_ffff_ffff_e000);
+    assert_eq!(exec(bad), -ENOEXEC);
+    // the end overflows
+    write_patched(bad, &image, loads[0] + 40, u64::MAX);
+    assert_eq!(exec(bad), -ENOEXEC);
+    // overlapping another segment
+    write_patched(bad, &image, loads[1] + 16, first_vaddr);
+    assert_eq!(exec(bad), -ENOEXEC);
+    assert_eq!(unlink(bad), 0);
+
+    assert_eq!(unlink("/bin\0"), -EISDIR);
+    assert_eq!(unlink("/none/file\0"), -ENOENT);
+    println!("ramfs_tests passed!");
+    0
+}
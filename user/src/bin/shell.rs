@@ -5,97 +5,927 @@
 extern crate alloc;
 extern crate user_lib;
 
-const LF: u8 = 0x0au8; // Line Feed
-const CR: u8 = 0x0du8; // Carriage Return
-const DL: u8 = 0x7fu8; // Delete
-const BS: u8 = 0x08u8; // Backspace
-const CC: u8 = 3; // Ctrl+C
 const ROOT: bool = true;
 
+use alloc::collections::BTreeMap;
+use alloc::format;
 use alloc::string::String;
-use user_lib::console::getchar;
+use alloc::vec;
+use alloc::vec::Vec;
+use user_lib::errno::strerror;
+use user_lib::shell::{
+    is_name, parse, AndOrList, Command, Connector, IfClause, LineEditor, Pipeline, Redirect,
+    RedirectTarget, SimpleCommand, Statement, WhileClause, Word,
+};
+use user_lib::signal::*;
 use user_lib::*;
 
-fn print_prompt() {
-    let prompt = if ROOT {
+/// Job control signals the shell itself does not respond to.
+/// Ctrl+C (SIGINT) is not ignored, it terminates the shell at the prompt.
+const JOB_CONTROL_SIGNALS: [i32; 3] = [SIGTSTP, SIGTTIN, SIGTTOU];
+
+fn set_job_control_signals(handler: usize) {
+    let action = SignalAction {
+        handler,
+        ..Default::default()
+    };
+    for signum in JOB_CONTROL_SIGNALS {
+        sigaction(signum, Some(&action), None);
+    }
+}
+
+fn prompt() -> &'static str {
+    if ROOT {
         "root@ACore:/# "
     } else {
         "\x1b[32mhenryhe@ACore\x1b[0m:\x1b[34m~\x1b[0m$ "
-    };
-    print!("{}", prompt);
+    }
 }
 
 fn check_permission(name: &str) -> bool {
-    if name == "proc_manager\0" || name == "initproc\0" || name == "shell\0" {
-        println!("Shell: Permission denied!");
-        return false;
-    } else if name == "shutdown\0" {
-        shutdown();
-    } else if name == "ls\0" {
-        ls();
+    if name == "proc_manager" || name == "initproc" {
+        eprintln!("Shell: Permission denied!");
         return false;
     }
     true
 }
 
-#[no_mangle]
-pub fn main() -> i32 {
-    println!("Welcome to Shell!"); // Print welcome message
-                                   // println!("Shell pid = {}", getpid()); // Print the PID of the shell process
-    let mut line: String = String::new(); // Initialize an empty string to store user input
-    print_prompt();
+/// Exit status of a command like `$?` in bash: the exit code, or 128 plus the
+/// signal which killed or stopped it
+fn command_status(status: i32) -> i32 {
+    if wifexited(status) {
+        wexitstatus(status)
+    } else if wifstopped(status) {
+        128 + wstopsig(status)
+    } else {
+        128 + wtermsig(status)
+    }
+}
 
-    loop {
-        let c = getchar(); // Read a character from user input
-        match c {
-            CC => {
-                println!("^C");
+// ------------------------------ jobs ------------------------------
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum JobState {
+    Running,
+    Stopped,
+}
+
+/// A pipeline not finished yet, running in its own process group
+struct Job {
+    /// job number shown as `[id]`, used as `%id`
+    id: usize,
+    /// process group, the pid of the first command
+    pgid: usize,
+    /// processes not exited yet
+    pids: Vec<usize>,
+    /// the last command, whose status is the status of the job
+    last_pid: usize,
+    /// wait status of `last_pid` after it exits
+    status: i32,
+    command: String,
+    state: JobState,
+}
+
+impl Job {
+    fn exited(&mut self, pid: usize, status: i32) {
+        self.pids.retain(|&p| p != pid);
+        if pid == self.last_pid {
+            self.status = status;
+        }
+    }
+}
+
+/// Background and stopped jobs
+struct JobTable {
+    /// in the order they are added, the last one is the current job (`+`)
+    jobs: Vec<Job>,
+}
+
+impl JobTable {
+    fn new() -> Self {
+        Self { jobs: Vec::new() }
+    }
+
+    /// Add a running job, return its id.
+    fn add(&mut self, pgid: usize, pids: Vec<usize>, command: String) -> usize {
+        let id = self.jobs.iter().map(|job| job.id).max().unwrap_or(0) + 1;
+        self.jobs.push(Job {
+            id,
+            pgid,
+            last_pid: *pids.last().unwrap(),
+            pids,
+            status: 0,
+            command,
+            state: JobState::Running,
+        });
+        id
+    }
+
+    fn remove(&mut self, index: usize) -> Job {
+        self.jobs.remove(index)
+    }
+
+    /// Find the job by `%id` (or just `id`), or the current job if `spec` is None.
+    fn find(&self, spec: Option<&str>) -> Option<usize> {
+        match spec {
+            None => self.jobs.len().checked_sub(1),
+            Some(spec) => {
+                let id: usize = spec.strip_prefix('%').unwrap_or(spec).parse().ok()?;
+                self.jobs.iter().position(|job| job.id == id)
+            }
+        }
+    }
+
+    fn find_by_pid(&self, pid: usize) -> Option<usize> {
+        self.jobs.iter().position(|job| job.pids.contains(&pid))
+    }
+
+    fn print(&self, index: usize, state: &str) {
+        let job = &self.jobs[index];
+        let current = if index + 1 == self.jobs.len() {
+            '+'
+        } else {
+            ' '
+        };
+        println!("[{}]{}  {:<24}{}", job.id, current, state, job.command);
+    }
+
+    fn print_all(&self) {
+        for (index, job) in self.jobs.iter().enumerate() {
+            let state = match job.state {
+                JobState::Running => "Running",
+                JobState::Stopped => "Stopped",
+            };
+            self.print(index, state);
+        }
+    }
+
+    /// Report background jobs which have finished or stopped since the last prompt.
+    fn reap(&mut self) {
+        loop {
+            let mut status = 0;
+            let pid = waitpid_options(-1, &mut status, WNOHANG | WUNTRACED);
+            if pid <= 0 {
+                break;
+            }
+            let index = match self.find_by_pid(pid as usize) {
+                Some(index) => index,
+                None => continue,
+            };
+            if wifstopped(status) {
+                if self.jobs[index].state != JobState::Stopped {
+                    self.jobs[index].state = JobState::Stopped;
+                    self.print(index, "Stopped");
+                }
+                continue;
+            }
+            self.jobs[index].exited(pid as usize, status);
+            if !self.jobs[index].pids.is_empty() {
+                continue;
+            }
+            let status = self.jobs[index].status;
+            if !wifexited(status) {
+                self.print(index, &signal_name(wtermsig(status)));
+            } else if wexitstatus(status) == 0 {
+                self.print(index, "Done");
+            } else {
+                self.print(index, &format!("Exit {}", wexitstatus(status)));
+            }
+            self.remove(index);
+        }
+    }
+}
+
+// ---------------------------- running ----------------------------
+
+const BUILTINS: [&str; 12] = [
+    "jobs", "fg", "bg", "kill", "history", "export", "exit", "true", "false", "cd", "pwd",
+    "shutdown",
+];
+
+/// A shell variable
+struct Variable {
+    value: String,
+    /// passed to programs in their environment
+    exported: bool,
+}
+
+struct Shell {
+    jobs: JobTable,
+    editor: LineEditor,
+    pid: usize,
+    /// process group of the shell
+    pgid: usize,
+    /// whether jobs run in their own process groups and take the terminal,
+    /// false in a subshell or when running a script
+    job_control: bool,
+    vars: BTreeMap<String, Variable>,
+    /// `$0`, `$1`, ...: the shell or the script, then its arguments
+    params: Vec<String>,
+    /// `$?`, the status of the last pipeline
+    last_status: i32,
+    /// a foreground job is interrupted by Ctrl+C, skip the rest of the line
+    interrupted: bool,
+}
+
+impl Shell {
+    fn new(job_control: bool, params: Vec<String>) -> Self {
+        let vars = vars()
+            .into_iter()
+            .map(|(name, value)| {
+                let var = Variable {
+                    value: String::from(value),
+                    exported: true,
+                };
+                (String::from(name), var)
+            })
+            .collect();
+        Self {
+            jobs: JobTable::new(),
+            editor: LineEditor::new(),
+            pid: getpid() as usize,
+            pgid: getpid() as usize,
+            job_control,
+            vars,
+            params,
+            last_status: 0,
+            interrupted: false,
+        }
+    }
+
+    /// Value of parameter `name`, empty if it is not set
+    fn lookup(&self, name: &str) -> String {
+        match name {
+            "?" => format!("{}", self.last_status),
+            "$" => format!("{}", self.pid),
+            "#" => format!("{}", self.params.len().saturating_sub(1)),
+            _ => match name.parse::<usize>() {
+                Ok(i) => self.params.get(i).cloned().unwrap_or_default(),
+                Err(_) => self
+                    .vars
+                    .get(name)
+                    .map(|var| var.value.clone())
+                    .unwrap_or_default(),
+            },
+        }
+    }
+
+    fn expand(&self, word: &Word) -> String {
+        word.expand(&|name| self.lookup(name))
+    }
+
+    fn set_var(&mut self, name: &str, value: String) {
+        match self.vars.get_mut(name) {
+            Some(var) => var.value = value,
+            None => {
+                let var = Variable {
+                    value,
+                    exported: false,
+                };
+                self.vars.insert(String::from(name), var);
+            }
+        }
+    }
+
+    /// `NAME=value\0` strings of the exported variables and `assignments`, for exec
+    fn environment(&self, assignments: &[(String, String)]) -> Vec<String> {
+        let mut env: BTreeMap<&str, &str> = self
+            .vars
+            .iter()
+            .filter(|(_, var)| var.exported)
+            .map(|(name, var)| (name.as_str(), var.value.as_str()))
+            .collect();
+        for (name, value) in assignments.iter() {
+            env.insert(name, value);
+        }
+        env.iter()
+            .map(|(name, value)| format!("{}={}\0", name, value))
+            .collect()
+    }
+
+    /// Make the descriptors refer to the redirection targets, return false on error.
+    fn apply_redirects(&self, redirects: &[Redirect]) -> bool {
+        for redirect in redirects {
+            let (path, flags) = match &redirect.target {
+                RedirectTarget::Fd(fd) => {
+                    if dup2(*fd, redirect.fd) < 0 {
+                        eprintln!("Shell: {}: Bad file descriptor", fd);
+                        return false;
+                    }
+                    continue;
+                }
+                RedirectTarget::Read(path) => (path, OpenFlags::empty()),
+                RedirectTarget::Write(path) => (
+                    path,
+                    OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC,
+                ),
+                RedirectTarget::Append(path) => (
+                    path,
+                    OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::APPEND,
+                ),
+            };
+            let path = self.expand(path);
+            let fd = open(format!("{}\0", path).as_str(), flags);
+            if fd < 0 {
+                eprintln!("Shell: {}: {}", path, strerror(-fd));
+                return false;
+            }
+            if fd as usize != redirect.fd {
+                dup2(fd as usize, redirect.fd);
+                close(fd as usize);
+            }
+        }
+        true
+    }
+
+    /// Run statements in order, return the status of the last one.
+    fn run_list(&mut self, statements: &[Statement]) -> i32 {
+        for statement in statements.iter() {
+            if self.interrupted {
                 break;
             }
-            LF | CR => {
-                // If it's a Line Feed or Carriage Return
-                println!(""); // Print a newline (echo the newline)
-                if !line.is_empty() {
-                    line.push('\0'); // Add a null terminator to the string (C-style string)
-
-                    if check_permission(line.as_str()) {
-                        let pid = fork(); // Create a child process
-                        if pid == 0 {
-                            // In the child process
-                            if exec(line.as_str()) == -1 {
-                                println!("Shell: Error when executing {}!", line); // Print error if execution fails
-                                return -4; // Return error code
-                            }
-                            unreachable!();
-                        } else {
-                            // In the parent process
-                            let mut exit_code: i32 = 0;
-                            let exit_pid = waitpid(pid as usize, &mut exit_code); // Wait for the child process to finish
-                            assert!(pid == exit_pid, "waitpid error"); // Ensure the process waited for is the correct child process
-                            println!("Shell: Process {} exited with code {}", pid, exit_code);
-                            // Print the exit code of the child process
-                        }
+            if statement.background {
+                self.run_background(&statement.and_or);
+                self.last_status = 0;
+            } else {
+                self.run_and_or(&statement.and_or);
+            }
+        }
+        self.last_status
+    }
+
+    /// Run pipelines connected by `&&` and `||`, return the status of the last one run.
+    fn run_and_or(&mut self, list: &AndOrList) -> i32 {
+        let mut status = self.run_pipeline(&list.first, false);
+        self.last_status = status;
+        for (connector, pipeline) in list.rest.iter() {
+            if self.interrupted {
+                break;
+            }
+            let run = match connector {
+                Connector::And => status == 0,
+                Connector::Or => status != 0,
+            };
+            if run {
+                status = self.run_pipeline(pipeline, false);
+                self.last_status = status;
+            }
+        }
+        status
+    }
+
+    fn run_background(&mut self, list: &AndOrList) {
+        if list.rest.is_empty() {
+            self.run_pipeline(&list.first, true);
+            return;
+        }
+        // a subshell runs the whole list as one job
+        let pid = fork();
+        if pid == 0 {
+            if self.job_control {
+                setpgid(0, 0);
+                set_job_control_signals(SIG_DFL);
+            }
+            self.job_control = false;
+            exit(self.run_and_or(list));
+        }
+        let pid = pid as usize;
+        if self.job_control {
+            setpgid(pid, pid);
+        }
+        let id = self.jobs.add(pid, vec![pid], format!("{}", list));
+        println!("[{}] {}", id, pid);
+    }
+
+    /// Run the commands connected by pipes, each in a child process.
+    /// A single builtin, assignment or compound command in the foreground runs
+    /// in the shell itself instead.
+    ///
+    /// Return the status of the last command, or 0 if it runs in the background.
+    fn run_pipeline(&mut self, pipeline: &Pipeline, background: bool) -> i32 {
+        let commands = &pipeline.commands;
+        if commands.len() == 1 && !background {
+            match &commands[0] {
+                Command::Simple(command) => {
+                    let name = command.args.first().map(|arg| self.expand(arg));
+                    match name {
+                        Some(name) if !BUILTINS.contains(&name.as_str()) => {}
+                        _ => return self.run_in_shell(command),
                     }
-                    line.clear(); // Clear the input line
                 }
-                print_prompt();
-            }
-            BS | DL => {
-                // If it's a Backspace or Delete
-                if !line.is_empty() {
-                    // If the input line is not empty
-                    print!("{}", BS as char); // Print a backspace character
-                    print!(" "); // Print a space character (erase the last character)
-                    print!("{}", BS as char); // Print another backspace character (move the cursor back)
-                    line.pop(); // Remove the last character from the input line
+                Command::If(clause) => return self.run_if(clause),
+                Command::While(clause) => return self.run_while(clause),
+            }
+        }
+        let mut pids = Vec::new();
+        let mut pgid = 0;
+        // read end of the pipe from the last command
+        let mut stdin_fd = None;
+        for (i, command) in commands.iter().enumerate() {
+            let mut fds = [0usize; 2];
+            let to_pipe = i + 1 < commands.len();
+            if to_pipe {
+                pipe(&mut fds);
+            }
+            let pid = fork(); // Create a child process
+            if pid == 0 {
+                // In the child process: join the job
+                if self.job_control {
+                    setpgid(0, pgid);
+                    if !background {
+                        tcsetpgrp(0, getpgrp() as usize);
+                    }
+                    set_job_control_signals(SIG_DFL);
+                }
+                // commands run by a compound command stay in this job
+                self.job_control = false;
+                if let Some(fd) = stdin_fd {
+                    dup2(fd, 0);
+                    close(fd);
+                }
+                if to_pipe {
+                    dup2(fds[1], 1);
+                    close(fds[0]);
+                    close(fds[1]);
                 }
+                exit(self.run_command(command));
             }
+            // In the parent process, also set the job up in case the child has not run yet
+            let pid = pid as usize;
+            if pgid == 0 {
+                pgid = pid;
+            }
+            if self.job_control {
+                setpgid(pid, pgid);
+            }
+            if let Some(fd) = stdin_fd.take() {
+                close(fd);
+            }
+            if to_pipe {
+                close(fds[1]);
+                stdin_fd = Some(fds[0]);
+            }
+            pids.push(pid);
+        }
+        let id = self.jobs.add(pgid, pids, format!("{}", pipeline));
+        if background {
+            println!("[{}] {}", id, pgid);
+            return 0;
+        }
+        self.wait_foreground(self.jobs.jobs.len() - 1)
+    }
+
+    /// Run `command` in a child process, return the exit code if it cannot be executed.
+    fn run_command(&mut self, command: &Command) -> i32 {
+        let command = match command {
+            Command::Simple(command) => command,
+            Command::If(clause) => return self.run_if(clause),
+            Command::While(clause) => return self.run_while(clause),
+        };
+        if !self.apply_redirects(&command.redirects) {
+            return 1;
+        }
+        let args: Vec<String> = command.args.iter().map(|arg| self.expand(arg)).collect();
+        if args.is_empty() {
+            // assignments in a child have no effect
+            return 0;
+        }
+        if BUILTINS.contains(&args[0].as_str()) {
+            return self.run_builtin(&args);
+        }
+        if !check_permission(&args[0]) {
+            return 126;
+        }
+        let assignments: Vec<(String, String)> = command
+            .assignments
+            .iter()
+            .map(|assignment| (assignment.name.clone(), self.expand(&assignment.value)))
+            .collect();
+        // C-style strings for exec
+        let args: Vec<String> = args.iter().map(|arg| format!("{}\0", arg)).collect();
+        let envs = self.environment(&assignments);
+        let mut argv: Vec<*const u8> = args.iter().map(|arg| arg.as_ptr()).collect();
+        argv.push(core::ptr::null());
+        let mut envp: Vec<*const u8> = envs.iter().map(|env| env.as_ptr()).collect();
+        envp.push(core::ptr::null());
+        execve(args[0].as_str(), &argv, &envp);
+        eprintln!("Shell: Error when executing {}!", command.args[0]); // Print error if execution fails
+        127
+    }
+
+    /// Run a builtin or set variables in the shell itself, redirections are undone
+    /// afterwards.
+    fn run_in_shell(&mut self, command: &SimpleCommand) -> i32 {
+        let saved: Vec<(usize, isize)> = command
+            .redirects
+            .iter()
+            .map(|redirect| (redirect.fd, dup(redirect.fd)))
+            .collect();
+        let status = if !self.apply_redirects(&command.redirects) {
+            1
+        } else if command.args.is_empty() {
+            for assignment in command.assignments.iter() {
+                let value = self.expand(&assignment.value);
+                self.set_var(&assignment.name, value);
+            }
+            0
+        } else {
+            let args: Vec<String> = command.args.iter().map(|arg| self.expand(arg)).collect();
+            self.run_builtin(&args)
+        };
+        for &(fd, saved_fd) in saved.iter().rev() {
+            if saved_fd >= 0 {
+                dup2(saved_fd as usize, fd);
+                close(saved_fd as usize);
+            } else {
+                close(fd);
+            }
+        }
+        status
+    }
+
+    fn run_if(&mut self, clause: &IfClause) -> i32 {
+        for (condition, body) in clause.branches.iter() {
+            let status = self.run_list(condition);
+            if self.interrupted {
+                return status;
+            }
+            if status == 0 {
+                return self.run_list(body);
+            }
+        }
+        match &clause.otherwise {
+            Some(otherwise) => self.run_list(otherwise),
+            None => 0,
+        }
+    }
+
+    fn run_while(&mut self, clause: &WhileClause) -> i32 {
+        let mut status = 0;
+        while self.run_list(&clause.condition) == 0 && !self.interrupted {
+            status = self.run_list(&clause.body);
+            if self.interrupted {
+                break;
+            }
+        }
+        status
+    }
+
+    /// Give the terminal to job `index` and wait until it exits or stops, then take
+    /// the terminal back. A job finished is removed from the job table.
+    fn wait_foreground(&mut self, index: usize) -> i32 {
+        let pgid = self.jobs.jobs[index].pgid;
+        let options = if self.job_control {
+            tcsetpgrp(0, pgid);
+            WUNTRACED
+        } else {
+            0
+        };
+        let mut stopped = None;
+        for pid in self.jobs.jobs[index].pids.clone() {
+            let mut exit_code: i32 = 0;
+            let exit_pid = waitpid_options(pid as isize, &mut exit_code, options); // Wait for the child process to finish or stop
+            assert!(pid as isize == exit_pid, "waitpid error"); // Ensure the process waited for is the correct child process
+            if wifstopped(exit_code) {
+                stopped = Some(exit_code);
+            } else {
+                self.jobs.jobs[index].exited(pid, exit_code);
+            }
+        }
+        if self.job_control {
+            tcsetpgrp(0, self.pgid);
+        }
+        if let Some(status) = stopped {
+            // it becomes the current job
+            let mut job = self.jobs.remove(index);
+            job.state = JobState::Stopped;
+            self.jobs.jobs.push(job);
+            println!("");
+            self.jobs.print(self.jobs.jobs.len() - 1, "Stopped");
+            return command_status(status);
+        }
+        let job = self.jobs.remove(index);
+        if wifsignaled(job.status) && wtermsig(job.status) == SIGINT {
+            self.interrupted = true;
+        }
+        if self.job_control {
+            if wifexited(job.status) {
+                println!(
+                    "Shell: Process {} exited with code {}",
+                    job.pgid,
+                    wexitstatus(job.status)
+                );
+            } else {
+                println!(
+                    "\nShell: Process {} killed by signal {}",
+                    job.pgid,
+                    wtermsig(job.status)
+                );
+            }
+        }
+        command_status(job.status)
+    }
+
+    fn run_builtin(&mut self, args: &[String]) -> i32 {
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        match args[0] {
+            "jobs" => {
+                self.jobs.print_all();
+                0
+            }
+            "fg" => self.builtin_fg(args.get(1).copied()),
+            "bg" => self.builtin_bg(args.get(1).copied()),
+            "kill" => self.builtin_kill(&args[1..]),
+            "history" => {
+                for (i, command) in self.editor.history().iter().enumerate() {
+                    println!("{:>5}  {}", i + 1, command);
+                }
+                0
+            }
+            "export" => self.builtin_export(&args[1..]),
+            "exit" => self.builtin_exit(args.get(1).copied()),
+            "true" => 0,
+            "false" => 1,
+            "cd" => self.builtin_cd(args.get(1).copied()),
+            "pwd" => {
+                println!("{}", getcwd());
+                0
+            }
+            "shutdown" => shutdown(),
+            _ => unreachable!(),
+        }
+    }
+
+    /// `cd [dir]`, to `$HOME` or `/` without arguments
+    fn builtin_cd(&mut self, dir: Option<&str>) -> i32 {
+        let mut path = match dir {
+            Some(dir) => String::from(dir),
+            None => self
+                .vars
+                .get("HOME")
+                .map_or(String::from("/"), |var| var.value.clone()),
+        };
+        path.push('\0');
+        let ret = chdir(path.as_str());
+        if ret < 0 {
+            eprintln!("cd: {}: {}", &path[..path.len() - 1], strerror(-ret));
+            return 1;
+        }
+        0
+    }
+
+    /// `export [NAME[=value]]...`, list the exported variables without arguments
+    fn builtin_export(&mut self, args: &[&str]) -> i32 {
+        if args.is_empty() {
+            for (name, var) in self.vars.iter().filter(|(_, var)| var.exported) {
+                println!("export {}='{}'", name, var.value.replace('\'', "'\\''"));
+            }
+            return 0;
+        }
+        let mut status = 0;
+        for arg in args.iter() {
+            let (name, value) = match arg.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (*arg, None),
+            };
+            if !is_name(name) {
+                eprintln!("export: `{}': not a valid identifier", arg);
+                status = 1;
+                continue;
+            }
+            let value = value.map(String::from).unwrap_or_else(|| self.lookup(name));
+            self.set_var(name, value);
+            self.vars.get_mut(name).unwrap().exported = true;
+        }
+        status
+    }
+
+    /// `exit [n]`, with the status of the last command by default
+    fn builtin_exit(&mut self, code: Option<&str>) -> i32 {
+        let code = match code.map(str::parse::<i32>) {
+            None => self.last_status,
+            Some(Ok(code)) => code,
+            Some(Err(_)) => {
+                eprintln!("exit: {}: numeric argument required", code.unwrap());
+                2
+            }
+        };
+        exit(code);
+        unreachable!()
+    }
+
+    fn builtin_fg(&mut self, spec: Option<&str>) -> i32 {
+        let index = match self.jobs.find(spec) {
+            Some(index) => index,
+            None => {
+                eprintln!("fg: no such job");
+                return 1;
+            }
+        };
+        let job = &mut self.jobs.jobs[index];
+        println!("{}", job.command);
+        job.state = JobState::Running;
+        // the terminal first, so that it does not stop again at once on reading
+        if self.job_control {
+            tcsetpgrp(0, job.pgid);
+        }
+        killpg(job.pgid, SIGCONT);
+        self.wait_foreground(index)
+    }
+
+    fn builtin_bg(&mut self, spec: Option<&str>) -> i32 {
+        let index = match self.jobs.find(spec) {
+            Some(index) => index,
+            None => {
+                eprintln!("bg: no such job");
+                return 1;
+            }
+        };
+        let job = &mut self.jobs.jobs[index];
+        if job.state == JobState::Running {
+            eprintln!("bg: job {} already in background", job.id);
+            return 0;
+        }
+        job.state = JobState::Running;
+        killpg(job.pgid, SIGCONT);
+        println!("[{}] {} &", job.id, job.command);
+        0
+    }
+
+    /// `kill [-SIGNAL] %job|pid`, SIGTERM by default
+    fn builtin_kill(&mut self, args: &[&str]) -> i32 {
+        let (signum, target) = match args {
+            [target] => (Some(SIGTERM), *target),
+            [signal, target] if signal.starts_with('-') => (parse_signal(&signal[1..]), *target),
             _ => {
-                // For any other character
-                print!("{}", c as char); // Print the character
-                line.push(c as char); // Add the character to the input line
+                eprintln!("kill: usage: kill [-SIGNAL] %job|pid");
+                return 2;
+            }
+        };
+        let signum = match signum {
+            Some(signum) => signum,
+            None => {
+                eprintln!("kill: invalid signal {}", args[0]);
+                return 1;
+            }
+        };
+        let ret = if target.starts_with('%') {
+            match self.jobs.find(Some(target)) {
+                Some(index) => killpg(self.jobs.jobs[index].pgid, signum),
+                None => {
+                    eprintln!("kill: {}: no such job", target);
+                    return 1;
+                }
+            }
+        } else {
+            match target.parse() {
+                Ok(pid) => kill(pid, signum),
+                Err(_) => {
+                    eprintln!("kill: {}: invalid pid", target);
+                    return 1;
+                }
+            }
+        };
+        if ret < 0 {
+            eprintln!("kill: ({}) failed with error {}", target, -ret);
+            return 1;
+        }
+        0
+    }
+}
+
+// ---------------------------- signals ----------------------------
+
+const SIGNAL_NAMES: [(&str, i32); 12] = [
+    ("HUP", SIGHUP),
+    ("INT", SIGINT),
+    ("QUIT", SIGQUIT),
+    ("KILL", SIGKILL),
+    ("USR1", SIGUSR1),
+    ("SEGV", SIGSEGV),
+    ("USR2", SIGUSR2),
+    ("TERM", SIGTERM),
+    ("CONT", SIGCONT),
+    ("STOP", SIGSTOP),
+    ("TSTP", SIGTSTP),
+    ("ALRM", SIGALRM),
+];
+
+/// Parse `9`, `KILL` or `SIGKILL`.
+fn parse_signal(name: &str) -> Option<i32> {
+    if let Ok(signum) = name.parse() {
+        return Some(signum);
+    }
+    let name = name.strip_prefix("SIG").unwrap_or(name);
+    SIGNAL_NAMES
+        .iter()
+        .find(|(signame, _)| *signame == name)
+        .map(|(_, signum)| *signum)
+}
+
+/// Description of a job killed by `signum`
+fn signal_name(signum: i32) -> String {
+    let name = match signum {
+        SIGHUP => "Hangup",
+        SIGINT => "Interrupt",
+        SIGQUIT => "Quit",
+        SIGILL => "Illegal instruction",
+        SIGTRAP => "Trace/breakpoint trap",
+        SIGBUS => "Bus error",
+        SIGKILL => "Killed",
+        SIGSEGV => "Segmentation fault",
+        SIGPIPE => "Broken pipe",
+        SIGTERM => "Terminated",
+        _ => return format!("Signal {}", signum),
+    };
+    String::from(name)
+}
+
+/// `shell script [args]...`: run a script without job control, exit with the
+/// status of its last command.
+fn run_script(params: Vec<String>) -> i32 {
+    let path = params[0].as_str();
+    let text = match read_file(&format!("{}\0", path)).map(String::from_utf8) {
+        Ok(Ok(text)) => text,
+        Ok(Err(_)) => {
+            eprintln!("Shell: {}: cannot execute binary file", path);
+            return 126;
+        }
+        Err(err) => {
+            eprintln!("Shell: {}: {}", path, strerror(-err));
+            return 127;
+        }
+    };
+    let statements = match parse(&text) {
+        Ok(statements) => statements,
+        Err(err) => {
+            eprintln!("Shell: {}: {}", path, err);
+            return 2;
+        }
+    };
+    let mut shell = Shell::new(false, params);
+    shell.run_list(&statements)
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let args = args();
+    if args.len() > 1 {
+        return run_script(args[1..].iter().map(|arg| String::from(*arg)).collect());
+    }
+    println!("Welcome to Shell!"); // Print welcome message
+                                   // println!("Shell pid = {}", getpid()); // Print the PID of the shell process
+
+    // run in our own process group, in the foreground of the console
+    let mut shell = Shell::new(true, vec![String::from("shell")]);
+    setpgid(0, 0);
+    tcsetpgrp(0, shell.pgid);
+    set_job_control_signals(SIG_IGN);
+
+    // builtins and apps, for tab completion
+    let commands: Vec<String> = BUILTINS
+        .iter()
+        .map(|builtin| String::from(*builtin))
+        .chain(apps())
+        .collect();
+    let complete = |before: &str, word: &str| -> Vec<String> {
+        // only program names, at the start of a command
+        let before = before.trim_end();
+        if !before.is_empty() && !before.ends_with(['|', '&', ';']) {
+            return Vec::new();
+        }
+        commands
+            .iter()
+            .filter(|command| command.starts_with(word))
+            .cloned()
+            .collect()
+    };
+
+    // lines of a command not complete yet, like `if true` or `echo 'a`
+    let mut text = String::new();
+    loop {
+        let prompt = if text.is_empty() { prompt() } else { "> " };
+        let line = match shell.editor.read_line(prompt, &complete) {
+            Some(line) => line,
+            None => break,
+        };
+        text.push_str(&line);
+        match parse(&text) {
+            Err(err) if err.is_incomplete() => {
+                text.push('\n');
+                continue;
+            }
+            Err(err) => {
+                eprintln!("Shell: {}", err);
+                shell.last_status = 2;
+            }
+            Ok(statements) => {
+                shell.interrupted = false;
+                shell.run_list(&statements);
             }
         }
+        text.clear();
+        shell.jobs.reap(); // Report background jobs before the next prompt
     }
-    0
+    shell.last_status
 }
@@ -0,0 +1,175 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+extern crate alloc;
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use user_lib::shell::{parse, Command};
+use user_lib::{
+    close, dup2, execv, exit, fork, open, read_file, waitpid, wexitstatus, wifexited, write_file,
+    OpenFlags,
+};
+
+const SCRIPT: &str = "shell_tests.sh\0";
+const OUTPUT: &str = "shell_tests.out\0";
+
+/// Run `script` by `shell shell_tests.sh x y`, return its exit code and output.
+fn run_script(script: &str) -> (i32, String) {
+    write_file(SCRIPT, script.as_bytes()).unwrap();
+    let pid = fork();
+    if pid == 0 {
+        let fd = open(
+            OUTPUT,
+            OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC,
+        );
+        assert!(fd >= 0);
+        dup2(fd as usize, 1);
+        close(fd as usize);
+        let argv = [
+            "shell\0".as_ptr(),
+            SCRIPT.as_ptr(),
+            "x\0".as_ptr(),
+            "y\0".as_ptr(),
+            core::ptr::null(),
+        ];
+        execv("shell\0", &argv);
+        exit(-1);
+    }
+    let mut status = 0;
+    assert_eq!(waitpid(pid as usize, &mut status), pid);
+    assert!(wifexited(status));
+    let output = String::from_utf8(read_file(OUTPUT).unwrap()).unwrap();
+    (wexitstatus(status), output)
+}
+
+fn parsing() {
+    // more lines may complete these
+    for text in ["if true", "echo 'a", "ls |", "while true; do ls; done &&"] {
+        assert!(parse(text).unwrap_err().is_incomplete(), "{}", text);
+    }
+    for text in ["fi", "if; then ls; fi", "ls ;;", "echo >", "echo ${a b}"] {
+        assert!(!parse(text).unwrap_err().is_incomplete(), "{}", text);
+    }
+    let statements = parse("a=1 b=$a env\nif true; then ls; fi").unwrap();
+    assert_eq!(statements.len(), 2);
+    match &statements[0].and_or.first.commands[0] {
+        Command::Simple(command) => {
+            assert_eq!(command.assignments.len(), 2);
+            assert_eq!(command.args.len(), 1);
+        }
+        _ => panic!("not a simple command"),
+    }
+    assert!(matches!(
+        statements[1].and_or.first.commands[0],
+        Command::If(_)
+    ));
+    println!("parsing passed!");
+}
+
+fn variables() {
+    let script = "a=hello\n\
+                  b=\"$a world\"\n\
+                  echo $b ${a}!\n\
+                  echo '$a' \"\\$a\" $undefined.\n\
+                  echo $# $1 $2 # a comment\n";
+    assert_eq!(
+        run_script(script),
+        (0, String::from("hello world hello!\n$a $a .\n2 x y\n"))
+    );
+    println!("variables passed!");
+}
+
+fn exit_status() {
+    let script = "false\n\
+                  echo $?\n\
+                  true && echo and\n\
+                  false || echo or\n\
+                  false && echo no\n\
+                  echo piped | cat\n\
+                  exit 3\n\
+                  echo unreachable\n";
+    assert_eq!(run_script(script), (3, String::from("1\nand\nor\npiped\n")));
+    // a missing program
+    assert_eq!(run_script("no_such_program").0, 127);
+    // nothing runs if the script is not valid
+    assert_eq!(run_script("echo no\nfi\n"), (2, String::new()));
+    println!("exit_status passed!");
+}
+
+fn control_flow() {
+    let script = "if false; then\n\
+                  \techo no\n\
+                  elif true; then\n\
+                  \techo elif\n\
+                  else\n\
+                  \techo no\n\
+                  fi\n\
+                  run=true\n\
+                  while $run; do\n\
+                  \techo once\n\
+                  \trun=false\n\
+                  done\n\
+                  if false; then echo no; fi; echo $?\n";
+    assert_eq!(run_script(script), (0, String::from("elif\nonce\n0\n")));
+    println!("control_flow passed!");
+}
+
+fn environment() {
+    let script = "A=1\n\
+                  export B=2\n\
+                  C=3 env\n\
+                  env\n";
+    let (status, output) = run_script(script);
+    assert_eq!(status, 0);
+    let lines: Vec<&str> = output.lines().collect();
+    assert!(!lines.contains(&"A=1"));
+    assert_eq!(lines.iter().filter(|line| **line == "B=2").count(), 2);
+    assert_eq!(lines.iter().filter(|line| **line == "C=3").count(), 1);
+    println!("environment passed!");
+}
+
+fn devices() {
+    let script = "echo hidden > /dev/null\n\
+                  echo shown\n\
+                  echo hidden 2>/dev/null >&2\n\
+                  cat < /dev/null\n\
+                  echo $?\n";
+    assert_eq!(run_script(script), (0, String::from("shown\n0\n")));
+    println!("devices passed!");
+}
+
+fn redirect_errors() {
+    let script = "echo a 2>&1 > /\n\
+                  echo $?\n\
+                  cat 2>&1 < /none\n\
+                  cd /bin/shell 2>&1\n";
+    assert_eq!(
+        run_script(script),
+        (
+            1,
+            String::from(
+                "Shell: /: Is a directory\n1\n\
+                 Shell: /none: No such file or directory\n\
+                 cd: /bin/shell: Not a directory\n"
+            )
+        )
+    );
+    println!("redirect_errors passed!");
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    parsing();
+    variables();
+    exit_status();
+    control_flow();
+    environment();
+    devices();
+    redirect_errors();
+    println!("shell_tests passed!");
+    0
+}
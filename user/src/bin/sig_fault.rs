@@ -0,0 +1,109 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::arch::asm;
+use user_lib::signal::*;
+use user_lib::{
+    exit, fork, sigaction, sigprocmask, waitpid, wexitstatus, wifexited, wifsignaled, wtermsig,
+};
+
+/// Not mapped in user space
+const BAD_ADDR: usize = 0x8;
+
+fn segv_handler(signum: i32, addr: usize) {
+    // the faulting store would run again after sigreturn, so exit here
+    if signum == SIGSEGV && addr == BAD_ADDR {
+        exit(0);
+    }
+    exit(1);
+}
+
+fn bad_store() {
+    unsafe { (BAD_ADDR as *mut u8).write_volatile(0) };
+}
+
+fn illegal_instruction() {
+    unsafe { asm!("unimp") };
+}
+
+fn misaligned_load() {
+    let word = [0u32; 2];
+    // QEMU traps misaligned loads of atomics only
+    let addr = word.as_ptr() as usize + 1;
+    unsafe { asm!("lr.w {}, ({})", out(reg) _, in(reg) addr) };
+}
+
+fn breakpoint() {
+    unsafe { asm!("ebreak") };
+}
+
+fn set_handler(signum: i32, handler: usize) {
+    let action = SignalAction {
+        handler,
+        mask: SignalFlags::empty(),
+    };
+    assert_eq!(sigaction(signum, Some(&action), None), 0);
+}
+
+/// Run `f` in a child process, return its wait status.
+fn run_child(f: fn()) -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        f();
+        exit(100);
+    }
+    let mut status = 0;
+    assert_eq!(waitpid(pid as usize, &mut status), pid);
+    status
+}
+
+fn assert_killed_by(status: i32, signum: i32) {
+    assert!(wifsignaled(status), "status = {:#x}", status);
+    assert_eq!(wtermsig(status), signum);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_killed_by(run_child(bad_store), SIGSEGV);
+    println!("default SIGSEGV passed!");
+
+    assert_killed_by(run_child(illegal_instruction), SIGILL);
+    println!("default SIGILL passed!");
+
+    assert_killed_by(run_child(misaligned_load), SIGBUS);
+    println!("default SIGBUS passed!");
+
+    assert_killed_by(run_child(breakpoint), SIGTRAP);
+    println!("default SIGTRAP passed!");
+
+    let status = run_child(|| {
+        set_handler(SIGSEGV, segv_handler as usize);
+        bad_store();
+    });
+    assert!(wifexited(status) && wexitstatus(status) == 0);
+    println!("SIGSEGV handler passed!");
+
+    // a fault cannot be ignored or blocked
+    assert_killed_by(
+        run_child(|| {
+            set_handler(SIGSEGV, SIG_IGN);
+            bad_store();
+        }),
+        SIGSEGV,
+    );
+    assert_killed_by(
+        run_child(|| {
+            set_handler(SIGSEGV, segv_handler as usize);
+            sigprocmask(SignalFlags::SIGSEGV);
+            bad_store();
+        }),
+        SIGSEGV,
+    );
+    println!("ignored or blocked SIGSEGV passed!");
+
+    println!("sig_fault passed!");
+    0
+}
@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicI32, Ordering};
+use user_lib::signal::{SignalAction, SignalFlags, SIGUSR1};
+use user_lib::{getpid, kill, sigaction, sigreturn};
+
+static RECEIVED: AtomicI32 = AtomicI32::new(0);
+
+fn handler(signum: i32) {
+    RECEIVED.store(signum, Ordering::Relaxed);
+    sigreturn();
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let action = SignalAction {
+        handler: handler as usize,
+        mask: SignalFlags::empty(),
+    };
+    let mut old_action = SignalAction::default();
+    assert_eq!(sigaction(SIGUSR1, Some(&action), Some(&mut old_action)), 0);
+    // the signal is delivered on return from kill
+    assert_eq!(kill(getpid() as usize, SIGUSR1), 0);
+    assert_eq!(RECEIVED.load(Ordering::Relaxed), SIGUSR1);
+    println!("sig_simple passed!");
+    0
+}
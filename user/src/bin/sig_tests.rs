@@ -0,0 +1,148 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::errno::{EINVAL, EPERM, ESRCH};
+use user_lib::signal::*;
+use user_lib::{
+    exit, fork, getpid, kill, sigaction, sigprocmask, sigreturn, sleep, waitpid, wexitstatus,
+    wifexited, wifsignaled, wtermsig, yield_,
+};
+
+static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+fn count_handler(_signum: i32) {
+    COUNT.fetch_add(1, Ordering::Relaxed);
+    sigreturn();
+}
+
+fn set_handler(signum: i32, handler: usize) {
+    let action = SignalAction {
+        handler,
+        mask: SignalFlags::empty(),
+    };
+    assert_eq!(sigaction(signum, Some(&action), None), 0);
+}
+
+fn invalid_arguments() {
+    let action = SignalAction::default();
+    assert_eq!(sigaction(SIGKILL, Some(&action), None), -EINVAL);
+    assert_eq!(sigaction(SIGSTOP, Some(&action), None), -EINVAL);
+    assert_eq!(sigaction(0, Some(&action), None), -EINVAL);
+    assert_eq!(sigaction(32, Some(&action), None), -EINVAL);
+    assert_eq!(kill(getpid() as usize, 32), -EINVAL);
+    assert_eq!(kill(1, SIGTERM), -EPERM);
+    assert_eq!(kill(0x7fff, SIGTERM), -ESRCH);
+    assert_eq!(kill(getpid() as usize, 0), 0);
+    println!("invalid_arguments passed!");
+}
+
+fn blocked_until_unmasked() {
+    COUNT.store(0, Ordering::Relaxed);
+    set_handler(SIGUSR1, count_handler as usize);
+    let old_mask = sigprocmask(SignalFlags::SIGUSR1);
+    kill(getpid() as usize, SIGUSR1);
+    assert_eq!(COUNT.load(Ordering::Relaxed), 0);
+    // delivered when the syscall returns
+    assert_eq!(sigprocmask(old_mask), SignalFlags::SIGUSR1);
+    assert_eq!(COUNT.load(Ordering::Relaxed), 1);
+    println!("blocked_until_unmasked passed!");
+}
+
+fn ignored() {
+    set_handler(SIGUSR2, SIG_IGN);
+    kill(getpid() as usize, SIGUSR2);
+    // SIGCHLD is ignored by default
+    kill(getpid() as usize, SIGCHLD);
+    set_handler(SIGUSR2, SIG_DFL);
+    println!("ignored passed!");
+}
+
+fn default_terminate() {
+    let pid = fork();
+    if pid == 0 {
+        loop {
+            yield_();
+        }
+    }
+    assert_eq!(kill(pid as usize, SIGTERM), 0);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert!(wifsignaled(exit_code) && wtermsig(exit_code) == SIGTERM);
+    println!("default_terminate passed!");
+}
+
+fn kill_is_not_caught() {
+    let pid = fork();
+    if pid == 0 {
+        // cannot catch, ignore or block SIGKILL
+        set_handler(SIGTERM, SIG_IGN);
+        sigprocmask(SignalFlags::all());
+        loop {
+            yield_();
+        }
+    }
+    sleep(10);
+    assert_eq!(kill(pid as usize, SIGTERM), 0);
+    assert_eq!(kill(pid as usize, SIGKILL), 0);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert!(wifsignaled(exit_code) && wtermsig(exit_code) == SIGKILL);
+    println!("kill_is_not_caught passed!");
+}
+
+fn handler_in_child() {
+    let pid = fork();
+    if pid == 0 {
+        COUNT.store(0, Ordering::Relaxed);
+        set_handler(SIGUSR1, count_handler as usize);
+        while COUNT.load(Ordering::Relaxed) == 0 {
+            yield_();
+        }
+        exit(42);
+    }
+    // wait for the handler to be installed
+    sleep(10);
+    assert_eq!(kill(pid as usize, SIGUSR1), 0);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert!(wifexited(exit_code) && wexitstatus(exit_code) == 42);
+    println!("handler_in_child passed!");
+}
+
+fn stop_and_continue() {
+    let pid = fork();
+    if pid == 0 {
+        loop {
+            yield_();
+        }
+    }
+    assert_eq!(kill(pid as usize, SIGSTOP), 0);
+    sleep(10);
+    assert_eq!(kill(pid as usize, SIGCONT), 0);
+    sleep(10);
+    // a stopped process can still be killed
+    assert_eq!(kill(pid as usize, SIGSTOP), 0);
+    sleep(10);
+    assert_eq!(kill(pid as usize, SIGKILL), 0);
+    let mut exit_code = 0;
+    assert_eq!(waitpid(pid as usize, &mut exit_code), pid);
+    assert!(wifsignaled(exit_code) && wtermsig(exit_code) == SIGKILL);
+    println!("stop_and_continue passed!");
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    invalid_arguments();
+    blocked_until_unmasked();
+    ignored();
+    default_terminate();
+    kill_is_not_caught();
+    handler_in_child();
+    stop_and_continue();
+    println!("sig_tests passed!");
+    0
+}
@@ -0,0 +1,31 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::hint::black_box;
+
+const DEPTH: usize = 64;
+
+/// Use about 4KB stack in each call, 256KB in total (more than the initial 16KB).
+fn f(depth: usize) -> usize {
+    let mut buf = [0u8; 4000];
+    buf[0] = depth as u8;
+    buf[3999] = depth as u8;
+    let buf = black_box(buf);
+    if depth == 0 {
+        return 0;
+    }
+    let sum = f(depth - 1);
+    assert_eq!(buf[0], depth as u8);
+    assert_eq!(buf[3999], depth as u8);
+    sum + depth
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    assert_eq!(f(DEPTH), DEPTH * (DEPTH + 1) / 2);
+    println!("stack_growth passed!");
+    0
+}
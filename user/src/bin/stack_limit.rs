@@ -0,0 +1,95 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::hint::black_box;
+use user_lib::errno::EINVAL;
+use user_lib::signal::SIGSEGV;
+use user_lib::{
+    exec, exit, fork, getrlimit, setrlimit, waitpid, wexitstatus, wifexited, wifsignaled,
+    wstackoverflow, wtermsig, RLimit, RLIMIT_STACK,
+};
+
+const KB: usize = 1024;
+
+/// Use about 4KB stack in each call, `4 * depth` KB in total, return `depth`.
+fn f(depth: usize) -> usize {
+    let buf = black_box([1u8; 4000]);
+    if depth == 0 {
+        return 0;
+    }
+    f(depth - 1) + buf[3999] as usize
+}
+
+fn set_stack_limit(limit: usize) {
+    let mut rlim = RLimit::default();
+    assert_eq!(getrlimit(RLIMIT_STACK, &mut rlim), 0);
+    rlim.rlim_cur = limit;
+    assert_eq!(setrlimit(RLIMIT_STACK, &rlim), 0);
+}
+
+/// Run `f` with a stack limit of `limit` bytes in a child, return its wait status.
+fn run_child(limit: usize, f: fn()) -> i32 {
+    let pid = fork();
+    if pid == 0 {
+        set_stack_limit(limit);
+        f();
+        exit(0);
+    }
+    let mut status = 0;
+    assert_eq!(waitpid(pid as usize, &mut status), pid);
+    status
+}
+
+fn assert_stack_overflow(status: i32) {
+    assert!(wifsignaled(status), "status = {:#x}", status);
+    assert_eq!(wtermsig(status), SIGSEGV);
+    assert!(wstackoverflow(status));
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut rlim = RLimit::default();
+    assert_eq!(getrlimit(RLIMIT_STACK, &mut rlim), 0);
+    assert_eq!(rlim.rlim_cur, 1024 * KB);
+    assert_eq!(rlim.rlim_max, 8192 * KB);
+    let bad = RLimit {
+        rlim_cur: rlim.rlim_max + 4 * KB,
+        rlim_max: rlim.rlim_max,
+    };
+    assert_eq!(setrlimit(RLIMIT_STACK, &bad), -EINVAL);
+    assert_eq!(setrlimit(0, &rlim), -EINVAL);
+
+    // beyond a lowered limit
+    let status = run_child(64 * KB, || {
+        f(64);
+    });
+    assert_stack_overflow(status);
+    println!("lowered stack limit passed!");
+
+    // beyond the default limit, within a raised one
+    let status = run_child(4096 * KB, || {
+        assert_eq!(f(512), 512);
+    });
+    assert!(wifexited(status) && wexitstatus(status) == 0);
+    println!("raised stack limit passed!");
+
+    // kept across exec, stack_growth needs 256KB
+    let status = run_child(128 * KB, || {
+        exec("stack_growth\0");
+        exit(100);
+    });
+    assert_stack_overflow(status);
+    println!("stack limit after exec passed!");
+
+    // other faults are not stack overflows
+    let status = run_child(rlim.rlim_cur, || unsafe {
+        (0x8 as *mut u8).write_volatile(0);
+    });
+    assert!(wifsignaled(status) && wtermsig(status) == SIGSEGV);
+    assert!(!wstackoverflow(status));
+    println!("stack_limit passed!");
+    0
+}
@@ -0,0 +1,21 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::hint::black_box;
+
+/// Use 16KB stack in each call, so that a frame skips pages below the stack.
+#[allow(unconditional_recursion)]
+fn f(depth: usize) -> u8 {
+    let mut buf = black_box([0u8; 16384]);
+    buf[depth % 16384] = depth as u8;
+    f(depth + 1).wrapping_add(black_box(buf)[0])
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    println!("It should trigger stack overflow with large frames!");
+    f(0) as i32
+}
@@ -0,0 +1,93 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::{EBADF, ENOENT};
+use user_lib::{
+    close, fstat, get_time, mkdir, open, pipe, rmdir, sleep, stat, unlink, write, OpenFlags, Stat,
+    S_IFBLK, S_IFCHR, S_IFDIR, S_IFIFO, S_IFMT, S_IFREG,
+};
+
+fn stat_of(path: &str) -> Stat {
+    let mut st = Stat::default();
+    assert_eq!(stat(path, &mut st), 0);
+    st
+}
+
+fn fstat_of(fd: usize) -> Stat {
+    let mut st = Stat::default();
+    assert_eq!(fstat(fd, &mut st), 0);
+    st
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // applications are executable regular files
+    let app = stat_of("/bin/hello_world\0");
+    assert!(app.is_file());
+    assert_eq!(app.st_mode & 0o777, 0o755);
+    assert_eq!(app.st_nlink, 1);
+    assert!(app.st_size > 0);
+    assert_eq!(app.st_blocks, (app.st_size + 511) / 512);
+    let fd = open("/bin/hello_world\0", OpenFlags::empty());
+    assert!(fd >= 0);
+    let opened = fstat_of(fd as usize);
+    assert_eq!(opened.st_ino, app.st_ino);
+    assert_eq!(opened.st_size, app.st_size);
+    close(fd as usize);
+
+    // writing changes the size and the modification time
+    let path = "/stat_tests\0";
+    let fd = open(path, OpenFlags::CREATE | OpenFlags::RDWR);
+    assert!(fd >= 0);
+    let fd = fd as usize;
+    let created = fstat_of(fd);
+    assert_eq!(created.st_mode, S_IFREG | 0o644);
+    assert_eq!(created.st_size, 0);
+    assert_ne!(created.st_ino, app.st_ino);
+    sleep(20);
+    let before = get_time() as usize;
+    assert_eq!(write(fd, b"0123456789"), 10);
+    let written = stat_of(path);
+    assert_eq!(written.st_size, 10);
+    assert_eq!(written.st_ino, created.st_ino);
+    assert!(written.mtime_ms() >= before);
+    assert!(written.mtime_ms() > created.mtime_ms());
+
+    // an unlinked file has no link left
+    assert_eq!(unlink(path), 0);
+    assert_eq!(fstat_of(fd).st_nlink, 0);
+    close(fd);
+    let mut st = Stat::default();
+    assert_eq!(stat(path, &mut st), -ENOENT);
+    assert_eq!(fstat(fd, &mut st), -EBADF);
+
+    // a directory is linked from its parent, itself and its subdirectories
+    assert_eq!(mkdir("/stat_dir\0"), 0);
+    let dir = stat_of("/stat_dir\0");
+    assert!(dir.is_dir());
+    assert_eq!(dir.st_nlink, 2);
+    assert_eq!(mkdir("/stat_dir/sub\0"), 0);
+    assert_eq!(stat_of("/stat_dir\0").st_nlink, 3);
+    assert_eq!(rmdir("/stat_dir/sub\0"), 0);
+    assert_eq!(rmdir("/stat_dir\0"), 0);
+
+    // devices, /proc and pipes
+    assert_eq!(stat_of("/dev/null\0").st_mode & S_IFMT, S_IFCHR);
+    let swap = stat_of("/dev/swap\0");
+    assert_eq!(swap.st_mode, S_IFBLK | 0o440);
+    assert!(swap.st_size > 0);
+    assert_eq!(stat_of("/proc\0").st_mode & S_IFMT, S_IFDIR);
+    let meminfo = stat_of("/proc/meminfo\0");
+    assert!(meminfo.is_file());
+    assert_eq!(meminfo.st_size, 0);
+    let mut fds = [0usize; 2];
+    assert_eq!(pipe(&mut fds), 0);
+    assert_eq!(fstat_of(fds[0]).st_mode & S_IFMT, S_IFIFO);
+    close(fds[0]);
+    close(fds[1]);
+    println!("stat_tests passed!");
+    0
+}
@@ -0,0 +1,58 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::ptr::addr_of_mut;
+use user_lib::{exit, fork, getpid, sleep, wait};
+
+const PAGE_SIZE: usize = 4096;
+const PAGES: usize = 1280; // 5MB per process
+const NUM: usize = 9; // more than physical memory in total
+
+static mut DATA: [u8; PAGES * PAGE_SIZE] = [0; PAGES * PAGE_SIZE];
+
+fn pattern(pid: usize, page: usize) -> u8 {
+    (pid * 31 + page) as u8
+}
+
+fn work() -> i32 {
+    let pid = getpid() as usize;
+    let data = unsafe { &mut *addr_of_mut!(DATA) };
+    for page in 0..PAGES {
+        data[page * PAGE_SIZE] = pattern(pid, page);
+        data[page * PAGE_SIZE + PAGE_SIZE - 1] = pattern(pid, page);
+    }
+    // let other processes run and push our pages out
+    sleep(500);
+    for page in 0..PAGES {
+        if data[page * PAGE_SIZE] != pattern(pid, page)
+            || data[page * PAGE_SIZE + PAGE_SIZE - 1] != pattern(pid, page)
+        {
+            println!("pid {}: page {} corrupted!", pid, page);
+            return -1;
+        }
+    }
+    println!("pid {}: all pages ok.", pid);
+    0
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    for _ in 0..NUM {
+        let pid = fork();
+        if pid == 0 {
+            exit(work());
+        }
+        assert!(pid > 0);
+    }
+    let mut exit_code: i32 = 0;
+    for _ in 0..NUM {
+        assert!(wait(&mut exit_code) > 0);
+        assert_eq!(exit_code, 0);
+    }
+    assert!(wait(&mut exit_code) < 0);
+    println!("swaptest passed!");
+    0
+}
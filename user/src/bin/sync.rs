@@ -0,0 +1,13 @@
+#![no_std]
+#![no_main]
+
+extern crate user_lib;
+
+use user_lib::sync;
+
+/// Write all changes cached in memory to the disks.
+#[no_mangle]
+pub fn main() -> i32 {
+    sync();
+    0
+}
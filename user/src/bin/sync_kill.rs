@@ -0,0 +1,76 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::AtomicU32;
+use user_lib::signal::{SIGKILL, SIGTERM};
+use user_lib::sync::{futex_wait, Condvar, Mutex, Semaphore};
+use user_lib::{exit, fork, kill, sleep, thread_create, waitpid, wifsignaled, wtermsig, yield_};
+
+struct Shared {
+    held: Mutex<()>,
+    free: Mutex<()>,
+    changed: Condvar,
+    never_up: Semaphore,
+    word: AtomicU32,
+}
+
+fn lock_held(shared: *const Shared) {
+    let shared = unsafe { &*shared };
+    let _guard = shared.held.lock();
+    exit(1);
+}
+
+fn wait_changed(shared: *const Shared) {
+    let shared = unsafe { &*shared };
+    let guard = shared.free.lock();
+    let _guard = shared.changed.wait(guard);
+    exit(1);
+}
+
+fn wait_word(shared: *const Shared) {
+    let shared = unsafe { &*shared };
+    futex_wait(&shared.word, 0);
+    exit(1);
+}
+
+/// Block every thread of the process for good.
+fn block_all() -> i32 {
+    let shared = Shared {
+        held: Mutex::new(()),
+        free: Mutex::new(()),
+        changed: Condvar::new(),
+        never_up: Semaphore::new(0),
+        word: AtomicU32::new(0),
+    };
+    let _guard = shared.held.lock();
+    let arg = &shared as *const _ as usize;
+    assert!(thread_create(lock_held as usize, arg) > 0);
+    assert!(thread_create(wait_changed as usize, arg) > 0);
+    assert!(thread_create(wait_word as usize, arg) > 0);
+    for _ in 0..10 {
+        yield_();
+    }
+    shared.never_up.down();
+    1
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    // threads blocked on a lock or a futex do not keep their process from being killed
+    for signum in [SIGKILL, SIGTERM] {
+        let pid = fork();
+        if pid == 0 {
+            exit(block_all());
+        }
+        sleep(50);
+        assert_eq!(kill(pid as usize, signum), 0);
+        let mut status = 0;
+        assert_eq!(waitpid(pid as usize, &mut status), pid);
+        assert!(wifsignaled(status) && wtermsig(status) == signum);
+    }
+    println!("sync_kill passed!");
+    0
+}
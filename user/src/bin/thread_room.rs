@@ -0,0 +1,99 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::errno::ENOMEM;
+use user_lib::{
+    args, close, execv, exit, fork, mmap, open, read_to_end, thread_create, unlink, waitpid,
+    waittid, wexitstatus, wifexited, write, MapFlags, OpenFlags, ProtFlags,
+};
+
+/// Mappings of `mmap` are placed from here, threads stacks below
+const MMAP_BASE: usize = 0x1000_0000;
+/// Where the program may end: the stack reservation and its guard gap are
+/// between it and `MMAP_BASE`
+const SEGMENTS_END: usize = MMAP_BASE - 0x10000 - 0x800000;
+
+fn worker(arg: usize) {
+    exit(arg as i32);
+}
+
+/// Offset of the last loadable program header of a 64-bit ELF image
+fn last_load_header(image: &[u8]) -> usize {
+    let read = |offset: usize, len: usize| {
+        let mut bytes = [0u8; 8];
+        bytes[..len].copy_from_slice(&image[offset..offset + len]);
+        u64::from_le_bytes(bytes) as usize
+    };
+    let (ph_offset, ph_size, ph_count) = (read(0x20, 8), read(0x36, 2), read(0x38, 2));
+    (0..ph_count)
+        .map(|i| ph_offset + i * ph_size)
+        .filter(|&ph| read(ph, 4) == 1)
+        .last()
+        .unwrap()
+}
+
+/// Run as a program ending at `SEGMENTS_END`: the main stack reaches
+/// `MMAP_BASE`, there is no room for another thread.
+fn run_big() -> i32 {
+    assert_eq!(thread_create(worker as usize, 0), -ENOMEM);
+    let addr = mmap(
+        0,
+        4096,
+        ProtFlags::READ | ProtFlags::WRITE,
+        MapFlags::PRIVATE | MapFlags::ANONYMOUS,
+        0,
+        0,
+    );
+    assert!(addr as usize >= MMAP_BASE);
+    0
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    if args().len() > 1 {
+        return run_big();
+    }
+    let tid = thread_create(worker as usize, 7);
+    assert!(tid > 0);
+    let mut exit_code = 0;
+    assert_eq!(waittid(tid as usize, &mut exit_code), tid);
+    assert_eq!(exit_code, 7);
+
+    // a copy whose last segment (bss, mapped on access) ends at `SEGMENTS_END`
+    let fd = open("/bin/thread_room\0", OpenFlags::empty());
+    assert!(fd >= 0);
+    let mut image = read_to_end(fd as usize).unwrap();
+    close(fd as usize);
+    let ph = last_load_header(&image);
+    let vaddr = u64::from_le_bytes(image[ph + 16..ph + 24].try_into().unwrap());
+    let mem_size = SEGMENTS_END as u64 - vaddr;
+    image[ph + 40..ph + 48].copy_from_slice(&mem_size.to_le_bytes());
+    let big = "/bin/thread_room_big\0";
+    let fd = open(
+        big,
+        OpenFlags::CREATE | OpenFlags::WRONLY | OpenFlags::TRUNC,
+    );
+    assert!(fd >= 0);
+    assert_eq!(write(fd as usize, &image), image.len() as isize);
+    close(fd as usize);
+
+    let pid = fork();
+    if pid == 0 {
+        let argv = [big.as_ptr(), "big\0".as_ptr(), core::ptr::null()];
+        execv(big, &argv);
+        exit(100);
+    }
+    let mut status = 0;
+    assert_eq!(waitpid(pid as usize, &mut status), pid);
+    assert!(
+        wifexited(status) && wexitstatus(status) == 0,
+        "status = {:#x}",
+        status
+    );
+    assert_eq!(unlink(big), 0);
+    println!("thread_room passed!");
+    0
+}
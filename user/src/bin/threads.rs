@@ -0,0 +1,35 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use user_lib::{exit, gettid, thread_create, waittid};
+
+const NUM: usize = 3;
+const ROUNDS: usize = 100;
+
+fn worker(id: usize) {
+    for _ in 0..ROUNDS {
+        print!("{}", (b'a' + id as u8) as char);
+    }
+    exit(gettid() as i32);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let mut tids = [0; NUM];
+    for (id, tid) in tids.iter_mut().enumerate() {
+        *tid = thread_create(worker as usize, id);
+        assert!(*tid > 0);
+    }
+    let mut exit_code: i32 = 0;
+    for tid in tids {
+        assert_eq!(waittid(tid as usize, &mut exit_code), tid);
+        assert_eq!(exit_code, tid as i32);
+    }
+    assert_eq!(waittid(tids[0] as usize, &mut exit_code), -1);
+    println!("\nmain thread exited.");
+    println!("threads passed!");
+    0
+}
@@ -0,0 +1,59 @@
+#![no_std]
+#![no_main]
+
+#[macro_use]
+extern crate user_lib;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use user_lib::{exit, fork, getpid, thread_create, wait, waittid, wexitstatus};
+
+const NUM: usize = 4;
+const ADD_PER_THREAD: usize = 1000;
+
+/// Threads share the address space of the process.
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+struct Arg {
+    pid: isize,
+    add: usize,
+}
+
+fn worker(arg: *const Arg) {
+    let arg = unsafe { &*arg };
+    assert_eq!(getpid(), arg.pid);
+    for _ in 0..arg.add {
+        COUNTER.fetch_add(1, Ordering::Relaxed);
+    }
+    exit(0);
+}
+
+#[no_mangle]
+pub fn main() -> i32 {
+    let arg = Arg {
+        pid: getpid(),
+        add: ADD_PER_THREAD,
+    };
+    let mut tids = [0; NUM];
+    for tid in tids.iter_mut() {
+        *tid = thread_create(worker as usize, &arg as *const _ as usize);
+        assert!(*tid > 0);
+    }
+    // a process with more than one thread cannot fork
+    assert_eq!(fork(), -1);
+    let mut exit_code: i32 = 0;
+    for tid in tids {
+        assert_eq!(waittid(tid as usize, &mut exit_code), tid);
+        assert_eq!(exit_code, 0);
+    }
+    assert_eq!(COUNTER.load(Ordering::Relaxed), NUM * ADD_PER_THREAD);
+    // only one thread now
+    let pid = fork();
+    if pid == 0 {
+        // only the lowest 8 bits of the exit code are kept
+        exit((COUNTER.load(Ordering::Relaxed) / ADD_PER_THREAD) as i32);
+    }
+    assert!(wait(&mut exit_code) == pid);
+    assert_eq!(wexitstatus(exit_code) as usize, NUM);
+    println!("threads_shared passed!");
+    0
+}
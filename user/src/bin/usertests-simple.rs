@@ -18,7 +18,7 @@ static TESTS: &[&str] = &[
     "yield\0",
 ];
 
-use user_lib::{exec, fork, waitpid};
+use user_lib::{exec, fork, waitpid, wexitstatus, wifexited, wtermsig};
 
 #[no_mangle]
 pub fn main() -> i32 {
@@ -32,10 +32,21 @@ pub fn main() -> i32 {
             let mut exit_code: i32 = Default::default();
             let wait_pid = waitpid(pid as usize, &mut exit_code);
             assert_eq!(pid, wait_pid);
-            println!(
-                "\x1b[32mUsertests: Test {} in Process {} exited with code {}\x1b[0m",
-                test, pid, exit_code
-            );
+            if wifexited(exit_code) {
+                println!(
+                    "\x1b[32mUsertests: Test {} in Process {} exited with code {}\x1b[0m",
+                    test,
+                    pid,
+                    wexitstatus(exit_code)
+                );
+            } else {
+                println!(
+                    "\x1b[32mUsertests: Test {} in Process {} killed by signal {}\x1b[0m",
+                    test,
+                    pid,
+                    wtermsig(exit_code)
+                );
+            }
         }
     }
     println!("Usertests passed!");
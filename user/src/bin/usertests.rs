@@ -7,28 +7,74 @@ extern crate user_lib;
 // not in SUCC_TESTS & FAIL_TESTS
 // count_lines, infloop, user_shell, usertests
 
-// item of TESTS : app_name(argv_0), argv_1, argv_2, argv_3, exit_code
+// item of TESTS : app_name(argv_0), argv_1, argv_2, argv_3, wait status
+// (exit code << 8 if exited, or the signal number if killed by a signal, with
+// STACK_OVERFLOW for a stack overflow)
 static SUCC_TESTS: &[(&str, &str, &str, &str, i32)] = &[
+    ("condvar\0", "\0", "\0", "\0", 0),
+    ("devfs_tests\0", "\0", "\0", "\0", 0),
+    ("dining_philosophers\0", "\0", "\0", "\0", 0),
+    ("dir_tests\0", "\0", "\0", "\0", 0),
     ("exit\0", "\0", "\0", "\0", 0),
     ("fantastic_text\0", "\0", "\0", "\0", 0),
+    ("fat_tests\0", "\0", "\0", "\0", 0),
+    ("file_tests\0", "\0", "\0", "\0", 0),
     ("forktest_simple\0", "\0", "\0", "\0", 0),
     ("forktest\0", "\0", "\0", "\0", 0),
     ("forktest2\0", "\0", "\0", "\0", 0),
     ("forktree\0", "\0", "\0", "\0", 0),
+    ("futex\0", "\0", "\0", "\0", 0),
     ("hello_world\0", "\0", "\0", "\0", 0),
+    ("job_control\0", "\0", "\0", "\0", 0),
     ("matrix\0", "\0", "\0", "\0", 0),
+    ("mmap_tests\0", "\0", "\0", "\0", 0),
     ("sleep_simple\0", "\0", "\0", "\0", 0),
     ("sleep\0", "\0", "\0", "\0", 0),
+    ("oomtest\0", "\0", "\0", "\0", 0),
+    ("pipe_tests\0", "\0", "\0", "\0", 0),
+    ("procfs_tests\0", "\0", "\0", "\0", 0),
+    ("producer_consumer\0", "\0", "\0", "\0", 0),
+    ("ramfs_tests\0", "\0", "\0", "\0", 0),
+    ("shell_tests\0", "\0", "\0", "\0", 0),
+    ("sig_fault\0", "\0", "\0", "\0", 0),
+    ("sig_simple\0", "\0", "\0", "\0", 0),
+    ("sig_tests\0", "\0", "\0", "\0", 0),
+    ("stack_growth\0", "\0", "\0", "\0", 0),
+    ("stack_limit\0", "\0", "\0", "\0", 0),
+    ("stat_tests\0", "\0", "\0", "\0", 0),
+    ("swaptest\0", "\0", "\0", "\0", 0),
+    ("sync_kill\0", "\0", "\0", "\0", 0),
+    ("thread_room\0", "\0", "\0", "\0", 0),
+    ("threads\0", "\0", "\0", "\0", 0),
+    ("threads_shared\0", "\0", "\0", "\0", 0),
     ("yield\0", "\0", "\0", "\0", 0),
 ];
 
-static FAIL_TESTS: &[(&str, &str, &str, &str, i32)] = &[("stack_overflow\0", "\0", "\0", "\0", -2)];
+static FAIL_TESTS: &[(&str, &str, &str, &str, i32)] = &[
+    (
+        "stack_overflow\0",
+        "\0",
+        "\0",
+        "\0",
+        SIGSEGV | STACK_OVERFLOW,
+    ),
+    (
+        "stack_overflow_frame\0",
+        "\0",
+        "\0",
+        "\0",
+        SIGSEGV | STACK_OVERFLOW,
+    ),
+];
 
-use user_lib::{exec, fork, waitpid};
+use user_lib::signal::SIGSEGV;
+use user_lib::{execv, fork, waitpid, wexitstatus, wifexited, wtermsig, STACK_OVERFLOW};
 
 fn run_tests(tests: &[(&str, &str, &str, &str, i32)]) -> i32 {
     let mut pass_num = 0;
-    let mut arr: [*const u8; 4] = [
+    // argv, always ends with a null pointer
+    let mut arr: [*const u8; 5] = [
+        core::ptr::null::<u8>(),
         core::ptr::null::<u8>(),
         core::ptr::null::<u8>(),
         core::ptr::null::<u8>(),
@@ -62,7 +108,7 @@ fn run_tests(tests: &[(&str, &str, &str, &str, i32)]) -> i32 {
 
         let pid = fork();
         if pid == 0 {
-            exec(test.0);
+            execv(test.0, &arr);
             panic!("unreachable!");
         } else {
             let mut exit_code: i32 = Default::default();
@@ -72,10 +118,21 @@ fn run_tests(tests: &[(&str, &str, &str, &str, i32)]) -> i32 {
                 // summary apps with  exit_code
                 pass_num = pass_num + 1;
             }
-            println!(
-                "\x1b[32mUsertests: Test {} in Process {} exited with code {}\x1b[0m",
-                test.0, pid, exit_code
-            );
+            if wifexited(exit_code) {
+                println!(
+                    "\x1b[32mUsertests: Test {} in Process {} exited with code {}\x1b[0m",
+                    test.0,
+                    pid,
+                    wexitstatus(exit_code)
+                );
+            } else {
+                println!(
+                    "\x1b[32mUsertests: Test {} in Process {} killed by signal {}\x1b[0m",
+                    test.0,
+                    pid,
+                    wtermsig(exit_code)
+                );
+            }
         }
     }
     pass_num
@@ -1,12 +1,15 @@
 use crate::api::*;
+use crate::errno::EINTR;
 use core::fmt::{self, Write};
 
 const STDIN: usize = 0;
 const STDOUT: usize = 1;
+const STDERR: usize = 2;
 
+/// Retry if interrupted by a signal handler.
 pub extern "C" fn getchar() -> u8 {
     let mut buf: [u8; 1] = [0; 1];
-    read(STDIN, &mut buf);
+    while read(STDIN, &mut buf) == -EINTR {}
     buf[0]
 }
 
@@ -23,10 +26,23 @@ impl Write for Stdout {
     }
 }
 
+struct Stderr;
+
+impl Write for Stderr {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        write(STDERR, s.as_bytes());
+        Ok(())
+    }
+}
+
 pub fn print(args: fmt::Arguments) {
     Stdout.write_fmt(args).unwrap();
 }
 
+pub fn eprint(args: fmt::Arguments) {
+    Stderr.write_fmt(args).unwrap();
+}
+
 #[macro_export]
 macro_rules! print {
     ($fmt: literal $(, $($arg: tt)+)?) => {
@@ -40,3 +56,17 @@ macro_rules! println {
         $crate::console::print(format_args!(concat!($fmt, "\n") $(, $($arg)+)?));
     }
 }
+
+#[macro_export]
+macro_rules! eprint {
+    ($fmt: literal $(, $($arg: tt)+)?) => {
+        $crate::console::eprint(format_args!($fmt $(, $($arg)+)?));
+    }
+}
+
+#[macro_export]
+macro_rules! eprintln {
+    ($fmt: literal $(, $($arg: tt)+)?) => {
+        $crate::console::eprint(format_args!(concat!($fmt, "\n") $(, $($arg)+)?));
+    }
+}
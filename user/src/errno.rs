@@ -0,0 +1,93 @@
+//! Error numbers returned (negated) by system calls, shared with the kernel
+
+/// Operation not permitted
+pub const EPERM: isize = 1;
+/// No such file or directory
+pub const ENOENT: isize = 2;
+/// No such process
+pub const ESRCH: isize = 3;
+/// Interrupted system call
+pub const EINTR: isize = 4;
+/// I/O error
+pub const EIO: isize = 5;
+/// Exec format error
+pub const ENOEXEC: isize = 8;
+/// Bad file number
+pub const EBADF: isize = 9;
+/// Try again
+pub const EAGAIN: isize = 11;
+/// Out of memory
+pub const ENOMEM: isize = 12;
+/// Permission denied
+pub const EACCES: isize = 13;
+/// Bad address
+pub const EFAULT: isize = 14;
+/// Device or resource busy
+pub const EBUSY: isize = 16;
+/// File exists
+pub const EEXIST: isize = 17;
+/// Cross-device link
+pub const EXDEV: isize = 18;
+/// No such device
+pub const ENODEV: isize = 19;
+/// Not a directory
+pub const ENOTDIR: isize = 20;
+/// Is a directory
+pub const EISDIR: isize = 21;
+/// Invalid argument
+pub const EINVAL: isize = 22;
+/// Too many open files
+pub const EMFILE: isize = 24;
+/// Not a typewriter
+pub const ENOTTY: isize = 25;
+/// Text file busy
+pub const ETXTBSY: isize = 26;
+/// File too large
+pub const EFBIG: isize = 27;
+/// No space left on device
+pub const ENOSPC: isize = 28;
+/// Read-only file system
+pub const EROFS: isize = 30;
+/// Broken pipe
+pub const EPIPE: isize = 32;
+/// Math result not representable
+pub const ERANGE: isize = 34;
+/// File name too long
+pub const ENAMETOOLONG: isize = 36;
+/// Directory not empty
+pub const ENOTEMPTY: isize = 39;
+
+/// Message of error `errno` (positive), following Linux
+pub fn strerror(errno: isize) -> &'static str {
+    match errno {
+        EPERM => "Operation not permitted",
+        ENOENT => "No such file or directory",
+        ESRCH => "No such process",
+        EINTR => "Interrupted system call",
+        EIO => "Input/output error",
+        ENOEXEC => "Exec format error",
+        EBADF => "Bad file descriptor",
+        EAGAIN => "Resource temporarily unavailable",
+        ENOMEM => "Cannot allocate memory",
+        EACCES => "Permission denied",
+        EFAULT => "Bad address",
+        EBUSY => "Device or resource busy",
+        EEXIST => "File exists",
+        EXDEV => "Invalid cross-device link",
+        ENODEV => "No such device",
+        ENOTDIR => "Not a directory",
+        EISDIR => "Is a directory",
+        EINVAL => "Invalid argument",
+        EMFILE => "Too many open files",
+        ENOTTY => "Inappropriate ioctl for device",
+        ETXTBSY => "Text file busy",
+        EFBIG => "File too large",
+        ENOSPC => "No space left on device",
+        EROFS => "Read-only file system",
+        EPIPE => "Broken pipe",
+        ERANGE => "Numerical result out of range",
+        ENAMETOOLONG => "File name too long",
+        ENOTEMPTY => "Directory not empty",
+        _ => "Unknown error",
+    }
+}
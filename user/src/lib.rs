@@ -4,9 +4,13 @@
 #![feature(alloc_error_handler)]
 
 pub mod api;
+pub mod errno;
 mod heap;
 mod lang_items;
 pub mod process;
+pub mod shell;
+pub mod signal;
+pub mod sync;
 mod syscall;
 mod up;
 
@@ -16,16 +20,21 @@ extern crate alloc;
 pub mod console;
 
 use crate::heap::init_heap;
+use alloc::vec::Vec;
 pub use api::*;
+use core::sync::atomic::{AtomicUsize, Ordering};
 
 const USER_HEAP_SIZE: usize = 4096 * 16;
 const SHARED_PAGE: usize = 0x83000000;
 
 #[no_mangle]
 #[link_section = ".text.entry"]
-pub extern "C" fn _start() -> ! {
+pub extern "C" fn _start(argc: usize, argv: usize, envp: usize) -> ! {
     clear_bss();
     init_heap();
+    ARGC.store(argc, Ordering::Relaxed);
+    ARGV.store(argv, Ordering::Relaxed);
+    ENVP.store(envp, Ordering::Relaxed);
     exit(main());
     panic!("Unreachable after sys_exit!");
 }
@@ -36,6 +45,65 @@ fn main() -> i32 {
     panic!("Cannot find main!");
 }
 
+static ARGC: AtomicUsize = AtomicUsize::new(0);
+static ARGV: AtomicUsize = AtomicUsize::new(0);
+static ENVP: AtomicUsize = AtomicUsize::new(0);
+
+/// # Safety
+///
+/// `ptr` must point to a string ended by `\0` which is never freed.
+unsafe fn c_str(ptr: *const u8) -> &'static str {
+    let mut len = 0;
+    while *ptr.add(len) != 0 {
+        len += 1;
+    }
+    core::str::from_utf8(core::slice::from_raw_parts(ptr, len)).unwrap()
+}
+
+/// Arguments of the program, starting with its name
+pub fn args() -> Vec<&'static str> {
+    let argv = ARGV.load(Ordering::Relaxed) as *const *const u8;
+    (0..ARGC.load(Ordering::Relaxed))
+        .map(|i| unsafe { c_str(*argv.add(i)) })
+        .collect()
+}
+
+/// Environment passed to the program, as `NAME=value` strings ended by `\0`,
+/// then a null pointer
+pub(crate) fn environ() -> *const *const u8 {
+    ENVP.load(Ordering::Relaxed) as *const *const u8
+}
+
+/// Environment variables of the program as `(name, value)`
+pub fn vars() -> Vec<(&'static str, &'static str)> {
+    let envp = environ();
+    let mut vars = Vec::new();
+    if envp.is_null() {
+        return vars;
+    }
+    let mut i = 0;
+    loop {
+        let ptr = unsafe { *envp.add(i) };
+        if ptr.is_null() {
+            break;
+        }
+        let var = unsafe { c_str(ptr) };
+        if let Some((name, value)) = var.split_once('=') {
+            vars.push((name, value));
+        }
+        i += 1;
+    }
+    vars
+}
+
+/// Value of environment variable `name`
+pub fn var(name: &str) -> Option<&'static str> {
+    vars()
+        .into_iter()
+        .find(|(var_name, _)| *var_name == name)
+        .map(|(_, value)| value)
+}
+
 fn clear_bss() {
     extern "C" {
         fn sbss();
@@ -21,6 +21,8 @@ struct PCBInner {
     pub children: Vec<Arc<Process>>,
     pub exit_code: i32,
     pub is_zombie: bool,
+    /// stopped by this signal, not reported to the parent yet
+    pub stop_signal: Option<i32>,
 }
 
 impl PCBInner {
@@ -30,6 +32,7 @@ impl PCBInner {
             children: Vec::new(),
             exit_code: 0,
             is_zombie: false,
+            stop_signal: None,
         }
     }
 }
@@ -65,6 +68,40 @@ impl Process {
     pub fn set_zombie(&self) {
         self.inner.exclusive_access().is_zombie = true;
     }
+
+    pub fn parent_pid(&self) -> usize {
+        match &self.inner.exclusive_access().parent {
+            Some(parent) => parent.upgrade().map_or(0, |parent| parent.pid.0),
+            None => 0,
+        }
+    }
+
+    pub fn set_stop_signal(&self, signum: Option<i32>) {
+        self.inner.exclusive_access().stop_signal = signum;
+    }
+
+    /// Take the stop not reported to the parent yet.
+    pub fn take_stop_signal(&self) -> Option<i32> {
+        self.inner.exclusive_access().stop_signal.take()
+    }
+}
+
+/// What the process manager knows about a process, for `/proc/<pid>/status`
+pub struct ProcessInfo {
+    /// 0 if it has no parent
+    pub parent: usize,
+    pub children: Vec<usize>,
+    pub zombie: bool,
+    /// wait status, if `zombie`
+    pub exit_code: i32,
+}
+
+/// `options` of `waitpid`: also report stopped children
+const WUNTRACED: usize = 2;
+
+/// Wait status of a child stopped by signal `signum`, following Linux
+fn stopped_status(signum: i32) -> i32 {
+    signum << 8 | 0x7f
 }
 
 // -----------------------------------------------------
@@ -114,15 +151,49 @@ impl ProcessManager {
         exit_inner.children.clear();
     }
 
+    pub fn stop(&mut self, pid: usize, signum: i32) {
+        self.get(pid).unwrap().set_stop_signal(Some(signum));
+    }
+
+    pub fn cont(&mut self, pid: usize) {
+        self.get(pid).unwrap().set_stop_signal(None);
+    }
+
+    /// Return 0 if `pid` does not exist or has no parent.
+    pub fn getppid(&self, pid: usize) -> usize {
+        self.get(pid).map_or(0, |process| process.parent_pid())
+    }
+
+    /// Pids of all processes not waited yet
+    pub fn pids(&self) -> Vec<usize> {
+        self.processes.keys().copied().collect()
+    }
+
+    /// Return None if `pid` does not exist or has been waited.
+    pub fn info(&self, pid: usize) -> Option<ProcessInfo> {
+        let process = self.get(pid)?;
+        let parent = process.parent_pid();
+        let inner = process.inner.exclusive_access();
+        Some(ProcessInfo {
+            parent,
+            children: inner.children.iter().map(|child| child.pid.0).collect(),
+            zombie: inner.is_zombie,
+            exit_code: inner.exit_code,
+        })
+    }
+
     /// If there is not a child process whose pid is same as given, return (0, _).
     /// Else if there is a child process but it is still running, return (1, _).
     /// Else return (found_pid, exit_code).
-    pub fn waitpid(&mut self, parent_pid: usize, pid: i32) -> (usize, i32) {
+    ///
+    /// With `WUNTRACED` in `options`, a stopped child is also reported, only once for each stop.
+    pub fn waitpid(&mut self, parent_pid: usize, pid: i32, options: usize) -> (usize, i32) {
         let parent_process = self.get(parent_pid).unwrap();
         let mut parent_inner = parent_process.inner.exclusive_access();
         let mut found_pid = 0; // assume not found at first
         let mut exit_code = 0;
         let mut remove = false;
+        let untraced = options & WUNTRACED != 0;
         if pid == -1 {
             if parent_inner.children.is_empty() {
                 return (0, 0);
@@ -136,6 +207,14 @@ impl ProcessManager {
                     remove = true;
                     break;
                 }
+                if !untraced {
+                    continue;
+                }
+                if let Some(signum) = child.take_stop_signal() {
+                    found_pid = child.pid.0;
+                    exit_code = stopped_status(signum);
+                    break;
+                }
             }
         } else {
             for child in parent_inner.children.iter() {
@@ -147,6 +226,12 @@ impl ProcessManager {
                         remove = true;
                     } else {
                         found_pid = 1;
+                        if untraced {
+                            if let Some(signum) = child.take_stop_signal() {
+                                found_pid = child.pid.0;
+                                exit_code = stopped_status(signum);
+                            }
+                        }
                     }
                     break;
                 }
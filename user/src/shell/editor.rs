@@ -0,0 +1,321 @@
+//! Line editing with history and completion
+//!
+//! The console sends every key as soon as it is typed and does not echo it, so
+//! the editor echoes and redraws the line itself. The keys are those of bash:
+//!
+//! | key                   | action                                   |
+//! |-----------------------|------------------------------------------|
+//! | Left / Ctrl-B         | move back a character                    |
+//! | Right / Ctrl-F        | move forward a character                 |
+//! | Home / Ctrl-A         | move to the start of the line            |
+//! | End / Ctrl-E          | move to the end of the line              |
+//! | Backspace             | delete the character before the cursor   |
+//! | Delete / Ctrl-D       | delete the character under the cursor    |
+//! | Ctrl-K                | delete to the end of the line            |
+//! | Ctrl-U                | delete to the start of the line          |
+//! | Ctrl-W                | delete the word before the cursor        |
+//! | Up / Ctrl-P           | previous command in history              |
+//! | Down / Ctrl-N         | next command in history                  |
+//! | Tab                   | complete the word before the cursor      |
+
+use crate::errno::EINTR;
+use crate::{print, println, read};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+const CTRL_A: u8 = 0x01;
+const CTRL_B: u8 = 0x02;
+const CTRL_D: u8 = 0x04;
+const CTRL_E: u8 = 0x05;
+const CTRL_F: u8 = 0x06;
+const BS: u8 = 0x08; // Backspace
+const TAB: u8 = 0x09;
+const LF: u8 = 0x0a; // Line Feed
+const CTRL_K: u8 = 0x0b;
+const CR: u8 = 0x0d; // Carriage Return
+const CTRL_N: u8 = 0x0e;
+const CTRL_P: u8 = 0x10;
+const CTRL_U: u8 = 0x15;
+const CTRL_W: u8 = 0x17;
+const ESC: u8 = 0x1b;
+const DL: u8 = 0x7f; // Delete
+
+/// Commands kept in history
+const HISTORY_SIZE: usize = 100;
+
+/// Characters which end a word to complete
+const WORD_BREAKS: &[u8] = b" \t|&;<>";
+
+/// Read a byte from standard input, return None at the end of input.
+fn read_byte() -> Option<u8> {
+    let mut buf = [0u8; 1];
+    loop {
+        match read(0, &mut buf) {
+            1 => return Some(buf[0]),
+            ret if ret == -EINTR => continue,
+            _ => return None,
+        }
+    }
+}
+
+/// Read a key, return None at the end of input.
+///
+/// The escape sequences of the arrow, Home, End and Delete keys are turned into
+/// the control keys of the same action, and unknown ones into 0.
+fn read_key() -> Option<u8> {
+    let c = read_byte()?;
+    if c != ESC {
+        return Some(c);
+    }
+    let c = read_byte()?;
+    if c != b'[' && c != b'O' {
+        return Some(0);
+    }
+    // `ESC [ <number> ~` or `ESC [ <letter>`
+    let mut number = 0;
+    let last = loop {
+        let c = read_byte()?;
+        match c {
+            b'0'..=b'9' => number = number * 10 + (c - b'0') as usize,
+            b';' => {}
+            _ => break c,
+        }
+    };
+    let key = match (last, number) {
+        (b'A', _) => CTRL_P,
+        (b'B', _) => CTRL_N,
+        (b'C', _) => CTRL_F,
+        (b'D', _) => CTRL_B,
+        (b'H', _) | (b'~', 1) | (b'~', 7) => CTRL_A,
+        (b'F', _) | (b'~', 4) | (b'~', 8) => CTRL_E,
+        (b'~', 3) => CTRL_D,
+        _ => 0,
+    };
+    Some(key)
+}
+
+/// Longest common prefix of `words`, which is not empty
+fn common_prefix(words: &[String]) -> &str {
+    let first = words[0].as_str();
+    let len = words[1..].iter().fold(first.len(), |len, word| {
+        first
+            .bytes()
+            .zip(word.bytes())
+            .take(len)
+            .take_while(|(a, b)| a == b)
+            .count()
+    });
+    &first[..len]
+}
+
+/// The line being edited
+struct Line<'a> {
+    prompt: &'a str,
+    /// printable ASCII only
+    buf: Vec<u8>,
+    cursor: usize,
+}
+
+impl<'a> Line<'a> {
+    fn new(prompt: &'a str) -> Self {
+        Self {
+            prompt,
+            buf: Vec::new(),
+            cursor: 0,
+        }
+    }
+
+    fn text(&self) -> &str {
+        core::str::from_utf8(&self.buf).unwrap()
+    }
+
+    /// Print the prompt and the line again, and put the cursor back.
+    fn refresh(&self) {
+        let mut out = String::from("\r");
+        out.push_str(self.prompt);
+        out.push_str(self.text());
+        // clear the rest of the old line
+        out.push_str("\x1b[K");
+        let back = self.buf.len() - self.cursor;
+        if back > 0 {
+            write!(out, "\x1b[{}D", back).unwrap();
+        }
+        print!("{}", out);
+    }
+
+    fn insert(&mut self, text: &[u8]) {
+        let at_end = self.cursor == self.buf.len();
+        self.buf
+            .splice(self.cursor..self.cursor, text.iter().copied());
+        self.cursor += text.len();
+        if at_end {
+            print!("{}", core::str::from_utf8(text).unwrap());
+        } else {
+            self.refresh();
+        }
+    }
+
+    /// Delete characters in `start..end`, and move the cursor to `start`.
+    fn delete(&mut self, start: usize, end: usize) {
+        if start < end {
+            self.buf.drain(start..end);
+            self.cursor = start;
+            self.refresh();
+        }
+    }
+
+    fn move_to(&mut self, cursor: usize) {
+        if cursor != self.cursor {
+            self.cursor = cursor;
+            self.refresh();
+        }
+    }
+
+    /// Replace the whole line with `text`.
+    fn set(&mut self, text: &str) {
+        self.buf = Vec::from(text.as_bytes());
+        self.cursor = self.buf.len();
+        self.refresh();
+    }
+
+    /// Start of the word before the cursor, for Ctrl-W
+    fn word_start(&self) -> usize {
+        let before = &self.buf[..self.cursor];
+        let end = before.iter().rposition(|c| *c != b' ').map_or(0, |i| i + 1);
+        before[..end]
+            .iter()
+            .rposition(|c| *c == b' ')
+            .map_or(0, |i| i + 1)
+    }
+}
+
+/// Reads lines from the console with editing keys, and keeps their history.
+pub struct LineEditor {
+    /// oldest first
+    history: Vec<String>,
+}
+
+impl LineEditor {
+    pub fn new() -> Self {
+        Self {
+            history: Vec::new(),
+        }
+    }
+
+    /// Commands read before, oldest first
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    fn add_history(&mut self, line: &str) {
+        if line.trim().is_empty() || self.history.last().map(String::as_str) == Some(line) {
+            return;
+        }
+        if self.history.len() == HISTORY_SIZE {
+            self.history.remove(0);
+        }
+        self.history.push(String::from(line));
+    }
+
+    /// Print `prompt` and read a line, return None at the end of input.
+    ///
+    /// On Tab, `complete(before, word)` gives the candidates for `word`, the word
+    /// before the cursor, where `before` is the text before the word.
+    pub fn read_line(
+        &mut self,
+        prompt: &str,
+        complete: &dyn Fn(&str, &str) -> Vec<String>,
+    ) -> Option<String> {
+        let mut line = Line::new(prompt);
+        print!("{}", prompt);
+        // position in history, `history.len()` for the new line
+        let mut index = self.history.len();
+        // the new line, kept while browsing history
+        let mut new_line = String::new();
+        loop {
+            let c = match read_key() {
+                Some(c) => c,
+                None if line.buf.is_empty() => return None,
+                None => break,
+            };
+            match c {
+                LF | CR => break,
+                CTRL_A => line.move_to(0),
+                CTRL_E => line.move_to(line.buf.len()),
+                CTRL_B => line.move_to(line.cursor.saturating_sub(1)),
+                CTRL_F => line.move_to((line.cursor + 1).min(line.buf.len())),
+                BS | DL => line.delete(line.cursor.saturating_sub(1), line.cursor),
+                CTRL_D => line.delete(line.cursor, (line.cursor + 1).min(line.buf.len())),
+                CTRL_K => line.delete(line.cursor, line.buf.len()),
+                CTRL_U => line.delete(0, line.cursor),
+                CTRL_W => line.delete(line.word_start(), line.cursor),
+                CTRL_P if index > 0 => {
+                    if index == self.history.len() {
+                        new_line = String::from(line.text());
+                    }
+                    index -= 1;
+                    line.set(&self.history[index]);
+                }
+                CTRL_N if index < self.history.len() => {
+                    index += 1;
+                    match self.history.get(index) {
+                        Some(command) => line.set(command),
+                        None => line.set(&new_line),
+                    }
+                }
+                TAB => Self::complete(&mut line, complete),
+                0x20..=0x7e => line.insert(&[c]),
+                _ => {}
+            }
+        }
+        println!("");
+        let text = String::from(line.text());
+        self.add_history(&text);
+        Some(text)
+    }
+
+    fn complete(line: &mut Line, complete: &dyn Fn(&str, &str) -> Vec<String>) {
+        let before = &line.buf[..line.cursor];
+        let start = before
+            .iter()
+            .rposition(|c| WORD_BREAKS.contains(c))
+            .map_or(0, |i| i + 1);
+        let text = line.text();
+        let word = &text[start..line.cursor];
+        let mut candidates = complete(&text[..start], word);
+        candidates.retain(|candidate| {
+            candidate.starts_with(word) && candidate.bytes().all(|c| (0x20..=0x7e).contains(&c))
+        });
+        candidates.sort();
+        candidates.dedup();
+        match candidates.len() {
+            0 => {
+                // bell
+                print!("\x07");
+            }
+            1 => {
+                let rest = String::from(&candidates[0][word.len()..]) + " ";
+                line.insert(rest.as_bytes());
+            }
+            _ => {
+                let common = common_prefix(&candidates);
+                if common.len() > word.len() {
+                    let rest = String::from(&common[word.len()..]);
+                    line.insert(rest.as_bytes());
+                } else {
+                    // list them below, then the line again
+                    println!("");
+                    println!("{}", candidates.join("  "));
+                    line.refresh();
+                }
+            }
+        }
+    }
+}
+
+impl Default for LineEditor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,234 @@
+//! Splitting a command line into tokens
+
+use super::parser::ParseError;
+use super::word::{is_name, Word, WordPart};
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::iter::Peekable;
+use core::str::Chars;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Token {
+    Word(Word),
+    /// a line break, which ends a statement like `;`
+    Newline,
+    /// `|`
+    Pipe,
+    /// `&&`
+    And,
+    /// `||`
+    Or,
+    /// `;`
+    Semi,
+    /// `&`
+    Amp,
+    /// `[fd]<`, `[fd]>` or `[fd]>>`, with the default fd if not given
+    Redirect {
+        fd: usize,
+        kind: RedirectKind,
+    },
+    /// `&n` right after a redirection operator
+    DupFd(usize),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectKind {
+    Read,
+    Write,
+    Append,
+}
+
+fn is_operator(c: char) -> bool {
+    matches!(c, '|' | '&' | ';' | '<' | '>')
+}
+
+pub fn tokenize(line: &str) -> Result<Vec<Token>, ParseError> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            '\n' => {
+                chars.next();
+                tokens.push(Token::Newline);
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '#' => {
+                // a comment lasts until the end of line
+                while chars.peek().is_some_and(|&c| c != '\n') {
+                    chars.next();
+                }
+            }
+            '|' | '&' | ';' => {
+                chars.next();
+                let token = match (c, chars.peek()) {
+                    ('|', Some('|')) => Token::Or,
+                    ('&', Some('&')) => Token::And,
+                    ('|', _) => Token::Pipe,
+                    ('&', _) => Token::Amp,
+                    _ => Token::Semi,
+                };
+                if matches!(token, Token::Or | Token::And) {
+                    chars.next();
+                }
+                tokens.push(token);
+            }
+            '<' | '>' => lex_redirect(&mut chars, None, &mut tokens),
+            _ => {
+                let word = lex_word(&mut chars)?;
+                // `2>` is a redirection of fd 2, but `2 >` and `"2">` are not
+                let fd = word
+                    .literal()
+                    .filter(|text| text.bytes().all(|c| c.is_ascii_digit()))
+                    .and_then(|text| text.parse::<usize>().ok());
+                match (fd, chars.peek()) {
+                    (Some(fd), Some('<' | '>')) => lex_redirect(&mut chars, Some(fd), &mut tokens),
+                    // nothing but a line continuation
+                    _ if word.parts.is_empty() => {}
+                    _ => tokens.push(Token::Word(word)),
+                }
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+/// Lex `<`, `>`, `>>`, and `&n` following them.
+fn lex_redirect(chars: &mut Peekable<Chars>, fd: Option<usize>, tokens: &mut Vec<Token>) {
+    let (default_fd, kind) = match chars.next() {
+        Some('<') => (0, RedirectKind::Read),
+        _ if chars.peek() == Some(&'>') => {
+            chars.next();
+            (1, RedirectKind::Append)
+        }
+        _ => (1, RedirectKind::Write),
+    };
+    tokens.push(Token::Redirect {
+        fd: fd.unwrap_or(default_fd),
+        kind,
+    });
+    if chars.peek() != Some(&'&') {
+        return;
+    }
+    let mut rest = chars.clone();
+    rest.next();
+    let mut n = String::new();
+    while let Some(&c) = rest.peek() {
+        if !c.is_ascii_digit() {
+            break;
+        }
+        n.push(c);
+        rest.next();
+    }
+    if let Ok(target) = n.parse() {
+        *chars = rest;
+        tokens.push(Token::DupFd(target));
+    }
+}
+
+/// Lex a word, removing quotes and escapes.
+fn lex_word(chars: &mut Peekable<Chars>) -> Result<Word, ParseError> {
+    let mut word = Word::default();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() || is_operator(c) {
+            break;
+        }
+        chars.next();
+        match c {
+            '\\' => match chars.next() {
+                // a line continuation
+                Some('\n') => {}
+                Some(c) => word.push_quoted(c),
+                None => return Err(ParseError::incomplete("unexpected end of input after `\\`")),
+            },
+            '\'' => {
+                // `''` is an empty word, but still a word
+                word.parts.push(WordPart::Quoted(String::new()));
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push_quoted(c),
+                        None => return Err(ParseError::incomplete("unterminated quote `'`")),
+                    }
+                }
+            }
+            '"' => {
+                word.parts.push(WordPart::Quoted(String::new()));
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('\n') => {}
+                            Some(c @ ('"' | '\\' | '$')) => word.push_quoted(c),
+                            Some(c) => {
+                                word.push_quoted('\\');
+                                word.push_quoted(c);
+                            }
+                            None => return Err(ParseError::incomplete("unterminated quote `\"`")),
+                        },
+                        Some('$') => lex_param(chars, &mut word, true)?,
+                        Some(c) => word.push_quoted(c),
+                        None => return Err(ParseError::incomplete("unterminated quote `\"`")),
+                    }
+                }
+            }
+            '$' => lex_param(chars, &mut word, false)?,
+            _ => word.push_literal(c),
+        }
+    }
+    Ok(word)
+}
+
+/// Lex `name`, `{name}` or a special parameter after `$`. A `$` followed by
+/// none of them is just `$`.
+fn lex_param(chars: &mut Peekable<Chars>, word: &mut Word, quoted: bool) -> Result<(), ParseError> {
+    let is_special = |c: char| matches!(c, '?' | '$' | '#') || c.is_ascii_digit();
+    let name = match chars.peek() {
+        Some('{') => {
+            chars.next();
+            let mut name = String::new();
+            loop {
+                match chars.next() {
+                    Some('}') => break,
+                    Some(c) => name.push(c),
+                    None => return Err(ParseError::incomplete("missing `}`")),
+                }
+            }
+            let special = name.len() == 1 && name.chars().all(is_special);
+            if !special && !is_name(&name) {
+                return Err(ParseError::new(&format!(
+                    "bad substitution `${{{}}}`",
+                    name
+                )));
+            }
+            name
+        }
+        Some(&c) if is_special(c) => {
+            chars.next();
+            String::from(c)
+        }
+        Some(&c) if c.is_ascii_alphabetic() || c == '_' => {
+            let mut name = String::new();
+            while let Some(&c) = chars.peek() {
+                if !c.is_ascii_alphanumeric() && c != '_' {
+                    break;
+                }
+                name.push(c);
+                chars.next();
+            }
+            name
+        }
+        _ => {
+            if quoted {
+                word.push_quoted('$');
+            } else {
+                word.push_literal('$');
+            }
+            return Ok(());
+        }
+    };
+    word.parts.push(WordPart::Param(name));
+    Ok(())
+}
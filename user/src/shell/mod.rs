@@ -0,0 +1,33 @@
+//! Command line parsing for the shell
+//!
+//! The grammar is a small subset of the POSIX shell:
+//!
+//! ```text
+//! list     := (and_or ('&' | ';' | newline))* and_or?
+//! and_or   := pipeline (('&&' | '||') pipeline)*
+//! pipeline := command ('|' command)*
+//! command  := simple | if | while
+//! simple   := (NAME=word)* (word | redirect)+
+//! redirect := [n] ('<' | '>' | '>>') (word | '&' n)
+//! if       := 'if' list 'then' list ('elif' list 'then' list)* ('else' list)? 'fi'
+//! while    := 'while' list 'do' list 'done'
+//! ```
+//!
+//! Words may be quoted with `'...'` (literally) or `"..."` (where `\` escapes
+//! `"`, `\` and `$`), and `\` escapes the next character outside quotes.
+//! `$NAME`, `${NAME}`, `$?`, `$$`, `$#` and `$0`-`$9` are expanded out of
+//! single quotes when the command runs. A `#` at the beginning of a word
+//! starts a comment, and `\` at the end of a line continues it.
+
+mod editor;
+mod lexer;
+mod parser;
+mod word;
+
+pub use editor::LineEditor;
+
+pub use parser::{
+    parse, AndOrList, Assignment, Command, Connector, IfClause, ParseError, Pipeline, Redirect,
+    RedirectTarget, SimpleCommand, Statement, WhileClause,
+};
+pub use word::{is_name, Word, WordPart};
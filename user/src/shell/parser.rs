@@ -0,0 +1,464 @@
+//! Building statements from tokens
+
+use super::lexer::{tokenize, RedirectKind, Token};
+use super::word::Word;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+use core::iter::Peekable;
+use core::slice::Iter;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    message: String,
+    /// the input ends in the middle of a command, which more lines may complete
+    incomplete: bool,
+}
+
+impl ParseError {
+    pub fn new(message: &str) -> Self {
+        Self {
+            message: String::from(message),
+            incomplete: false,
+        }
+    }
+
+    pub fn incomplete(message: &str) -> Self {
+        Self {
+            message: String::from(message),
+            incomplete: true,
+        }
+    }
+
+    /// Whether the input is a valid beginning of a command, e.g. `if true` or
+    /// `echo 'a`
+    pub fn is_incomplete(&self) -> bool {
+        self.incomplete
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "syntax error: {}", self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RedirectTarget {
+    /// `< file`
+    Read(Word),
+    /// `> file`
+    Write(Word),
+    /// `>> file`
+    Append(Word),
+    /// `>&n` or `<&n`, a copy of descriptor `n`
+    Fd(usize),
+}
+
+/// Make descriptor `fd` refer to `target`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Redirect {
+    pub fd: usize,
+    pub target: RedirectTarget,
+}
+
+/// `NAME=value`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assignment {
+    pub name: String,
+    pub value: Word,
+}
+
+/// A program with its arguments, `args[0]` is the program
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SimpleCommand {
+    /// variables set for the program, or for the shell if there is no program
+    pub assignments: Vec<Assignment>,
+    /// empty if there are only assignments
+    pub args: Vec<Word>,
+    /// applied in order
+    pub redirects: Vec<Redirect>,
+}
+
+/// `if list; then list; [elif list; then list;]... [else list;] fi`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IfClause {
+    /// conditions and the statements run if they succeed, tried in order
+    pub branches: Vec<(Vec<Statement>, Vec<Statement>)>,
+    /// `else` part
+    pub otherwise: Option<Vec<Statement>>,
+}
+
+/// `while list; do list; done`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WhileClause {
+    pub condition: Vec<Statement>,
+    pub body: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Simple(SimpleCommand),
+    If(IfClause),
+    While(WhileClause),
+}
+
+/// Commands connected by pipes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pipeline {
+    pub commands: Vec<Command>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Connector {
+    /// `&&`, run the next pipeline if the last one succeeded
+    And,
+    /// `||`, run the next pipeline if the last one failed
+    Or,
+}
+
+/// Pipelines connected by `&&` and `||`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AndOrList {
+    pub first: Pipeline,
+    pub rest: Vec<(Connector, Pipeline)>,
+}
+
+/// An and-or list ended by `;`, `&` or a line break
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Statement {
+    pub and_or: AndOrList,
+    /// ended by `&`
+    pub background: bool,
+}
+
+/// Words which begin or end a compound command
+const RESERVED_WORDS: [&str; 8] = ["if", "then", "elif", "else", "fi", "while", "do", "done"];
+
+impl fmt::Display for SimpleCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut words = Vec::new();
+        for assignment in self.assignments.iter() {
+            words.push(format!("{}={}", assignment.name, assignment.value));
+        }
+        for arg in self.args.iter() {
+            words.push(format!("{}", arg));
+        }
+        for redirect in self.redirects.iter() {
+            let (op, target) = match &redirect.target {
+                RedirectTarget::Read(path) => ("<", format!("{}", path)),
+                RedirectTarget::Write(path) => (">", format!("{}", path)),
+                RedirectTarget::Append(path) => (">>", format!("{}", path)),
+                RedirectTarget::Fd(fd) if redirect.fd == 0 => ("<&", format!("{}", fd)),
+                RedirectTarget::Fd(fd) => (">&", format!("{}", fd)),
+            };
+            let default_fd = match redirect.target {
+                RedirectTarget::Read(_) => 0,
+                RedirectTarget::Fd(_) if redirect.fd == 0 => 0,
+                _ => 1,
+            };
+            if redirect.fd == default_fd {
+                words.push(format!("{}{}", op, target));
+            } else {
+                words.push(format!("{}{}{}", redirect.fd, op, target));
+            }
+        }
+        write!(f, "{}", words.join(" "))
+    }
+}
+
+/// Display statements on one line, each ended by `;` or `&`.
+struct List<'a>(&'a [Statement]);
+
+impl fmt::Display for List<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for statement in self.0.iter() {
+            let end = if statement.background { '&' } else { ';' };
+            write!(f, " {}{}", statement.and_or, end)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Command::Simple(command) => write!(f, "{}", command),
+            Command::If(clause) => {
+                for (i, (condition, body)) in clause.branches.iter().enumerate() {
+                    let keyword = if i == 0 { "if" } else { " elif" };
+                    write!(f, "{}{} then{}", keyword, List(condition), List(body))?;
+                }
+                if let Some(otherwise) = &clause.otherwise {
+                    write!(f, " else{}", List(otherwise))?;
+                }
+                write!(f, " fi")
+            }
+            Command::While(clause) => write!(
+                f,
+                "while{} do{} done",
+                List(&clause.condition),
+                List(&clause.body)
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Pipeline {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, command) in self.commands.iter().enumerate() {
+            if i > 0 {
+                write!(f, " | ")?;
+            }
+            write!(f, "{}", command)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for AndOrList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.first)?;
+        for (connector, pipeline) in self.rest.iter() {
+            match connector {
+                Connector::And => write!(f, " && {}", pipeline)?,
+                Connector::Or => write!(f, " || {}", pipeline)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse a command line, or a whole script, into statements.
+pub fn parse(text: &str) -> Result<Vec<Statement>, ParseError> {
+    let tokens = tokenize(text)?;
+    let mut tokens = tokens.iter().peekable();
+    let statements = parse_list(&mut tokens)?;
+    match tokens.next() {
+        // a reserved word out of place, like `fi`
+        Some(token) => Err(unexpected(Some(token))),
+        None => Ok(statements),
+    }
+}
+
+type Tokens<'a> = Peekable<Iter<'a, Token>>;
+
+fn unexpected(token: Option<&Token>) -> ParseError {
+    let text = match token {
+        None => return ParseError::incomplete("unexpected end of input"),
+        Some(Token::Word(word)) => {
+            return ParseError::new(&format!("unexpected word `{}`", word));
+        }
+        Some(Token::Newline) => "newline",
+        Some(Token::Pipe) => "|",
+        Some(Token::And) => "&&",
+        Some(Token::Or) => "||",
+        Some(Token::Semi) => ";",
+        Some(Token::Amp) => "&",
+        Some(Token::Redirect { kind, .. }) => match kind {
+            RedirectKind::Read => "<",
+            RedirectKind::Write => ">",
+            RedirectKind::Append => ">>",
+        },
+        Some(Token::DupFd(_)) => "&",
+    };
+    ParseError::new(&format!("unexpected token `{}`", text))
+}
+
+/// The reserved word `token` is, if it is one
+fn reserved_word(token: Option<&&Token>) -> Option<&'static str> {
+    match token {
+        Some(Token::Word(word)) => {
+            let text = word.literal()?;
+            RESERVED_WORDS
+                .iter()
+                .copied()
+                .find(|reserved| *reserved == text)
+        }
+        _ => None,
+    }
+}
+
+fn skip_newlines(tokens: &mut Tokens) {
+    while tokens.peek() == Some(&&Token::Newline) {
+        tokens.next();
+    }
+}
+
+/// Consume the reserved word `expected`.
+fn expect(tokens: &mut Tokens, expected: &str) -> Result<(), ParseError> {
+    if reserved_word(tokens.peek()) == Some(expected) {
+        tokens.next();
+        return Ok(());
+    }
+    match tokens.peek() {
+        None => Err(ParseError::incomplete(&format!(
+            "unexpected end of input, expecting `{}`",
+            expected
+        ))),
+        token => Err(unexpected(token.copied())),
+    }
+}
+
+/// Parse statements until the end of input or a reserved word which ends a
+/// list, like `then` or `fi`.
+fn parse_list(tokens: &mut Tokens) -> Result<Vec<Statement>, ParseError> {
+    let mut statements = Vec::new();
+    loop {
+        skip_newlines(tokens);
+        match reserved_word(tokens.peek()) {
+            Some("if") | Some("while") => {}
+            Some(_) => break,
+            None if tokens.peek().is_none() => break,
+            None => {}
+        }
+        let and_or = parse_and_or(tokens)?;
+        let background = match tokens.peek() {
+            None => false,
+            Some(Token::Semi) | Some(Token::Newline) => {
+                tokens.next();
+                false
+            }
+            Some(Token::Amp) => {
+                tokens.next();
+                true
+            }
+            token => return Err(unexpected(token.copied())),
+        };
+        statements.push(Statement { and_or, background });
+    }
+    Ok(statements)
+}
+
+/// Parse a list which must not be empty, like the condition of `if`.
+fn parse_nonempty_list(tokens: &mut Tokens) -> Result<Vec<Statement>, ParseError> {
+    let statements = parse_list(tokens)?;
+    if statements.is_empty() {
+        return Err(unexpected(tokens.peek().copied()));
+    }
+    Ok(statements)
+}
+
+fn parse_and_or(tokens: &mut Tokens) -> Result<AndOrList, ParseError> {
+    let first = parse_pipeline(tokens)?;
+    let mut rest = Vec::new();
+    loop {
+        let connector = match tokens.peek() {
+            Some(Token::And) => Connector::And,
+            Some(Token::Or) => Connector::Or,
+            _ => break,
+        };
+        tokens.next();
+        skip_newlines(tokens);
+        rest.push((connector, parse_pipeline(tokens)?));
+    }
+    Ok(AndOrList { first, rest })
+}
+
+fn parse_pipeline(tokens: &mut Tokens) -> Result<Pipeline, ParseError> {
+    let mut commands = Vec::from([parse_command(tokens)?]);
+    while tokens.peek() == Some(&&Token::Pipe) {
+        tokens.next();
+        skip_newlines(tokens);
+        commands.push(parse_command(tokens)?);
+    }
+    Ok(Pipeline { commands })
+}
+
+fn parse_command(tokens: &mut Tokens) -> Result<Command, ParseError> {
+    match reserved_word(tokens.peek()) {
+        Some("if") => parse_if(tokens).map(Command::If),
+        Some("while") => parse_while(tokens).map(Command::While),
+        Some(_) => Err(unexpected(tokens.peek().copied())),
+        None => parse_simple_command(tokens).map(Command::Simple),
+    }
+}
+
+fn parse_if(tokens: &mut Tokens) -> Result<IfClause, ParseError> {
+    expect(tokens, "if")?;
+    let mut branches = Vec::new();
+    let mut otherwise = None;
+    loop {
+        let condition = parse_nonempty_list(tokens)?;
+        expect(tokens, "then")?;
+        branches.push((condition, parse_nonempty_list(tokens)?));
+        match reserved_word(tokens.peek()) {
+            Some("elif") => {
+                tokens.next();
+            }
+            Some("else") => {
+                tokens.next();
+                otherwise = Some(parse_nonempty_list(tokens)?);
+                expect(tokens, "fi")?;
+                break;
+            }
+            _ => {
+                expect(tokens, "fi")?;
+                break;
+            }
+        }
+    }
+    Ok(IfClause {
+        branches,
+        otherwise,
+    })
+}
+
+fn parse_while(tokens: &mut Tokens) -> Result<WhileClause, ParseError> {
+    expect(tokens, "while")?;
+    let condition = parse_nonempty_list(tokens)?;
+    expect(tokens, "do")?;
+    let body = parse_nonempty_list(tokens)?;
+    expect(tokens, "done")?;
+    Ok(WhileClause { condition, body })
+}
+
+fn parse_simple_command(tokens: &mut Tokens) -> Result<SimpleCommand, ParseError> {
+    let mut assignments = Vec::new();
+    let mut args = Vec::new();
+    let mut redirects = Vec::new();
+    loop {
+        match tokens.peek() {
+            Some(Token::Word(word)) => {
+                // `NAME=value` before the program name
+                match word.assignment() {
+                    Some((name, value)) if args.is_empty() => {
+                        assignments.push(Assignment { name, value })
+                    }
+                    _ => args.push(word.clone()),
+                }
+                tokens.next();
+            }
+            Some(&&Token::Redirect { fd, kind }) => {
+                tokens.next();
+                let target = match (tokens.next(), kind) {
+                    (Some(Token::DupFd(target)), _) => RedirectTarget::Fd(*target),
+                    (Some(Token::Word(path)), RedirectKind::Read) => {
+                        RedirectTarget::Read(path.clone())
+                    }
+                    (Some(Token::Word(path)), RedirectKind::Write) => {
+                        RedirectTarget::Write(path.clone())
+                    }
+                    (Some(Token::Word(path)), RedirectKind::Append) => {
+                        RedirectTarget::Append(path.clone())
+                    }
+                    // not waiting for more lines, like bash
+                    (None, _) => return Err(ParseError::new("unexpected end of input")),
+                    (token, _) => return Err(unexpected(token)),
+                };
+                redirects.push(Redirect { fd, target });
+            }
+            token => {
+                if args.is_empty() && assignments.is_empty() && redirects.is_empty() {
+                    return Err(unexpected(token.copied()));
+                }
+                return Ok(SimpleCommand {
+                    assignments,
+                    args,
+                    redirects,
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,105 @@
+//! Words with quoting and parameter expansions
+
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WordPart {
+    /// text out of quotes
+    Literal(String),
+    /// text quoted or escaped, never a reserved word or an assignment
+    Quoted(String),
+    /// `$name` or `${name}`, where `name` is a variable or one of `?`, `$`, `#`
+    /// and `0`-`9`
+    Param(String),
+}
+
+/// A word of the command line, expanded just before it is used.
+///
+/// Expansions are not split into fields, `$a` is one argument even if `a`
+/// contains spaces.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Word {
+    pub parts: Vec<WordPart>,
+}
+
+/// Whether `name` is a valid variable name
+pub fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl Word {
+    pub(super) fn push_literal(&mut self, c: char) {
+        match self.parts.last_mut() {
+            Some(WordPart::Literal(text)) => text.push(c),
+            _ => self.parts.push(WordPart::Literal(String::from(c))),
+        }
+    }
+
+    pub(super) fn push_quoted(&mut self, c: char) {
+        match self.parts.last_mut() {
+            Some(WordPart::Quoted(text)) => text.push(c),
+            _ => self.parts.push(WordPart::Quoted(String::from(c))),
+        }
+    }
+
+    /// The text of a word without quotes and expansions, which may be a
+    /// reserved word such as `if`
+    pub fn literal(&self) -> Option<&str> {
+        match self.parts.as_slice() {
+            [WordPart::Literal(text)] => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Split `NAME=value` into the name and the value.
+    pub fn assignment(&self) -> Option<(String, Word)> {
+        let (first, rest) = self.parts.split_first()?;
+        let text = match first {
+            WordPart::Literal(text) => text,
+            _ => return None,
+        };
+        let (name, value) = text.split_once('=')?;
+        if !is_name(name) {
+            return None;
+        }
+        let mut parts = Vec::new();
+        if !value.is_empty() {
+            parts.push(WordPart::Literal(String::from(value)));
+        }
+        parts.extend_from_slice(rest);
+        Some((String::from(name), Word { parts }))
+    }
+
+    /// Replace parameters by their values given by `lookup`.
+    pub fn expand(&self, lookup: &dyn Fn(&str) -> String) -> String {
+        let mut text = String::new();
+        for part in self.parts.iter() {
+            match part {
+                WordPart::Literal(s) | WordPart::Quoted(s) => text.push_str(s),
+                WordPart::Param(name) => text.push_str(&lookup(name)),
+            }
+        }
+        text
+    }
+}
+
+impl fmt::Display for Word {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for part in self.parts.iter() {
+            match part {
+                WordPart::Literal(text) => write!(f, "{}", text)?,
+                WordPart::Quoted(text) => write!(f, "'{}'", text.replace('\'', "'\\''"))?,
+                WordPart::Param(name) if is_name(name) => write!(f, "${{{}}}", name)?,
+                WordPart::Param(name) => write!(f, "${}", name)?,
+            }
+        }
+        if self.parts.is_empty() {
+            write!(f, "''")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,121 @@
+//! Thread synchronization
+//!
+//! [`Mutex`], [`Semaphore`] and [`Condvar`] own a kernel object id of the
+//! current process. Kernel objects are recycled when the process exits or
+//! execs, so they are never destroyed here.
+//!
+//! [`FutexMutex`] and [`Once`] live in user space and only enter the kernel
+//! under contention.
+
+mod futex;
+
+use crate::syscall::*;
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+
+pub use futex::{futex_wait, futex_wake, FutexMutex, FutexMutexGuard, Once};
+
+/// A mutex protecting `T`, blocking the waiting threads in the kernel.
+pub struct Mutex<T> {
+    id: usize,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for Mutex<T> {}
+unsafe impl<T: Send> Send for Mutex<T> {}
+
+impl<T> Mutex<T> {
+    pub fn new(data: T) -> Self {
+        let id = sys_mutex_create();
+        assert!(id >= 0, "Failed to create mutex!");
+        Self {
+            id: id as usize,
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        assert_eq!(sys_mutex_lock(self.id), 0);
+        MutexGuard { mutex: self }
+    }
+}
+
+/// ### RAII
+///
+/// - get resource : `Mutex::lock` -> `MutexGuard`
+/// - release resource automatically : `drop` -> `sys_mutex_unlock`
+pub struct MutexGuard<'a, T> {
+    mutex: &'a Mutex<T>,
+}
+
+impl<T> Deref for MutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T> DerefMut for MutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T> Drop for MutexGuard<'_, T> {
+    fn drop(&mut self) {
+        assert_eq!(sys_mutex_unlock(self.mutex.id), 0);
+    }
+}
+
+/// A counting semaphore.
+pub struct Semaphore {
+    id: usize,
+}
+
+impl Semaphore {
+    pub fn new(count: usize) -> Self {
+        let id = sys_semaphore_create(count);
+        assert!(id >= 0, "Failed to create semaphore!");
+        Self { id: id as usize }
+    }
+
+    pub fn up(&self) {
+        assert_eq!(sys_semaphore_up(self.id), 0);
+    }
+
+    pub fn down(&self) {
+        assert_eq!(sys_semaphore_down(self.id), 0);
+    }
+}
+
+/// A condition variable used together with a [`Mutex`].
+pub struct Condvar {
+    id: usize,
+}
+
+impl Condvar {
+    pub fn new() -> Self {
+        let id = sys_condvar_create();
+        assert!(id >= 0, "Failed to create condvar!");
+        Self { id: id as usize }
+    }
+
+    /// Wake up one thread blocked in `wait` (if any).
+    pub fn signal(&self) {
+        assert_eq!(sys_condvar_signal(self.id), 0);
+    }
+
+    /// Unlock the mutex of `guard` and block until signaled, the mutex is
+    /// locked again when this returns.
+    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        assert_eq!(sys_condvar_wait(self.id, guard.mutex.id), 0);
+        guard
+    }
+}
+
+impl Default for Condvar {
+    fn default() -> Self {
+        Self::new()
+    }
+}
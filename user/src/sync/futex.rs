@@ -0,0 +1,146 @@
+//! User-space primitives built on `sys_futex`
+
+use crate::syscall::sys_futex;
+use core::cell::UnsafeCell;
+use core::ops::{Deref, DerefMut};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+const FUTEX_WAIT: usize = 0;
+const FUTEX_WAKE: usize = 1;
+
+/// Block until woken if `futex` still holds `val`.
+pub fn futex_wait(futex: &AtomicU32, val: u32) -> isize {
+    sys_futex(futex.as_ptr(), FUTEX_WAIT, val)
+}
+
+/// Wake up at most `count` threads waiting on `futex`, return the number woken.
+pub fn futex_wake(futex: &AtomicU32, count: u32) -> isize {
+    sys_futex(futex.as_ptr(), FUTEX_WAKE, count)
+}
+
+const UNLOCKED: u32 = 0;
+const LOCKED: u32 = 1;
+/// locked, and there may be threads waiting
+const CONTENDED: u32 = 2;
+
+/// A mutex protecting `T`, taking the lock needs no syscall if there is no contention.
+pub struct FutexMutex<T> {
+    state: AtomicU32,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for FutexMutex<T> {}
+unsafe impl<T: Send> Send for FutexMutex<T> {}
+
+impl<T> FutexMutex<T> {
+    pub const fn new(data: T) -> Self {
+        Self {
+            state: AtomicU32::new(UNLOCKED),
+            data: UnsafeCell::new(data),
+        }
+    }
+
+    pub fn lock(&self) -> FutexMutexGuard<'_, T> {
+        if self
+            .state
+            .compare_exchange(UNLOCKED, LOCKED, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // we cannot tell whether others are waiting, so assume they are
+            while self.state.swap(CONTENDED, Ordering::Acquire) != UNLOCKED {
+                futex_wait(&self.state, CONTENDED);
+            }
+        }
+        FutexMutexGuard { mutex: self }
+    }
+}
+
+/// ### RAII
+///
+/// - get resource : `FutexMutex::lock` -> `FutexMutexGuard`
+/// - release resource automatically : `drop` -> wake up a waiter if contended
+pub struct FutexMutexGuard<'a, T> {
+    mutex: &'a FutexMutex<T>,
+}
+
+impl<T> Deref for FutexMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T> DerefMut for FutexMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T> Drop for FutexMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        if self.mutex.state.swap(UNLOCKED, Ordering::Release) == CONTENDED {
+            futex_wake(&self.mutex.state, 1);
+        }
+    }
+}
+
+const INCOMPLETE: u32 = 0;
+const RUNNING: u32 = 1;
+/// running, and there may be threads waiting
+const RUNNING_CONTENDED: u32 = 2;
+const COMPLETE: u32 = 3;
+
+/// Run an initialization exactly once, other callers block until it is done.
+pub struct Once {
+    state: AtomicU32,
+}
+
+impl Once {
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU32::new(INCOMPLETE),
+        }
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.state.load(Ordering::Acquire) == COMPLETE
+    }
+
+    /// Call `f` if no one has called it, or wait for the running call to finish.
+    pub fn call_once<F: FnOnce()>(&self, f: F) {
+        loop {
+            match self.state.compare_exchange(
+                INCOMPLETE,
+                RUNNING,
+                Ordering::Acquire,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    f();
+                    if self.state.swap(COMPLETE, Ordering::Release) == RUNNING_CONTENDED {
+                        futex_wake(&self.state, u32::MAX);
+                    }
+                    return;
+                }
+                Err(COMPLETE) => return,
+                Err(_) => {
+                    // if it has completed in the meantime, the wait fails at once
+                    let _ = self.state.compare_exchange(
+                        RUNNING,
+                        RUNNING_CONTENDED,
+                        Ordering::Relaxed,
+                        Ordering::Relaxed,
+                    );
+                    futex_wait(&self.state, RUNNING_CONTENDED);
+                }
+            }
+        }
+    }
+}
+
+impl Default for Once {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,3 +1,5 @@
+use crate::api::{RLimit, Stat};
+use crate::signal::SignalAction;
 use core::arch::asm;
 
 fn syscall(id: usize, args: [usize; 3]) -> isize {
@@ -14,9 +16,42 @@ fn syscall(id: usize, args: [usize; 3]) -> isize {
     ret
 }
 
+/// 有 6 个参数的系统调用，如 mmap。
+fn syscall6(id: usize, args: [usize; 6]) -> isize {
+    let mut ret: isize;
+    unsafe {
+        asm!(
+            "ecall",
+            inlateout("x10") args[0] => ret,
+            in("x11") args[1],
+            in("x12") args[2],
+            in("x13") args[3],
+            in("x14") args[4],
+            in("x15") args[5],
+            in("x17") id
+        );
+    }
+    ret
+}
+
+const SYSCALL_GETCWD: usize = 17;
+const SYSCALL_DUP: usize = 23;
+const SYSCALL_DUP3: usize = 24;
+const SYSCALL_MKDIR: usize = 34;
+const SYSCALL_UNLINK: usize = 35;
+const SYSCALL_RENAME: usize = 38;
+const SYSCALL_CHDIR: usize = 49;
+const SYSCALL_OPEN: usize = 56;
+const SYSCALL_CLOSE: usize = 57;
+const SYSCALL_PIPE: usize = 59;
+const SYSCALL_GETDENTS64: usize = 61;
 const SYSCALL_WRITE: usize = 64;
 const SYSCALL_EXIT: usize = 93;
 const SYSCALL_READ: usize = 63;
+const SYSCALL_STAT: usize = 79;
+const SYSCALL_FSTAT: usize = 80;
+const SYSCALL_SYNC: usize = 81;
+const SYSCALL_FSYNC: usize = 82;
 const SYSCALL_YIELD: usize = 124;
 const SYSCALL_TIME: usize = 169;
 
@@ -25,9 +60,171 @@ const SYSCALL_FORK: usize = 220;
 const SYSCALL_EXEC: usize = 221;
 const SYSCALL_WAITPID: usize = 260;
 
+const SYSCALL_MUNMAP: usize = 215;
+const SYSCALL_MMAP: usize = 222;
+const SYSCALL_MSYNC: usize = 227;
+
 const SYSCALL_SHUTDOWN: usize = 216;
-const SYSCALL_LS: usize = 217;
+const SYSCALL_APPS: usize = 218;
+
+const SYSCALL_THREAD_CREATE: usize = 1000;
+const SYSCALL_GETTID: usize = 1001;
+const SYSCALL_WAITTID: usize = 1002;
+
+const SYSCALL_MUTEX_CREATE: usize = 1010;
+const SYSCALL_MUTEX_LOCK: usize = 1011;
+const SYSCALL_MUTEX_UNLOCK: usize = 1012;
+const SYSCALL_SEMAPHORE_CREATE: usize = 1020;
+const SYSCALL_SEMAPHORE_UP: usize = 1021;
+const SYSCALL_SEMAPHORE_DOWN: usize = 1022;
+const SYSCALL_CONDVAR_CREATE: usize = 1030;
+const SYSCALL_CONDVAR_SIGNAL: usize = 1031;
+const SYSCALL_CONDVAR_WAIT: usize = 1032;
+const SYSCALL_FUTEX: usize = 98;
+
+const SYSCALL_KILL: usize = 129;
+const SYSCALL_SIGACTION: usize = 134;
+const SYSCALL_SIGPROCMASK: usize = 135;
+const SYSCALL_SIGRETURN: usize = 139;
+const SYSCALL_SETPGID: usize = 154;
+const SYSCALL_GETPGID: usize = 155;
+const SYSCALL_GETSID: usize = 156;
+const SYSCALL_SETSID: usize = 157;
+const SYSCALL_GETRLIMIT: usize = 163;
+const SYSCALL_SETRLIMIT: usize = 164;
+const SYSCALL_GETPPID: usize = 173;
+const SYSCALL_TCGETPGRP: usize = 1040;
+const SYSCALL_TCSETPGRP: usize = 1041;
+
+/// 功能：打开文件 path，相对路径从当前工作目录开始，flags 与 Linux 相同（O_WRONLY、O_RDWR、O_CREAT、O_TRUNC、O_APPEND、O_DIRECTORY）。
+/// 返回值：最小的空闲文件描述符；文件不存在且没有 O_CREAT 时返回 -ENOENT，flags 不合法时返回 -EINVAL，
+/// 有 O_DIRECTORY 而 path 不是目录时返回 -ENOTDIR。
+pub fn sys_open(path: &str, flags: u32) -> isize {
+    syscall(SYSCALL_OPEN, [path.as_ptr() as usize, flags as usize, 0])
+}
+
+/// 功能：删除文件 path，已打开的文件描述符仍可继续读写；flags 含 AT_REMOVEDIR (0x200) 时删除空目录 path。
+/// 返回值：成功返回 0；文件不存在返回 -ENOENT，删除文件时 path 是目录返回 -EISDIR，
+/// 删除目录时 path 不是目录返回 -ENOTDIR、目录非空返回 -ENOTEMPTY，path 上挂载了文件系统返回 -EBUSY。
+pub fn sys_unlink(path: &str, flags: u32) -> isize {
+    syscall(SYSCALL_UNLINK, [path.as_ptr() as usize, flags as usize, 0])
+}
+
+/// 功能：将 path 的元数据（inode 编号、类型和权限、链接数、大小、时间）写入 st，格式与 Linux 的 struct stat 相同。
+/// 返回值：成功返回 0；path 不存在返回 -ENOENT。
+pub fn sys_stat(path: &str, st: *mut Stat) -> isize {
+    syscall(SYSCALL_STAT, [path.as_ptr() as usize, st as usize, 0])
+}
+
+/// 功能：将文件描述符 fd 对应文件的元数据写入 st，见 sys_stat。
+/// 返回值：成功返回 0；fd 不合法返回 -EBADF。
+pub fn sys_fstat(fd: usize, st: *mut Stat) -> isize {
+    syscall(SYSCALL_FSTAT, [fd, st as usize, 0])
+}
+
+/// 功能：将缓存在内存中的所有修改写回磁盘。
+/// 返回值：总是返回 0。
+pub fn sys_sync() -> isize {
+    syscall(SYSCALL_SYNC, [0, 0, 0])
+}
+
+/// 功能：将文件描述符 fd 对应文件的修改写回其所在的磁盘。
+/// 返回值：成功返回 0；fd 不合法返回 -EBADF，文件不支持写回（如管道）返回 -EINVAL。
+pub fn sys_fsync(fd: usize) -> isize {
+    syscall(SYSCALL_FSYNC, [fd, 0, 0])
+}
+
+/// 功能：将文件描述符 fd 对应文件从 offset 开始的 len 字节映射到内存，flags 含 MAP_ANONYMOUS 时映射清零的内存。
+/// 参数：addr 为建议的地址，flags 含 MAP_FIXED 时必须映射在 addr，但不替换已有的映射；prot 与 flags 与 Linux 相同。
+/// 返回值：映射的起始地址；fd 不合法返回 -EBADF，文件不能映射（如管道）返回 -ENODEV，
+/// 文件不可读、或以 MAP_SHARED 和 PROT_WRITE 映射只读打开的文件返回 -EACCES，
+/// 参数不合法返回 -EINVAL，MAP_FIXED 时 addr 已被映射返回 -EEXIST，没有足够的地址空间或内存返回 -ENOMEM。
+pub fn sys_mmap(
+    addr: usize,
+    len: usize,
+    prot: usize,
+    flags: usize,
+    fd: usize,
+    offset: usize,
+) -> isize {
+    syscall6(SYSCALL_MMAP, [addr, len, prot, flags, fd, offset])
+}
+
+/// 功能：取消 [addr, addr + len) 中的映射，MAP_SHARED 映射的修改写回文件。
+/// 返回值：成功返回 0；addr 未按页对齐、len 为 0 或有映射只有一部分在范围内返回 -EINVAL。
+pub fn sys_munmap(addr: usize, len: usize) -> isize {
+    syscall(SYSCALL_MUNMAP, [addr, len, 0])
+}
+
+/// 功能：将 [addr, addr + len) 中 MAP_SHARED 映射的修改写回文件，flags 含 MS_SYNC 时同时写回磁盘。
+/// 返回值：成功返回 0；addr 未按页对齐或 flags 不合法返回 -EINVAL，范围内有未映射的页返回 -ENOMEM。
+pub fn sys_msync(addr: usize, len: usize, flags: usize) -> isize {
+    syscall(SYSCALL_MSYNC, [addr, len, flags])
+}
+
+/// 功能：创建目录 path。
+/// 返回值：成功返回 0；path 已存在返回 -EEXIST，上级目录不存在返回 -ENOENT。
+pub fn sys_mkdir(path: &str) -> isize {
+    syscall(SYSCALL_MKDIR, [path.as_ptr() as usize, 0, 0])
+}
+
+/// 功能：将 old_path 移动到 new_path，new_path 已存在时被替换（非空目录除外）。
+/// 返回值：成功返回 0；两者不在同一个文件系统中返回 -EXDEV，把目录移入其自身返回 -EINVAL。
+pub fn sys_rename(old_path: &str, new_path: &str) -> isize {
+    syscall(
+        SYSCALL_RENAME,
+        [old_path.as_ptr() as usize, new_path.as_ptr() as usize, 0],
+    )
+}
+
+/// 功能：将当前进程的工作目录改为 path。
+/// 返回值：成功返回 0；path 不存在返回 -ENOENT，不是目录返回 -ENOTDIR。
+pub fn sys_chdir(path: &str) -> isize {
+    syscall(SYSCALL_CHDIR, [path.as_ptr() as usize, 0, 0])
+}
+
+/// 功能：将当前工作目录的绝对路径写入缓冲区，以 '\0' 结尾。
+/// 返回值：包括 '\0' 在内的长度；缓冲区太小返回 -ERANGE。
+pub fn sys_getcwd(buf: &mut [u8]) -> isize {
+    syscall(SYSCALL_GETCWD, [buf.as_mut_ptr() as usize, buf.len(), 0])
+}
+
+/// 功能：将目录 fd 的后续目录项以 linux_dirent64 格式写入缓冲区，包括 "." 和 ".."。
+/// 返回值：写入的字节数，读完时返回 0；fd 不是目录返回 -ENOTDIR，缓冲区放不下下一项时返回 -EINVAL。
+pub fn sys_getdents64(fd: usize, buf: &mut [u8]) -> isize {
+    syscall(
+        SYSCALL_GETDENTS64,
+        [fd, buf.as_mut_ptr() as usize, buf.len()],
+    )
+}
+
+/// 功能：关闭文件描述符 fd，文件的所有描述符都被关闭后文件才被关闭。
+/// 返回值：成功返回 0；fd 不合法返回 -EBADF。
+pub fn sys_close(fd: usize) -> isize {
+    syscall(SYSCALL_CLOSE, [fd, 0, 0])
+}
+
+/// 功能：创建一个管道，读端和写端的文件描述符分别保存在 pipe[0] 和 pipe[1]。
+/// 写端全部关闭后，读取管道返回 0；读端全部关闭后，写入管道会收到 SIGPIPE 并返回 -EPIPE。
+/// 返回值：总是返回 0。
+pub fn sys_pipe(pipe: &mut [usize]) -> isize {
+    syscall(SYSCALL_PIPE, [pipe.as_mut_ptr() as usize, 0, 0])
+}
+
+/// 功能：复制文件描述符 fd 到最小的空闲文件描述符。
+/// 返回值：新的文件描述符；fd 不合法返回 -EBADF。
+pub fn sys_dup(fd: usize) -> isize {
+    syscall(SYSCALL_DUP, [fd, 0, 0])
+}
+
+/// 功能：复制文件描述符 old_fd 到 new_fd，如果 new_fd 已打开则先将其关闭，flags 必须为 0。
+/// 返回值：new_fd；old_fd 不合法返回 -EBADF；old_fd 与 new_fd 相同或 flags 不为 0 时返回 -EINVAL。
+pub fn sys_dup3(old_fd: usize, new_fd: usize, flags: u32) -> isize {
+    syscall(SYSCALL_DUP3, [old_fd, new_fd, flags as usize])
+}
 
+/// 功能：将缓冲区中的内容写入文件。
+/// 返回值：实际写入的字节数；fd 不合法返回 -EBADF。
 pub fn sys_write(fd: usize, buffer: &[u8]) -> isize {
     syscall(SYSCALL_WRITE, [fd, buffer.as_ptr() as usize, buffer.len()])
 }
@@ -38,7 +235,7 @@ pub fn sys_exit(xstate: i32) -> isize {
 
 /// 功能：从文件中读取一段内容到缓冲区。
 /// 参数：fd 是待读取文件的文件描述符，切片 buffer 则给出缓冲区。
-/// 返回值：如果出现了错误则返回 -1，否则返回实际读到的字节数。
+/// 返回值：实际读到的字节数，读到文件末尾时返回 0；fd 不合法返回 -EBADF；被信号打断时返回 -EINTR。
 pub fn sys_read(fd: usize, buf: &mut [u8]) -> isize {
     syscall(SYSCALL_READ, [fd, buf.as_mut_ptr() as usize, buf.len()])
 }
@@ -62,19 +259,31 @@ pub fn sys_fork() -> isize {
 }
 
 /// 功能：将当前进程的地址空间清空并加载一个特定的可执行文件，返回用户态后开始它的执行。
-/// 参数：path 给出了要加载的可执行文件的名字；
-/// 返回值：如果出错的话（如找不到名字相符的可执行文件）则返回 -1，否则不应该返回。
-pub fn sys_exec(path: &str) -> isize {
-    syscall(SYSCALL_EXEC, [path.as_ptr() as usize, 0, 0])
+/// 参数：path 给出了要加载的可执行文件的路径，不含 '/' 时在 /bin 中查找；args 为以空指针结尾的参数字符串数组，
+/// 为空指针时唯一的参数是 path；envp 为以空指针结尾的 "NAME=value" 环境变量字符串数组，
+/// 为空指针时环境为空。打开的文件保持不变。
+/// 返回值：如果出错的话（如找不到名字相符的可执行文件）则返回 -1，文件不是 ELF 格式时返回 -ENOEXEC，否则不应该返回。
+pub fn sys_exec(path: &str, args: *const *const u8, envp: *const *const u8) -> isize {
+    syscall(
+        SYSCALL_EXEC,
+        [path.as_ptr() as usize, args as usize, envp as usize],
+    )
 }
 
-/// 功能：当前进程等待一个子进程变为僵尸进程，回收其全部资源并收集其返回值。
+/// 功能：当前进程等待一个子进程变为僵尸进程，回收其全部资源并收集其退出状态。
 /// 参数：pid 表示要等待的子进程的进程 ID，如果为 -1 的话表示等待任意一个子进程；
-/// exit_code 表示保存子进程返回值的地址，如果这个地址为 0 的话表示不必保存。
+/// exit_code 表示保存子进程退出状态的地址，如果这个地址为 0 的话表示不必保存。
+/// 退出状态与 Linux 相同：正常退出时第 8~15 位为返回值的低 8 位，被信号终止时低 7 位为信号编号，
+/// 被信号暂停时低 8 位为 0x7f、第 8~15 位为信号编号。
+/// options 包含 WUNTRACED 时，被暂停的子进程也会被报告（每次暂停只报告一次）。
 /// 返回值：如果要等待的子进程不存在则返回 -1；否则如果要等待的子进程均未结束则返回 -2；
-/// 否则返回结束的子进程的进程 ID。
-pub fn sys_waitpid(pid: isize, exit_code: *mut i32) -> isize {
-    syscall(SYSCALL_WAITPID, [pid as usize, exit_code as usize, 0])
+/// 否则返回结束（或暂停）的子进程的进程 ID。
+pub fn sys_waitpid(pid: isize, exit_code: *mut i32, options: usize) -> isize {
+    syscall(SYSCALL_WAITPID, [pid as usize, exit_code as usize, options])
+}
+
+pub fn sys_getppid() -> isize {
+    syscall(SYSCALL_GETPPID, [0, 0, 0])
 }
 
 pub fn sys_shutdown() -> ! {
@@ -82,6 +291,174 @@ pub fn sys_shutdown() -> ! {
     unreachable!()
 }
 
-pub fn sys_ls() -> isize {
-    syscall(SYSCALL_LS, [0, 0, 0])
+/// 功能：将可以从 shell 运行的应用名写入缓冲区，每个名字以 '\n' 结尾。
+/// 参数：buf 表示缓冲区的起始地址，len 表示缓冲区的长度，列表超出 len 的部分被截断。
+/// 返回值：完整列表的长度。
+pub fn sys_apps(buf: &mut [u8]) -> isize {
+    syscall(SYSCALL_APPS, [buf.as_mut_ptr() as usize, buf.len(), 0])
+}
+
+/// 功能：在当前进程中创建一个线程，从 entry 开始执行，arg 作为第一个参数传入。
+/// 返回值：新线程的线程 ID；内存不足，或程序与 mmap 区域之间没有空间放置线程栈时返回 -ENOMEM。
+pub fn sys_thread_create(entry: usize, arg: usize) -> isize {
+    syscall(SYSCALL_THREAD_CREATE, [entry, arg, 0])
+}
+
+pub fn sys_gettid() -> isize {
+    syscall(SYSCALL_GETTID, [0, 0, 0])
+}
+
+/// 功能：等待当前进程中的一个线程退出，回收其资源并收集其返回值。
+/// 返回值：如果线程不存在（或者是调用者自身）则返回 -1；如果线程尚未退出则返回 -2；
+/// 否则返回该线程的线程 ID。
+pub fn sys_waittid(tid: usize, exit_code: *mut i32) -> isize {
+    syscall(SYSCALL_WAITTID, [tid, exit_code as usize, 0])
+}
+
+/// 功能：在当前进程中创建一个互斥锁。
+/// 返回值：互斥锁的 ID。
+pub fn sys_mutex_create() -> isize {
+    syscall(SYSCALL_MUTEX_CREATE, [0, 0, 0])
+}
+
+/// 功能：获取互斥锁，如果锁已被占用则阻塞当前线程。
+/// 返回值：如果互斥锁不存在则返回 -1，否则返回 0。
+pub fn sys_mutex_lock(id: usize) -> isize {
+    syscall(SYSCALL_MUTEX_LOCK, [id, 0, 0])
+}
+
+/// 功能：释放互斥锁，唤醒一个等待的线程。
+/// 返回值：如果互斥锁不存在或未被锁定则返回 -1，否则返回 0。
+pub fn sys_mutex_unlock(id: usize) -> isize {
+    syscall(SYSCALL_MUTEX_UNLOCK, [id, 0, 0])
+}
+
+/// 功能：在当前进程中创建一个初值为 count 的信号量。
+/// 返回值：信号量的 ID。
+pub fn sys_semaphore_create(count: usize) -> isize {
+    syscall(SYSCALL_SEMAPHORE_CREATE, [count, 0, 0])
+}
+
+/// 功能：信号量的 V 操作，唤醒一个等待的线程。
+/// 返回值：如果信号量不存在则返回 -1，否则返回 0。
+pub fn sys_semaphore_up(id: usize) -> isize {
+    syscall(SYSCALL_SEMAPHORE_UP, [id, 0, 0])
+}
+
+/// 功能：信号量的 P 操作，资源不足时阻塞当前线程。
+/// 返回值：如果信号量不存在则返回 -1，否则返回 0。
+pub fn sys_semaphore_down(id: usize) -> isize {
+    syscall(SYSCALL_SEMAPHORE_DOWN, [id, 0, 0])
+}
+
+/// 功能：在当前进程中创建一个条件变量。
+/// 返回值：条件变量的 ID。
+pub fn sys_condvar_create() -> isize {
+    syscall(SYSCALL_CONDVAR_CREATE, [0, 0, 0])
+}
+
+/// 功能：唤醒一个等待在条件变量上的线程。
+/// 返回值：如果条件变量不存在则返回 -1，否则返回 0。
+pub fn sys_condvar_signal(condvar_id: usize) -> isize {
+    syscall(SYSCALL_CONDVAR_SIGNAL, [condvar_id, 0, 0])
+}
+
+/// 功能：释放互斥锁并阻塞在条件变量上，被唤醒后重新获取互斥锁。
+/// 返回值：如果条件变量或互斥锁不存在，或者互斥锁未被锁定则返回 -1，否则返回 0。
+pub fn sys_condvar_wait(condvar_id: usize, mutex_id: usize) -> isize {
+    syscall(SYSCALL_CONDVAR_WAIT, [condvar_id, mutex_id, 0])
+}
+
+/// 功能：基于用户地址 uaddr 处的 32 位字进行等待或唤醒。
+/// 参数：op 为 FUTEX_WAIT 时，如果 *uaddr == val 则阻塞当前线程直到被唤醒；
+/// op 为 FUTEX_WAKE 时，唤醒至多 val 个等待在 uaddr 上的线程。
+/// 返回值：FUTEX_WAIT 成功返回 0，*uaddr != val 时返回 -EAGAIN；FUTEX_WAKE 返回被唤醒的线程数；
+/// uaddr 未对齐或 op 不合法时返回 -EINVAL。
+pub fn sys_futex(uaddr: *const u32, op: usize, val: u32) -> isize {
+    syscall(SYSCALL_FUTEX, [uaddr as usize, op, val as usize])
+}
+
+/// 功能：向进程 pid 发送信号 signum，signum 为 0 时只检查进程是否存在。
+/// pid 为 0 时发送给调用者所在的进程组，小于 -1 时发送给进程组 -pid。
+/// 返回值：成功返回 0；signum 不合法或 pid 为 -1 时返回 -EINVAL；进程（组）不存在返回 -ESRCH；
+/// 目标是进程管理器或初始进程时返回 -EPERM。
+pub fn sys_kill(pid: isize, signum: i32) -> isize {
+    syscall(SYSCALL_KILL, [pid as usize, signum as usize, 0])
+}
+
+/// 功能：设置信号 signum 的处理方式，并保存原来的处理方式。
+/// 参数：action 为新的处理方式，为空时不修改；old_action 保存原来的处理方式，为空时不保存。
+/// 返回值：成功返回 0；signum 不合法或为 SIGKILL、SIGSTOP 时返回 -EINVAL。
+pub fn sys_sigaction(
+    signum: i32,
+    action: *const SignalAction,
+    old_action: *mut SignalAction,
+) -> isize {
+    syscall(
+        SYSCALL_SIGACTION,
+        [signum as usize, action as usize, old_action as usize],
+    )
+}
+
+/// 功能：设置当前线程的信号掩码，被屏蔽的信号在解除屏蔽前不会被递送。
+/// 返回值：原来的信号掩码。
+pub fn sys_sigprocmask(mask: u32) -> isize {
+    syscall(SYSCALL_SIGPROCMASK, [mask as usize, 0, 0])
+}
+
+/// 功能：从信号处理函数返回到被打断的地方，必须在信号处理函数的最后调用。
+/// 返回值：没有正在执行的信号处理函数时返回 -1，否则不应该返回。
+pub fn sys_sigreturn() -> isize {
+    syscall(SYSCALL_SIGRETURN, [0, 0, 0])
+}
+
+/// 功能：将进程 pid 加入进程组 pgid，pid 为 0 表示调用者自身，pgid 为 0 表示使用 pid 作为组号。
+/// 返回值：成功返回 0；pid 既不是调用者也不是其子进程时返回 -ESRCH；
+/// pid 是会话首进程、与调用者不在同一会话，或进程组 pgid 不在该会话中时返回 -EPERM。
+pub fn sys_setpgid(pid: usize, pgid: usize) -> isize {
+    syscall(SYSCALL_SETPGID, [pid, pgid, 0])
+}
+
+/// 功能：获取进程 pid 的进程组号，pid 为 0 表示调用者自身。
+/// 返回值：进程组号；进程不存在时返回 -ESRCH。
+pub fn sys_getpgid(pid: usize) -> isize {
+    syscall(SYSCALL_GETPGID, [pid, 0, 0])
+}
+
+/// 功能：获取进程 pid 的会话号，pid 为 0 表示调用者自身。
+/// 返回值：会话号；进程不存在时返回 -ESRCH。
+pub fn sys_getsid(pid: usize) -> isize {
+    syscall(SYSCALL_GETSID, [pid, 0, 0])
+}
+
+/// 功能：创建一个新会话，调用者成为会话首进程和新进程组的组长，新会话没有控制终端。
+/// 返回值：新会话号；调用者已经是进程组组长时返回 -EPERM。
+pub fn sys_setsid() -> isize {
+    syscall(SYSCALL_SETSID, [0, 0, 0])
+}
+
+/// 功能：将资源 resource 的限制写入 rlim，目前只支持 RLIMIT_STACK（主线程用户栈可以增长到的字节数）。
+/// 返回值：成功返回 0；resource 不支持时返回 -EINVAL。
+pub fn sys_getrlimit(resource: usize, rlim: *mut RLimit) -> isize {
+    syscall(SYSCALL_GETRLIMIT, [resource, rlim as usize, 0])
+}
+
+/// 功能：设置资源 resource 的限制，子进程继承该限制，exec 后保持不变。
+/// RLIMIT_STACK 的硬限制是为栈预留的空间，不能修改；栈增长超过软限制时进程被 SIGSEGV 终止，等待状态含 STACK_OVERFLOW。
+/// 返回值：成功返回 0；resource 不支持、软限制大于硬限制或修改硬限制时返回 -EINVAL。
+pub fn sys_setrlimit(resource: usize, rlim: *const RLimit) -> isize {
+    syscall(SYSCALL_SETRLIMIT, [resource, rlim as usize, 0])
+}
+
+/// 功能：获取终端 fd 的前台进程组。
+/// 返回值：前台进程组号；fd 不是调用者的控制终端时返回 -ENOTTY。
+pub fn sys_tcgetpgrp(fd: usize) -> isize {
+    syscall(SYSCALL_TCGETPGRP, [fd, 0, 0])
+}
+
+/// 功能：将进程组 pgid 设为终端 fd 的前台进程组，只有前台进程组可以读取终端，
+/// 终端上的 Ctrl-C、Ctrl-Z、Ctrl-\\ 分别向前台进程组发送 SIGINT、SIGTSTP、SIGQUIT。
+/// 返回值：成功返回 0；fd 不是调用者的控制终端时返回 -ENOTTY；进程组不在调用者的会话中时返回 -EPERM。
+pub fn sys_tcsetpgrp(fd: usize, pgid: usize) -> isize {
+    syscall(SYSCALL_TCSETPGRP, [fd, pgid, 0])
 }